@@ -4,13 +4,15 @@
 
 use std::env::current_exe;
 use std::error::Error;
+use std::process::ExitStatus;
 use std::string::FromUtf8Error;
 use thiserror::Error;
 use tokio::process::Command;
 
 use base64::{engine::general_purpose, Engine};
+use maxima::core::auth::login::{login_callback_port, store_login_callback};
 use maxima::core::launch::BootstrapLaunchArgs;
-use maxima::util::native::NativeError;
+use maxima::util::native::{NativeError, SpawnWithContext};
 #[cfg(windows)]
 use maxima::util::service::{is_service_valid, register_service};
 use maxima::util::BackgroundServiceControlError;
@@ -37,12 +39,68 @@ pub(crate) enum RunError {
     ParseUtf8(#[from] FromUtf8Error),
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
+
+    /// Not a failure of the bootstrap, but passed up so it can exit with the game's code
+    #[error("the game exited with {0}")]
+    GameExited(GameExit),
+}
+
+/// How a game that didn't exit cleanly ended. It becomes the bootstrap's exit code,
+/// so whoever launched the game can tell a clean exit from a crash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GameExit {
+    Code(i32),
+    /// Killed by a signal, which leaves no exit code
+    Signal(i32),
+}
+
+impl GameExit {
+    /// `None` for a clean exit
+    fn from_status(status: ExitStatus) -> Option<Self> {
+        if status.success() {
+            return None;
+        }
+
+        if let Some(code) = status.code() {
+            return Some(Self::Code(code));
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return Some(Self::Signal(signal));
+            }
+        }
+
+        Some(Self::Code(1))
+    }
+
+    /// Signals are reported like shells do, as 128 plus the signal number
+    fn code(&self) -> i32 {
+        match self {
+            Self::Code(code) => *code,
+            Self::Signal(signal) => 128 + signal,
+        }
+    }
+}
+
+impl std::fmt::Display for GameExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Code(code) => write!(f, "code {}", code),
+            Self::Signal(signal) => write!(f, "signal {}", signal),
+        }
+    }
 }
 
 #[cfg(not(target_os = "macos"))]
 #[tokio::main]
 async fn main() -> Result<(), RunError> {
-    let _ = handle_launch_args().await?;
+    match handle_launch_args().await {
+        Err(RunError::GameExited(exit)) => std::process::exit(exit.code()),
+        result => result?,
+    };
 
     Ok(())
 }
@@ -112,31 +170,42 @@ fn service_setup() -> Result<(), BackgroundServiceControlError> {
 }
 
 #[cfg(windows)]
-async fn platform_launch(args: BootstrapLaunchArgs) -> Result<(), NativeError> {
+async fn platform_launch(args: BootstrapLaunchArgs) -> Result<ExitStatus, NativeError> {
     let mut binding = Command::new(args.path);
-    let child = binding.args(args.args);
+    let child = binding.args(args.args).envs(args.env);
+    if let Some(working_dir) = args.working_dir {
+        child.current_dir(working_dir);
+    }
 
-    let status = child.spawn()?.wait().await?;
-    // bail!("{}", status.code().unwrap());
-    Ok(())
+    Ok(child.spawn_with_context()?.wait().await?)
 }
 
 #[cfg(unix)]
-async fn platform_launch(args: BootstrapLaunchArgs) -> Result<(), NativeError> {
+async fn platform_launch(args: BootstrapLaunchArgs) -> Result<ExitStatus, NativeError> {
     use maxima::unix::wine::run_wine_command;
-    use maxima::unix::wine::CommandType;
+    use maxima::unix::wine::{check_wine_launch, CommandType};
+    use maxima::util::native::WineError;
+    use std::os::unix::process::ExitStatusExt;
+
+    check_wine_launch(&args.slug)?;
 
-    run_wine_command(
+    let result = run_wine_command(
         args.path,
         Some(args.args),
-        None,
+        args.working_dir,
         false,
         CommandType::WaitForExitAndRun,
         Some(&args.slug),
+        Some(&args.env),
     )
-    .await?;
+    .await;
 
-    Ok(())
+    // A game exiting with an error isn't wine failing to run it
+    match result {
+        Ok(_) => Ok(ExitStatus::from_raw(0)),
+        Err(NativeError::Wine(WineError::Command { exit, .. })) => Ok(exit),
+        Err(err) => Err(err),
+    }
 }
 
 async fn run(args: &[String]) -> Result<bool, RunError> {
@@ -153,34 +222,48 @@ async fn run(args: &[String]) -> Result<bool, RunError> {
         }
 
         if arg.starts_with("origin2") {
+            // Sent by Battlelog when clicking play, with the arguments for the server to join
             let url = Url::parse(arg)?;
-            let query = querystring::querify(url.query().unwrap());
-            let _offer_id = query.iter().find(|(x, _)| *x == "offerIds").unwrap().1;
-            let cmd_params = query.iter().find(|(x, _)| *x == "cmdParams").unwrap().1;
-
-            let mut child = Command::new(current_exe()?.with_file_name("maxima-cli.exe"));
-            child.env(
-                "MAXIMA_LAUNCH_ARGS",
-                urlencoding::decode(cmd_params)?
-                    .into_owned()
-                    .replace("\\\"", "\""),
-            );
-            println!(
-                "{}",
-                urlencoding::decode(cmd_params)?
-                    .into_owned()
-                    .replace("\\\"", "\"")
+            let query = querystring::querify(url.query().unwrap_or_default());
+            let offer_ids = match query.iter().find(|(x, _)| *x == "offerIds") {
+                Some((_, offer_ids)) => urlencoding::decode(offer_ids)?.into_owned(),
+                None => return Ok(false),
+            };
+            let cmd_params = query
+                .iter()
+                .find(|(x, _)| *x == "cmdParams")
+                .map(|(_, params)| params.to_owned())
+                .unwrap_or_default();
+            let launch_args = urlencoding::decode(cmd_params)?
+                .into_owned()
+                .replace("\\\"", "\"");
+
+            let mut child = Command::new(
+                current_exe()?
+                    .with_file_name(format!("maxima-cli{}", std::env::consts::EXE_SUFFIX)),
             );
-            child.env("KYBER_INTERFACE_PORT", "3005");
-            child.args(["--mode", "launch", "--offer-id", "Origin.OFR.50.0002148"]);
-            child.spawn()?.wait().await?;
+            child.env("MAXIMA_LAUNCH_ARGS", launch_args);
+            child.args(["launch-battlelog", &offer_ids]);
+            child.spawn_with_context()?.wait().await?;
 
             return Ok(true);
         }
 
         if arg.starts_with("qrc") {
             let query = arg.split("login_successful.html?").collect::<Vec<&str>>()[1];
-            reqwest::get(format!("http://127.0.0.1:31033/auth?{}", query)).await?;
+            let state = querystring::querify(query)
+                .into_iter()
+                .find(|(x, _)| *x == "state")
+                .map(|(_, state)| state);
+            let port = login_callback_port(state);
+            let result = reqwest::get(format!("http://127.0.0.1:{}/auth?{}", port, query)).await;
+
+            // Maxima isn't listening anymore, keep the login for when it's started again
+            if let Err(err) = result {
+                if !store_login_callback(query) {
+                    return Err(err.into());
+                }
+            }
 
             return Ok(true);
         }
@@ -194,7 +277,10 @@ async fn run(args: &[String]) -> Result<bool, RunError> {
             "launch" => {
                 let decoded = general_purpose::STANDARD.decode(&args[1])?;
                 let launch_args: BootstrapLaunchArgs = serde_json::from_slice(&decoded)?;
-                platform_launch(launch_args).await?;
+                let status = platform_launch(launch_args).await?;
+                if let Some(exit) = GameExit::from_status(status) {
+                    return Err(RunError::GameExited(exit));
+                }
 
                 true
             }
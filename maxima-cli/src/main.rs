@@ -6,42 +6,60 @@ use lazy_static::lazy_static;
 use log::{debug, error, info, warn};
 use regex::Regex;
 
-use std::{path::PathBuf, sync::Arc, time::Instant};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
 
 #[cfg(windows)]
 use is_elevated::is_elevated;
 
 #[cfg(windows)]
-use maxima::{
-    core::background_service::request_registry_setup,
-    util::service::{is_service_running, is_service_valid, register_service_user, start_service},
-};
+use maxima::core::background_service::{request_registry_setup, set_up_service};
 
 use maxima::{
     content::{
         downloader::ZipDownloader,
+        install_template::DEFAULT_INSTALL_TEMPLATE,
         manager::{QueuedGame, QueuedGameBuilder},
-        ContentService,
     },
     core::{
         auth::{
-            context::AuthContext,
+            context::{AuthContext, LOGIN_SCOPES},
+            hardware::HardwareInfo,
             login::{begin_oauth_login_flow, manual_login},
-            nucleus_auth_exchange, nucleus_token_exchange, TokenResponse,
+            nucleus_auth_exchange, nucleus_auth_exchange_or_default_scopes, nucleus_token_exchange,
+            TokenResponse,
         },
+        batch::BatchQueueReport,
         clients::JUNO_PC_CLIENT_ID,
-        cloudsync::CloudSyncLockMode,
-        launch::{self, LaunchMode, LaunchOptions},
+        cloudsync::{CloudSyncFilter, CloudSyncLockMode},
+        concurrency::CancellationToken,
+        friends::FriendRequestOutcome,
+        launch::{self, LaunchMode, LaunchOptions, LaunchPlan},
+        library::InstallState,
+        manifest::{self, MANIFEST_RELATIVE_PATH},
+        save_backup,
         service_layer::{
-            ServiceGetBasicPlayerRequestBuilder, ServiceGetLegacyCatalogDefsRequestBuilder,
-            ServiceLegacyOffer, ServicePlayer, SERVICE_REQUEST_GETBASICPLAYER,
-            SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+            ServiceGetBasicPlayerRequestBuilder, ServiceLayerError, ServicePlayer,
+            SERVICE_REQUEST_GETBASICPLAYER,
         },
-        LockedMaxima, Maxima, MaximaEvent, MaximaOptionsBuilder,
+        system_requirements::RequirementsVerdict,
+        CachedData, LockedMaxima, Maxima, MaximaEvent, MaximaOptionsBuilder,
+    },
+    gameinfo::{
+        channel::{build_channel, set_build_channel, BuildChannel},
+        GameInstallInfo,
     },
     ooa,
     rtm::client::BasicPresence,
     util::{
+        http,
         log::init_logger,
         native::{maxima_dir, take_foreground_focus},
         registry::check_registry_validity,
@@ -54,6 +72,12 @@ lazy_static! {
 
 #[derive(Subcommand, Debug)]
 enum Mode {
+    /// Print the version with the commit, platform and component versions, for bug reports
+    Version {
+        /// Print it as JSON, for scripts
+        #[arg(long)]
+        json: bool,
+    },
     Launch {
         slug: String,
 
@@ -68,19 +92,122 @@ enum Mode {
         /// in place of your real username, and any online LSX requests will fail
         #[arg(long)]
         login: Option<String>,
+
+        /// Work out the executable, arguments, environment and working directory, and
+        /// print them instead of starting the game. No license is requested.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Launch a game from Battlelog, which hands over a comma-separated list of offer IDs.
+    /// The game arguments it sends are read from MAXIMA_LAUNCH_ARGS.
+    LaunchBattlelog {
+        offer_ids: String,
     },
     ListGames,
+    /// List the builds of a game that can be downloaded, with their version and when they went live
+    ListBuilds {
+        slug: String,
+
+        /// Print the builds as JSON, for scripts
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the files in a build of a game, with their sizes and CRC32s. Lists the
+    /// live build unless one is given.
+    ListFiles {
+        slug: String,
+
+        #[arg(long)]
+        build_id: Option<String>,
+
+        /// Print the files as JSON, for scripts
+        #[arg(long)]
+        json: bool,
+    },
     LocateGame {
         path: String,
         slug: String,
     },
+    /// Use a game the EA app or Origin installed, without downloading it again. Only
+    /// finds them on Windows, use locate-game elsewhere.
+    Adopt {
+        slug: Option<String>,
+
+        /// Adopt every owned game that's installed
+        #[arg(long)]
+        all: bool,
+    },
+    /// Install every owned game that isn't installed yet, each in its own folder inside
+    /// --path. Asks which game to install without --all.
+    Install {
+        #[arg(long)]
+        all: bool,
+
+        /// Folder to install the games in
+        #[arg(long)]
+        path: Option<PathBuf>,
+
+        /// Where each game goes inside --path, using {slug}, {name} and {publisher}
+        #[arg(long, default_value = DEFAULT_INSTALL_TEMPLATE)]
+        template: String,
+    },
+    /// Update an installed game, or every installed game that's out of date
+    Update {
+        slug: Option<String>,
+
+        #[arg(long)]
+        all: bool,
+    },
+    /// Finish an install that was interrupted, keeping the files already downloaded
+    Resume {
+        slug: String,
+    },
+    /// Download the files an install gave up on again
+    Retry {
+        slug: String,
+    },
+    /// Print which builds of a game are installed and updated to, or switch to live or
+    /// staged. Staged builds aren't released yet and may be unstable.
+    BuildChannel {
+        slug: String,
+        channel: Option<BuildChannel>,
+    },
+    /// Move an installed game to a new folder
+    Move {
+        slug: String,
+        path: String,
+    },
     CloudSync {
         game_slug: String,
 
         #[arg(long)]
         write: bool,
+
+        /// Show which files would be synced, without transferring anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Release a cloud save lock left behind by a session that exited mid-sync, instead of
+    /// waiting for EA to expire it
+    ReleaseSyncLock,
+    /// List the saves in the cloud for a game, without syncing them
+    CloudManifest {
+        game_slug: String,
+    },
+    /// Put back local saves that a cloud save download replaced. Asks which backup
+    /// to restore unless one is given.
+    RestoreSaveBackup {
+        game_slug: String,
+
+        #[arg(long)]
+        backup: Option<String>,
     },
     AccountInfo,
+    /// Pick which logged in account to use, here and in the other frontends. Asks
+    /// which one unless a user ID is given. Use --login to add another account.
+    SwitchAccount {
+        user_id: Option<String>,
+    },
     CreateAuthCode {
         #[arg(long)]
         client_id: String,
@@ -90,6 +217,16 @@ enum Mode {
         #[arg(long)]
         content_id: String,
     },
+    /// Bundle logs, build info, service and registry state, installed games and recent
+    /// events into a zip to attach to bug reports. Tokens are redacted.
+    Diagnose,
+    /// Print the hashes that identify this machine in license and login requests.
+    /// They should be the same every time, or licenses get requested again.
+    MachineHash {
+        /// Game whose wine prefix to read, which changes the hash outside of Windows
+        #[arg(long)]
+        slug: Option<String>,
+    },
     GetUserById {
         #[arg(long)]
         user_id: String,
@@ -97,9 +234,18 @@ enum Mode {
     GetGameBySlug {
         #[arg(long)]
         slug: String,
+
+        /// Folder the game would be installed to, to check its drive has enough space
+        #[arg(long)]
+        path: Option<PathBuf>,
     },
     TestRTMConnection,
     ListFriends,
+    /// Send a friend request, or accept theirs if they've already sent one
+    AddFriend {
+        /// ID of the user to add, as shown by get-user-by-id or list-friends
+        user_id: String,
+    },
     GetLegacyCatalogDef {
         #[arg(long)]
         offer_id: String,
@@ -113,6 +259,10 @@ enum Mode {
 
         #[arg(long)]
         file: String,
+
+        /// Folder to write the file to, keeping its path inside the build
+        #[arg(long, default_value = ".")]
+        output: PathBuf,
     },
 }
 
@@ -125,6 +275,18 @@ struct Args {
     #[arg(long)]
     #[clap(global = true)]
     login: Option<String>,
+
+    /// Proxy to reach EA through, like http://127.0.0.1:8080. HTTPS_PROXY, HTTP_PROXY and
+    /// ALL_PROXY take precedence when they're set.
+    #[arg(long)]
+    #[clap(global = true)]
+    proxy: Option<String>,
+
+    /// Backups of local saves to keep per game, taken before a cloud save download
+    /// replaces them. 0 to not take any.
+    #[arg(long)]
+    #[clap(global = true)]
+    save_backups: Option<usize>,
 }
 
 #[tokio::main]
@@ -142,16 +304,8 @@ async fn main() {
 #[cfg(windows)]
 async fn native_setup() -> Result<()> {
     if !is_elevated() {
-        if !is_service_valid()? {
-            info!("Installing service...");
-            register_service_user()?;
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-        }
-
-        if !is_service_running()? {
-            info!("Starting service...");
-            start_service().await?;
-        }
+        // The steps are logged as they're taken
+        set_up_service(|_| true).await?;
     }
 
     if let Err(err) = check_registry_validity() {
@@ -175,7 +329,7 @@ async fn native_setup() -> Result<()> {
 }
 
 pub async fn login_flow(login_override: Option<String>) -> Result<TokenResponse> {
-    let mut auth_context = AuthContext::new()?;
+    let mut auth_context = AuthContext::for_login()?;
 
     if let Some(access_token) = &login_override {
         let access_token = if let Some(captures) = MANUAL_LOGIN_PATTERN.captures(&access_token) {
@@ -193,7 +347,9 @@ pub async fn login_flow(login_override: Option<String>) -> Result<TokenResponse>
         };
 
         auth_context.set_access_token(&access_token);
-        let code = nucleus_auth_exchange(&auth_context, JUNO_PC_CLIENT_ID, "code").await?;
+        let code =
+            nucleus_auth_exchange_or_default_scopes(&mut auth_context, JUNO_PC_CLIENT_ID, "code")
+                .await?;
         auth_context.set_code(&code);
     } else {
         begin_oauth_login_flow(&mut auth_context).await?
@@ -218,7 +374,13 @@ pub async fn login_flow(login_override: Option<String>) -> Result<TokenResponse>
 async fn startup() -> Result<()> {
     let args = Args::parse();
 
+    // Doesn't need anything set up, so it works when the rest of Maxima doesn't
+    if let Some(Mode::Version { json }) = args.mode {
+        return print_build_info(json);
+    }
+
     init_logger();
+    http::set_proxy(args.proxy.as_deref())?;
 
     info!("Starting Maxima...");
 
@@ -230,6 +392,7 @@ async fn startup() -> Result<()> {
             game_args: _,
             slug: _,
             ref login,
+            dry_run: _,
         }) = args.mode
         {
             login.is_some()
@@ -244,8 +407,17 @@ async fn startup() -> Result<()> {
         .build()?;
 
     let maxima_arc = Maxima::new_with_options(options).await?;
+    if let Some(count) = args.save_backups {
+        maxima_arc
+            .lock()
+            .await
+            .mut_cloud_sync()
+            .set_backup_retention(count);
+    }
 
-    if !skip_login {
+    // Diagnostics are most wanted when logging in is what's broken, so they don't need it
+    let diagnosing = matches!(args.mode, Some(Mode::Diagnose));
+    if !skip_login && !diagnosing {
         let maxima = maxima_arc.lock().await;
 
         {
@@ -255,6 +427,9 @@ async fn startup() -> Result<()> {
                 info!("Logging in...");
                 let token_res = login_flow(args.login).await?;
                 auth_storage.add_account(&token_res).await?;
+
+                // Anything cached is from the account that was logged in before
+                maxima.invalidate(CachedData::Account);
             }
         }
 
@@ -269,34 +444,30 @@ async fn startup() -> Result<()> {
     // Take back the focus since the browser and bootstrap will take it
     take_foreground_focus()?;
 
-    if args.mode.is_none() {
-        run_interactive(maxima_arc.clone()).await?;
-        return Ok(());
-    }
+    let result = match args.mode {
+        None => run_interactive(maxima_arc.clone()).await,
+        Some(mode) => run_mode(mode, maxima_arc.clone()).await,
+    };
+
+    // Flush cloud saves and download progress, even if the command failed
+    maxima_arc.lock().await.shutdown().await;
+    result
+}
 
-    let mode = args.mode.unwrap();
+async fn run_mode(mode: Mode, maxima_arc: LockedMaxima) -> Result<()> {
     match mode {
+        Mode::Version { json } => print_build_info(json),
         Mode::Launch {
             slug,
             game_path,
             game_args,
             login,
+            dry_run,
         } => {
             let offer_id = if login.is_none() {
                 let mut maxima = maxima_arc.lock().await;
-                let offer = maxima.mut_library().game_by_base_slug(&slug).await;
-                // TODO: ideally this function should return an Error type, but this frontend makes that complicated
-                if let Err(err) = offer {
-                    bail!("Error fetching offer for slug `{}`: {}", slug, err);
-                } else {
-                    let offer = offer.unwrap();
-                    // TODO: could do a match here as well, same problem as above
-                    if offer.is_some() {
-                        offer.unwrap().offer_id().to_owned()
-                    } else {
-                        bail!("No owned offer found for '{}'", slug);
-                    }
-                }
+                let offer = maxima.mut_library().game_by_base_slug(&slug).await?;
+                offer.offer_id().to_owned()
             } else {
                 slug.clone()
             };
@@ -307,24 +478,74 @@ async fn startup() -> Result<()> {
                 game_path,
                 game_args,
                 login,
+                dry_run,
                 maxima_arc.clone(),
             )
             .await
         }
+        Mode::LaunchBattlelog { offer_ids } => {
+            let offer_ids: Vec<&str> = offer_ids.split(',').collect();
+            let (offer_id, slug) = {
+                let mut maxima = maxima_arc.lock().await;
+                let offer = maxima
+                    .mut_library()
+                    .game_by_any_base_offer(&offer_ids)
+                    .await?;
+                (offer.offer_id().to_owned(), offer.slug().to_owned())
+            };
+
+            start_game(&offer_id, &slug, None, Vec::new(), None, maxima_arc.clone()).await
+        }
         Mode::ListGames => list_games(maxima_arc.clone()).await,
+        Mode::ListBuilds { slug, json } => list_builds(maxima_arc.clone(), &slug, json).await,
+        Mode::ListFiles {
+            slug,
+            build_id,
+            json,
+        } => list_files(maxima_arc.clone(), &slug, build_id, json).await,
         Mode::LocateGame { path, slug } => locate_game(maxima_arc.clone(), &path, &slug).await,
-        Mode::CloudSync { game_slug, write } => {
-            do_cloud_sync(maxima_arc.clone(), &game_slug, write).await
+        Mode::Adopt { slug, all } => adopt_games(maxima_arc.clone(), slug, all).await,
+        Mode::Install {
+            all,
+            path,
+            template,
+        } => match (all, path) {
+            (true, Some(path)) => install_all(maxima_arc.clone(), &path, &template).await,
+            (true, None) => bail!("Give the folder to install the games in with --path"),
+            (false, _) => interactive_install_game(maxima_arc.clone()).await,
+        },
+        Mode::Update { slug, all } => update_games(maxima_arc.clone(), slug, all).await,
+        Mode::Resume { slug } => resume_install(maxima_arc.clone(), &slug).await,
+        Mode::Retry { slug } => retry_install(maxima_arc.clone(), &slug).await,
+        Mode::BuildChannel { slug, channel } => update_build_channel(&slug, channel),
+        Mode::Move { slug, path } => move_game(maxima_arc.clone(), &slug, &path).await,
+        Mode::CloudSync {
+            game_slug,
+            write,
+            dry_run,
+        } => do_cloud_sync(maxima_arc.clone(), &game_slug, write, dry_run).await,
+        Mode::ReleaseSyncLock => release_sync_lock(maxima_arc.clone()).await,
+        Mode::CloudManifest { game_slug } => {
+            print_cloud_manifest(maxima_arc.clone(), &game_slug).await
+        }
+        Mode::RestoreSaveBackup { game_slug, backup } => {
+            restore_save_backup(&game_slug, backup).await
         }
         Mode::AccountInfo => print_account_info(maxima_arc.clone()).await,
+        Mode::SwitchAccount { user_id } => switch_account(maxima_arc.clone(), user_id).await,
         Mode::CreateAuthCode { client_id } => {
             create_auth_code(maxima_arc.clone(), &client_id).await
         }
         Mode::JunoTokenRefresh => juno_token_refresh(maxima_arc.clone()).await,
         Mode::ReadLicenseFile { content_id } => read_license_file(&content_id).await,
+        Mode::MachineHash { slug } => print_machine_hash(slug.as_deref()),
+        Mode::Diagnose => diagnose(maxima_arc.clone()).await,
         Mode::ListFriends => list_friends(maxima_arc.clone()).await,
+        Mode::AddFriend { user_id } => add_friend(maxima_arc.clone(), &user_id).await,
         Mode::GetUserById { user_id } => get_user_by_id(maxima_arc.clone(), &user_id).await,
-        Mode::GetGameBySlug { slug } => get_game_by_slug(maxima_arc.clone(), &slug).await,
+        Mode::GetGameBySlug { slug, path } => {
+            get_game_by_slug(maxima_arc.clone(), &slug, path.as_deref()).await
+        }
         Mode::TestRTMConnection => test_rtm_connection(maxima_arc.clone()).await,
         Mode::GetLegacyCatalogDef { offer_id } => {
             get_legacy_catalog_def(maxima_arc.clone(), &offer_id).await
@@ -333,7 +554,8 @@ async fn startup() -> Result<()> {
             offer_id,
             build_id,
             file,
-        } => download_specific_file(maxima_arc.clone(), &offer_id, &build_id, &file).await,
+            output,
+        } => download_specific_file(maxima_arc.clone(), &offer_id, &build_id, &file, &output).await,
     }?;
 
     Ok(())
@@ -369,7 +591,7 @@ async fn interactive_start_game(maxima_arc: LockedMaxima) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
     let mut owned_games = Vec::new();
     let owned_games_strs = {
-        for game in maxima.mut_library().games().await? {
+        for game in maxima.mut_library().launchable_games().await? {
             if !game.base_offer().is_installed().await {
                 continue;
             }
@@ -383,6 +605,11 @@ async fn interactive_start_game(maxima_arc: LockedMaxima) -> Result<()> {
             .collect::<Vec<String>>()
     };
 
+    if owned_games_strs.is_empty() {
+        info!("You don't have any installed games");
+        return Ok(());
+    }
+
     let name = Select::new("What game would you like to play?", owned_games_strs).prompt()?;
     let game = owned_games.iter().find(|g| g.name() == name).unwrap();
 
@@ -399,7 +626,7 @@ async fn interactive_install_game(maxima_arc: LockedMaxima) -> Result<()> {
 
     let game = {
         let mut owned_games = Vec::new();
-        for game in maxima.mut_library().games().await? {
+        for game in maxima.mut_library().launchable_games().await? {
             if game.base_offer().is_installed().await {
                 continue;
             }
@@ -412,6 +639,11 @@ async fn interactive_install_game(maxima_arc: LockedMaxima) -> Result<()> {
             .map(|g| g.name())
             .collect::<Vec<String>>();
 
+        if owned_games_strs.is_empty() {
+            info!("You don't have any games that aren't already installed");
+            return Ok(());
+        }
+
         let name =
             Select::new("What game would you like to install?", owned_games_strs).prompt()?;
         owned_games
@@ -421,15 +653,20 @@ async fn interactive_install_game(maxima_arc: LockedMaxima) -> Result<()> {
             .clone()
     };
 
+    if let Some(reason) = game.unavailable() {
+        bail!("{} can't be installed: {}", game.name(), reason);
+    }
+
     let offer_id = game.base_offer().offer_id().to_owned();
     let slug = game.base_offer().slug().to_owned();
+    let content_id = game.base_offer().offer().content_id().to_owned();
 
     let builds = maxima
         .content_manager()
         .service()
         .available_builds(&offer_id)
         .await?;
-    let build = builds.live_build();
+    let build = builds.channel_build(build_channel(&slug));
     if build.is_none() {
         bail!("Couldn't find a suitable game build");
     }
@@ -458,12 +695,24 @@ async fn interactive_install_game(maxima_arc: LockedMaxima) -> Result<()> {
         return Ok(());
     }
 
+    let real_path = Text::new(
+        "Store the files on another drive and link them to the install path? Enter that folder, or leave empty to skip",
+    )
+    .prompt()?;
+    let real_path = if real_path.is_empty() {
+        None
+    } else {
+        Some(PathBuf::from(real_path))
+    };
+
     let game = QueuedGameBuilder::default()
-        .offer_id(offer_id)
+        .offer_id(offer_id.clone())
         .build_id(build.build_id().to_owned())
         .path(path.clone())
-        .slug(slug) // Needs the slug here for the manifest touchup after installation, which needs to know the wine prefix path
+        .slug(slug.clone()) // Needs the slug here for the manifest touchup after installation, which needs to know the wine prefix path
         .wine_prefix(Some(wine_prefix))
+        .real_path(real_path)
+        .content_id(Some(content_id))
         .build()?;
 
     let start_time = Instant::now();
@@ -474,17 +723,45 @@ async fn interactive_install_game(maxima_arc: LockedMaxima) -> Result<()> {
     loop {
         let mut maxima = maxima_arc.lock().await;
 
+        maxima.update().await;
+
         for event in maxima.consume_pending_events() {
             match event {
-                MaximaEvent::ReceivedLSXRequest(_pid, _request) => (),
+                MaximaEvent::InstallEntryFailed { file, reason, .. } => {
+                    warn!("Failed to download {}: {}", file, reason)
+                }
+                MaximaEvent::InstallFailed(offer_id, files) => {
+                    if let Some(log) = maxima.content_manager().install_log(&offer_id) {
+                        info!("See {} for what happened", log.display());
+                    }
+                    bail!(
+                        "{} files couldn't be downloaded, retry them with `retry {}`",
+                        files.len(),
+                        slug
+                    );
+                }
                 _ => {}
             }
         }
 
-        maxima.update().await;
-
-        if let Some(downloader) = maxima.content_manager().current() {
-            info!("Downloading: {}%/100%", downloader.percentage_done());
+        if let Some(downloader) = maxima.content_manager().download(&offer_id) {
+            match (
+                downloader.verification_progress(),
+                downloader.current_speed_bps(),
+                downloader.eta(),
+            ) {
+                (Some((verified, total)), _, _) => {
+                    info!("Verifying files: {}/{}", verified, total)
+                }
+                (None, Some(speed), Some(eta)) => info!(
+                    "Downloading: {:.1}%/100% ({:.1} MB/s, {}m {:02}s left)",
+                    downloader.percentage_done(),
+                    speed as f64 / 1_000_000.0,
+                    eta.as_secs() / 60,
+                    eta.as_secs() % 60
+                ),
+                _ => info!("Downloading: {:.1}%/100%", downloader.percentage_done()),
+            }
         } else {
             break;
         }
@@ -505,15 +782,168 @@ async fn interactive_install_game(maxima_arc: LockedMaxima) -> Result<()> {
     Ok(())
 }
 
+async fn install_all(maxima_arc: LockedMaxima, path: &Path, template: &str) -> Result<()> {
+    let report = maxima_arc
+        .lock()
+        .await
+        .queue_install_all(path, template)
+        .await?;
+    print_batch_report(&report);
+    wait_for_downloads(maxima_arc).await
+}
+
+async fn update_games(maxima_arc: LockedMaxima, slug: Option<String>, all: bool) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+    if all {
+        let report = maxima.update_all().await?;
+        print_batch_report(&report);
+    } else {
+        let slug = match slug {
+            Some(slug) => slug,
+            None => bail!("Give the slug of a game to update, or --all"),
+        };
+
+        let offer = maxima.mut_library().game_by_base_slug(&slug).await?.clone();
+        maxima.content_manager().add_update(&offer).await?;
+        info!("Queued an update of {}", slug);
+    }
+
+    drop(maxima);
+    wait_for_downloads(maxima_arc).await
+}
+
+async fn resume_install(maxima_arc: LockedMaxima, slug: &str) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+    let offer = maxima.mut_library().game_by_base_slug(slug).await?.clone();
+    match offer.install_state(maxima.content_manager()).await {
+        InstallState::Partial { .. } => {}
+        InstallState::Installed => bail!("{} is already installed", slug),
+        InstallState::External(path) => bail!(
+            "{} was installed by the EA app or Origin at {}, adopt it instead",
+            slug,
+            path.display()
+        ),
+        InstallState::NotInstalled => bail!("{} was never installed, install it instead", slug),
+    }
+
+    maxima.content_manager().resume_install(&offer).await?;
+    info!("Resuming the install of {}", slug);
+
+    drop(maxima);
+    wait_for_downloads(maxima_arc).await
+}
+
+async fn retry_install(maxima_arc: LockedMaxima, slug: &str) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+    let offer_id = match maxima
+        .content_manager()
+        .queue()
+        .failed()
+        .iter()
+        .find(|x| x.slug() == slug)
+    {
+        Some(game) => game.offer_id().to_owned(),
+        None => bail!("No install of {} gave up on any files", slug),
+    };
+
+    maxima.content_manager().retry_failed(&offer_id).await?;
+    info!("Retrying the files {} couldn't download", slug);
+
+    drop(maxima);
+    wait_for_downloads(maxima_arc).await
+}
+
+fn print_batch_report(report: &BatchQueueReport) {
+    for (slug, reason) in &report.skipped {
+        info!("Skipped {}: {}", slug, reason);
+    }
+
+    info!(
+        "Queued {} games, skipped {}",
+        report.queued.len(),
+        report.skipped.len()
+    );
+}
+
+/// Keep Maxima running until every queued download has finished
+async fn wait_for_downloads(maxima_arc: LockedMaxima) -> Result<()> {
+    let mut failed = 0;
+    loop {
+        let mut maxima = maxima_arc.lock().await;
+        maxima.update().await;
+
+        for event in maxima.consume_pending_events() {
+            match event {
+                MaximaEvent::InstallEntryFailed { file, reason, .. } => {
+                    warn!("Failed to download {}: {}", file, reason)
+                }
+                MaximaEvent::InstallFinished(offer_id) => info!("Finished {}", offer_id),
+                MaximaEvent::InstallFailed(offer_id, files) => {
+                    let slug = maxima
+                        .content_manager()
+                        .queue()
+                        .failed()
+                        .iter()
+                        .find(|x| x.offer_id() == &offer_id)
+                        .map_or(offer_id.clone(), |x| x.slug().to_owned());
+                    error!(
+                        "{} files of {} couldn't be downloaded, retry them with `retry {}`",
+                        files.len(),
+                        offer_id,
+                        slug
+                    );
+                    if let Some(log) = maxima.content_manager().install_log(&offer_id) {
+                        info!("See {} for what happened", log.display());
+                    }
+                    failed += 1;
+                }
+                _ => {}
+            }
+        }
+
+        let content_manager = maxima.content_manager();
+        if content_manager.active().is_empty() && content_manager.queue().queued().is_empty() {
+            break;
+        }
+
+        for downloader in content_manager.active() {
+            match downloader.verification_progress() {
+                Some((verified, total)) => info!(
+                    "{}: verifying files {}/{}",
+                    downloader.offer_id(),
+                    verified,
+                    total
+                ),
+                None => info!(
+                    "{}: {:.1}%/100%",
+                    downloader.offer_id(),
+                    downloader.percentage_done()
+                ),
+            }
+        }
+
+        drop(maxima);
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+
+    if failed > 0 {
+        bail!("{} games couldn't be downloaded", failed);
+    }
+
+    info!("All downloads finished");
+    Ok(())
+}
+
 async fn download_specific_file(
     maxima_arc: LockedMaxima,
     offer: &str,
     build_id: &str,
     file: &str,
+    output: &Path,
 ) -> Result<()> {
-    let maxima = maxima_arc.lock().await;
+    let mut maxima = maxima_arc.lock().await;
 
-    let content_service = ContentService::new(maxima.auth_storage().clone());
+    let content_service = maxima.content_manager().service().clone();
     let builds = content_service.available_builds(offer).await?;
     let build = builds.build(build_id);
     if build.is_none() {
@@ -529,7 +959,9 @@ async fn download_specific_file(
 
     debug!("URL: {}", url.url());
 
-    let downloader = ZipDownloader::new("test-game", &url.url(), "C:/DownloadTest").await?;
+    std::fs::create_dir_all(output)?;
+    let output = std::env::current_dir()?.join(output);
+    let downloader = ZipDownloader::new("test-game", &url.url(), output.clone()).await?;
     let num_of_entries = downloader.manifest().entries().len();
     info!("Entries: {}", num_of_entries);
 
@@ -543,12 +975,13 @@ async fn download_specific_file(
     }
 
     let ele = entry.unwrap();
-    downloader.download_single_file(ele, None).await.unwrap();
+    downloader.download_single_file(ele, None).await?;
 
     info!(
-        "Downloaded file {} from game build {}",
+        "Downloaded file {} from game build {} to {}",
         file,
-        build.to_string()
+        build.to_string(),
+        output.display()
     );
     Ok(())
 }
@@ -556,7 +989,7 @@ async fn download_specific_file(
 async fn generate_download_links(maxima_arc: LockedMaxima) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
 
-    let content_service = ContentService::new(maxima.auth_storage().clone());
+    let content_service = maxima.content_manager().service().clone();
 
     let owned_games = maxima.mut_library().games().await?;
     let owned_games_strs = owned_games
@@ -564,6 +997,11 @@ async fn generate_download_links(maxima_arc: LockedMaxima) -> Result<()> {
         .map(|g| g.name())
         .collect::<Vec<String>>();
 
+    if owned_games_strs.is_empty() {
+        info!("You don't own any games");
+        return Ok(());
+    }
+
     let name = Select::new(
         "What game would you like to list builds for?",
         owned_games_strs,
@@ -598,6 +1036,131 @@ async fn generate_download_links(maxima_arc: LockedMaxima) -> Result<()> {
     Ok(())
 }
 
+async fn list_builds(maxima_arc: LockedMaxima, slug: &str, json: bool) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+
+    let offer_id = maxima
+        .mut_library()
+        .game_by_base_slug(slug)
+        .await?
+        .offer_id()
+        .to_owned();
+
+    let builds = maxima
+        .content_manager()
+        .service()
+        .available_builds(&offer_id)
+        .await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&builds.builds)?);
+        return Ok(());
+    }
+
+    for build in &builds.builds {
+        info!("{}: {}", build.build_id(), build.to_string());
+    }
+
+    Ok(())
+}
+
+async fn list_files(
+    maxima_arc: LockedMaxima,
+    slug: &str,
+    build_id: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+
+    let offer_id = maxima
+        .mut_library()
+        .game_by_base_slug(slug)
+        .await?
+        .offer_id()
+        .to_owned();
+
+    let build_id = match build_id {
+        Some(build_id) => build_id,
+        None => {
+            let builds = maxima
+                .content_manager()
+                .service()
+                .available_builds(&offer_id)
+                .await?;
+            match builds.live_build() {
+                Some(build) => build.build_id().to_owned(),
+                None => bail!("{} has no live build", slug),
+            }
+        }
+    };
+
+    let entries = maxima.build_manifest(&offer_id, &build_id).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    for entry in &entries {
+        info!(
+            "{} ({} bytes, {:08x})",
+            entry.name(),
+            entry.uncompressed_size(),
+            entry.crc32()
+        );
+    }
+    info!("{} files in build {}", entries.len(), build_id);
+
+    Ok(())
+}
+
+fn print_build_info(json: bool) -> Result<()> {
+    let info = maxima::build_info();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+    } else {
+        println!("{}", info);
+    }
+
+    Ok(())
+}
+
+async fn switch_account(maxima_arc: LockedMaxima, user_id: Option<String>) -> Result<()> {
+    let maxima = maxima_arc.lock().await;
+
+    {
+        let mut auth_storage = maxima.auth_storage().lock().await;
+        let user_id = match user_id {
+            Some(id) => id,
+            None => {
+                let ids: Vec<String> = auth_storage
+                    .account_ids()
+                    .into_iter()
+                    .map(|x| x.to_owned())
+                    .collect();
+                Select::new("Which account would you like to use?", ids).prompt()?
+            }
+        };
+
+        auth_storage.select_account(&user_id)?;
+    }
+
+    maxima.invalidate(CachedData::Account);
+    let user = maxima.local_user().await?;
+    let player = user
+        .player()
+        .as_ref()
+        .ok_or(ServiceLayerError::MissingField)?;
+    info!("Switched to {}", player.display_name());
+
+    Ok(())
+}
+
+async fn diagnose(maxima_arc: LockedMaxima) -> Result<()> {
+    let path = maxima_arc.lock().await.write_diagnostics().await?;
+    info!("Attach {} to your bug report", path.display());
+    Ok(())
+}
+
 async fn print_account_info(maxima_arc: LockedMaxima) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
     let user = maxima.local_user().await?;
@@ -605,10 +1168,21 @@ async fn print_account_info(maxima_arc: LockedMaxima) -> Result<()> {
     info!("Access Token: {}", maxima.access_token().await?);
     info!("PC Sign: {}", AuthContext::new()?.generate_pc_sign()?);
 
-    let player = user.player().as_ref().unwrap();
+    let player = user
+        .player()
+        .as_ref()
+        .ok_or(ServiceLayerError::MissingField)?;
     info!("Username: {}", player.unique_name());
     info!("User ID: {}", user.id());
     info!("Persona ID: {}", player.psd());
+
+    if let Some(token_info) = maxima.auth_storage().lock().await.token_info().await? {
+        info!("Scopes: {}", token_info.scopes().join(" "));
+        let missing = token_info.missing_scopes(&LOGIN_SCOPES);
+        if !missing.is_empty() {
+            warn!("Missing scopes: {}", missing.join(" "));
+        }
+    }
     Ok(())
 }
 
@@ -627,14 +1201,11 @@ async fn create_auth_code(maxima_arc: LockedMaxima, client_id: &str) -> Result<(
 async fn juno_token_refresh(maxima_arc: LockedMaxima) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
 
-    let mut context = AuthContext::new()?;
+    let mut context = AuthContext::for_login()?;
     context.set_access_token(&maxima.access_token().await?);
 
-    context.add_scope("basic.identity");
-    context.add_scope("basic.persona");
-    context.add_scope("basic.entitlement");
-
-    let code = nucleus_auth_exchange(&context, JUNO_PC_CLIENT_ID, "code").await?;
+    let code =
+        nucleus_auth_exchange_or_default_scopes(&mut context, JUNO_PC_CLIENT_ID, "code").await?;
     context.set_code(&code);
 
     if context.code().is_none() {
@@ -654,20 +1225,33 @@ async fn juno_token_refresh(maxima_arc: LockedMaxima) -> Result<()> {
 }
 
 async fn read_license_file(content_id: &str) -> Result<()> {
-    let path = ooa::get_license_dir(None)?.join(format!("{}.dlf", content_id));
-    let mut data = tokio::fs::read(path).await?;
-    data.drain(0..65); // Signature
-
-    let license = ooa::decrypt_license(data.as_slice())?;
+    let license = ooa::read_saved_license(content_id, None).await?;
     info!("License: {:?}", license);
 
     Ok(())
 }
 
+fn print_machine_hash(slug: Option<&str>) -> Result<()> {
+    // The PC sign is made from version 1
+    info!(
+        "Machine ID: {}",
+        HardwareInfo::cached(1, slug).generate_mid()?
+    );
+
+    // Licenses use the version of the game's OOA
+    for version in 1..=4 {
+        let hash = HardwareInfo::cached(version, slug).generate_hardware_hash();
+        info!("Hardware hash (OOA v{}): {}", version, hash);
+    }
+
+    Ok(())
+}
+
 async fn list_friends(maxima_arc: LockedMaxima) -> Result<()> {
     let maxima = maxima_arc.lock().await;
 
-    for ele in maxima.friends(0).await? {
+    let friends = maxima.all_friends().await?;
+    for ele in friends.friends() {
         info!(
             "{} [ID: {}, Persona ID: {}]",
             ele.display_name(),
@@ -676,6 +1260,25 @@ async fn list_friends(maxima_arc: LockedMaxima) -> Result<()> {
         );
     }
 
+    info!(
+        "{} friends ({} reported by EA)",
+        friends.friends().len(),
+        friends.total_count()
+    );
+
+    Ok(())
+}
+
+async fn add_friend(maxima_arc: LockedMaxima, user_id: &str) -> Result<()> {
+    let maxima = maxima_arc.lock().await;
+
+    match maxima.send_friend_request(user_id).await? {
+        FriendRequestOutcome::Sent => info!("Sent a friend request to {}", user_id),
+        FriendRequestOutcome::Accepted => {
+            info!("{} had already sent a friend request, accepted it", user_id)
+        }
+    }
+
     Ok(())
 }
 
@@ -698,27 +1301,45 @@ async fn get_user_by_id(maxima_arc: LockedMaxima, user_id: &str) -> Result<()> {
     Ok(())
 }
 
-async fn get_game_by_slug(maxima_arc: LockedMaxima, _slug: &str) -> Result<()> {
-    let _maxima = maxima_arc.lock().await;
+async fn get_game_by_slug(
+    maxima_arc: LockedMaxima,
+    slug: &str,
+    install_path: Option<&Path>,
+) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
 
-    // match maxima.owned_game_by_slug(slug).await {
-    //     Ok(game) => info!("Game: {}", game.id()),
-    //     Err(err) => error!("{}", err),
-    // };
+    let game = maxima.mut_library().game_by_base_slug(slug).await?;
+    info!("Game: {}", game.offer().display_name());
+
+    let verdict = match install_path {
+        Some(path) => maxima.meets_requirements_at(slug, Some(path)).await?,
+        None => maxima.meets_requirements(slug).await?,
+    };
+    let verdict = match verdict {
+        RequirementsVerdict::Recommended => "meets the recommended requirements",
+        RequirementsVerdict::Minimum => "meets the minimum requirements",
+        RequirementsVerdict::BelowMinimum => "does not meet the minimum requirements",
+        RequirementsVerdict::Unknown => "could not be compared to the requirements",
+    };
+    info!("This PC {}", verdict);
 
     Ok(())
 }
 
 async fn test_rtm_connection(maxima_arc: LockedMaxima) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
-    let friends = maxima.friends(0).await?;
+    let friends = maxima.all_friends().await?;
 
     let rtm = maxima.rtm();
     rtm.login().await?;
     rtm.set_presence(BasicPresence::Online, "Test", "Origin.OFR.50.0002148")
         .await?;
 
-    let players: Vec<String> = friends.iter().map(|f| f.id().to_owned()).collect();
+    let players: Vec<String> = friends
+        .friends()
+        .iter()
+        .map(|f| f.id().to_owned())
+        .collect();
     info!("Subscribed to {} players", players.len());
 
     rtm.subscribe(&players).await?;
@@ -734,13 +1355,21 @@ async fn test_rtm_connection(maxima_arc: LockedMaxima) -> Result<()> {
                 info!(
                     "{}/{} is {:?}: In {}",
                     friends
+                        .friends()
                         .iter()
                         .find(|x| x.id().to_owned() == *entry.0)
                         .unwrap()
                         .display_name(),
                     entry.0,
                     entry.1.basic(),
-                    entry.1.status()
+                    match entry.1.activity() {
+                        Some(activity) => format!(
+                            "{} ({})",
+                            activity.title().as_deref().unwrap_or(entry.1.status()),
+                            activity.rich_presence().as_deref().unwrap_or("no details")
+                        ),
+                        None => entry.1.status().to_owned(),
+                    }
                 );
             }
         }
@@ -753,37 +1382,102 @@ async fn test_rtm_connection(maxima_arc: LockedMaxima) -> Result<()> {
 
 async fn get_legacy_catalog_def(maxima_arc: LockedMaxima, offer_id: &str) -> Result<()> {
     let maxima = maxima_arc.lock().await;
-    let defs: Vec<ServiceLegacyOffer> = maxima
-        .service_layer()
-        .request(
-            SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
-            ServiceGetLegacyCatalogDefsRequestBuilder::default()
-                .offer_ids(vec![offer_id.to_owned()])
-                .locale(maxima.locale().clone())
-                .build()?,
-        )
-        .await?;
+    let def = maxima.legacy_offer(offer_id).await?;
 
-    info!("Content ID: {}", defs[0].content_id());
+    info!("Name: {}", def.display_name());
+    info!("Content ID: {}", def.content_id());
+    if let Some(multiplayer_id) = def.multiplayer_id() {
+        info!("Multiplayer ID: {}", multiplayer_id);
+    }
     Ok(())
 }
 
 async fn list_games(maxima_arc: LockedMaxima) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
 
-    info!("Owned games:");
+    let states = maxima.reconcile_installs().await?;
     let titles = maxima.mut_library().games().await?;
+    if titles.is_empty() {
+        info!("You don't own any games");
+        return Ok(());
+    }
+
+    info!("Owned games:");
 
     for title in titles {
+        let status = match title.trial() {
+            _ if title.is_disabled() => " (Can't launch: refunded or revoked)".to_owned(),
+            Some(trial) if trial.is_expired() => " (Trial, expired)".to_owned(),
+            Some(trial) => match trial.seconds_remaining() {
+                Some(seconds) => format!(" (Trial, {} minutes left)", seconds / 60),
+                None => " (Trial)".to_owned(),
+            },
+            None => String::new(),
+        };
+        let status = match title.unavailable() {
+            Some(reason) => format!("{} (Unavailable: {})", status, reason),
+            None => status,
+        };
+
+        let ownership = title
+            .ownership_method()
+            .map(|x| x.label())
+            .unwrap_or("Unknown");
+        let platforms = title
+            .platforms()
+            .iter()
+            .map(|x| x.label())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        let state = states.get(title.base_offer().slug());
+        let installed = state == Some(&InstallState::Installed);
+        let mut usage = Vec::new();
+        if installed {
+            match title.base_offer().installed_size().await {
+                Ok(Some(size)) => usage.push(format!("{:.1} GB", size as f64 / 1_000_000_000.0)),
+                Ok(None) => {}
+                Err(err) => warn!("Failed to measure {}: {}", title.base_offer().slug(), err),
+            }
+        }
+        if let Some(last_played) = title.base_offer().stats().last_played() {
+            usage.push(format!("last played {}", last_played.format("%Y-%m-%d")));
+        }
+        let usage = match usage.is_empty() {
+            true => String::new(),
+            false => format!(" ({})", usage.join(", ")),
+        };
+        let status = match state {
+            Some(InstallState::Partial { queued: true }) => {
+                format!("{} (Partially installed, resumes on its own)", status)
+            }
+            Some(InstallState::Partial { queued: false }) => {
+                format!("{} (Partially installed, finish with `resume`)", status)
+            }
+            Some(InstallState::External(_)) => {
+                format!(
+                    "{} (Installed by the EA app, take it over with `adopt`)",
+                    status
+                )
+            }
+            _ => status,
+        };
+
         info!(
-            "{:<width$} - {:<width2$} - {:<width3$} - Installed: {}",
+            "{:<width$} - {:<width2$} - {:<width3$} - {:<width4$} - {:<width5$} - Installed: {}{}{}",
             title.base_offer().slug(),
             title.name(),
             title.base_offer().offer_id(),
-            title.base_offer().is_installed().await,
+            ownership,
+            platforms,
+            installed,
+            usage,
+            status,
             width = 35,
             width2 = 35,
             width3 = 25,
+            width4 = 22,
+            width5 = 11,
         );
 
         for game in title.extra_offers() {
@@ -801,18 +1495,110 @@ async fn list_games(maxima_arc: LockedMaxima) -> Result<()> {
 }
 
 async fn locate_game(maxima_arc: LockedMaxima, path: &str, slug: &str) -> Result<()> {
-    let path = PathBuf::from(path);
-    info!("Installed!");
+    let mut maxima = maxima_arc.lock().await;
+    let content_id = maxima
+        .mut_library()
+        .game_by_base_slug(slug)
+        .await?
+        .offer()
+        .content_id()
+        .to_owned();
+    drop(maxima);
+
+    let path = PathBuf::from(path.trim_end_matches(['/', '\\']));
+    let manifest = manifest::read(path.join(MANIFEST_RELATIVE_PATH)).await?;
+    manifest.check_for(&content_id)?;
+
+    #[cfg(unix)]
+    let wine_prefix = {
+        let input =
+            Text::new("Where is the game's Wine prefix? (must be an absolute path)").prompt()?;
+        Some(PathBuf::from(input))
+    };
+
+    #[cfg(not(unix))]
+    let wine_prefix = None;
+
+    GameInstallInfo::new(path.clone(), wine_prefix).save_to_json(slug);
+    manifest.run_touchup(&path, slug).await?;
+
+    info!("Located `{}` at {}", slug, path.display());
     Ok(())
 }
 
-async fn do_cloud_sync(maxima_arc: LockedMaxima, game_slug: &str, write: bool) -> Result<()> {
+async fn adopt_games(maxima_arc: LockedMaxima, slug: Option<String>, all: bool) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+
+    if all {
+        let adopted = maxima.adopt_existing_installs().await?;
+        if adopted.is_empty() {
+            info!("No games installed by the EA app or Origin were found");
+        }
+        for (slug, path) in adopted {
+            info!("Adopted `{}` at {}", slug, path.display());
+        }
+        return Ok(());
+    }
+
+    let slug = match slug {
+        Some(slug) => slug,
+        None => bail!("Give the slug of a game to adopt, or --all"),
+    };
+
+    let offer = maxima.mut_library().game_by_base_slug(&slug).await?.clone();
+    let path = match offer.existing_install().await {
+        Some(path) => path,
+        None => bail!(
+            "No install of `{}` by the EA app or Origin was found, try locate-game",
+            slug
+        ),
+    };
+
+    offer.adopt(&path, None).await?;
+    info!("Adopted `{}` at {}", slug, path.display());
+    Ok(())
+}
+
+async fn move_game(maxima_arc: LockedMaxima, slug: &str, path: &str) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+    let offer_id = maxima
+        .mut_library()
+        .game_by_base_slug(slug)
+        .await?
+        .offer_id()
+        .to_owned();
+
+    let install_move =
+        maxima
+            .content_manager()
+            .prepare_move(&offer_id, slug, PathBuf::from(path))?;
+    drop(maxima);
+
+    let last_percent = AtomicU64::new(0);
+    install_move
+        .run(Some(Box::new(move |copied, total| {
+            let percent = copied * 100 / total.max(1);
+            if last_percent.swap(percent, Ordering::Relaxed) != percent {
+                info!("Copying files... {}%", percent);
+            }
+        })))
+        .await?;
+
+    info!("Moved `{}` to {}", slug, path);
+    Ok(())
+}
+
+async fn do_cloud_sync(
+    maxima_arc: LockedMaxima,
+    game_slug: &str,
+    write: bool,
+    dry_run: bool,
+) -> Result<()> {
     let mut maxima = maxima_arc.lock().await;
     let offer = maxima
         .mut_library()
         .game_by_base_slug(game_slug)
         .await?
-        .unwrap()
         .clone();
 
     info!("Got offer");
@@ -826,34 +1612,187 @@ async fn do_cloud_sync(maxima_arc: LockedMaxima, game_slug: &str, write: bool) -
             } else {
                 CloudSyncLockMode::Read
             },
+            &CloudSyncFilter::default(),
         )
         .await?;
-    let res = lock.sync_files().await;
+
+    // Stop between files on Ctrl+C, so the lock still gets released
+    let cancel = CancellationToken::new();
+    let ctrl_c_cancel = cancel.clone();
+    let ctrl_c = tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            warn!("Stopping cloud sync...");
+            ctrl_c_cancel.cancel();
+        }
+    });
+
+    let res = lock.sync_files(dry_run, &cancel).await;
+    ctrl_c.abort();
     lock.release().await?;
-    res?;
+    let plan = res?;
+
+    if dry_run {
+        let (transfer, action) = if write {
+            (&plan.upload, "Would upload")
+        } else {
+            (&plan.download, "Would download")
+        };
+
+        for path in transfer {
+            info!("{} {}", action, path.display());
+        }
+        for path in &plan.skipped {
+            info!("Would skip {}", path.display());
+        }
+
+        if transfer.is_empty() {
+            info!("Nothing to sync");
+        }
+    }
 
     info!("Done");
 
     Ok(())
 }
 
+async fn release_sync_lock(maxima_arc: LockedMaxima) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+    if maxima.release_orphaned_lock().await? {
+        info!("Released the leftover cloud save lock");
+    } else {
+        info!("No cloud save lock was left behind");
+    }
+
+    Ok(())
+}
+
+async fn print_cloud_manifest(maxima_arc: LockedMaxima, game_slug: &str) -> Result<()> {
+    let mut maxima = maxima_arc.lock().await;
+    let offer = maxima
+        .mut_library()
+        .game_by_base_slug(game_slug)
+        .await?
+        .clone();
+
+    let manifest = maxima.cloud_sync().fetch_manifest(&offer).await?;
+    for file in manifest.file() {
+        info!(
+            "{} [{} bytes, md5 {}] at {}",
+            file.local_name(),
+            file.attr_size(),
+            file.attr_md5().as_deref().unwrap_or("unknown"),
+            file.attr_href()
+        );
+    }
+
+    if manifest.file().is_empty() {
+        info!("No saves in the cloud");
+    }
+
+    Ok(())
+}
+
+fn update_build_channel(slug: &str, channel: Option<BuildChannel>) -> Result<()> {
+    let channel = match channel {
+        Some(channel) => channel,
+        None => {
+            info!("{} uses the {:?} channel", slug, build_channel(slug));
+            return Ok(());
+        }
+    };
+
+    set_build_channel(slug, channel)?;
+    if channel == BuildChannel::Staged {
+        warn!("Staged builds aren't released yet, and may be unstable");
+    }
+    info!(
+        "{} will be installed and updated from the {:?} channel",
+        slug, channel
+    );
+
+    Ok(())
+}
+
+async fn restore_save_backup(game_slug: &str, backup: Option<String>) -> Result<()> {
+    let id = match backup {
+        Some(id) => id,
+        None => {
+            let ids: Vec<String> = save_backup::save_backups(game_slug)
+                .await?
+                .iter()
+                .map(|x| x.id().to_owned())
+                .collect();
+            if ids.is_empty() {
+                info!("{} has no save backups", game_slug);
+                return Ok(());
+            }
+
+            Select::new("Which backup would you like to restore?", ids).prompt()?
+        }
+    };
+
+    let backup = save_backup::restore_save_backup(game_slug, Some(&id)).await?;
+    for path in backup.files() {
+        info!("Restored {}", path.display());
+    }
+
+    Ok(())
+}
+
+fn print_launch_plan(plan: &LaunchPlan) {
+    if let Some(url) = plan.battlelog() {
+        info!("Battlelog would be opened at {} instead of the game", url);
+        return;
+    }
+
+    info!("Executable: {}", plan.path());
+    info!("Arguments: {:?}", plan.args());
+    info!("Working directory: {}", plan.working_dir().display());
+    info!(
+        "License: {}",
+        if *plan.requests_license() {
+            "a new one would be requested"
+        } else {
+            "the saved one is still valid"
+        }
+    );
+    if *plan.lsx_listening() {
+        info!("LSX server: listening on port {}", plan.lsx_port());
+    } else {
+        warn!(
+            "LSX server: nothing is listening on port {}",
+            plan.lsx_port()
+        );
+    }
+
+    info!("Environment:");
+    for (key, value) in plan.env() {
+        info!("  {}={}", key, value);
+    }
+}
+
 async fn start_game(
     offer_id: &str,
     slug: &str,
     game_path_override: Option<String>,
     game_args: Vec<String>,
     login: Option<String>,
+    dry_run: bool,
     maxima_arc: LockedMaxima,
 ) -> Result<()> {
     {
         let mut maxima = maxima_arc.lock().await;
         maxima.start_lsx(maxima_arc.clone()).await?;
 
-        if login.is_none() {
+        if login.is_none() && !dry_run {
             maxima.rtm().login().await?;
 
-            let friends = maxima.friends(0).await?;
-            let players: Vec<String> = friends.iter().map(|f| f.id().to_owned()).collect();
+            let friends = maxima.all_friends().await?;
+            let players: Vec<String> = friends
+                .friends()
+                .iter()
+                .map(|f| f.id().to_owned())
+                .collect();
             info!("Subscribed to {} players", players.len());
 
             maxima.rtm().subscribe(&players).await?;
@@ -864,27 +1803,34 @@ async fn start_game(
         path_override: game_path_override,
         arguments: game_args,
         cloud_saves: true,
+        cloud_sync_filter: CloudSyncFilter::default(),
+        cancel: CancellationToken::new(),
+        join: None,
+        env: HashMap::new(),
+        license_request: None,
+        working_dir: None,
     };
 
-    if login.is_none() {
-        launch::start_game(
-            maxima_arc.clone(),
-            LaunchMode::Online(offer_id.to_owned()),
-            launch_options,
-        )
-        .await?;
-    } else if let Some(captures) = MANUAL_LOGIN_PATTERN.captures(&login.unwrap()) {
-        let persona = &captures[1];
-        let password = &captures[2];
-
-        launch::start_game(
-            maxima_arc.clone(),
-            LaunchMode::OnlineOffline(offer_id.to_owned(), persona.to_owned(), password.to_owned()),
-            launch_options,
-        )
-        .await?;
+    let mode = match login {
+        None => LaunchMode::Online(offer_id.to_owned()),
+        Some(login) => match MANUAL_LOGIN_PATTERN.captures(&login) {
+            Some(captures) => LaunchMode::OnlineOffline(
+                offer_id.to_owned(),
+                captures[1].to_owned(),
+                captures[2].to_owned(),
+            ),
+            None => bail!("--login must be given as persona:password"),
+        },
+    };
+
+    if dry_run {
+        let plan = launch::dry_run_game(maxima_arc.clone(), mode, launch_options).await?;
+        print_launch_plan(&plan);
+        return Ok(());
     }
 
+    launch::start_game(maxima_arc.clone(), mode, launch_options).await?;
+
     loop {
         let mut maxima = maxima_arc.lock().await;
 
@@ -897,6 +1843,8 @@ async fn start_game(
 
         maxima.update().await;
         if maxima.playing().is_none() {
+            // Saves are uploaded in the background, exiting would cut that off
+            maxima.finish_saves_upload().await;
             break;
         }
 
@@ -1,3 +1,33 @@
+use std::{
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 fn main() -> std::io::Result<()> {
-    prost_build::compile_protos(&["src/rtm/proto/rtm.proto"], &["src/rtm/proto/"])
+    prost_build::compile_protos(&["src/rtm/proto/rtm.proto"], &["src/rtm/proto/"])?;
+
+    // For util::build_info. Reproducible builds set SOURCE_DATE_EPOCH to pin the timestamp.
+    let git_sha = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|x| x.status.success())
+        .and_then(|x| String::from_utf8(x.stdout).ok())
+        .map(|x| x.trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+    let timestamp = std::env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|x| x.as_secs())
+            .unwrap_or_default()
+            .to_string()
+    });
+
+    println!("cargo:rustc-env=MAXIMA_GIT_SHA={}", git_sha);
+    println!("cargo:rustc-env=MAXIMA_BUILD_TIMESTAMP={}", timestamp);
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    Ok(())
 }
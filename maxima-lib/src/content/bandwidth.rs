@@ -0,0 +1,116 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Token bucket shared by every active download, capping their combined speed
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    /// Bytes per second, 0 when unlimited
+    limit: AtomicU64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Bytes that can be taken without waiting. Goes negative when downloads are ahead of the limit.
+    available: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    /// Take `bytes` out of the bucket, returning how long to wait before using them
+    fn reserve(&mut self, now: Instant, bytes: usize, limit: u64) -> Option<Duration> {
+        let limit = limit as f64;
+        let elapsed = now.saturating_duration_since(self.last_refill);
+        self.last_refill = now;
+
+        // Allow bursts of up to a second's worth
+        self.available = (self.available + elapsed.as_secs_f64() * limit).min(limit);
+        self.available -= bytes as f64;
+
+        if self.available >= 0.0 {
+            None
+        } else {
+            Some(Duration::from_secs_f64(-self.available / limit))
+        }
+    }
+}
+
+impl BandwidthLimiter {
+    pub fn new(limit: Option<u64>) -> Self {
+        Self {
+            limit: AtomicU64::new(limit.unwrap_or(0)),
+            bucket: Mutex::new(Bucket {
+                available: 0.0,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> Option<u64> {
+        match self.limit.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Change the limit, in bytes per second. Takes effect for downloads already in progress.
+    pub fn set_limit(&self, limit: Option<u64>) {
+        self.limit.store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    /// Wait until `bytes` more can be downloaded without going over the limit
+    pub async fn acquire(&self, bytes: usize) {
+        let limit = match self.limit() {
+            Some(limit) => limit,
+            None => return,
+        };
+
+        let wait = self
+            .bucket
+            .lock()
+            .unwrap()
+            .reserve(Instant::now(), bytes, limit);
+        if let Some(wait) = wait {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserve_waits_for_debt() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            available: 0.0,
+            last_refill: start,
+        };
+
+        // Two downloads taking half a second's worth each, at the same moment
+        assert_eq!(
+            bucket.reserve(start, 500, 1000),
+            Some(Duration::from_millis(500))
+        );
+        assert_eq!(
+            bucket.reserve(start, 500, 1000),
+            Some(Duration::from_secs(1))
+        );
+
+        // Paid off after a second, and the bucket never holds more than a second's worth
+        assert_eq!(
+            bucket.reserve(start + Duration::from_secs(5), 1000, 1000),
+            None
+        );
+        assert_eq!(
+            bucket.reserve(start + Duration::from_secs(5), 500, 1000),
+            Some(Duration::from_millis(500))
+        );
+    }
+}
@@ -4,18 +4,24 @@ use std::{
     path::{Path, PathBuf},
     pin::Pin,
     prelude,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
     task,
+    time::Duration,
 };
 
 use crate::{
     content::{
+        bandwidth::BandwidthLimiter,
         manager::DownloaderError,
-        zip::{CompressionType, ZipFile, ZipFileEntry},
+        zip::{CompressionType, ZipArchiveId, ZipFile, ZipFileEntry},
         zlib::{restore_zlib_state, write_zlib_state},
     },
     util::{
         hash::hash_file_crc32,
+        http,
         native::{maxima_dir, NativeError, SafeParent, SafeStr},
     },
 };
@@ -24,7 +30,7 @@ use async_trait::async_trait;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 use derive_getters::Getters;
 use flate2::bufread::DeflateDecoder as BufreadDeflateDecoder;
-use futures::{Stream, StreamExt, TryStreamExt};
+use futures::{future::BoxFuture, Stream, StreamExt, TryStreamExt};
 use log::{debug, error, warn};
 use reqwest::Client;
 use strum_macros::Display;
@@ -36,8 +42,51 @@ use tokio::{
 };
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 
+/// Attempts at downloading an entry before giving up on it
+const ENTRY_DOWNLOAD_TRIES: u32 = 5;
+
+/// Failed attempts on a mirror, across every entry, before downloads move to the next one
+const MIRROR_FAILURES: u32 = 3;
+
+/// Requests new URLs for a zip once all of its mirrors keep failing, since signed CDN
+/// URLs can expire partway through a long download. `None` if it couldn't.
+pub type UrlRefresher = Box<dyn Fn() -> BoxFuture<'static, Option<Vec<String>>> + Send + Sync>;
+
+/// Where inflate state for in-progress downloads is kept
+pub(crate) fn zstate_root() -> Result<PathBuf, NativeError> {
+    Ok(maxima_dir()?.join("temp/downloader"))
+}
+
+/// Kept with an offer's inflate state, holding the folder its download extracts to.
+/// Inflate state files all end in `.eazstate`, so it can't clash with one.
+const ZSTATE_DESTINATION: &str = "destination";
+
+/// Record that `id` is being extracted to `path`, see [`zstate_destination`]
+pub(crate) fn save_zstate_destination(id: &str, path: &Path) -> Result<(), NativeError> {
+    let dir = zstate_root()?.join(id);
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(
+        dir.join(ZSTATE_DESTINATION),
+        path.to_string_lossy().as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// The folder an unfinished download of `id` was extracting to, if there is one
+pub(crate) fn zstate_destination(id: &str) -> Option<PathBuf> {
+    let file = zstate_root().ok()?.join(id).join(ZSTATE_DESTINATION);
+    std::fs::read_to_string(file).ok().map(PathBuf::from)
+}
+
+/// Forget where `id` was extracting to, once its download is done
+pub(crate) fn clear_zstate_destination(id: &str) {
+    if let Ok(root) = zstate_root() {
+        let _ = std::fs::remove_file(root.join(id).join(ZSTATE_DESTINATION));
+    }
+}
+
 fn zstate_path(id: &str, path: &str) -> Result<PathBuf, DownloaderError> {
-    let mut path = maxima_dir()?.join("temp/downloader").join(id).join(path);
+    let mut path = zstate_root()?.join(id).join(path);
     path.set_extension("eazstate");
     std::fs::create_dir_all(path.safe_parent()?)?;
     Ok(path)
@@ -171,6 +220,8 @@ struct AsyncWriterWrapper<'a> {
     zlib_state_file: std::fs::File,
     decoder: &'a mut Box<dyn DownloadDecoder>,
     inner: Arc<Mutex<dyn AsyncWriteWrapper>>,
+    /// Compressed bytes the decoder has taken so far, where a retry picks up from
+    written: &'a mut i64,
 }
 
 impl<'a> AsyncWriterWrapper<'a> {
@@ -178,6 +229,7 @@ impl<'a> AsyncWriterWrapper<'a> {
         id: String,
         path: String,
         decoder: &'a mut Box<dyn DownloadDecoder>,
+        written: &'a mut i64,
     ) -> Result<Self, DownloaderError> {
         let inner = decoder.get_mut();
         Ok(AsyncWriterWrapper {
@@ -189,6 +241,7 @@ impl<'a> AsyncWriterWrapper<'a> {
                 .open(zstate_path(&id, &path)?)?,
             decoder,
             inner,
+            written,
         })
     }
 }
@@ -205,6 +258,10 @@ impl<'a> AsyncWrite for AsyncWriterWrapper<'a> {
             inner.poll_write(cx, buf)
         };
 
+        if let task::Poll::Ready(Ok(count)) = poll_result {
+            *self.written += count as i64;
+        }
+
         // State serialization is disabled for now.
         // let mut bytes = BytesMut::new();
         // self.decoder.save_state(&mut bytes);
@@ -246,10 +303,26 @@ pub enum DownloadError {
     },
 }
 
+/// Why an extracted file doesn't match its manifest entry
+#[derive(Error, Debug)]
+pub enum EntryMismatch {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error("the file is missing")]
+    Missing,
+    #[error("the file is {actual} bytes instead of {expected}")]
+    Size { expected: u64, actual: u64 },
+    #[error("the file's CRC32 is {actual:08x} instead of {expected:08x}")]
+    Crc32 { expected: u32, actual: u32 },
+}
+
 #[derive(PartialEq, Debug)]
 enum EntryDownloadState {
     Fresh,
     Resumable,
+    /// A stored entry that was cut off after this many bytes, the rest can be requested on its own
+    Partial(u64),
     Complete,
     Borked,
 }
@@ -259,36 +332,157 @@ struct DownloadContext {
     path: PathBuf,
 }
 
+/// The URLs a zip can be downloaded from, preferred first. Downloads stay on one until it
+/// keeps failing, then move on to the next. Once every one has failed they start over,
+/// with new URLs if there's a refresher.
+///
+/// Entries are read from whichever mirror is current, at the offsets of the central
+/// directory read at the start, so mirrors are only moved to if they serve the same archive.
+struct Mirrors {
+    urls: RwLock<Vec<String>>,
+    current: AtomicUsize,
+    /// Failed attempts on the current mirror since the last success
+    failures: AtomicU32,
+    refresher: Option<UrlRefresher>,
+    /// The archive downloads started on, which other mirrors are checked against
+    archive: Option<ZipArchiveId>,
+    /// Held while moving on, so entries failing at the same time only move once
+    moving: tokio::sync::Mutex<()>,
+}
+
+impl Mirrors {
+    fn new(urls: Vec<String>, current: usize) -> Self {
+        Self {
+            urls: RwLock::new(urls),
+            current: AtomicUsize::new(current),
+            failures: AtomicU32::new(0),
+            refresher: None,
+            archive: None,
+            moving: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Whether `url` serves the archive downloads started on
+    async fn serves_same_archive(&self, url: &str) -> bool {
+        let archive = match &self.archive {
+            Some(archive) => archive,
+            None => return true,
+        };
+
+        match ZipFile::fetch(url).await {
+            Ok(zip) if zip.archive_id() == archive => true,
+            Ok(_) => {
+                warn!("A download mirror serves a different archive, skipping it");
+                false
+            }
+            Err(err) => {
+                warn!("Failed to check a download mirror: {}", err);
+                false
+            }
+        }
+    }
+
+    fn current(&self) -> String {
+        let urls = self.urls.read().unwrap();
+        urls[self.current.load(Ordering::SeqCst) % urls.len()].clone()
+    }
+
+    fn succeeded(&self) {
+        self.failures.store(0, Ordering::SeqCst);
+    }
+
+    /// Count a failed attempt on `url`, moving on once it has failed too often
+    async fn failed(&self, url: &str) {
+        // Another entry already moved on from it
+        if self.current() != url {
+            return;
+        }
+
+        if self.failures.fetch_add(1, Ordering::SeqCst) + 1 < MIRROR_FAILURES {
+            return;
+        }
+
+        let _moving = self.moving.lock().await;
+        if self.current() != url {
+            return;
+        }
+        self.failures.store(0, Ordering::SeqCst);
+
+        let current = self.current.load(Ordering::SeqCst);
+        let urls = self.urls.read().unwrap().clone();
+        for (next, url) in urls.iter().enumerate().skip(current + 1) {
+            if self.serves_same_archive(url).await {
+                warn!(
+                    "Download mirror {} keeps failing, switching to mirror {}",
+                    current, next
+                );
+                self.current.store(next, Ordering::SeqCst);
+                return;
+            }
+        }
+
+        if let Some(refresher) = &self.refresher {
+            let mut verified = Vec::new();
+            for url in refresher().await.unwrap_or_default() {
+                if self.serves_same_archive(&url).await {
+                    verified.push(url);
+                }
+            }
+
+            if verified.is_empty() {
+                warn!("Every download mirror keeps failing, and no usable new URLs were given");
+            } else {
+                warn!("Every download mirror keeps failing, switching to new URLs");
+                *self.urls.write().unwrap() = verified;
+            }
+        } else {
+            warn!("Every download mirror keeps failing, starting over");
+        }
+        self.current.store(0, Ordering::SeqCst);
+    }
+}
+
 type BytesDownloadedCallback = Box<dyn Fn(usize) + Send + Sync>;
 
 struct EntryDownloadRequest<'a> {
     context: &'a DownloadContext,
-    url: &'a str,
+    mirrors: &'a Mirrors,
     entry: &'a ZipFileEntry,
     client: Client,
     decoder: Box<dyn DownloadDecoder>,
     callback: Option<BytesDownloadedCallback>,
+    limiter: Option<Arc<BandwidthLimiter>>,
+    written: i64,
 }
 
 impl<'a> EntryDownloadRequest<'a> {
     pub fn new(
         context: &'a DownloadContext,
-        url: &'a str,
+        mirrors: &'a Mirrors,
         entry: &'a ZipFileEntry,
         client: Client,
         decoder: Box<dyn DownloadDecoder>,
         callback: Option<BytesDownloadedCallback>,
+        limiter: Option<Arc<BandwidthLimiter>>,
     ) -> Self {
         Self {
             context,
-            url,
+            mirrors,
             entry,
             client,
             decoder,
             callback,
+            limiter,
+            written: 0,
         }
     }
 
+    /// Continue an entry that already has `written` bytes on disk
+    fn resume_from(mut self, written: i64) -> Self {
+        self.written = written;
+        self
+    }
+
     async fn state(
         context: &DownloadContext,
         entry: &ZipFileEntry,
@@ -310,6 +504,15 @@ impl<'a> EntryDownloadRequest<'a> {
                 return Ok(EntryDownloadState::Borked);
             }
 
+            // Stored entries are written straight through, so what's on disk is exactly the
+            // start of the entry. Inflated ones would need the zlib state. The state file only
+            // sticks around while the entry is unfinished, otherwise this may be an older build's file.
+            if entry.compression_type() == &CompressionType::None
+                && zstate_path(&context.id, entry.name())?.exists()
+            {
+                return Ok(EntryDownloadState::Partial(file_size as u64));
+            }
+
             return Ok(EntryDownloadState::Borked);
         }
 
@@ -331,13 +534,15 @@ impl<'a> EntryDownloadRequest<'a> {
         Ok(EntryDownloadState::Complete)
     }
 
-    async fn download(&mut self) -> Result<(), DownloadError> {
+    /// Download the rest of the entry. Failed attempts are retried from the last
+    /// byte the decoder took, giving up after [`ENTRY_DOWNLOAD_TRIES`]. Attempts go to
+    /// whichever mirror is current, see [`Mirrors`].
+    async fn download(&mut self) -> Result<(), DownloaderError> {
         let mut tries = 0;
-        while tries < 5 {
-            // State serialization is disabled for now.
-            //let start = self.decoder.write_in_pos() as i64;
-
-            let start = 0;
+        loop {
+            // State serialization is disabled for now, so this only
+            // carries over between tries and from partial stored entries.
+            let start = self.written;
 
             debug!(
                 "Downloading {} from {} to {} ({})",
@@ -348,29 +553,41 @@ impl<'a> EntryDownloadRequest<'a> {
             );
             let end = *self.entry.compressed_size();
 
-            let result = self.download_range(start, end).await;
+            let url = self.mirrors.current();
+            let result = self.download_range(&url, start, end).await;
             if result.is_ok() {
-                break;
+                self.mirrors.succeeded();
+                return Ok(());
             }
+            self.mirrors.failed(&url).await;
 
             tries += 1;
-        }
+            if tries >= ENTRY_DOWNLOAD_TRIES {
+                return result;
+            }
 
-        Ok(())
+            warn!(
+                "Retrying {} from byte {} ({}/{})",
+                self.entry.name(),
+                self.written,
+                tries,
+                ENTRY_DOWNLOAD_TRIES - 1
+            );
+            tokio::time::sleep(Duration::from_secs(tries as u64)).await;
+        }
     }
 
     /// End is not inclusive
-    pub async fn download_range(&mut self, start: i64, end: i64) -> Result<(), DownloaderError> {
+    pub async fn download_range(
+        &mut self,
+        url: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<(), DownloaderError> {
         let offset = self.entry.data_offset();
         let range = format!("bytes={}-{}", offset + start as i64, offset + end - 1);
 
-        let data = match self
-            .client
-            .get(self.url)
-            .header("range", range)
-            .send()
-            .await
-        {
+        let data = match self.client.get(url).header("range", range).send().await {
             Ok(res) => res,
             Err(err) => {
                 error!("Failed to download ({}): {}", self.entry.name(), err);
@@ -381,7 +598,21 @@ impl<'a> EntryDownloadRequest<'a> {
             }
         };
 
-        let stream = data.bytes_stream();
+        // Anything else would be an error page, which mustn't end up in the file
+        if data.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+            return Err(DownloaderError::Http(data.status()));
+        }
+
+        let limiter = self.limiter.clone();
+        let stream = Box::pin(data.bytes_stream().then(move |chunk| {
+            let limiter = limiter.clone();
+            async move {
+                if let (Some(limiter), Ok(chunk)) = (limiter, &chunk) {
+                    limiter.acquire(chunk.len()).await;
+                }
+                chunk
+            }
+        }));
         let counting_stream = ByteCountingStream::new(stream, self.callback.as_ref());
         let stream = counting_stream.into_async_read();
         let mut stream_reader = BufReader::new(stream.compat());
@@ -394,6 +625,7 @@ impl<'a> EntryDownloadRequest<'a> {
             self.context.id.to_owned(),
             self.entry.name().to_owned(),
             &mut self.decoder,
+            &mut self.written,
         )
         .await?;
 
@@ -412,10 +644,12 @@ impl<'a> EntryDownloadRequest<'a> {
 #[derive(Getters)]
 pub struct ZipDownloader {
     id: String,
-    url: String,
+    #[getter(skip)]
+    mirrors: Mirrors,
     path: PathBuf,
     client: Client,
     manifest: ZipFile,
+    bandwidth_limiter: Option<Arc<BandwidthLimiter>>,
 }
 
 impl ZipDownloader {
@@ -424,6 +658,19 @@ impl ZipDownloader {
         zip_url: &str,
         path: P,
     ) -> Result<Self, DownloaderError>
+    where
+        PathBuf: From<P>,
+    {
+        Self::new_with_mirrors(id, vec![zip_url.to_owned()], path).await
+    }
+
+    /// Like [`ZipDownloader::new`], but falling back on the later `urls` when the ones
+    /// before them fail
+    pub async fn new_with_mirrors<P: AsRef<Path>>(
+        id: &str,
+        urls: Vec<String>,
+        path: P,
+    ) -> Result<Self, DownloaderError>
     where
         PathBuf: From<P>,
     {
@@ -432,17 +679,39 @@ impl ZipDownloader {
             return Err(DownloaderError::PathNotAbsolute(path));
         }
 
-        let manifest = ZipFile::fetch(zip_url).await?;
+        let (manifest, current) = ZipFile::fetch_any(&urls).await?;
+        let mut mirrors = Mirrors::new(urls, current);
+        mirrors.archive = Some(*manifest.archive_id());
 
         Ok(Self {
             id: id.to_owned(),
-            url: zip_url.to_owned(),
+            mirrors,
             path,
-            client: Client::builder().build()?,
+            client: http::client_builder()
+                .redirect(http::secure_redirects())
+                .build()?,
             manifest,
+            bandwidth_limiter: None,
         })
     }
 
+    /// Ask `refresher` for new URLs once every mirror keeps failing
+    pub fn with_url_refresher(mut self, refresher: UrlRefresher) -> Self {
+        self.mirrors.refresher = Some(refresher);
+        self
+    }
+
+    /// The URL downloads currently go to
+    pub fn url(&self) -> String {
+        self.mirrors.current()
+    }
+
+    /// Throttle downloads with `limiter`, which may be shared with other downloaders
+    pub fn with_bandwidth_limiter(mut self, limiter: Arc<BandwidthLimiter>) -> Self {
+        self.bandwidth_limiter = Some(limiter);
+        self
+    }
+
     pub async fn read_zip_entry_bytes(
         &self,
         entry: &ZipFileEntry,
@@ -455,7 +724,7 @@ impl ZipDownloader {
 
         let response = self
             .client
-            .get(&self.url)
+            .get(self.mirrors.current())
             .header("Range", range_header)
             .send()
             .await?;
@@ -497,6 +766,60 @@ impl ZipDownloader {
         Ok(decompressed_data)
     }
 
+    /// Check an extracted entry against the manifest, by size and by CRC32 when the
+    /// manifest has one. Folders and empty files, which aren't written, always pass.
+    pub async fn verify_entry(&self, entry: &ZipFileEntry) -> Result<(), EntryMismatch> {
+        if entry.name().ends_with('/') || *entry.uncompressed_size() == 0 {
+            return Ok(());
+        }
+
+        let path = self.path.join(entry.name());
+        let metadata = match tokio::fs::metadata(&path).await {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(EntryMismatch::Missing)
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let expected = *entry.uncompressed_size() as u64;
+        if metadata.len() != expected {
+            return Err(EntryMismatch::Size {
+                expected,
+                actual: metadata.len(),
+            });
+        }
+
+        let expected = *entry.crc32();
+        if expected == 0 {
+            return Ok(());
+        }
+
+        let actual = tokio::task::spawn_blocking(move || hash_file_crc32(path))
+            .await
+            .map_err(io::Error::other)??;
+        if actual != expected {
+            return Err(EntryMismatch::Crc32 { expected, actual });
+        }
+
+        Ok(())
+    }
+
+    /// Delete an extracted entry and its inflate state, so it's downloaded from scratch
+    pub async fn discard_entry(&self, entry: &ZipFileEntry) -> Result<(), DownloaderError> {
+        match tokio::fs::remove_file(self.path.join(entry.name())).await {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+
+        tokio::fs::remove_file(zstate_path(&self.id, entry.name())?)
+            .await
+            .ok();
+        Ok(())
+    }
+
+    /// Download and extract a single entry. Returns the entry's size if it was
+    /// already complete on disk and didn't need downloading, otherwise 0.
     pub async fn download_single_file(
         &self,
         entry: &ZipFileEntry,
@@ -527,7 +850,7 @@ impl ZipDownloader {
         debug!("Compressed Size: {}", entry.compressed_size());
         debug!("Offset: {}", offset);
 
-        let file = OpenOptions::new()
+        let mut file = OpenOptions::new()
             .write(true)
             .create(true)
             .open(&file_path)
@@ -543,7 +866,7 @@ impl ZipDownloader {
             if let Some(callback) = callback {
                 callback(*entry.compressed_size() as usize);
             }
-            return Ok(0);
+            return Ok(*entry.compressed_size() as usize);
         }
 
         if state == EntryDownloadState::Borked {
@@ -551,6 +874,16 @@ impl ZipDownloader {
             file.set_len(*entry.uncompressed_size() as u64).await?;
         }
 
+        let mut resume_from = 0;
+        if let EntryDownloadState::Partial(written) = state {
+            debug!("Resuming {} from byte {}", entry.name(), written);
+            file.seek(SeekFrom::Start(written)).await?;
+            if let Some(callback) = &callback {
+                callback(written as usize);
+            }
+            resume_from = written as i64;
+        }
+
         let writer = tokio::io::BufWriter::new(file);
 
         let mut decoder: Box<dyn DownloadDecoder> = match entry.compression_type() {
@@ -570,14 +903,20 @@ impl ZipDownloader {
 
         let mut request = EntryDownloadRequest::new(
             &context,
-            &self.url,
+            &self.mirrors,
             entry,
             self.client.clone(),
             decoder,
             callback,
-        );
+            self.bandwidth_limiter.clone(),
+        )
+        .resume_from(resume_from);
 
         request.download().await?;
+
+        tokio::fs::remove_file(zstate_path(&self.id, entry.name())?)
+            .await
+            .ok();
         Ok(0)
     }
 }
@@ -636,3 +975,47 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mirrors_move_on_after_repeated_failures() {
+        let mirrors = Mirrors::new(vec!["a".to_owned(), "b".to_owned()], 0);
+
+        for _ in 0..MIRROR_FAILURES - 1 {
+            mirrors.failed("a").await;
+        }
+        mirrors.succeeded();
+        mirrors.failed("a").await;
+        assert_eq!(mirrors.current(), "a");
+
+        for _ in 0..MIRROR_FAILURES {
+            mirrors.failed("a").await;
+        }
+        assert_eq!(mirrors.current(), "b");
+
+        // Failures on a mirror that was already left behind don't count against the next
+        mirrors.failed("a").await;
+        for _ in 0..MIRROR_FAILURES {
+            mirrors.failed("b").await;
+        }
+        assert_eq!(mirrors.current(), "a");
+    }
+
+    #[tokio::test]
+    async fn mirrors_serving_another_archive_are_skipped() {
+        let mut mirrors = Mirrors::new(vec!["a".to_owned(), "b".to_owned()], 0);
+        mirrors.archive = Some(ZipArchiveId {
+            content_length: 1024,
+            cd_offset: 512,
+        });
+
+        // `b` can't be read, so it can't be shown to be the same archive
+        for _ in 0..MIRROR_FAILURES {
+            mirrors.failed("a").await;
+        }
+        assert_eq!(mirrors.current(), "a");
+    }
+}
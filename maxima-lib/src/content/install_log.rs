@@ -0,0 +1,101 @@
+use std::{
+    fmt::Display,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use chrono::Utc;
+use lazy_static::lazy_static;
+use log::warn;
+use regex::Regex;
+
+use crate::{core::diagnostics::redact, util::native::maxima_dir};
+
+const INSTALL_LOGS_DIR: &str = "logs/installs";
+
+lazy_static! {
+    /// Everything after the host of a URL. CDN paths and queries carry signed tokens.
+    static ref URL_PATTERN: Regex = Regex::new(r#"(https?://[^/\s"'`?)]+)[^\s"'`)]*"#).unwrap();
+}
+
+/// Blank out URLs past their host, and tokens, in `text`
+fn redact_line(text: &str) -> String {
+    redact(&URL_PATTERN.replace_all(text, "${1}/[REDACTED]"))
+}
+
+/// What happened during one install: files downloaded, retries, errors and touchup.
+/// Written to `maxima_dir()/logs/installs/<slug>-<timestamp>.log` as it goes, so it
+/// can be looked at after an install fails.
+pub struct InstallLog {
+    path: PathBuf,
+    /// None when the file couldn't be created. The install goes ahead without it.
+    file: Mutex<Option<File>>,
+}
+
+impl InstallLog {
+    pub fn create(slug: &str) -> Self {
+        let name = format!("{}-{}.log", slug, Utc::now().format("%Y%m%d-%H%M%S"));
+        let dir = match maxima_dir() {
+            Ok(dir) => dir.join(INSTALL_LOGS_DIR),
+            Err(err) => {
+                warn!("Not keeping a log of installing {}: {}", slug, err);
+                return Self {
+                    path: PathBuf::from(name),
+                    file: Mutex::new(None),
+                };
+            }
+        };
+
+        let path = dir.join(name);
+        let file = fs::create_dir_all(&dir).and_then(|_| File::create(&path));
+        let file = match file {
+            Ok(file) => Some(file),
+            Err(err) => {
+                warn!("Failed to create install log {:?}: {}", path, err);
+                None
+            }
+        };
+
+        Self {
+            path,
+            file: Mutex::new(file),
+        }
+    }
+
+    /// Where the log is written, which may not exist if it couldn't be created
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(&self, line: impl Display) {
+        let mut file = self.file.lock().unwrap();
+        if let Some(file) = file.as_mut() {
+            let line = redact_line(&line.to_string());
+            let _ = writeln!(
+                file,
+                "{} {}",
+                Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                line
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_urls() {
+        let line = redact_line(
+            "error sending request for url (https://origin-a.akamaihd.net/Origin-Client-Download/game.zip?__token__=exp=1700000000~hmac=abcdef): timed out",
+        );
+
+        assert_eq!(
+            line,
+            "error sending request for url (https://origin-a.akamaihd.net/[REDACTED]): timed out"
+        );
+    }
+}
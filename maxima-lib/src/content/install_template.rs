@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+/// Installs go in a folder named after the game's slug, inside the chosen folder
+pub const DEFAULT_INSTALL_TEMPLATE: &str = "{base}/{slug}";
+
+/// Characters Windows doesn't allow in file names, which keeps templates portable
+const INVALID_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum InstallTemplateError {
+    #[error("the template has to start with `{{base}}`")]
+    MissingBase,
+    #[error("unknown placeholder `{{{0}}}`, use `{{slug}}`, `{{name}}` or `{{publisher}}`")]
+    UnknownPlaceholder(String),
+    #[error("a `{{` in the template isn't closed")]
+    Unclosed,
+    #[error("`{0}` can't be used as a folder name")]
+    InvalidFolder(String),
+    #[error("the template doesn't leave a folder for the game")]
+    NoGameFolder,
+}
+
+/// What an install path template is filled in with
+pub struct InstallTemplateValues<'a> {
+    /// The folder the user picked to install to
+    pub base: &'a Path,
+    pub slug: &'a str,
+    /// The game's display name
+    pub name: &'a str,
+    /// `None` when the catalog doesn't name one, which leaves the folder out if that's all
+    /// it has
+    pub publisher: Option<&'a str>,
+}
+
+/// Drops the characters a folder name can't have, and the trailing dots and spaces
+/// Windows strips off
+fn sanitize(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .filter(|x| !x.is_control() && !INVALID_CHARS.contains(x))
+        .collect();
+    value.trim().trim_end_matches(['.', ' ']).to_owned()
+}
+
+fn resolve_folder(
+    folder: &str,
+    values: &InstallTemplateValues,
+) -> Result<String, InstallTemplateError> {
+    let mut resolved = String::new();
+    let mut rest = folder;
+
+    while let Some(start) = rest.find('{') {
+        resolved.push_str(&rest[..start]);
+
+        let end = rest[start..]
+            .find('}')
+            .ok_or(InstallTemplateError::Unclosed)?;
+        let value = match &rest[start + 1..start + end] {
+            "slug" => values.slug,
+            "name" => values.name,
+            "publisher" => values.publisher.unwrap_or_default(),
+            other => return Err(InstallTemplateError::UnknownPlaceholder(other.to_owned())),
+        };
+        resolved.push_str(&sanitize(value));
+
+        rest = &rest[start + end + 1..];
+    }
+    resolved.push_str(rest);
+
+    // Text written into the template is checked rather than cleaned up, so a typo
+    // doesn't silently end up in the path
+    let resolved = resolved.trim();
+    if resolved == "." || resolved == ".." || resolved.contains(INVALID_CHARS) {
+        return Err(InstallTemplateError::InvalidFolder(resolved.to_owned()));
+    }
+
+    Ok(resolved.to_owned())
+}
+
+/// Resolves an install path template like `{base}/{slug}`. It starts with `{base}`, and
+/// each folder after it can use `{slug}`, `{name}` and `{publisher}`. The result is always
+/// inside `base`.
+pub fn resolve_install_template(
+    template: &str,
+    values: &InstallTemplateValues,
+) -> Result<PathBuf, InstallTemplateError> {
+    let relative = template
+        .trim()
+        .strip_prefix("{base}")
+        .ok_or(InstallTemplateError::MissingBase)?;
+
+    let mut path = values.base.to_path_buf();
+    let mut folders = 0;
+    for folder in relative.split(['/', '\\']) {
+        let folder = resolve_folder(folder, values)?;
+        if folder.is_empty() {
+            continue;
+        }
+
+        path.push(folder);
+        folders += 1;
+    }
+
+    if folders == 0 {
+        return Err(InstallTemplateError::NoGameFolder);
+    }
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_templates() {
+        let values = InstallTemplateValues {
+            base: Path::new("games"),
+            slug: "battlefield-4",
+            name: "Battlefield 4™: Premium Edition?",
+            publisher: Some("Electronic Arts"),
+        };
+
+        assert_eq!(
+            resolve_install_template(DEFAULT_INSTALL_TEMPLATE, &values),
+            Ok(PathBuf::from("games").join("battlefield-4"))
+        );
+        assert_eq!(
+            resolve_install_template("{base}/EA/{name}", &values),
+            Ok(PathBuf::from("games/EA/Battlefield 4™ Premium Edition"))
+        );
+        assert_eq!(
+            resolve_install_template("{base}/../{slug}", &values),
+            Err(InstallTemplateError::InvalidFolder("..".to_owned()))
+        );
+        assert_eq!(
+            resolve_install_template("{base}/{publisher}/{name}", &values),
+            Ok(PathBuf::from(
+                "games/Electronic Arts/Battlefield 4™ Premium Edition"
+            ))
+        );
+        assert_eq!(
+            resolve_install_template("{base}/{developer}", &values),
+            Err(InstallTemplateError::UnknownPlaceholder(
+                "developer".to_owned()
+            ))
+        );
+        assert_eq!(
+            resolve_install_template("{base}/", &values),
+            Err(InstallTemplateError::NoGameFolder)
+        );
+
+        let unknown_publisher = InstallTemplateValues {
+            publisher: None,
+            ..values
+        };
+        assert_eq!(
+            resolve_install_template("{base}/{publisher}/{slug}", &unknown_publisher),
+            Ok(PathBuf::from("games").join("battlefield-4"))
+        );
+    }
+}
@@ -1,15 +1,18 @@
 use std::{
-    path::PathBuf,
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
+    time::Duration,
 };
 
 use derive_builder::Builder;
 use derive_getters::Getters;
 use futures::StreamExt;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
@@ -18,23 +21,48 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{
     content::{
-        downloader::{DownloadError, ZipDownloader},
+        bandwidth::BandwidthLimiter,
+        downloader::{
+            clear_zstate_destination, save_zstate_destination, zstate_destination, zstate_root,
+            DownloadError, ZipDownloader,
+        },
         exclusion::get_exclusion_list,
-        zip::{self, CompressionType, ZipError, ZipFileEntry},
+        install_log::InstallLog,
+        mover::{InstallMove, MoveProgressCallback},
+        rate::RateEstimator,
+        zip::{CompressionType, ZipError, ZipFileEntry},
+        zlib::Z_STATE_MAX_SIZE,
         ContentService,
     },
     core::{
         auth::storage::LockedAuthStorage,
+        concurrency::{Cancelled, Priority, FILE_VERIFICATION},
+        library::OwnedOffer,
         manifest::{self, ManifestError, MANIFEST_RELATIVE_PATH},
-        service_layer::ServiceLayerError,
+        service_layer::{ServiceLayerError, SharedServiceLayer},
         MaximaEvent,
     },
-    gameinfo::GameInstallInfo,
-    util::native::{maxima_dir, NativeError},
+    gameinfo::{
+        channel::build_channel, load_game_info_from_json, stats::installed_size, GameInstallInfo,
+    },
+    util::native::{link_dir, maxima_dir, remove_link, volume_space, NativeError},
 };
 
 const QUEUE_FILE: &str = "download_queue.json";
 
+/// Passes over the files that failed, after the first. Each pass already retries a file a
+/// few times, these wait a while first to ride out longer CDN trouble.
+const ENTRY_RETRY_PASSES: u32 = 2;
+/// How long the first retry pass waits, and how much longer each one after it does
+const ENTRY_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Files lined up to be verified at once. More than [`FILE_VERIFICATION`] allows to run,
+/// so one slow file doesn't leave workers idle.
+const VERIFY_QUEUE_DEPTH: usize = 64;
+
+/// Files an install downloads at once
+const CONCURRENT_ENTRIES: usize = 16;
+
 #[derive(Default, Builder, Getters, Clone, Serialize, Deserialize, PartialEq)]
 pub struct QueuedGame {
     offer_id: String,
@@ -42,15 +70,42 @@ pub struct QueuedGame {
     path: PathBuf,
     slug: String,
     wine_prefix: Option<PathBuf>,
+    /// Store the files here instead, and link `path` to it
+    #[builder(default)]
+    #[serde(default)]
+    real_path: Option<PathBuf>,
+    /// Lets an install into a folder that already has a game in it go ahead
+    /// if the game's manifest lists this content ID
+    #[builder(default)]
+    #[serde(default)]
+    content_id: Option<String>,
+    /// Only download these files, set when retrying the ones an install gave up on
+    #[builder(default)]
+    #[serde(default)]
+    only_entries: Option<Vec<String>>,
 }
 
-#[derive(Default, Getters, Serialize, Deserialize)]
+#[derive(Clone, Default, Getters, Serialize, Deserialize)]
 pub struct DownloadQueue {
+    /// Installs in progress, up to the content manager's concurrency limit
+    #[serde(default)]
+    active: Vec<QueuedGame>,
+    /// Queues saved before concurrent installs had a single current install instead
+    #[getter(skip)]
+    #[serde(default, skip_serializing)]
     current: Option<QueuedGame>,
     paused: bool,
 
     queued: Vec<QueuedGame>,
     completed: Vec<QueuedGame>,
+    /// Installs that gave up on some files, see [`ContentManager::retry_failed`]
+    #[serde(default)]
+    failed: Vec<QueuedGame>,
+
+    /// Where the queue is saved, if not in Maxima's folder
+    #[getter(skip)]
+    #[serde(skip)]
+    file: Option<PathBuf>,
 }
 
 #[derive(Error, Debug)]
@@ -63,13 +118,35 @@ pub enum ContentManagerError {
     Io(#[from] std::io::Error),
     #[error(transparent)]
     Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Manifest(#[from] ManifestError),
+    #[error(transparent)]
+    Join(#[from] tokio::task::JoinError),
 
     #[error("download in progress, you must cancel it before starting a new one")]
     DownloadInProgress,
+    #[error("`{0}` is not installed")]
+    NotInstalled(String),
+    #[error("no failed install of `{0}` to retry")]
+    NotFailed(String),
+    #[error("`{0}` has no live build to update to")]
+    NoLiveBuild(String),
+    #[error("`{0}` already exists and isn't empty")]
+    DestinationExists(PathBuf),
+    #[error("`{0}` already contains a different game")]
+    DestinationHasOtherGame(PathBuf),
+    #[error("not enough free space for `{path}`: {required} bytes are needed but only {available} are available ({} MB short)", (.required - .available) / 1_000_000)]
+    InsufficientSpace {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
 }
 
 #[derive(Error, Debug)]
 pub enum DownloaderError {
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
     #[error(transparent)]
     ServiceLayer(#[from] ServiceLayerError),
     #[error(transparent)]
@@ -93,36 +170,187 @@ pub enum DownloaderError {
     EntrySize { requested: u64, entry: usize },
     #[error("unsupported compression type `{0:?}`")]
     CompressionType(CompressionType),
+    #[error("{} files couldn't be downloaded: {}", .0.len(), .0.join(", "))]
+    EntriesFailed(Vec<String>),
+}
+
+/// Disk space an install still needs before it can finish
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RequiredSpace {
+    /// Uncompressed size of every file that hasn't been fully written yet
+    pub install_bytes: u64,
+    /// Inflate state kept in the downloader's temp directory, one per deflated file
+    /// that's being downloaded at the same time
+    pub temp_bytes: u64,
+}
+
+impl RequiredSpace {
+    /// Count the space needed to extract `entries` into `path`. Files already
+    /// written there, from an earlier attempt, aren't counted again.
+    pub fn for_entries(entries: &[ZipFileEntry], path: Option<&Path>) -> Self {
+        let mut space = Self::default();
+        let mut deflated = 0;
+
+        for entry in entries {
+            let size = (*entry.uncompressed_size()).max(0) as u64;
+            let existing = path
+                .and_then(|path| std::fs::metadata(path.join(entry.name())).ok())
+                .filter(|x| x.is_file())
+                .map_or(0, |x| x.len().min(size));
+
+            space.install_bytes += size - existing;
+            if entry.compression_type() == &CompressionType::Deflate && existing < size {
+                deflated += 1;
+            }
+        }
+
+        // Inflate state is removed once its file is done, so only the files being
+        // downloaded at the same time have any
+        space.temp_bytes = deflated.min(CONCURRENT_ENTRIES) as u64 * Z_STATE_MAX_SIZE;
+
+        space
+    }
+
+    pub fn total(&self) -> u64 {
+        self.install_bytes + self.temp_bytes
+    }
+
+    /// Make sure the volumes holding `path` and the temp directory can fit this install.
+    /// If the free space can't be determined, the install is allowed to go ahead.
+    pub fn check(&self, path: &Path) -> Result<(), ContentManagerError> {
+        Self::check_combined(&[(path, *self)])
+    }
+
+    /// Like [`Self::check`], for several installs that will be downloading at the same time
+    pub fn check_combined(installs: &[(&Path, RequiredSpace)]) -> Result<(), ContentManagerError> {
+        let temp_dir = zstate_root()?;
+
+        // Mount point, free space, space needed, and a path on it for the error
+        let mut volumes: Vec<(PathBuf, u64, u64, PathBuf)> = Vec::new();
+        let mut reserve = |path: &Path, bytes: u64| match volume_space(path) {
+            Some((mount_point, available)) => {
+                match volumes.iter_mut().find(|x| x.0 == mount_point) {
+                    Some(volume) => volume.2 += bytes,
+                    None => volumes.push((mount_point, available, bytes, path.to_owned())),
+                }
+            }
+            None => warn!("Couldn't determine free space for `{}`", path.display()),
+        };
+
+        for (path, space) in installs {
+            reserve(path, space.install_bytes);
+            reserve(&temp_dir, space.temp_bytes);
+        }
+
+        for (_, available, required, path) in volumes {
+            if available < required {
+                return Err(ContentManagerError::InsufficientSpace {
+                    path,
+                    required,
+                    available,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Manifest entries that will actually be downloaded for `slug`
+fn install_entries(entries: &[ZipFileEntry], slug: &str) -> Vec<ZipFileEntry> {
+    let exclusion_list = get_exclusion_list(slug);
+
+    entries
+        .iter()
+        // TODO: Filtering
+        .filter(|x| !exclusion_list.is_match(x.name()))
+        .cloned()
+        .collect()
+}
+
+/// Make sure installing `game` won't extract over something else. The folder has to be
+/// empty, hold an unfinished download of the same offer, or already have the game in it.
+async fn check_destination(game: &QueuedGame) -> Result<(), ContentManagerError> {
+    let path = game.real_path.as_ref().unwrap_or(&game.path);
+    if !path.exists() {
+        return Ok(());
+    }
+
+    if !path.is_dir() {
+        return Err(ContentManagerError::DestinationExists(path.clone()));
+    }
+
+    if fs::read_dir(path).await?.next_entry().await?.is_none() {
+        return Ok(());
+    }
+
+    // Resuming, the downloader keeps its state outside of the install folder
+    if zstate_destination(&game.offer_id).as_ref() == Some(path) {
+        return Ok(());
+    }
+
+    let manifest = manifest::read(path.join(MANIFEST_RELATIVE_PATH)).await.ok();
+    if let (Some(manifest), Some(content_id)) = (&manifest, &game.content_id) {
+        if manifest.is_for(content_id) {
+            return Ok(());
+        }
+
+        return Err(ContentManagerError::DestinationHasOtherGame(path.clone()));
+    }
+
+    // Nothing to compare against, only allow the folder we know the game is installed in
+    if let Ok(info) = load_game_info_from_json(&game.slug) {
+        if info.real_path() == *path {
+            return Ok(());
+        }
+    }
+
+    if manifest.is_some() {
+        return Err(ContentManagerError::DestinationHasOtherGame(path.clone()));
+    }
+
+    Err(ContentManagerError::DestinationExists(path.clone()))
 }
 
 impl DownloadQueue {
     pub(crate) async fn load() -> Result<DownloadQueue, ContentManagerError> {
-        let file = maxima_dir()?.join(QUEUE_FILE);
-        if !file.exists() {
-            return Ok(Self::default());
-        }
+        let mut queue = Self::load_from(&maxima_dir()?.join(QUEUE_FILE)).await?;
+        queue.file = None;
+        Ok(queue)
+    }
 
-        let data = fs::read_to_string(file).await?;
-        let result = serde_json::from_str(&data);
-        if result.is_err() {
-            return Ok(Self::default());
+    /// Load the queue saved in `file`, which it's saved back to
+    async fn load_from(file: &Path) -> Result<DownloadQueue, ContentManagerError> {
+        let mut queue = match fs::read_to_string(file).await {
+            Ok(data) => serde_json::from_str(&data).unwrap_or_default(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(err.into()),
+        };
+
+        if let Some(current) = queue.current.take() {
+            queue.active.push(current);
         }
 
-        Ok(result?)
+        queue.file = Some(file.to_owned());
+        Ok(queue)
     }
 
     pub(crate) async fn save(&self) -> Result<(), ContentManagerError> {
-        let file = maxima_dir()?.join(QUEUE_FILE);
+        let file = match &self.file {
+            Some(file) => file.clone(),
+            None => maxima_dir()?.join(QUEUE_FILE),
+        };
         fs::write(file, serde_json::to_string(&self)?).await?;
         Ok(())
     }
 
-    pub fn push_to_current(&mut self, game: QueuedGame) {
-        if let Some(current) = &self.current {
-            self.queued.push(current.clone());
-        }
+    pub fn is_active(&self, offer_id: &str) -> bool {
+        self.active.iter().any(|x| x.offer_id == offer_id)
+    }
 
-        self.current = Some(game.clone());
+    /// Whether `offer_id` is downloading or waiting to
+    pub fn is_installing(&self, offer_id: &str) -> bool {
+        self.is_active(offer_id) || self.queued.iter().any(|x| x.offer_id == offer_id)
     }
 }
 
@@ -131,85 +359,145 @@ pub struct GameDownloader {
     slug: String,
     path: PathBuf,
     wine_prefix: Option<PathBuf>,
+    real_path: Option<PathBuf>,
 
     downloader: Arc<ZipDownloader>,
     entries: Vec<ZipFileEntry>,
 
     cancel_token: CancellationToken,
     completed_bytes: Arc<AtomicUsize>,
-    total_count: usize,
+    /// Bytes of files that were already complete on disk, which count as done but weren't downloaded
+    skipped_bytes: Arc<AtomicUsize>,
+    rate: Mutex<RateEstimator>,
     total_bytes: usize,
     notify: Arc<Notify>,
+    /// Files that failed since the last update, and why. They may still be recovered.
+    failures: Arc<Mutex<Vec<(String, String)>>>,
+    /// Set when the install gave up, to the files it couldn't download
+    unrecovered: Arc<Mutex<Option<Vec<String>>>>,
+    verification: Arc<VerificationProgress>,
+    log: Arc<InstallLog>,
+}
+
+/// Files checked against the manifest so far, out of how many. The total is 0 while
+/// nothing is being checked.
+#[derive(Default)]
+struct VerificationProgress {
+    verified: AtomicUsize,
+    total: AtomicUsize,
+}
+
+/// Take `bytes` of a file that's downloaded again back off the progress. Never goes
+/// below zero, since a file that was already on disk or resumed from an earlier session
+/// wasn't counted in full.
+fn uncount_bytes(completed_bytes: &AtomicUsize, bytes: usize) {
+    let _ = completed_bytes.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+        Some(x.saturating_sub(bytes))
+    });
 }
 
 impl GameDownloader {
     pub async fn new(
         content_service: &ContentService,
         game: &QueuedGame,
+        bandwidth_limiter: Arc<BandwidthLimiter>,
+        log: Arc<InstallLog>,
     ) -> Result<Self, DownloaderError> {
         let url = content_service
             .download_url(&game.offer_id, Some(&game.build_id))
             .await?;
 
         debug!("URL: {}", url.url());
-
-        let downloader = ZipDownloader::new(&game.offer_id, &url.url(), &game.path).await?;
-
-        let mut entries = Vec::new();
-
-        let exclusion_list = get_exclusion_list(game.slug.as_str());
-
-        for ele in downloader.manifest().entries() {
-            // TODO: Filtering
-            if exclusion_list.is_match(&ele.name()) {
-                // info!("Excluding file from download: {}", ele.name()); Spams if a lot of files are excluded
-                continue;
-            }
-            entries.push(ele.clone());
+        log.write(format_args!(
+            "Downloading from {} ({} mirrors)",
+            url.url(),
+            url.mirrors().len()
+        ));
+
+        let downloader = ZipDownloader::new_with_mirrors(&game.offer_id, url.mirrors(), &game.path)
+            .await?
+            .with_url_refresher(content_service.url_refresher(&game.offer_id, &game.build_id))
+            .with_bandwidth_limiter(bandwidth_limiter);
+
+        let mut entries = install_entries(downloader.manifest().entries(), &game.slug);
+        if let Some(only) = &game.only_entries {
+            entries.retain(|x| only.contains(x.name()));
         }
 
-        let total_count = entries.len();
         let total_bytes = entries
             .iter()
             .map(|x| *x.compressed_size() as usize)
             .sum::<usize>()
             + 1; // Add 1 to account for running touchup at the end. Bad solution, but we're a bit rushed
+        log.write(format_args!(
+            "{} files to install, {} bytes compressed",
+            entries.len(),
+            total_bytes - 1
+        ));
 
         Ok(GameDownloader {
             offer_id: game.offer_id.to_owned(),
             slug: game.slug.to_owned(),
             path: game.path.to_owned(),
             wine_prefix: game.wine_prefix.clone(),
+            real_path: game.real_path.clone(),
             downloader: Arc::new(downloader),
             entries,
             cancel_token: CancellationToken::new(),
             completed_bytes: Arc::new(AtomicUsize::new(0)),
-            total_count,
+            skipped_bytes: Arc::new(AtomicUsize::new(0)),
+            rate: Mutex::new(RateEstimator::new()),
             total_bytes,
             notify: Arc::new(Notify::new()),
+            failures: Arc::new(Mutex::new(Vec::new())),
+            unrecovered: Arc::new(Mutex::new(None)),
+            verification: Arc::new(VerificationProgress::default()),
+            log,
         })
     }
 
     pub fn download(&self) {
         let (downloader_arc, entries, cancel_token, completed_bytes, notify) =
             self.prepare_download_vars();
-        let total_count = self.total_count;
+        let skipped_bytes = self.skipped_bytes.clone();
+        let failures = self.failures.clone();
+        let unrecovered = self.unrecovered.clone();
+        let verification = self.verification.clone();
+        let log = self.log.clone();
         let slug = self.slug.clone();
-        let game_install_info = GameInstallInfo::new(self.path.clone(), self.wine_prefix.clone());
+        let game_install_info = GameInstallInfo::new(self.path.clone(), self.wine_prefix.clone())
+            .with_real_path(self.real_path.clone());
         tokio::spawn(async move {
             let dl = GameDownloader::start_downloads(
-                total_count,
                 downloader_arc,
                 entries,
                 cancel_token,
                 completed_bytes,
+                skipped_bytes,
+                failures,
+                verification,
                 notify,
-                slug,
+                &log,
+                slug.clone(),
                 game_install_info,
             )
             .await;
-            if let Err(err) = dl {
-                error!("Error when downloading!: `{:?}", err)
+            match dl {
+                Ok(()) => log.write("Installation finished"),
+                Err(DownloaderError::Cancelled(_)) => log.write("Paused"),
+                Err(DownloaderError::EntriesFailed(files)) => {
+                    error!("Gave up installing {}, {} files failed", slug, files.len());
+                    log.write(format_args!(
+                        "Gave up, {} files failed: {}",
+                        files.len(),
+                        files.join(", ")
+                    ));
+                    *unrecovered.lock().unwrap() = Some(files);
+                }
+                Err(err) => {
+                    error!("Error when downloading!: `{:?}", err);
+                    log.write(format_args!("Install failed: {}", err));
+                }
             }
         });
     }
@@ -233,49 +521,94 @@ impl GameDownloader {
     }
 
     async fn start_downloads(
-        total_count: usize,
         downloader_arc: Arc<ZipDownloader>,
         entries: Vec<ZipFileEntry>,
         cancel_token: CancellationToken,
         completed_bytes: Arc<AtomicUsize>,
+        skipped_bytes: Arc<AtomicUsize>,
+        failures: Arc<Mutex<Vec<(String, String)>>>,
+        verification: Arc<VerificationProgress>,
         notify: Arc<Notify>,
+        log: &Arc<InstallLog>,
         slug: String,
         game_install_info: GameInstallInfo,
     ) -> Result<(), DownloaderError> {
-        let mut handles = Vec::with_capacity(total_count);
-
-        for i in 0..total_count {
-            let downloader = downloader_arc.clone();
-            let ele = entries[i].clone();
-
-            let cancel_token = cancel_token.clone();
-            let completed_bytes = completed_bytes.clone();
+        GameDownloader::download_with_retries(
+            entries.clone(),
+            &cancel_token,
+            log,
+            ENTRY_RETRY_DELAY,
+            |pending| {
+                GameDownloader::download_entries(
+                    &downloader_arc,
+                    pending,
+                    &cancel_token,
+                    &completed_bytes,
+                    &skipped_bytes,
+                    &failures,
+                    log,
+                )
+            },
+        )
+        .await?;
+
+        // A file that was cut short without an error would otherwise pass as installed
+        log.write("Verifying files");
+        let broken = GameDownloader::verify_entries(
+            &downloader_arc,
+            &entries,
+            &failures,
+            &verification,
+            log,
+        )
+        .await;
+        if !broken.is_empty() {
+            warn!(
+                "{} files don't match the manifest, downloading them again",
+                broken.len()
+            );
+            log.write(format_args!(
+                "{} files don't match the manifest, downloading them again",
+                broken.len()
+            ));
+            for entry in &broken {
+                uncount_bytes(&completed_bytes, *entry.compressed_size() as usize);
+                downloader_arc.discard_entry(entry).await?;
+            }
 
-            handles.push(async move {
-                if ele.name().contains("Cleanup") {
-                    info!("Ele: {:?}", ele);
-                }
+            let failed = GameDownloader::download_entries(
+                &downloader_arc,
+                broken.clone(),
+                &cancel_token,
+                &completed_bytes,
+                &skipped_bytes,
+                &failures,
+                log,
+            )
+            .await;
+            if cancel_token.is_cancelled() {
+                return Err(Cancelled.into());
+            }
 
-                tokio::select! {
-                    result = downloader.download_single_file(&ele, Some(Box::new(move |bytes| {
-                        completed_bytes.fetch_add(bytes, Ordering::SeqCst);
-                    }))) => {
-                        if let Err(err) = result {
-                            error!("File download failed: {}", err);
-                        }
-                    },
-                    _ = cancel_token.cancelled() => {
-                        info!("Download of {} cancelled", ele.name());
-                    },
+            let mut unrecovered: Vec<String> = failed.iter().map(|x| x.name().to_owned()).collect();
+            let still_broken = GameDownloader::verify_entries(
+                &downloader_arc,
+                &broken,
+                &failures,
+                &verification,
+                log,
+            )
+            .await;
+            for entry in still_broken {
+                if !unrecovered.contains(entry.name()) {
+                    unrecovered.push(entry.name().to_owned());
                 }
-            });
+            }
+            if !unrecovered.is_empty() {
+                return Err(DownloaderError::EntriesFailed(unrecovered));
+            }
         }
 
-        let _results = futures::stream::iter(handles)
-            .buffer_unordered(16)
-            .collect::<Vec<_>>()
-            .await;
-
         let path = downloader_arc.path();
 
         game_install_info.save_to_json(&slug);
@@ -285,21 +618,216 @@ impl GameDownloader {
         // Touchup will be run on linux/mac when first running the game, so we don't need to run it here
         {
             info!("Running touchup...");
+            log.write("Running touchup");
             let manifest = manifest::read(path.join(MANIFEST_RELATIVE_PATH)).await?;
             manifest.run_touchup(path, &slug).await?;
+            log.write("Touchup finished");
         }
 
         info!("Installation finished!");
 
+        // Updates and repairs change the size, and saving the install info forgot it
+        if let Err(err) = installed_size(&slug, &game_install_info).await {
+            warn!("Failed to measure the size of {}: {}", slug, err);
+        }
+
         completed_bytes.fetch_add(1, Ordering::SeqCst);
 
         notify.notify_one();
         Ok(())
     }
 
+    /// Entries whose extracted files don't match the manifest, in the order they were
+    /// given. Files are hashed in parallel, as many at once as [`FILE_VERIFICATION`] allows.
+    async fn verify_entries(
+        downloader_arc: &Arc<ZipDownloader>,
+        entries: &[ZipFileEntry],
+        failures: &Arc<Mutex<Vec<(String, String)>>>,
+        verification: &Arc<VerificationProgress>,
+        log: &Arc<InstallLog>,
+    ) -> Vec<ZipFileEntry> {
+        verification.verified.store(0, Ordering::SeqCst);
+        verification.total.store(entries.len(), Ordering::SeqCst);
+
+        // Results come back in order whichever file finishes first, so the report is the
+        // same every time. The limiter decides how many are really hashed at once.
+        let results: Vec<_> = futures::stream::iter(entries.iter().map(|entry| {
+            let downloader = downloader_arc.clone();
+            async move {
+                let _permit = FILE_VERIFICATION.acquire(Priority::High).await;
+                let result = downloader.verify_entry(entry).await;
+                verification.verified.fetch_add(1, Ordering::SeqCst);
+                result
+            }
+        }))
+        .buffered(VERIFY_QUEUE_DEPTH)
+        .collect()
+        .await;
+
+        verification.total.store(0, Ordering::SeqCst);
+
+        let mut broken = Vec::new();
+        for (entry, result) in entries.iter().zip(results) {
+            if let Err(err) = result {
+                warn!("{} is broken: {}", entry.name(), err);
+                log.write(format_args!("{} is broken: {}", entry.name(), err));
+                let failure = (entry.name().to_owned(), err.to_string());
+                failures.lock().unwrap().push(failure);
+                broken.push(entry.clone());
+            }
+        }
+
+        broken
+    }
+
+    /// Run `download` over `entries`, then again over the ones it returns as failed, up to
+    /// [`ENTRY_RETRY_PASSES`] more times. Each pass waits `delay` longer than the last.
+    async fn download_with_retries<F, Fut>(
+        entries: Vec<ZipFileEntry>,
+        cancel_token: &CancellationToken,
+        log: &InstallLog,
+        delay: Duration,
+        mut download: F,
+    ) -> Result<(), DownloaderError>
+    where
+        F: FnMut(Vec<ZipFileEntry>) -> Fut,
+        Fut: Future<Output = Vec<ZipFileEntry>>,
+    {
+        let mut pending = entries;
+        let mut pass = 0;
+        loop {
+            let failed = download(pending).await;
+
+            // Paused or shut down, the files aren't all there
+            if cancel_token.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
+            if failed.is_empty() {
+                return Ok(());
+            }
+
+            pass += 1;
+            if pass > ENTRY_RETRY_PASSES {
+                let files = failed.iter().map(|x| x.name().to_owned()).collect();
+                return Err(DownloaderError::EntriesFailed(files));
+            }
+
+            warn!(
+                "{} files failed to download, retrying them ({}/{})",
+                failed.len(),
+                pass,
+                ENTRY_RETRY_PASSES
+            );
+            log.write(format_args!(
+                "{} files failed, retrying them ({}/{})",
+                failed.len(),
+                pass,
+                ENTRY_RETRY_PASSES
+            ));
+            tokio::select! {
+                _ = tokio::time::sleep(delay * pass) => {},
+                _ = cancel_token.cancelled() => return Err(Cancelled.into()),
+            }
+            pending = failed;
+        }
+    }
+
+    /// Download `entries`, returning the ones that failed
+    async fn download_entries(
+        downloader_arc: &Arc<ZipDownloader>,
+        entries: Vec<ZipFileEntry>,
+        cancel_token: &CancellationToken,
+        completed_bytes: &Arc<AtomicUsize>,
+        skipped_bytes: &Arc<AtomicUsize>,
+        failures: &Arc<Mutex<Vec<(String, String)>>>,
+        log: &Arc<InstallLog>,
+    ) -> Vec<ZipFileEntry> {
+        let mut handles = Vec::with_capacity(entries.len());
+
+        for ele in entries {
+            let downloader = downloader_arc.clone();
+            let cancel_token = cancel_token.clone();
+            let completed_bytes = completed_bytes.clone();
+            let skipped_bytes = skipped_bytes.clone();
+            let failures = failures.clone();
+            let log = log.clone();
+
+            handles.push(async move {
+                if ele.name().contains("Cleanup") {
+                    info!("Ele: {:?}", ele);
+                }
+
+                // Bytes this attempt counted towards the progress
+                let attempt_bytes = Arc::new(AtomicUsize::new(0));
+                let counted_bytes = attempt_bytes.clone();
+                let progress_bytes = completed_bytes.clone();
+
+                tokio::select! {
+                    result = downloader.download_single_file(&ele, Some(Box::new(move |bytes| {
+                        progress_bytes.fetch_add(bytes, Ordering::SeqCst);
+                        counted_bytes.fetch_add(bytes, Ordering::SeqCst);
+                    }))) => {
+                        match result {
+                            Ok(skipped) => {
+                                skipped_bytes.fetch_add(skipped, Ordering::SeqCst);
+                                if skipped > 0 {
+                                    log.write(format_args!("{} was already there", ele.name()));
+                                } else {
+                                    log.write(format_args!("Downloaded {}", ele.name()));
+                                }
+                                None
+                            }
+                            Err(err) => {
+                                error!("File download failed: {}", err);
+                                log.write(format_args!("Failed to download {}: {}", ele.name(), err));
+                                // A retry counts whatever it downloads again
+                                let counted = attempt_bytes.load(Ordering::SeqCst);
+                                uncount_bytes(&completed_bytes, counted);
+                                let failure = (ele.name().to_owned(), err.to_string());
+                                failures.lock().unwrap().push(failure);
+                                Some(ele)
+                            }
+                        }
+                    },
+                    _ = cancel_token.cancelled() => {
+                        info!("Download of {} cancelled", ele.name());
+                        None
+                    },
+                }
+            });
+        }
+
+        futures::stream::iter(handles)
+            .buffer_unordered(CONCURRENT_ENTRIES)
+            .filter_map(|x| async move { x })
+            .collect()
+            .await
+    }
+
     pub fn cancel(&self) {
         info!("Pausing installation of {}", self.offer_id);
         self.cancel_token.cancel();
+        self.rate.lock().unwrap().reset();
+    }
+
+    /// Feed the rate estimator, called from [`ContentManager::update`]
+    fn sample_rate(&self) {
+        let downloaded = self
+            .bytes_downloaded()
+            .saturating_sub(self.skipped_bytes.load(Ordering::SeqCst));
+        self.rate.lock().unwrap().sample(downloaded as u64);
+    }
+
+    /// Smoothed download speed, once there's been enough progress to estimate it
+    pub fn current_speed_bps(&self) -> Option<u64> {
+        self.rate.lock().unwrap().bytes_per_second()
+    }
+
+    /// Estimated time left at the current speed
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.bytes_total().saturating_sub(self.bytes_downloaded());
+        self.rate.lock().unwrap().eta(remaining as u64)
     }
 
     pub async fn wait(&self) {
@@ -319,6 +847,15 @@ impl GameDownloader {
         self.completed_bytes.load(Ordering::SeqCst)
     }
 
+    /// Files checked against the manifest out of the total, while the install is being
+    /// verified after downloading
+    pub fn verification_progress(&self) -> Option<(usize, usize)> {
+        match self.verification.total.load(Ordering::SeqCst) {
+            0 => None,
+            total => Some((self.verification.verified.load(Ordering::SeqCst), total)),
+        }
+    }
+
     pub fn bytes_total(&self) -> usize {
         self.total_bytes
     }
@@ -326,48 +863,248 @@ impl GameDownloader {
     pub fn offer_id(&self) -> &String {
         &self.offer_id
     }
+
+    pub fn required_space(&self) -> RequiredSpace {
+        RequiredSpace::for_entries(&self.entries, Some(self.storage_path()))
+    }
+
+    /// Where this install is being logged, see [`InstallLog`]
+    pub fn log_path(&self) -> &Path {
+        self.log.path()
+    }
+
+    /// Where the files end up, which is on another drive if `path` is a link
+    pub fn storage_path(&self) -> &Path {
+        self.real_path.as_deref().unwrap_or(&self.path)
+    }
 }
 
 #[derive(Getters)]
 pub struct ContentManager {
     queue: DownloadQueue,
     service: ContentService,
-    current: Option<GameDownloader>,
+    /// Downloads in progress, in the order they were started
+    active: Vec<GameDownloader>,
+    /// How many games may download at once
+    max_concurrent: usize,
+    #[getter(skip)]
+    bandwidth_limiter: Arc<BandwidthLimiter>,
+    /// Set when slots free up, so the next update starts queued games
+    #[getter(skip)]
+    fill_slots: bool,
+    /// Set when the queue changes, so the next update reports it
+    #[getter(skip)]
+    queue_changed: bool,
+    /// Log of the last install started of each offer, kept after it finishes or fails
+    #[getter(skip)]
+    install_logs: HashMap<String, PathBuf>,
 }
 
 impl ContentManager {
-    pub async fn new(auth: LockedAuthStorage, _resume: bool) -> Result<Self, ContentManagerError> {
-        Ok(Self {
-            queue: DownloadQueue::load().await?,
-            service: ContentService::new(auth),
-            current: None,
-        })
+    pub async fn new(
+        service_layer: SharedServiceLayer,
+        auth_storage: LockedAuthStorage,
+    ) -> Result<Self, ContentManagerError> {
+        Ok(Self::with_queue(
+            ContentService::new(service_layer, auth_storage),
+            DownloadQueue::load().await?,
+        ))
+    }
+
+    /// Like [`ContentManager::new`], with the queue saved in `file` instead of Maxima's
+    /// folder and lookups that aren't persisted
+    #[cfg(test)]
+    pub(crate) async fn with_queue_file(
+        service_layer: SharedServiceLayer,
+        file: &Path,
+    ) -> Result<Self, ContentManagerError> {
+        Ok(Self::with_queue(
+            crate::content::tests::memory_service(service_layer),
+            DownloadQueue::load_from(file).await?,
+        ))
+    }
+
+    fn with_queue(service: ContentService, mut queue: DownloadQueue) -> Self {
+        // Installs that were running when Maxima closed are resumed on the first update
+        let interrupted = std::mem::take(&mut queue.active);
+        queue.queued.extend(interrupted);
+
+        Self {
+            fill_slots: !queue.queued.is_empty(),
+            // Frontends get the queue as it was loaded on the first update
+            queue_changed: true,
+            queue,
+            service,
+            active: Vec::new(),
+            max_concurrent: 1,
+            bandwidth_limiter: Arc::new(BandwidthLimiter::new(None)),
+            install_logs: HashMap::new(),
+        }
+    }
+
+    /// Builds and download URLs are looked up through this, and their caching is tuned on it
+    pub fn mut_service(&mut self) -> &mut ContentService {
+        &mut self.service
+    }
+
+    /// Allow up to `max` games to download at once. Extra slots are filled from the
+    /// queue on the next update, and lowering it doesn't interrupt running downloads.
+    pub fn set_max_concurrent(&mut self, max: usize) {
+        let max = max.max(1);
+        self.fill_slots |= max > self.max_concurrent;
+        self.max_concurrent = max;
+    }
+
+    /// Cap the combined speed of all downloads, in bytes per second
+    pub fn set_bandwidth_limit(&self, limit: Option<u64>) {
+        self.bandwidth_limiter.set_limit(limit);
+    }
+
+    pub fn bandwidth_limit(&self) -> Option<u64> {
+        self.bandwidth_limiter.limit()
+    }
+
+    pub fn download(&self, offer_id: &str) -> Option<&GameDownloader> {
+        self.active.iter().find(|x| x.offer_id == offer_id)
+    }
+
+    /// Log of the last install of `offer_id` started since Maxima started, to point at
+    /// when it fails
+    pub fn install_log(&self, offer_id: &str) -> Option<&Path> {
+        self.install_logs.get(offer_id).map(|x| x.as_path())
+    }
+
+    /// Whether `offer_id` is being installed or updated, or is queued to be. Its files
+    /// may be half written until [`crate::core::MaximaEvent::InstallFinished`].
+    pub fn is_installing(&self, offer_id: &str) -> bool {
+        self.download(offer_id).is_some() || self.queue.is_installing(offer_id)
+    }
+
+    fn has_free_slot(&self) -> bool {
+        self.active.len() < self.max_concurrent
     }
 
     pub async fn add_install(&mut self, game: QueuedGame) -> Result<(), ContentManagerError> {
-        if self.queue.queued.is_empty() && self.queue.current == None && self.current.is_none() {
-            self.install_now(game).await?;
+        if self.queue.queued.is_empty()
+            && self.has_free_slot()
+            && !self.queue.is_active(&game.offer_id)
+        {
+            self.install_direct(game).await?;
         } else {
+            check_destination(&game).await?;
             self.queue.queued.push(game);
-            self.queue.save().await?;
+            self.save_queue().await?;
         }
 
         Ok(())
     }
 
-    pub async fn install_now(&mut self, game: QueuedGame) -> Result<(), ContentManagerError> {
-        if let Some(current) = &self.current {
-            current.cancel();
-            self.current = None;
+    /// Queue an install that gave up again, downloading only the files it couldn't get
+    pub async fn retry_failed(&mut self, offer_id: &str) -> Result<(), ContentManagerError> {
+        let index = self
+            .queue
+            .failed
+            .iter()
+            .position(|x| x.offer_id == offer_id)
+            .ok_or_else(|| ContentManagerError::NotFailed(offer_id.to_owned()))?;
+
+        let game = self.queue.failed.remove(index);
+        self.add_install(game).await
+    }
+
+    /// Queue the build of an installed game's channel over its files, in the folder it's
+    /// installed in. Files that haven't changed are kept, so only the rest is downloaded.
+    pub async fn add_update(&mut self, offer: &OwnedOffer) -> Result<(), ContentManagerError> {
+        let info = load_game_info_from_json(offer.slug())
+            .map_err(|_| ContentManagerError::NotInstalled(offer.slug().clone()))?;
+
+        let builds = self
+            .service
+            .available_builds(offer.offer_id())
+            .await
+            .map_err(DownloaderError::from)?;
+        let build = builds
+            .channel_build(build_channel(offer.slug()))
+            .ok_or_else(|| ContentManagerError::NoLiveBuild(offer.offer_id().clone()))?;
+
+        let game = QueuedGame {
+            offer_id: offer.offer_id().clone(),
+            build_id: build.build_id().to_owned(),
+            path: info.path.clone(),
+            slug: offer.slug().clone(),
+            wine_prefix: info.wine_prefix.clone(),
+            real_path: info.real_path.clone(),
+            content_id: Some(offer.offer().content_id().to_owned()),
+            only_entries: None,
+        };
+        self.add_install(game).await
+    }
+
+    /// Pick a partial install back up, see [`crate::core::library::InstallState::Partial`].
+    /// One that gave up is retried, and one with install info is downloaded over again
+    /// in its folder, keeping the files that are already complete.
+    pub async fn resume_install(&mut self, offer: &OwnedOffer) -> Result<(), ContentManagerError> {
+        if self.is_installing(offer.offer_id()) {
+            return Ok(());
         }
 
-        if let Some(current) = &self.queue.current {
-            if current == &game {
-                self.install_direct(game).await?;
-                return Ok(());
-            }
+        if self
+            .queue
+            .failed
+            .iter()
+            .any(|x| &x.offer_id == offer.offer_id())
+        {
+            return self.retry_failed(offer.offer_id()).await;
+        }
 
-            self.queue.queued.push(current.clone());
+        self.add_update(offer).await
+    }
+
+    /// Stop installing or updating `offer_id`, and take it out of the queue. The files
+    /// written so far are kept, so installing it again picks up where this left off.
+    /// Returns whether there was anything to stop.
+    pub async fn cancel_install(&mut self, offer_id: &str) -> Result<bool, ContentManagerError> {
+        let mut found = false;
+        if let Some(index) = self.active.iter().position(|x| x.offer_id == offer_id) {
+            self.active.remove(index).cancel();
+            self.fill_slots = true;
+            found = true;
+        }
+
+        found |= self.queue.is_installing(offer_id);
+        self.queue.active.retain(|x| x.offer_id != offer_id);
+        self.queue.queued.retain(|x| x.offer_id != offer_id);
+
+        if found {
+            info!("Cancelled installation of {}", offer_id);
+            self.save_queue().await?;
+        }
+        Ok(found)
+    }
+
+    /// Start installing `game` right away. If every slot is taken, the most recently
+    /// started download is paused and put back in the queue to make room.
+    pub async fn install_now(&mut self, game: QueuedGame) -> Result<(), ContentManagerError> {
+        // Already running, restart it
+        if let Some(index) = self.active.iter().position(|x| x.offer_id == game.offer_id) {
+            self.active.remove(index).cancel();
+        }
+        self.queue.active.retain(|x| x.offer_id != game.offer_id);
+
+        if !self.has_free_slot() {
+            if let Some(preempted) = self.active.pop() {
+                preempted.cancel();
+
+                if let Some(index) = self
+                    .queue
+                    .active
+                    .iter()
+                    .position(|x| x.offer_id == preempted.offer_id)
+                {
+                    let queued = self.queue.active.remove(index);
+                    self.queue.queued.push(queued);
+                }
+            }
         }
 
         self.install_direct(game).await?;
@@ -375,36 +1112,512 @@ impl ContentManager {
     }
 
     async fn install_direct(&mut self, game: QueuedGame) -> Result<(), ContentManagerError> {
-        if self.current.is_some() {
+        if !self.has_free_slot() || self.download(&game.offer_id).is_some() {
             return Err(ContentManagerError::DownloadInProgress);
         }
 
-        self.queue.current = Some(game.clone());
-        self.queue.save().await?;
+        let log = Arc::new(InstallLog::create(&game.slug));
+        log.write(format_args!(
+            "Installing {} build {} to {:?}",
+            game.offer_id, game.build_id, game.path
+        ));
+        if let Some(only) = &game.only_entries {
+            log.write(format_args!(
+                "Retrying {} files that failed before",
+                only.len()
+            ));
+        }
+        self.install_logs
+            .insert(game.offer_id.clone(), log.path().to_owned());
+
+        let result = self.start_install(game, log.clone()).await;
+        if let Err(err) = &result {
+            log.write(format_args!("Couldn't start: {}", err));
+        }
+        result
+    }
+
+    async fn start_install(
+        &mut self,
+        game: QueuedGame,
+        log: Arc<InstallLog>,
+    ) -> Result<(), ContentManagerError> {
+        check_destination(&game).await?;
+
+        // Whether the link is made here, and has to go again if the install can't start
+        let linked = match &game.real_path {
+            Some(real_path) => {
+                if !real_path.is_absolute() {
+                    return Err(DownloaderError::PathNotAbsolute(real_path.clone()).into());
+                }
+
+                let existed = std::fs::read_link(&game.path).is_ok();
+                link_dir(real_path, &game.path)?;
+                !existed
+            }
+            None => false,
+        };
+
+        let downloader = match self.checked_downloader(&game, log).await {
+            Ok(downloader) => downloader,
+            Err(err) => {
+                if linked {
+                    if let Err(err) = remove_link(&game.path) {
+                        warn!("Failed to remove link `{}`: {}", game.path.display(), err);
+                    }
+                }
+                return Err(err);
+            }
+        };
+
+        if let Err(err) = save_zstate_destination(&game.offer_id, downloader.storage_path()) {
+            warn!(
+                "Failed to save where {} is installing to: {}",
+                game.slug, err
+            );
+        }
+
+        self.queue.failed.retain(|x| x.offer_id != game.offer_id);
+        self.queue.active.retain(|x| x.offer_id != game.offer_id);
+        self.queue.active.push(game.clone());
+        self.save_queue().await?;
 
-        let downloader = GameDownloader::new(&self.service, &game).await?;
         downloader.download();
-        self.current = Some(downloader);
+        self.active.push(downloader);
         Ok(())
     }
 
-    pub(crate) async fn update(&mut self) -> Result<Option<MaximaEvent>, ContentManagerError> {
-        let mut event = None;
+    /// Set up the download of `game`, as long as there's room for it alongside
+    /// everything that's already downloading
+    async fn checked_downloader(
+        &self,
+        game: &QueuedGame,
+        log: Arc<InstallLog>,
+    ) -> Result<GameDownloader, ContentManagerError> {
+        let downloader =
+            GameDownloader::new(&self.service, game, self.bandwidth_limiter.clone(), log).await?;
+
+        // Everything that's already downloading needs its space too
+        let mut installs = vec![(downloader.storage_path(), downloader.required_space())];
+        for active in &self.active {
+            installs.push((active.storage_path(), active.required_space()));
+        }
+        RequiredSpace::check_combined(&installs)?;
 
-        if let Some(current) = &self.current {
-            if current.is_done() {
-                event = Some(MaximaEvent::InstallFinished(current.offer_id.to_owned()));
-                self.current = None;
-                self.queue.current = None;
+        Ok(downloader)
+    }
 
-                if let Some(game) = self.queue.queued.pop() {
-                    self.install_now(game).await?;
-                }
+    /// Space an install of `offer_id`'s `build_id` would need, without starting it.
+    /// Pass the install path to leave out files that are already there.
+    pub async fn required_space(
+        &self,
+        offer_id: &str,
+        build_id: &str,
+        slug: &str,
+        path: Option<&Path>,
+    ) -> Result<RequiredSpace, ContentManagerError> {
+        let manifest = self.service.build_manifest(offer_id, build_id).await?;
+
+        let entries = install_entries(&manifest, slug);
+        Ok(RequiredSpace::for_entries(&entries, path))
+    }
+
+    /// Start relocating an installed game, see [`InstallMove::run`].
+    /// Fails if the game is being downloaded.
+    pub fn prepare_move(
+        &self,
+        offer_id: &str,
+        slug: &str,
+        new_path: PathBuf,
+    ) -> Result<InstallMove, ContentManagerError> {
+        if self.download(offer_id).is_some() || self.queue.is_active(offer_id) {
+            return Err(ContentManagerError::DownloadInProgress);
+        }
+
+        InstallMove::new(slug, new_path)
+    }
+
+    /// Move an installed game to `new_path`, copying across drives if needed
+    pub async fn move_install(
+        &self,
+        offer_id: &str,
+        slug: &str,
+        new_path: PathBuf,
+        progress: Option<MoveProgressCallback>,
+    ) -> Result<(), ContentManagerError> {
+        self.prepare_move(offer_id, slug, new_path)?
+            .run(progress)
+            .await
+    }
+
+    pub(crate) async fn update(&mut self) -> Result<Vec<MaximaEvent>, ContentManagerError> {
+        let mut events = Vec::new();
+
+        for downloader in &self.active {
+            downloader.sample_rate();
+
+            for (file, reason) in downloader.failures.lock().unwrap().drain(..) {
+                events.push(MaximaEvent::InstallEntryFailed {
+                    offer_id: downloader.offer_id.to_owned(),
+                    file,
+                    reason,
+                });
+            }
+        }
+
+        let (failed, active): (Vec<_>, Vec<_>) = self
+            .active
+            .drain(..)
+            .partition(|x| x.unrecovered.lock().unwrap().is_some());
+        let (finished, active): (Vec<_>, Vec<_>) = active.into_iter().partition(|x| x.is_done());
+        self.active = active;
+
+        // Kept aside with the files that are missing, so a retry only downloads those
+        for downloader in &failed {
+            let files = downloader
+                .unrecovered
+                .lock()
+                .unwrap()
+                .take()
+                .unwrap_or_default();
+            if let Some(index) = self
+                .queue
+                .active
+                .iter()
+                .position(|x| x.offer_id == downloader.offer_id)
+            {
+                let mut game = self.queue.active.remove(index);
+                game.only_entries = Some(files.clone());
+                self.queue.failed.push(game);
+            }
 
-                self.queue.save().await?;
+            events.push(MaximaEvent::InstallFailed(
+                downloader.offer_id.to_owned(),
+                files,
+            ));
+        }
+
+        for downloader in &finished {
+            // Builds and URLs looked up before the install may be out of date now
+            self.service.evict(&downloader.offer_id);
+            clear_zstate_destination(&downloader.offer_id);
+            events.push(MaximaEvent::InstallFinished(downloader.offer_id.to_owned()));
+            self.queue
+                .active
+                .retain(|x| x.offer_id != downloader.offer_id);
+        }
+
+        let mut changed = !finished.is_empty() || !failed.is_empty();
+        if changed || self.fill_slots {
+            self.fill_slots = false;
+            self.fill_free_slots().await;
+            changed = true;
+        }
+
+        if changed {
+            self.save_queue().await?;
+        }
+
+        if std::mem::take(&mut self.queue_changed) {
+            events.push(MaximaEvent::QueueChanged(self.queue.clone()));
+        }
+
+        Ok(events)
+    }
+
+    /// Save the queue, and report it to frontends on the next update
+    async fn save_queue(&mut self) -> Result<(), ContentManagerError> {
+        self.queue_changed = true;
+        self.queue.save().await
+    }
+
+    /// Stop every download, leaving them in the saved queue so they resume on the next start
+    pub async fn shutdown(&mut self) -> Result<(), ContentManagerError> {
+        for downloader in self.active.drain(..) {
+            downloader.cancel();
+        }
+
+        self.queue.save().await
+    }
+
+    /// Start queued games until every slot is taken
+    async fn fill_free_slots(&mut self) {
+        while self.has_free_slot() {
+            let game = match self.queue.queued.pop() {
+                Some(game) => game,
+                None => break,
+            };
+
+            // Keep it queued, so it's tried again next time a slot frees up
+            if let Err(err) = self.install_direct(game.clone()).await {
+                error!(
+                    "Failed to start queued install of {}: {}",
+                    game.offer_id, err
+                );
+                self.queue.queued.push(game);
+                break;
             }
         }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{
+        content::zip::tests::{deflated_entry, stored_entry},
+        core::service_layer::MockServiceLayer,
+        util::temp_dir::{TempDataDir, TempDir},
+    };
+
+    use super::*;
+
+    /// Save a queue with `queued` waiting and `failed` given up on to `file`, for
+    /// [`ContentManager::with_queue_file`]
+    pub(crate) fn write_queue_file(file: &Path, queued: Vec<QueuedGame>, failed: Vec<QueuedGame>) {
+        let queue = DownloadQueue {
+            queued,
+            failed,
+            ..Default::default()
+        };
+        std::fs::write(file, serde_json::to_string(&queue).unwrap()).unwrap();
+    }
+
+    pub(crate) fn queued_game(offer_id: &str, slug: &str, path: &Path) -> QueuedGame {
+        QueuedGameBuilder::default()
+            .offer_id(offer_id.to_owned())
+            .build_id("2".to_owned())
+            .path(path.to_owned())
+            .slug(slug.to_owned())
+            .wine_prefix(None)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn redownloaded_files_are_taken_off_the_progress() {
+        let completed = AtomicUsize::new(1000);
+        uncount_bytes(&completed, 400);
+        assert_eq!(completed.load(Ordering::SeqCst), 600);
+    }
+
+    #[test]
+    fn progress_never_goes_below_zero() {
+        // A broken file that was skipped as already there was never counted
+        let completed = AtomicUsize::new(100);
+        uncount_bytes(&completed, 400);
+        assert_eq!(completed.load(Ordering::SeqCst), 0);
+
+        uncount_bytes(&completed, 1);
+        assert_eq!(completed.load(Ordering::SeqCst), 0);
+    }
+
+    const OTHER_GAME_MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<game gameVersion="1.0.3.0" manifestVersion="2.0">
+  <contentIDs>
+    <contentID>71067</contentID>
+  </contentIDs>
+  <executable>
+    <filePath>__Installer/Cleanup.exe</filePath>
+    <parameters>/silent /dir="{installLocation}"</parameters>
+  </executable>
+</game>"#;
+
+    /// A game to install to `path`, with an offer ID of its own so tests don't share
+    /// inflate state
+    fn destination_game(name: &str, path: &Path, content_id: Option<&str>) -> QueuedGame {
+        let id = format!("{}-{}", name, std::process::id());
+        let mut game = queued_game(
+            &format!("Origin.OFR.destination-{}", id),
+            &format!("destination-test-{}", id),
+            path,
+        );
+        game.content_id = content_id.map(str::to_owned);
+        game
+    }
+
+    #[tokio::test]
+    async fn destinations_have_to_be_empty_folders() {
+        let dir = TempDir::new("destination-empty");
+        let game = destination_game("empty", &dir.join("game"), None);
+        let missing = check_destination(&game).await;
+
+        std::fs::create_dir_all(dir.join("game")).unwrap();
+        let empty = check_destination(&game).await;
+
+        std::fs::write(dir.join("game").join("notes.txt"), "").unwrap();
+        let taken = check_destination(&game).await;
+        let file = destination_game("empty", &dir.join("game").join("notes.txt"), None);
+        let not_folder = check_destination(&file).await;
+
+        assert!(missing.is_ok());
+        assert!(empty.is_ok());
+        assert!(matches!(
+            taken,
+            Err(ContentManagerError::DestinationExists(_))
+        ));
+        assert!(matches!(
+            not_folder,
+            Err(ContentManagerError::DestinationExists(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn destinations_with_a_game_have_to_hold_the_same_one() {
+        let dir = TempDir::new("destination-game");
+        let manifest = dir.join(MANIFEST_RELATIVE_PATH);
+        std::fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+        std::fs::write(&manifest, OTHER_GAME_MANIFEST).unwrap();
+
+        let game = |content_id| destination_game("game", dir.path(), content_id);
+        let same = check_destination(&game(Some("71067"))).await;
+        let other = check_destination(&game(Some("1035052"))).await;
+        // Without a content ID, a manifest of any game is in the way
+        let unknown = check_destination(&game(None)).await;
+
+        assert!(same.is_ok());
+        assert!(matches!(
+            other,
+            Err(ContentManagerError::DestinationHasOtherGame(_))
+        ));
+        assert!(matches!(
+            unknown,
+            Err(ContentManagerError::DestinationHasOtherGame(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unfinished_downloads_resume_only_in_their_own_folder() {
+        // The destination is kept with the download's state, in the data folder
+        let dir = TempDataDir::new();
+        for folder in ["game", "other"] {
+            std::fs::create_dir_all(dir.join(folder)).unwrap();
+            std::fs::write(dir.join(folder).join("game.exe"), "").unwrap();
+        }
+
+        let game = destination_game("resume", &dir.join("game"), None);
+        let mut elsewhere = game.clone();
+        elsewhere.path = dir.join("other");
+
+        save_zstate_destination(&game.offer_id, &game.path).unwrap();
+        let resumed = check_destination(&game).await;
+        let moved = check_destination(&elsewhere).await;
+
+        clear_zstate_destination(&game.offer_id);
+        let finished = check_destination(&game).await;
+
+        assert!(resumed.is_ok());
+        assert!(matches!(
+            moved,
+            Err(ContentManagerError::DestinationExists(_))
+        ));
+        assert!(matches!(
+            finished,
+            Err(ContentManagerError::DestinationExists(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn links_are_removed_when_installs_cant_start() {
+        // The install log goes in the data folder
+        let dir = TempDataDir::new();
+        let link = dir.join("link");
+
+        let queue_file = dir.join("download_queue.json");
+        write_queue_file(&queue_file, Vec::new(), Vec::new());
+        // No download URL to give, so the downloader can't be set up
+        let mut manager =
+            ContentManager::with_queue_file(Arc::new(MockServiceLayer::new()), &queue_file)
+                .await
+                .unwrap();
+
+        let mut game = queued_game("Origin.OFR.50.718", "link-cleanup-test", &link);
+        game.real_path = Some(dir.join("real"));
+        let result = manager.install_direct(game).await;
+        let link_left = std::fs::symlink_metadata(&link).is_ok();
+
+        assert!(result.is_err());
+        assert!(!link_left);
+    }
+
+    #[test]
+    fn inflate_state_is_counted_for_concurrent_files() {
+        let deflated = |x: usize| deflated_entry(&format!("{}.bin", x), 1024);
+
+        let few: Vec<ZipFileEntry> = (0..3).map(deflated).collect();
+        let space = RequiredSpace::for_entries(&few, None);
+        assert_eq!(space.install_bytes, 3 * 1024);
+        assert_eq!(space.temp_bytes, 3 * Z_STATE_MAX_SIZE);
+
+        let mut many: Vec<ZipFileEntry> = (0..100).map(deflated).collect();
+        many.push(stored_entry("stored.bin", 1024));
+        let space = RequiredSpace::for_entries(&many, None);
+        assert_eq!(space.install_bytes, 101 * 1024);
+        assert_eq!(
+            space.temp_bytes,
+            CONCURRENT_ENTRIES as u64 * Z_STATE_MAX_SIZE
+        );
+    }
+
+    fn names(entries: &[ZipFileEntry]) -> Vec<String> {
+        entries.iter().map(|x| x.name().to_owned()).collect()
+    }
+
+    /// Run [`GameDownloader::download_with_retries`] over three files, with `fails`
+    /// deciding which fail on each pass. Returns the files each pass was given.
+    async fn retry_passes(
+        name: &str,
+        mut fails: impl FnMut(u32, &ZipFileEntry) -> bool,
+    ) -> (Result<(), DownloaderError>, Vec<Vec<String>>) {
+        let log = InstallLog::create(&format!("{}-{}", name, std::process::id()));
+        let entries = vec![
+            stored_entry("a.bin", 1),
+            stored_entry("b.bin", 1),
+            stored_entry("c.bin", 1),
+        ];
+
+        let mut passes = Vec::new();
+        let result = GameDownloader::download_with_retries(
+            entries,
+            &CancellationToken::new(),
+            &log,
+            Duration::ZERO,
+            |pending| {
+                let pass = passes.len() as u32;
+                passes.push(names(&pending));
+                let failed: Vec<ZipFileEntry> =
+                    pending.into_iter().filter(|x| fails(pass, x)).collect();
+                async move { failed }
+            },
+        )
+        .await;
+
+        let _ = std::fs::remove_file(log.path());
+        (result, passes)
+    }
+
+    #[tokio::test]
+    async fn failed_files_are_retried() {
+        let (result, passes) = retry_passes("retry-test", |pass, entry| {
+            pass == 0 && entry.name() != "a.bin"
+        })
+        .await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            passes,
+            vec![vec!["a.bin", "b.bin", "c.bin"], vec!["b.bin", "c.bin"]]
+        );
+    }
+
+    #[tokio::test]
+    async fn installs_give_up_after_the_retry_passes() {
+        let (result, passes) = retry_passes("give-up-test", |pass, entry| {
+            pass == 0 || entry.name() == "c.bin"
+        })
+        .await;
 
-        Ok(event)
+        assert_eq!(passes.len() as u32, ENTRY_RETRY_PASSES + 1);
+        assert_eq!(passes.last().unwrap(), &vec!["c.bin"]);
+        assert!(matches!(result, Err(DownloaderError::EntriesFailed(files)) if files == ["c.bin"]));
     }
 }
@@ -1,47 +1,148 @@
-use std::time::Duration;
-
-use crate::core::{
-    auth::storage::LockedAuthStorage,
-    cache::DynamicCache,
-    service_layer::{
-        ServiceAvailableBuild, ServiceAvailableBuilds, ServiceAvailableBuildsBuilder,
-        ServiceAvailableBuildsRequestBuilder, ServiceDownloadUrlMetadata,
-        ServiceDownloadUrlRequestBuilder, ServiceLayerClient, ServiceLayerError,
-        SERVICE_REQUEST_AVAILABLEBUILDS, SERVICE_REQUEST_DOWNLOADURL,
+use std::{sync::Arc, time::Duration};
+
+use log::warn;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    content::{
+        downloader::UrlRefresher,
+        manager::DownloaderError,
+        zip::{ZipFile, ZipFileEntry},
+    },
+    core::{
+        auth::storage::LockedAuthStorage,
+        cache::{unix_now, DynamicCache},
+        concurrency::KeyedLock,
+        service_layer::{
+            ServiceAvailableBuild, ServiceAvailableBuilds, ServiceAvailableBuildsBuilder,
+            ServiceAvailableBuildsRequestBuilder, ServiceDownloadUrlMetadata,
+            ServiceDownloadUrlRequestBuilder, ServiceLayerError, SharedServiceLayer,
+            SERVICE_REQUEST_AVAILABLEBUILDS, SERVICE_REQUEST_DOWNLOADURL,
+        },
     },
 };
 
+pub mod bandwidth;
 pub mod downloader;
 pub mod exclusion;
+pub mod install_log;
+pub mod install_template;
 pub mod manager;
+pub mod mover;
+pub mod rate;
 pub mod zip;
 pub mod zlib;
 
+/// Default of [`ContentService::set_builds_cache_ttl`]
+pub const DEFAULT_BUILDS_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+/// Default of [`ContentService::set_download_url_cache_ttl`]
+pub const DEFAULT_DOWNLOAD_URL_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+/// Cached download URLs are dropped this long before the CDN says they expire, so a
+/// download doesn't start on one that's about to stop working
+const URL_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// A cached lookup and when it stops being valid. The request cache's TTL is the same
+/// for every entry, so this is checked on top of it.
+#[derive(Clone, Serialize, Deserialize)]
+struct Expiring<T> {
+    expires_at: u64,
+    value: T,
+}
+
+/// Clones share their cache and in-flight lookups, so a clone can be handed to a task
+#[derive(Clone)]
 pub struct ContentService {
-    service_layer: ServiceLayerClient,
-    request_cache: DynamicCache<String>,
+    service_layer: SharedServiceLayer,
+    /// Download URLs are signed for the user, so they're cached per user
+    auth_storage: LockedAuthStorage,
+    request_cache: Arc<DynamicCache<String>>,
+    /// Held while a build or download URL lookup is requested, so games looked up by
+    /// several tasks at once are only requested once
+    in_flight: Arc<KeyedLock>,
+    builds_ttl: Duration,
+    download_url_ttl: Duration,
 }
 
 impl ContentService {
-    pub fn new(auth: LockedAuthStorage) -> Self {
+    pub fn new(service_layer: SharedServiceLayer, auth_storage: LockedAuthStorage) -> Self {
         let request_cache = DynamicCache::new(
             100,
             Duration::from_secs(30 * 60),
             Duration::from_secs(5 * 60),
-        );
+        )
+        .persistent("content");
 
         Self {
-            service_layer: ServiceLayerClient::new(auth),
-            request_cache,
+            service_layer,
+            auth_storage,
+            request_cache: Arc::new(request_cache),
+            in_flight: Arc::default(),
+            builds_ttl: DEFAULT_BUILDS_CACHE_TTL,
+            download_url_ttl: DEFAULT_DOWNLOAD_URL_CACHE_TTL,
         }
     }
 
+    /// Keep each game's available builds for `ttl`, across restarts. Zero stops caching them.
+    pub fn set_builds_cache_ttl(&mut self, ttl: Duration) {
+        self.builds_ttl = ttl;
+    }
+
+    /// Keep download URLs for `ttl`, across restarts, or until shortly before the CDN says
+    /// they expire if that's sooner. Zero stops caching them.
+    pub fn set_download_url_cache_ttl(&mut self, ttl: Duration) {
+        self.download_url_ttl = ttl;
+    }
+
+    /// Forget the builds and download URLs cached for `offer_id`, for every user, since
+    /// installing or updating it is when they're known to have changed
+    pub fn evict(&self, offer_id: &str) {
+        self.request_cache
+            .invalidate(&("builds_".to_owned() + offer_id));
+        self.request_cache
+            .invalidate_prefix_persisted(&("download_url_".to_owned() + offer_id + "_"));
+    }
+
+    fn cached<T>(&self, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned + Sync + Send + Clone + 'static,
+    {
+        let entry: Expiring<T> = self.request_cache.get_persisted(key)?;
+        if entry.expires_at > unix_now() {
+            return Some(entry.value);
+        }
+
+        self.request_cache.invalidate(key);
+        None
+    }
+
+    fn cache<T>(&self, key: String, value: T, ttl: Duration)
+    where
+        T: Serialize + Sync + Send + Clone + 'static,
+    {
+        if ttl.is_zero() {
+            return;
+        }
+
+        let entry = Expiring {
+            expires_at: unix_now() + ttl.as_secs(),
+            value,
+        };
+        self.request_cache.insert_persisted_for(key, entry, ttl);
+    }
+
     pub async fn available_builds(
         &self,
         offer_id: &str,
     ) -> Result<ServiceAvailableBuilds, ServiceLayerError> {
         let cache_key = "builds_".to_owned() + offer_id;
-        if let Some(cached) = self.request_cache.get(&cache_key) {
+        if let Some(cached) = self.cached(&cache_key) {
+            return Ok(cached);
+        }
+
+        let _in_flight = self.in_flight.lock(&cache_key).await;
+        // Whoever held the lock before us may have just cached it
+        if let Some(cached) = self.cached(&cache_key) {
             return Ok(cached);
         }
 
@@ -58,7 +159,7 @@ impl ContentService {
         let builds = ServiceAvailableBuildsBuilder::default()
             .builds(builds)
             .build()?;
-        self.request_cache.insert(cache_key, builds.clone());
+        self.cache(cache_key, builds.clone(), self.builds_ttl);
         Ok(builds)
     }
 
@@ -67,23 +168,388 @@ impl ContentService {
         offer_id: &str,
         build_id: Option<&str>,
     ) -> Result<ServiceDownloadUrlMetadata, ServiceLayerError> {
-        let cache_key = "download_url_".to_owned() + offer_id + "_" + build_id.unwrap_or("live");
-        if let Some(cached) = self.request_cache.get(&cache_key) {
+        let cache_key = download_url_key(offer_id, build_id, &self.user_id().await?);
+        if let Some(cached) = self.cached(&cache_key) {
             return Ok(cached);
         }
 
-        let url: ServiceDownloadUrlMetadata = self
-            .service_layer
-            .request(
-                SERVICE_REQUEST_DOWNLOADURL,
-                ServiceDownloadUrlRequestBuilder::default()
-                    .offer_id(offer_id.to_owned())
-                    .build_id(build_id.unwrap_or_default().to_owned())
-                    .build()?,
-            )
-            .await?;
+        let _in_flight = self.in_flight.lock(&cache_key).await;
+        if let Some(cached) = self.cached(&cache_key) {
+            return Ok(cached);
+        }
+
+        self.refresh_download_url(offer_id, build_id).await
+    }
 
-        self.request_cache.insert(cache_key, url.clone());
+    /// Request a build's download URLs again, replacing the cached ones
+    async fn refresh_download_url(
+        &self,
+        offer_id: &str,
+        build_id: Option<&str>,
+    ) -> Result<ServiceDownloadUrlMetadata, ServiceLayerError> {
+        let url = request_download_url(&self.service_layer, offer_id, build_id).await?;
+        let ttl = download_url_ttl(&url, self.download_url_ttl);
+        let cache_key = download_url_key(offer_id, build_id, &self.user_id().await?);
+        self.cache(cache_key, url.clone(), ttl);
         Ok(url)
     }
+
+    async fn user_id(&self) -> Result<String, ServiceLayerError> {
+        let mut auth_storage = self.auth_storage.lock().await;
+        Ok(auth_storage.user_id().await?.unwrap_or_default())
+    }
+
+    /// Requests a build's download URLs again, bypassing the cache, for a
+    /// [`ZipDownloader`](downloader::ZipDownloader) whose URLs stopped working.
+    /// The new URLs are cached in place of the old ones.
+    pub fn url_refresher(&self, offer_id: &str, build_id: &str) -> UrlRefresher {
+        let service = self.clone();
+        let offer_id = offer_id.to_owned();
+        let build_id = build_id.to_owned();
+
+        Box::new(move || {
+            let service = service.clone();
+            let offer_id = offer_id.clone();
+            let build_id = build_id.clone();
+
+            Box::pin(async move {
+                match service
+                    .refresh_download_url(&offer_id, Some(&build_id))
+                    .await
+                {
+                    Ok(url) => Some(url.mirrors()),
+                    Err(err) => {
+                        warn!("Failed to request new download URLs: {}", err);
+                        None
+                    }
+                }
+            })
+        })
+    }
+
+    /// Every file in a build, with its size and CRC32, read from the end of the
+    /// build's zip without downloading it. Builds don't change, so this is cached.
+    pub async fn build_manifest(
+        &self,
+        offer_id: &str,
+        build_id: &str,
+    ) -> Result<Vec<ZipFileEntry>, DownloaderError> {
+        let cache_key = "build_manifest_".to_owned() + offer_id + "_" + build_id;
+        if let Some(cached) = self.request_cache.get(&cache_key) {
+            return Ok(cached);
+        }
+
+        let url = self.download_url(offer_id, Some(build_id)).await?;
+        let (zip, _) = ZipFile::fetch_any(&url.mirrors()).await?;
+        let entries = zip.entries().clone();
+
+        self.request_cache.insert(cache_key, entries.clone());
+        Ok(entries)
+    }
+}
+
+/// The user comes last, so [`ContentService::evict`] can match every user's by prefix
+fn download_url_key(offer_id: &str, build_id: Option<&str>, user_id: &str) -> String {
+    "download_url_".to_owned() + offer_id + "_" + build_id.unwrap_or("live") + "_" + user_id
+}
+
+async fn request_download_url(
+    service_layer: &SharedServiceLayer,
+    offer_id: &str,
+    build_id: Option<&str>,
+) -> Result<ServiceDownloadUrlMetadata, ServiceLayerError> {
+    service_layer
+        .request(
+            SERVICE_REQUEST_DOWNLOADURL,
+            ServiceDownloadUrlRequestBuilder::default()
+                .offer_id(offer_id.to_owned())
+                .build_id(build_id.unwrap_or_default().to_owned())
+                .build()?,
+        )
+        .await
+}
+
+/// When a signed CDN URL stops working, in seconds since epoch, if it says
+fn url_expiry(url: &str) -> Option<u64> {
+    let url = Url::parse(url).ok()?;
+    let expiry = url
+        .query_pairs()
+        .find_map(|(key, value)| match key.as_ref() {
+            "Expires" => value.parse().ok(),
+            // Akamai tokens, like `exp=1700000000~acl=/*~hmac=...`
+            "__token__" | "hdnts" => value
+                .split('~')
+                .find_map(|x| x.strip_prefix("exp=")?.parse().ok()),
+            _ => None,
+        });
+    expiry
+}
+
+/// How long `url` can be cached, `ttl` unless one of its mirrors expires sooner
+fn download_url_ttl(url: &ServiceDownloadUrlMetadata, ttl: Duration) -> Duration {
+    let now = unix_now();
+    url.mirrors()
+        .iter()
+        .filter_map(|x| url_expiry(x))
+        .map(|expiry| {
+            Duration::from_secs(expiry.saturating_sub(now)).saturating_sub(URL_EXPIRY_MARGIN)
+        })
+        .fold(ttl, Duration::min)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::Path;
+
+    use tokio::sync::Mutex;
+
+    use crate::{
+        core::{
+            auth::storage::{tests::storage, AuthStorage},
+            service_layer::MockServiceLayer,
+        },
+        util::temp_dir::TempDir,
+    };
+
+    use super::*;
+
+    fn service_with(
+        service_layer: SharedServiceLayer,
+        auth_storage: LockedAuthStorage,
+        request_cache: DynamicCache<String>,
+    ) -> ContentService {
+        ContentService {
+            service_layer,
+            auth_storage,
+            request_cache: Arc::new(request_cache),
+            in_flight: Arc::default(),
+            builds_ttl: DEFAULT_BUILDS_CACHE_TTL,
+            download_url_ttl: DEFAULT_DOWNLOAD_URL_CACHE_TTL,
+        }
+    }
+
+    fn request_cache() -> DynamicCache<String> {
+        DynamicCache::new(100, Duration::from_secs(60), Duration::from_secs(60))
+    }
+
+    /// A content service whose lookups aren't persisted
+    pub(crate) fn memory_service(service_layer: SharedServiceLayer) -> ContentService {
+        service_with(service_layer, AuthStorage::new(), request_cache())
+    }
+
+    /// A content service for `user_id` that persists its lookups in `dir`, like one
+    /// started again after a restart would
+    fn persistent_service(
+        service_layer: SharedServiceLayer,
+        dir: &Path,
+        user_id: &str,
+    ) -> ContentService {
+        service_with(
+            service_layer,
+            Arc::new(Mutex::new(storage(&[user_id], user_id))),
+            request_cache().persistent_in(dir.to_owned()),
+        )
+    }
+
+    fn builds_json() -> serde_json::Value {
+        serde_json::json!([{
+            "buildId": "1",
+            "downloadType": "LIVE",
+            "gameVersion": "1.0",
+            "buildReleaseVersion": null,
+            "buildLiveDate": null,
+        }])
+    }
+
+    /// Have `offer_id`'s `build_id` list `entries`, without fetching its zip
+    pub(crate) fn cache_build_manifest(
+        service: &ContentService,
+        offer_id: &str,
+        build_id: &str,
+        entries: Vec<ZipFileEntry>,
+    ) {
+        service.request_cache.insert(
+            "build_manifest_".to_owned() + offer_id + "_" + build_id,
+            entries,
+        );
+    }
+
+    fn download_url_json(url: &str) -> serde_json::Value {
+        serde_json::json!({ "url": url, "syncUrl": null })
+    }
+
+    #[test]
+    fn reads_cdn_url_expiry() {
+        assert_eq!(
+            url_expiry("https://cdn.example.com/build.zip?Expires=1700000000&Signature=abc"),
+            Some(1700000000)
+        );
+        assert_eq!(
+            url_expiry(
+                "https://cdn.example.com/build.zip?__token__=exp=1700000300~acl=%2F*~hmac=abc"
+            ),
+            Some(1700000300)
+        );
+        assert_eq!(url_expiry("https://cdn.example.com/build.zip"), None);
+    }
+
+    #[tokio::test]
+    async fn refreshed_urls_replace_cached_ones() {
+        let service_layer = Arc::new(MockServiceLayer::new().with_response(
+            SERVICE_REQUEST_DOWNLOADURL,
+            download_url_json("https://cdn.example.com/old.zip"),
+        ));
+        let service = memory_service(service_layer.clone());
+
+        let url = service
+            .download_url("Origin.OFR.50.1", Some("1"))
+            .await
+            .unwrap();
+        assert_eq!(url.url(), "https://cdn.example.com/old.zip");
+
+        service_layer.set_response(
+            SERVICE_REQUEST_DOWNLOADURL,
+            download_url_json("https://cdn.example.com/new.zip"),
+        );
+        let refreshed = service.url_refresher("Origin.OFR.50.1", "1")().await;
+        assert_eq!(refreshed.unwrap(), ["https://cdn.example.com/new.zip"]);
+
+        let url = service
+            .download_url("Origin.OFR.50.1", Some("1"))
+            .await
+            .unwrap();
+        assert_eq!(url.url(), "https://cdn.example.com/new.zip");
+        assert_eq!(service_layer.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn concurrent_lookups_are_requested_once() {
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json())
+                .with_response(
+                    SERVICE_REQUEST_DOWNLOADURL,
+                    download_url_json("https://cdn.example.com/build.zip"),
+                ),
+        );
+        let service = memory_service(service_layer.clone());
+
+        let (first, second) = tokio::join!(
+            service.available_builds("Origin.OFR.50.1"),
+            service.available_builds("Origin.OFR.50.1"),
+        );
+        first.unwrap();
+        second.unwrap();
+        let (first, second) = tokio::join!(
+            service.download_url("Origin.OFR.50.1", Some("1")),
+            service.download_url("Origin.OFR.50.1", Some("1")),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        assert_eq!(service_layer.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn download_urls_are_kept_across_restarts_until_they_expire() {
+        let dir = TempDir::new("content-restart");
+        // Cached for a second, since it's dropped a minute before the CDN stops taking it
+        let url = format!(
+            "https://cdn.example.com/build.zip?Expires={}",
+            unix_now() + URL_EXPIRY_MARGIN.as_secs() + 1
+        );
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(SERVICE_REQUEST_DOWNLOADURL, download_url_json(&url)),
+        );
+        let service = || persistent_service(service_layer.clone(), dir.path(), "1");
+
+        service()
+            .download_url("Origin.OFR.50.1", Some("1"))
+            .await
+            .unwrap();
+        let url_after_restart = service()
+            .download_url("Origin.OFR.50.1", Some("1"))
+            .await
+            .unwrap();
+        assert_eq!(url_after_restart.url(), &url);
+        assert_eq!(service_layer.requests().len(), 1);
+
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        service()
+            .download_url("Origin.OFR.50.1", Some("1"))
+            .await
+            .unwrap();
+        assert_eq!(service_layer.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn download_urls_are_kept_per_user() {
+        let dir = TempDir::new("content-user");
+        let service_layer = Arc::new(MockServiceLayer::new().with_response(
+            SERVICE_REQUEST_DOWNLOADURL,
+            download_url_json("https://cdn.example.com/build.zip"),
+        ));
+
+        for user_id in ["1", "2", "1"] {
+            persistent_service(service_layer.clone(), dir.path(), user_id)
+                .download_url("Origin.OFR.50.1", Some("1"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(service_layer.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn zero_ttl_turns_caching_off() {
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json())
+                .with_response(
+                    SERVICE_REQUEST_DOWNLOADURL,
+                    download_url_json("https://cdn.example.com/build.zip"),
+                ),
+        );
+        let mut service = memory_service(service_layer.clone());
+        service.set_builds_cache_ttl(Duration::ZERO);
+        service.set_download_url_cache_ttl(Duration::ZERO);
+
+        for _ in 0..2 {
+            service.available_builds("Origin.OFR.50.1").await.unwrap();
+            service
+                .download_url("Origin.OFR.50.1", Some("1"))
+                .await
+                .unwrap();
+        }
+        assert_eq!(service_layer.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn evict_forgets_only_that_offer() {
+        let dir = TempDir::new("content-evict");
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json())
+                .with_response(
+                    SERVICE_REQUEST_DOWNLOADURL,
+                    download_url_json("https://cdn.example.com/build.zip"),
+                ),
+        );
+        async fn look_up(service: &ContentService, offer_id: &str) {
+            service.available_builds(offer_id).await.unwrap();
+            service.download_url(offer_id, Some("1")).await.unwrap();
+        }
+
+        let service = persistent_service(service_layer.clone(), dir.path(), "1");
+        look_up(&service, "Origin.OFR.50.1").await;
+        look_up(&service, "Origin.OFR.50.2").await;
+        assert_eq!(service_layer.requests().len(), 4);
+
+        // Gone from disk too, so a restart doesn't bring it back
+        service.evict("Origin.OFR.50.1");
+        let restarted = persistent_service(service_layer.clone(), dir.path(), "1");
+        look_up(&restarted, "Origin.OFR.50.1").await;
+        look_up(&restarted, "Origin.OFR.50.2").await;
+        assert_eq!(service_layer.requests().len(), 6);
+    }
 }
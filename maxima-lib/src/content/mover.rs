@@ -0,0 +1,170 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use derive_getters::Getters;
+use log::{info, warn};
+
+use crate::{
+    content::manager::{ContentManagerError, DownloaderError},
+    gameinfo::{load_game_info_from_json, stats::dir_size, GameInstallInfo},
+    util::native::{remove_link, volume_space, SafeParent},
+};
+
+#[cfg(windows)]
+use crate::core::manifest::{self, MANIFEST_RELATIVE_PATH};
+
+/// Called with the bytes copied so far and the total, while moving across drives
+pub type MoveProgressCallback = Box<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Relocation of an installed game's files. Created with [`ContentManager::prepare_move`],
+/// so the move itself can run without holding a lock on Maxima.
+///
+/// [`ContentManager::prepare_move`]: crate::content::manager::ContentManager::prepare_move
+#[derive(Getters)]
+pub struct InstallMove {
+    slug: String,
+    /// Where the files currently live, following the link for linked installs
+    from: PathBuf,
+    to: PathBuf,
+    #[getter(skip)]
+    info: GameInstallInfo,
+}
+
+impl InstallMove {
+    pub fn new(slug: &str, to: PathBuf) -> Result<Self, ContentManagerError> {
+        if !to.is_absolute() {
+            return Err(DownloaderError::PathNotAbsolute(to).into());
+        }
+
+        let info = load_game_info_from_json(slug)
+            .map_err(|_| ContentManagerError::NotInstalled(slug.to_owned()))?;
+        let from = info.real_path();
+        if !from.exists() {
+            return Err(ContentManagerError::NotInstalled(slug.to_owned()));
+        }
+
+        if to.exists() && (!to.is_dir() || fs::read_dir(&to)?.next().is_some()) {
+            return Err(ContentManagerError::DestinationExists(to));
+        }
+
+        if to.starts_with(&from) {
+            return Err(ContentManagerError::DestinationExists(to));
+        }
+
+        Ok(Self {
+            slug: slug.to_owned(),
+            from,
+            to,
+            info,
+        })
+    }
+
+    /// Move the files, then point the game's install info, and on Windows its registry
+    /// entries, at the new location. Linked installs lose their link and become regular ones.
+    pub async fn run(
+        self,
+        progress: Option<MoveProgressCallback>,
+    ) -> Result<(), ContentManagerError> {
+        info!(
+            "Moving `{}` from {} to {}",
+            self.slug,
+            self.from.display(),
+            self.to.display()
+        );
+
+        let from = self.from.clone();
+        let to = self.to.clone();
+        tokio::task::spawn_blocking(move || move_dir(&from, &to, progress)).await??;
+
+        if self.info.real_path.is_some() {
+            if let Err(err) = remove_link(&self.info.path) {
+                warn!("Failed to remove old install link: {}", err);
+            }
+        }
+
+        let mut info = self.info;
+        info.path = self.to.clone();
+        info.real_path = None;
+        info.save_to_json(&self.slug);
+
+        // Touchup records the install location in the registry. On linux/mac it's run on every launch
+        #[cfg(windows)]
+        {
+            info!("Running touchup...");
+            let manifest = manifest::read(self.to.join(MANIFEST_RELATIVE_PATH)).await?;
+            manifest.run_touchup(&self.to, &self.slug).await?;
+        }
+
+        info!("Moved `{}`", self.slug);
+        Ok(())
+    }
+}
+
+fn move_dir(
+    from: &Path,
+    to: &Path,
+    progress: Option<MoveProgressCallback>,
+) -> Result<(), ContentManagerError> {
+    if to.exists() {
+        // Renaming over an empty folder isn't allowed everywhere
+        fs::remove_dir(to)?;
+    }
+
+    fs::create_dir_all(to.safe_parent()?)?;
+
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+
+    // Usually a move across drives, so copy and delete instead
+    let total = dir_size(from)?;
+    if let Some((_, available)) = volume_space(to) {
+        if available < total {
+            return Err(ContentManagerError::InsufficientSpace {
+                path: to.to_owned(),
+                required: total,
+                available,
+            });
+        }
+    }
+
+    let mut copied = 0;
+    let result = copy_dir(from, to, &mut copied, total, progress.as_ref());
+    if let Err(err) = result {
+        // Don't leave a half-copied game behind, the original is still intact
+        let _ = fs::remove_dir_all(to);
+        return Err(err);
+    }
+
+    fs::remove_dir_all(from)?;
+    Ok(())
+}
+
+fn copy_dir(
+    from: &Path,
+    to: &Path,
+    copied: &mut u64,
+    total: u64,
+    progress: Option<&MoveProgressCallback>,
+) -> Result<(), ContentManagerError> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target, copied, total, progress)?;
+            continue;
+        }
+
+        *copied += fs::copy(entry.path(), target)?;
+        if let Some(progress) = progress {
+            progress(*copied, total);
+        }
+    }
+
+    Ok(())
+}
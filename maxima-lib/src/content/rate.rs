@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+/// Samples closer together than this are skipped, they're mostly noise
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+/// Weight of the newest sample in the moving average
+const SMOOTHING: f64 = 0.2;
+
+/// Smoothed transfer rate, from an exponential moving average over progress samples
+#[derive(Debug, Default)]
+pub struct RateEstimator {
+    last_sample: Option<(Instant, u64)>,
+    bytes_per_second: Option<f64>,
+}
+
+impl RateEstimator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that `bytes` have been transferred in total so far
+    pub fn sample(&mut self, bytes: u64) {
+        self.sample_at(Instant::now(), bytes);
+    }
+
+    pub fn sample_at(&mut self, now: Instant, bytes: u64) {
+        let (last_time, last_bytes) = match self.last_sample {
+            Some(sample) => sample,
+            None => {
+                self.last_sample = Some((now, bytes));
+                return;
+            }
+        };
+
+        let elapsed = now.saturating_duration_since(last_time);
+        if elapsed < MIN_SAMPLE_INTERVAL {
+            return;
+        }
+
+        let rate = bytes.saturating_sub(last_bytes) as f64 / elapsed.as_secs_f64();
+        self.bytes_per_second = Some(match self.bytes_per_second {
+            Some(average) => average + SMOOTHING * (rate - average),
+            None => rate,
+        });
+        self.last_sample = Some((now, bytes));
+    }
+
+    /// Forget all samples, for when the transfer is paused or restarted
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn bytes_per_second(&self) -> Option<u64> {
+        self.bytes_per_second.map(|x| x as u64)
+    }
+
+    /// Time left to transfer `remaining` bytes at the current rate
+    pub fn eta(&self, remaining: u64) -> Option<Duration> {
+        match self.bytes_per_second {
+            Some(rate) if rate >= 1.0 => Some(Duration::from_secs_f64(remaining as f64 / rate)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smooths_rate() {
+        let start = Instant::now();
+        let mut estimator = RateEstimator::new();
+        assert_eq!(estimator.bytes_per_second(), None);
+
+        estimator.sample_at(start, 0);
+        estimator.sample_at(start + Duration::from_secs(1), 1000);
+        assert_eq!(estimator.bytes_per_second(), Some(1000));
+        assert_eq!(estimator.eta(5000), Some(Duration::from_secs(5)));
+
+        // A burst only moves the average part of the way
+        estimator.sample_at(start + Duration::from_secs(2), 11000);
+        assert_eq!(estimator.bytes_per_second(), Some(2800));
+
+        // Too close to the last sample, ignored
+        estimator.sample_at(start + Duration::from_millis(2100), 100000);
+        assert_eq!(estimator.bytes_per_second(), Some(2800));
+    }
+
+    #[test]
+    fn reset_forgets_rate() {
+        let start = Instant::now();
+        let mut estimator = RateEstimator::new();
+        estimator.sample_at(start, 0);
+        estimator.sample_at(start + Duration::from_secs(1), 1000);
+
+        estimator.reset();
+        assert_eq!(estimator.bytes_per_second(), None);
+        assert_eq!(estimator.eta(1000), None);
+    }
+}
@@ -3,11 +3,13 @@ use derive_getters::Getters;
 use encoding::{all::WINDOWS_1252, DecoderTrap, Encoding};
 use log::{debug, warn};
 use reqwest::header::ToStrError;
-use reqwest::Client;
+use serde::Serialize;
 use std::cmp;
 use std::string::FromUtf8Error;
 use thiserror::Error;
 
+use crate::util::http;
+
 /// This module is based on https://users.cs.jmu.edu/buchhofp/forensics/formats/pkzip.html
 
 const ZIP_EOCD_SIGNATURE: u32 = 0x06054b50;
@@ -20,6 +22,14 @@ const ZIP_EOCD_FIXED_PART_SIZE: u32 = 22;
 const ZIP64_EOCD_FIXED_PART_SIZE: u32 = 56;
 
 const ZIP_FILE_HEADER_SIGNATURE: u32 = 0x02014b50;
+const ZIP_LOCAL_FILE_HEADER_SIGNATURE: u32 = 0x04034b50;
+
+/// Version 2.0, the lowest that has folders and deflate
+const ZIP_VERSION: u16 = 20;
+/// General purpose flag for names encoded as UTF-8
+const ZIP_UTF8_FLAG: u16 = 0x0800;
+/// 1980-01-01 in DOS date format, the earliest a zip can hold
+const ZIP_EPOCH_DATE: u16 = 0x21;
 
 const MAX_BACKSCAN_OFFSET: usize = 6 * 1024 * 1024;
 
@@ -74,6 +84,8 @@ pub enum ZipError {
     NotEnoughSpace(usize),
     #[error("no content length found in response")]
     NoContentLength,
+    #[error("no URLs to download the zip from")]
+    NoUrls,
     #[error("requested read was too big (attempted {attempted}, limit is {max}")]
     ReadTooBig { attempted: i64, max: i64 },
     #[error("invalid signature {0:#10x}")]
@@ -95,7 +107,7 @@ fn signature_scan_rev(data: &[u8], signature: u32) -> Option<usize> {
     None
 }
 
-#[derive(Default, Clone, Debug, PartialEq)]
+#[derive(Default, Clone, Debug, PartialEq, Serialize)]
 pub enum CompressionType {
     #[default]
     None = 0,
@@ -111,7 +123,7 @@ impl CompressionType {
     }
 }
 
-#[derive(Default, Debug, Clone, Getters)]
+#[derive(Default, Debug, Clone, Getters, Serialize)]
 pub struct ZipFileEntry {
     name: String,
     crc32: u32,
@@ -123,6 +135,7 @@ pub struct ZipFileEntry {
     data_offset: i64,
 
     #[getter(skip)]
+    #[serde(skip)]
     extra_field: Vec<u8>,
 }
 
@@ -224,9 +237,18 @@ impl ZipFileEntry {
     }
 }
 
+/// Tells one archive from another without reading all of it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ZipArchiveId {
+    pub content_length: i64,
+    /// Where the central directory starts
+    pub cd_offset: i64,
+}
+
 #[derive(Default, Getters)]
 pub struct ZipFile {
     entries: Vec<ZipFileEntry>,
+    archive_id: ZipArchiveId,
 }
 
 #[derive(Default)]
@@ -297,9 +319,11 @@ impl EndOfCentralDirectory {
 
 impl ZipFile {
     pub async fn fetch(url: &str) -> Result<Self, ZipError> {
-        let client = Client::new();
+        let client = http::client_builder()
+            .redirect(http::secure_redirects())
+            .build()?;
 
-        let response = client.head(url).send().await?;
+        let response = client.head(url).send().await?.error_for_status()?;
         let content_length = response
             .headers()
             .get("content-length")
@@ -315,6 +339,7 @@ impl ZipFile {
         }
 
         let mut zip = Self::default();
+        zip.archive_id.content_length = content_length;
 
         while offset > 0 && data.len() < MAX_BACKSCAN_OFFSET {
             let read = content_length - offset - data.len() as i64;
@@ -342,6 +367,23 @@ impl ZipFile {
         Ok(zip)
     }
 
+    /// Read the zip from the first of `urls` that works, returning it along with the
+    /// index of that URL
+    pub async fn fetch_any(urls: &[String]) -> Result<(Self, usize), ZipError> {
+        let mut result = Err(ZipError::NoUrls);
+        for (idx, url) in urls.iter().enumerate() {
+            match Self::fetch(url).await {
+                Ok(zip) => return Ok((zip, idx)),
+                Err(err) => {
+                    warn!("Failed to read the zip from mirror {}: {}", idx, err);
+                    result = Err(err);
+                }
+            }
+        }
+
+        result
+    }
+
     fn load(&mut self, data: &mut ByteBuffer, total_size: i64) -> Result<i64, ZipError> {
         data.set_endian(Endian::LittleEndian);
 
@@ -400,6 +442,7 @@ impl ZipFile {
 
         let pos = eocd.cd_offset - (total_size - data.len() as i64);
         data.set_rpos(pos as usize);
+        self.archive_id.cd_offset = eocd.cd_offset;
 
         self.load_central_directory(data, eocd)?;
 
@@ -450,3 +493,91 @@ impl ZipFile {
         Ok(())
     }
 }
+
+/// Pack `files`, given as names and contents, into a zip without compressing them.
+/// Names are relative paths with forward slashes. Meant for small bundles written by
+/// Maxima itself, so zip64 isn't supported.
+pub fn write_stored_zip(files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut data = ByteBuffer::new();
+    data.set_endian(Endian::LittleEndian);
+    let mut central_directory = ByteBuffer::new();
+    central_directory.set_endian(Endian::LittleEndian);
+
+    for (name, content) in files {
+        let local_header_offset = data.len() as u32;
+        let crc32 = crc32fast::hash(content);
+        let size = content.len() as u32;
+
+        data.write_u32(ZIP_LOCAL_FILE_HEADER_SIGNATURE);
+        data.write_u16(ZIP_VERSION);
+        data.write_u16(ZIP_UTF8_FLAG);
+        data.write_u16(CompressionType::None as u16);
+        data.write_u16(0); // Modification time
+        data.write_u16(ZIP_EPOCH_DATE);
+        data.write_u32(crc32);
+        data.write_u32(size); // Compressed size
+        data.write_u32(size);
+        data.write_u16(name.len() as u16);
+        data.write_u16(0); // Extra field length
+        data.write_bytes(name.as_bytes());
+        data.write_bytes(content);
+
+        central_directory.write_u32(ZIP_FILE_HEADER_SIGNATURE);
+        central_directory.write_u16(ZIP_VERSION); // Made by
+        central_directory.write_u16(ZIP_VERSION); // Needed to extract
+        central_directory.write_u16(ZIP_UTF8_FLAG);
+        central_directory.write_u16(CompressionType::None as u16);
+        central_directory.write_u16(0);
+        central_directory.write_u16(ZIP_EPOCH_DATE);
+        central_directory.write_u32(crc32);
+        central_directory.write_u32(size);
+        central_directory.write_u32(size);
+        central_directory.write_u16(name.len() as u16);
+        central_directory.write_u16(0); // Extra field length
+        central_directory.write_u16(0); // Comment length
+        central_directory.write_u16(0); // Disk number start
+        central_directory.write_u16(0); // Internal attributes
+        central_directory.write_u32(0); // External attributes
+        central_directory.write_u32(local_header_offset);
+        central_directory.write_bytes(name.as_bytes());
+    }
+
+    let cd_offset = data.len() as u32;
+    let cd_size = central_directory.len() as u32;
+    data.write_bytes(central_directory.as_bytes());
+
+    data.write_u32(ZIP_EOCD_SIGNATURE);
+    data.write_u16(0); // This disk
+    data.write_u16(0); // Disk with the central directory
+    data.write_u16(files.len() as u16); // Entries on this disk
+    data.write_u16(files.len() as u16);
+    data.write_u32(cd_size);
+    data.write_u32(cd_offset);
+    data.write_u16(0); // Comment length
+
+    data.into_vec()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// An entry stored without compression
+    pub(crate) fn stored_entry(name: &str, size: i64) -> ZipFileEntry {
+        ZipFileEntry {
+            name: name.to_owned(),
+            compression_type: CompressionType::None,
+            compressed_size: size,
+            uncompressed_size: size,
+            ..Default::default()
+        }
+    }
+
+    /// A deflated entry, as far as its header goes
+    pub(crate) fn deflated_entry(name: &str, size: i64) -> ZipFileEntry {
+        ZipFileEntry {
+            compression_type: CompressionType::Deflate,
+            ..stored_entry(name, size)
+        }
+    }
+}
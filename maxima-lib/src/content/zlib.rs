@@ -13,6 +13,10 @@ pub const Z_ENOUGH: usize = Z_ENOUGH_LENS + Z_ENOUGH_DISTS;
 
 const Z_MAGIC: u32 = u32::from_be_bytes(*b"ZSTA");
 
+/// Upper bound of a serialized inflate state: header, raw state, and a full 32KB window
+pub(crate) const Z_STATE_MAX_SIZE: u64 =
+    (4 + 8 + 8 + 4 + 8 + std::mem::size_of::<ZInflateState>() + (1 << 15)) as u64;
+
 #[repr(C)]
 #[derive(Copy, Clone)]
 pub struct ZCode {
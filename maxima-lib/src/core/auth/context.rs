@@ -10,6 +10,19 @@ use rand::random;
 use ring::hmac::HMAC_SHA256;
 use sha2_const::Sha256;
 
+/// Scopes the login flow asks for, enough for everything Maxima does with the token. If the
+/// server refuses them with `invalid_scope`, the login falls back to the client's default
+/// ones. Check what a token was granted with
+/// [`super::token_info::NucleusTokenInfo::missing_scopes`].
+pub const LOGIN_SCOPES: [&str; 6] = [
+    "basic.identity",    // user, service layer and RTM
+    "basic.persona",     // persona, service layer and RTM
+    "basic.entitlement", // owned games and licenses
+    "basic.social",      // friends, presence, blocked players
+    "basic.domaindata",  // cloud saves
+    "offline",           // refresh token
+];
+
 /// Context with utilities for auth flow
 pub struct AuthContext<'a> {
     code_verifier: String,
@@ -19,6 +32,8 @@ pub struct AuthContext<'a> {
     access_token: Option<String>,
     token_format: Option<String>,
     expires_in: Option<i64>,
+    /// Sent back untouched with the redirect, see [`super::login::begin_oauth_login_flow`]
+    state: Option<String>,
     pc_sign: PCSign<'a>,
 }
 
@@ -36,10 +51,18 @@ impl AuthContext<'_> {
             access_token: None,
             token_format: None,
             expires_in: None,
+            state: None,
             pc_sign: signature,
         })
     }
 
+    /// A context asking for [`LOGIN_SCOPES`]
+    pub fn for_login() -> Result<Self, AuthError> {
+        let mut context = Self::new()?;
+        context.add_scopes(&LOGIN_SCOPES);
+        Ok(context)
+    }
+
     fn generate_code_verifier() -> String {
         let rand_bytes: [u8; 32] = random();
         URL_SAFE_NO_PAD.encode(&rand_bytes)
@@ -64,12 +87,37 @@ impl AuthContext<'_> {
         &self.code_verifier
     }
 
+    /// Use the verifier of an earlier login, so a code it was given can still be exchanged
+    pub fn set_code_verifier(&mut self, verifier: &str) {
+        self.code_challenge = Self::generate_challenge(&verifier.to_owned());
+        self.code_verifier = verifier.to_owned();
+    }
+
     pub fn code(&self) -> Option<&str> {
         self.code.as_deref()
     }
 
+    /// Scopes the token will be asked for, sent space-separated in the auth URL
+    pub fn scopes(&self) -> &[String] {
+        &self.scopes
+    }
+
+    /// Ask for `scope` too. Scopes already asked for are ignored.
     pub fn add_scope(&mut self, scope: &str) {
-        self.scopes.push(scope.to_owned());
+        if !self.scopes.iter().any(|x| x == scope) {
+            self.scopes.push(scope.to_owned());
+        }
+    }
+
+    pub fn add_scopes(&mut self, scopes: &[&str]) {
+        for scope in scopes {
+            self.add_scope(scope);
+        }
+    }
+
+    /// Stop asking for any scope, for when the server refused one
+    pub fn clear_scopes(&mut self) {
+        self.scopes.clear();
     }
 
     pub fn set_code(&mut self, code: &str) {
@@ -100,6 +148,14 @@ impl AuthContext<'_> {
         self.expires_in = Some(expires_in);
     }
 
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    pub fn set_state(&mut self, state: &str) {
+        self.state = Some(state.to_owned());
+    }
+
     pub fn nucleus_auth_url(
         &self,
         client_id: &str,
@@ -139,7 +195,11 @@ impl AuthContext<'_> {
             query.push(("expires_in", Cow::Owned(expires_in.to_string())));
         }
 
-        let url = reqwest::Url::parse_with_params(API_NUCLEUS_AUTH, query)?;
+        if let Some(state) = &self.state {
+            query.push(("state", Cow::Borrowed(state)));
+        }
+
+        let url = reqwest::Url::parse_with_params(&API_NUCLEUS_AUTH, query)?;
         Ok(url.to_string())
     }
 }
@@ -1,11 +1,23 @@
 use crate::util::hash::hash_fnv1a;
 use gethostname::gethostname;
 use hex::ToHex;
+use lazy_static::lazy_static;
 use regex::Regex;
 use ring::digest::SHA1_FOR_LEGACY_USE_ONLY;
-use std::arch::x86_64::CpuidResult;
+use std::{
+    arch::x86_64::CpuidResult,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 use thiserror::Error;
 
+lazy_static! {
+    /// Gathering hardware info is slow, and it isn't expected to change while running.
+    /// Keyed by hash version and the slug of the wine prefix it was read for.
+    static ref HARDWARE_INFO: Mutex<HashMap<(u32, Option<String>), Arc<HardwareInfo>>> =
+        Mutex::new(HashMap::new());
+}
+
 #[derive(Debug)]
 pub struct CpuDetails {
     pub flags: CpuidResult,
@@ -52,6 +64,16 @@ pub enum HardwareHashError {
 }
 
 impl HardwareInfo {
+    /// [`HardwareInfo::new`], gathered once and shared after that. License requests and
+    /// the PC sign both go through here, so every request describes the same machine.
+    pub fn cached(version: u32, slug: Option<&str>) -> Arc<Self> {
+        let mut cache = HARDWARE_INFO.lock().unwrap();
+        cache
+            .entry((version, slug.map(str::to_owned)))
+            .or_insert_with(|| Arc::new(Self::new(version, slug)))
+            .clone()
+    }
+
     #[cfg(windows)]
     pub fn new(version: u32, _slug: Option<&str>) -> Self {
         use std::collections::HashMap;
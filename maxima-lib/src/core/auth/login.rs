@@ -1,21 +1,279 @@
+use std::{
+    env,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::Utc;
 use lazy_static::lazy_static;
+use log::{info, warn};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
 
-use crate::core::{auth::storage::AuthError, clients::JUNO_PC_CLIENT_ID};
+use crate::{
+    core::{auth::storage::AuthError, clients::JUNO_PC_CLIENT_ID},
+    util::native::{maxima_dir, NativeError},
+};
 
 use super::context::AuthContext;
 
+/// Port the login callback is listened for on, unless set with `MAXIMA_LOGIN_PORT` or
+/// already taken
+pub const DEFAULT_LOGIN_PORT: u16 = 31033;
+
+/// How long [`begin_oauth_login_flow`] waits for the user to finish logging in through the
+/// browser, which never calls back if it's closed
+pub const DEFAULT_LOGIN_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Holds the port of the login in progress, for the bootstrap to send the callback to
+const LOGIN_PORT_FILE: &str = "login_port";
+
+/// Holds the login in progress, see [`PendingLogin`]
+const PENDING_LOGIN_FILE: &str = "pending_login.json";
+
+/// How long a login in progress can be finished after Maxima restarts. The code
+/// verifier is a secret, so it's kept no longer than it takes to log in.
+pub const PENDING_LOGIN_TTL: Duration = Duration::from_secs(5 * 60);
+
 lazy_static! {
     static ref HTTP_PATTERN: Regex =
         Regex::new(r"^([A-Za-z]+) +(.*) +(HTTP/[0-9][.][0-9])").unwrap();
 }
 
+fn login_port_file() -> Result<PathBuf, NativeError> {
+    Ok(maxima_dir()?.join(LOGIN_PORT_FILE))
+}
+
+/// A browser login in progress, kept on disk so a Maxima that crashed or was closed
+/// during it can still exchange the code once it's started again
+#[derive(Serialize, Deserialize)]
+struct PendingLogin {
+    code_verifier: String,
+    state: String,
+    /// When the login was started, in seconds since epoch
+    started: i64,
+    /// Query of the callback, if the bootstrap got it while Maxima wasn't running
+    #[serde(default)]
+    callback: Option<String>,
+}
+
+fn pending_login_file() -> Result<PathBuf, NativeError> {
+    Ok(maxima_dir()?.join(PENDING_LOGIN_FILE))
+}
+
+/// The login in progress, if it was started within [`PENDING_LOGIN_TTL`]. One that's
+/// older is deleted.
+fn load_pending_login() -> Option<PendingLogin> {
+    load_pending_login_from(&pending_login_file().ok()?)
+}
+
+fn load_pending_login_from(path: &Path) -> Option<PendingLogin> {
+    let pending: PendingLogin = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())?;
+
+    if Utc::now().timestamp() - pending.started > PENDING_LOGIN_TTL.as_secs() as i64 {
+        let _ = std::fs::remove_file(path);
+        return None;
+    }
+
+    Some(pending)
+}
+
+fn save_pending_login(pending: &PendingLogin) -> Result<(), AuthError> {
+    save_pending_login_to(&pending_login_file()?, pending)
+}
+
+fn save_pending_login_to(path: &Path, pending: &PendingLogin) -> Result<(), AuthError> {
+    let json = serde_json::to_string(pending).map_err(std::io::Error::from)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+fn clear_pending_login() {
+    if let Ok(path) = pending_login_file() {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// The `code` in a callback query
+fn callback_code(query: &str) -> Option<&str> {
+    querystring::querify(query)
+        .into_iter()
+        .find(|(key, _)| *key == "code")
+        .map(|(_, code)| code)
+}
+
+/// The `code` in a callback query, or [`AuthError::Refused`] with its `error` if the login
+/// was refused
+fn callback_result(query: &str) -> Result<&str, AuthError> {
+    let query = querystring::querify(query);
+    if let Some((_, error)) = query.iter().find(|(key, _)| *key == "error") {
+        return Err(AuthError::Refused(error.to_string()));
+    }
+
+    query
+        .into_iter()
+        .find(|(key, _)| *key == "code")
+        .map(|(_, code)| code)
+        .ok_or(AuthError::NoAuthCode)
+}
+
+/// Keep the callback of a login whose listener is gone, because Maxima crashed or was
+/// closed, so it can be finished once Maxima is started again. Called by the bootstrap.
+/// Returns whether there was a login in progress it belongs to, which the callback's
+/// `state` has to match.
+pub fn store_login_callback(query: &str) -> bool {
+    match pending_login_file() {
+        Ok(path) => store_login_callback_in(&path, query),
+        Err(_) => false,
+    }
+}
+
+fn store_login_callback_in(path: &Path, query: &str) -> bool {
+    let mut pending = match load_pending_login_from(path) {
+        Some(pending) => pending,
+        None => return false,
+    };
+
+    let state = querystring::querify(query)
+        .into_iter()
+        .find(|(key, _)| *key == "state")
+        .map(|(_, state)| state);
+    if state != Some(pending.state.as_str()) || callback_code(query).is_none() {
+        return false;
+    }
+
+    pending.callback = Some(query.to_owned());
+    match save_pending_login_to(path, &pending) {
+        Ok(()) => true,
+        Err(err) => {
+            warn!("Failed to keep the login callback: {}", err);
+            false
+        }
+    }
+}
+
+/// Port the callback of the login in progress should go to. `state` is the one sent back
+/// with the redirect, checked first since it can't belong to a different login.
+pub fn login_callback_port(state: Option<&str>) -> u16 {
+    if let Some(port) = state.and_then(|x| x.parse().ok()) {
+        return port;
+    }
+
+    login_port_file()
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|port| port.trim().parse().ok())
+        .unwrap_or(DEFAULT_LOGIN_PORT)
+}
+
+/// Listens on the configured port, or any free one if something else has it
+async fn bind_login_listener() -> Result<TcpListener, AuthError> {
+    let port = match env::var("MAXIMA_LOGIN_PORT") {
+        Ok(port) => port
+            .parse()
+            .map_err(|_| AuthError::InvalidLoginPort(port))?,
+        Err(_) => DEFAULT_LOGIN_PORT,
+    };
+
+    match TcpListener::bind(("127.0.0.1", port)).await {
+        Ok(listener) => Ok(listener),
+        Err(err) if err.kind() == ErrorKind::AddrInUse => {
+            warn!("Login port {} is in use, picking another one", port);
+            Ok(TcpListener::bind(("127.0.0.1", 0)).await?)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
 pub async fn begin_oauth_login_flow<'a>(context: &mut AuthContext<'a>) -> Result<(), AuthError> {
+    begin_cancellable_oauth_login_flow(context, DEFAULT_LOGIN_TIMEOUT, &CancellationToken::new())
+        .await
+}
+
+/// Like [`begin_oauth_login_flow`], but gives up with [`AuthError::LoginCancelled`] when
+/// `cancel` is cancelled or the login isn't finished within `timeout`. A login that was
+/// interrupted by a restart less than [`PENDING_LOGIN_TTL`] ago is picked up instead of
+/// starting over: its code is used if the bootstrap kept one, and otherwise its verifier
+/// is, so the browser tab that's still open can finish it.
+pub async fn begin_cancellable_oauth_login_flow<'a>(
+    context: &mut AuthContext<'a>,
+    timeout: Duration,
+    cancel: &CancellationToken,
+) -> Result<(), AuthError> {
+    // Still counted from the first try, so restarting doesn't keep the verifier around longer
+    let mut started = Utc::now().timestamp();
+    if let Some(pending) = load_pending_login() {
+        context.set_code_verifier(&pending.code_verifier);
+        started = pending.started;
+
+        if let Some(code) = pending.callback.as_deref().and_then(callback_code) {
+            info!("Finishing the login that was in progress before Maxima restarted");
+            context.set_code(code);
+            clear_pending_login();
+            return Ok(());
+        }
+    }
+
+    let listener = bind_login_listener().await?;
+    let port = listener.local_addr()?.port();
+
+    // The redirect goes to the bootstrap, which finds the listener through these
+    std::fs::write(login_port_file()?, port.to_string())?;
+    context.set_state(&port.to_string());
+    save_pending_login(&PendingLogin {
+        code_verifier: context.code_verifier().to_owned(),
+        state: port.to_string(),
+        started,
+        callback: None,
+    })?;
+
+    let result = tokio::select! {
+        result = browser_login(&listener, context) => result,
+        _ = tokio::time::sleep(timeout) => {
+            info!("Login wasn't finished within {} seconds", timeout.as_secs());
+            Err(AuthError::LoginCancelled)
+        }
+        _ = cancel.cancelled() => {
+            info!("Login was cancelled");
+            Err(AuthError::LoginCancelled)
+        }
+    };
+
+    // Only a login that never got to end here is worth finishing after a restart
+    clear_pending_login();
+    result
+}
+
+/// Opens the login in the browser and waits for its code. If the server refuses the scopes,
+/// the login is opened again asking for the default ones.
+async fn browser_login(
+    listener: &TcpListener,
+    context: &mut AuthContext<'_>,
+) -> Result<(), AuthError> {
     open::that(context.nucleus_auth_url(JUNO_PC_CLIENT_ID, "code")?)?;
-    let listener = TcpListener::bind("127.0.0.1:31033").await?;
 
+    match wait_for_auth_code(listener, context).await {
+        Err(err) if err.is_invalid_scope() && !context.scopes().is_empty() => {
+            warn!("The login scopes were refused, logging in with the default ones instead");
+            context.clear_scopes();
+            open::that(context.nucleus_auth_url(JUNO_PC_CLIENT_ID, "code")?)?;
+            wait_for_auth_code(listener, context).await
+        }
+        result => result,
+    }
+}
+
+/// Takes the code from the callback the bootstrap sends once the browser login is done
+async fn wait_for_auth_code(
+    listener: &TcpListener,
+    context: &mut AuthContext<'_>,
+) -> Result<(), AuthError> {
     loop {
         let (mut socket, _) = listener.accept().await?;
 
@@ -35,17 +293,10 @@ pub async fn begin_oauth_login_flow<'a>(context: &mut AuthContext<'a>) -> Result
             let query = path_and_query
                 .split_once("?")
                 .map(|(_, qs)| qs.trim())
-                .map(querystring::querify)
                 .ok_or(AuthError::Query)?;
 
-            for query in query {
-                if query.0 == "code" {
-                    context.set_code(query.1);
-                    return Ok(());
-                }
-            }
-
-            return Err(AuthError::NoAuthCode.into());
+            context.set_code(callback_result(query)?);
+            return Ok(());
         }
     }
 }
@@ -55,3 +306,88 @@ pub async fn begin_oauth_login_flow<'a>(context: &mut AuthContext<'a>) -> Result
 pub async fn manual_login(_persona: &str, _password: &str) -> Result<String, AuthError> {
     unimplemented!();
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    fn pending_login(path: &Path, started: i64) {
+        let pending = PendingLogin {
+            code_verifier: "verifier".to_owned(),
+            state: "31033".to_owned(),
+            started,
+            callback: None,
+        };
+        save_pending_login_to(path, &pending).unwrap();
+    }
+
+    #[test]
+    fn expired_logins_are_deleted() {
+        let dir = TempDir::new("login-expired");
+        let path = dir.join("expired.json");
+        let started = Utc::now().timestamp() - PENDING_LOGIN_TTL.as_secs() as i64 - 1;
+        pending_login(&path, started);
+
+        assert!(load_pending_login_from(&path).is_none());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn callback_needs_the_login_state() {
+        let dir = TempDir::new("login-state");
+        let path = dir.join("state.json");
+        pending_login(&path, Utc::now().timestamp());
+
+        assert!(!store_login_callback_in(&path, "code=abc&state=1234"));
+        assert!(!store_login_callback_in(&path, "code=abc"));
+        assert!(load_pending_login_from(&path).unwrap().callback.is_none());
+
+        assert!(store_login_callback_in(&path, "code=abc&state=31033"));
+        let pending = load_pending_login_from(&path).unwrap();
+
+        assert_eq!(pending.callback.as_deref(), Some("code=abc&state=31033"));
+        assert_eq!(
+            pending.callback.as_deref().and_then(callback_code),
+            Some("abc")
+        );
+    }
+
+    #[test]
+    fn callback_needs_a_code() {
+        let dir = TempDir::new("login-code");
+        let path = dir.join("code.json");
+        pending_login(&path, Utc::now().timestamp());
+
+        let stored = store_login_callback_in(&path, "state=31033&error=access_denied");
+        let pending = load_pending_login_from(&path).unwrap();
+
+        assert!(!stored);
+        assert!(pending.callback.is_none());
+    }
+
+    #[test]
+    fn refused_callbacks_have_the_error() {
+        assert_eq!(callback_result("code=abc&state=31033").unwrap(), "abc");
+        assert!(matches!(
+            callback_result("state=31033"),
+            Err(AuthError::NoAuthCode)
+        ));
+
+        let refused = callback_result("error=invalid_scope&state=31033").unwrap_err();
+        assert!(refused.is_invalid_scope());
+
+        let refused = callback_result("error=access_denied&state=31033").unwrap_err();
+        assert!(matches!(&refused, AuthError::Refused(err) if err == "access_denied"));
+        assert!(!refused.is_invalid_scope());
+    }
+
+    #[test]
+    fn logins_without_a_file_have_nothing_to_store() {
+        let dir = TempDir::new("login-missing");
+        let path = dir.join("missing.json");
+        assert!(!store_login_callback_in(&path, "code=abc&state=31033"));
+        assert!(!path.exists());
+    }
+}
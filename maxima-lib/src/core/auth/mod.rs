@@ -10,13 +10,65 @@ use super::{
     endpoints::API_NUCLEUS_TOKEN,
 };
 use crate::core::auth::storage::{AuthError, TokenError};
+use crate::util::http;
 use context::AuthContext;
 use derive_getters::Getters;
-use reqwest::{redirect, Client, Url};
+use log::warn;
+use reqwest::{redirect, Url};
 use serde::Deserialize;
+use std::time::Duration;
 use thiserror::Error;
 
+/// How many times the exchanges after a login are tried before giving up, so a network
+/// blip doesn't throw away a login the user already went through
+const EXCHANGE_TRIES: u32 = 3;
+
 pub async fn nucleus_auth_exchange<'a>(
+    auth_context: &AuthContext<'a>,
+    client_id: &str,
+    response_type: &str,
+) -> Result<String, AuthError> {
+    let mut tries = 0;
+    loop {
+        let result = try_nucleus_auth_exchange(auth_context, client_id, response_type).await;
+        match result {
+            Err(err) if err.is_transient() => {
+                tries += 1;
+                if tries >= EXCHANGE_TRIES {
+                    return Err(err);
+                }
+
+                warn!(
+                    "Auth exchange failed, retrying ({}/{}): {}",
+                    tries,
+                    EXCHANGE_TRIES - 1,
+                    err
+                );
+                tokio::time::sleep(Duration::from_secs(tries as u64)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Like [`nucleus_auth_exchange`], but asks again for the client's default scopes if the
+/// server refuses the ones in `auth_context` with `invalid_scope`
+pub async fn nucleus_auth_exchange_or_default_scopes<'a>(
+    auth_context: &mut AuthContext<'a>,
+    client_id: &str,
+    response_type: &str,
+) -> Result<String, AuthError> {
+    match nucleus_auth_exchange(auth_context, client_id, response_type).await {
+        Err(err) if err.is_invalid_scope() && !auth_context.scopes().is_empty() => {
+            warn!("The scopes were refused, asking for the default ones instead");
+            auth_context.clear_scopes();
+            nucleus_auth_exchange(auth_context, client_id, response_type).await
+        }
+        result => result,
+    }
+}
+
+async fn try_nucleus_auth_exchange<'a>(
     auth_context: &AuthContext<'a>,
     client_id: &str,
     mut response_type: &str,
@@ -27,7 +79,7 @@ pub async fn nucleus_auth_exchange<'a>(
 
     let url: String = auth_context.nucleus_auth_url(client_id, response_type)?;
 
-    let client = Client::builder()
+    let client = http::client_builder()
         .redirect(redirect::Policy::none())
         .build()?;
     let res = client.get(url).send().await?.error_for_status()?;
@@ -62,6 +114,10 @@ pub async fn nucleus_auth_exchange<'a>(
     };
 
     let query = querystring::querify(query.ok_or(AuthError::Query)?);
+    // Refusals, like `invalid_scope`, come back as a redirect too
+    if let Some((_, error)) = query.iter().find(|(x, _)| *x == "error") {
+        return Err(AuthError::Refused(error.to_string()));
+    }
 
     if response_type == "token" {
         response_type = "access_token";
@@ -99,6 +155,31 @@ pub struct TokenResponse {
 
 pub async fn nucleus_token_exchange(
     auth_context: &AuthContext<'_>,
+) -> Result<TokenResponse, TokenError> {
+    let mut tries = 0;
+    loop {
+        match try_nucleus_token_exchange(auth_context).await {
+            Err(err) if err.is_transient() => {
+                tries += 1;
+                if tries >= EXCHANGE_TRIES {
+                    return Err(err);
+                }
+
+                warn!(
+                    "Token exchange failed, retrying ({}/{}): {}",
+                    tries,
+                    EXCHANGE_TRIES - 1,
+                    err
+                );
+                tokio::time::sleep(Duration::from_secs(tries as u64)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+async fn try_nucleus_token_exchange(
+    auth_context: &AuthContext<'_>,
 ) -> Result<TokenResponse, TokenError> {
     assert!(auth_context.code().is_some());
 
@@ -112,14 +193,21 @@ pub async fn nucleus_token_exchange(
         ("token_format", "JWS"), // Force JWT for Kyber
     ];
 
-    let client = Client::builder()
+    let client = http::client_builder()
         .redirect(redirect::Policy::none())
         .build()?;
-    let res = client.post(API_NUCLEUS_TOKEN).form(&query).send().await?;
+    let res = client
+        .post(API_NUCLEUS_TOKEN.as_str())
+        .form(&query)
+        .send()
+        .await?;
 
     let status = res.status();
     let text = res.text().await?;
-    if status.is_client_error() || status.is_server_error() {
+    if status.is_server_error() {
+        return Err(TokenError::ExchangeUnavailable(status.as_u16(), text));
+    }
+    if status.is_client_error() {
         return Err(TokenError::Exchange(text));
     }
 
@@ -137,10 +225,14 @@ pub async fn nucleus_connect_token_refresh(
         ("client_secret", JUNO_PC_CLIENT_SECRET),
     ];
 
-    let client = Client::builder()
+    let client = http::client_builder()
         .redirect(redirect::Policy::none())
         .build()?;
-    let res = client.post(API_NUCLEUS_TOKEN).form(&query).send().await?;
+    let res = client
+        .post(API_NUCLEUS_TOKEN.as_str())
+        .form(&query)
+        .send()
+        .await?;
 
     let status = res.status();
     let text = res.text().await?;
@@ -34,7 +34,7 @@ pub struct PCSign<'a> {
 
 impl PCSign<'_> {
     pub fn new() -> Result<Self, HardwareHashError> {
-        let hw_info = HardwareInfo::new(1, None);
+        let hw_info = HardwareInfo::cached(1, None);
 
         let timestamp = Utc::now();
         let formatted_timestamp = timestamp.format("%Y-%m-%d %H:%M:%S:%3f");
@@ -47,12 +47,12 @@ impl PCSign<'_> {
 
         Ok(Self {
             av: "v1",
-            bsn: hw_info.bios_sn,
+            bsn: hw_info.bios_sn.clone(),
             gid,
-            hsn: hw_info.disk_sn,
+            hsn: hw_info.disk_sn.clone(),
             sv,
-            msn: hw_info.board_sn,
-            mac: hw_info.mac,
+            msn: hw_info.board_sn.clone(),
+            mac: hw_info.mac.clone(),
             mid,
             ts: formatted_timestamp.to_string(),
         })
@@ -1,16 +1,20 @@
 use super::{
-    nucleus_connect_token_refresh, token_info::NucleusTokenInfo, TokenRefreshError, TokenResponse,
+    context::LOGIN_SCOPES, nucleus_connect_token_refresh, token_info::NucleusTokenInfo,
+    TokenRefreshError, TokenResponse,
 };
 use crate::core::auth::hardware::HardwareHashError;
 use crate::ooa::LicenseError;
-use crate::util::native::{maxima_dir, NativeError};
-use log::info;
+use crate::util::{
+    http,
+    native::{maxima_dir, NativeError},
+};
+use log::{info, warn};
 use reqwest::header::ToStrError;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fs,
+    path::Path,
     sync::Arc,
     time::{SystemTime, SystemTimeError, UNIX_EPOCH},
 };
@@ -38,12 +42,25 @@ pub enum TokenError {
 
     #[error("token exchange failed: {0}")]
     Exchange(String),
+    #[error("token exchange failed with server error {0}: {1}")]
+    ExchangeUnavailable(u16, String),
     #[error("a refresh token was not provided")]
     NoRefresh,
     #[error("an access token was not provided")]
     Absent,
 }
 
+impl TokenError {
+    /// Whether trying again could work, as opposed to the token being rejected
+    pub fn is_transient(&self) -> bool {
+        match self {
+            TokenError::Request(err) => is_transient_request(err),
+            TokenError::ExchangeUnavailable(..) => true,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum AuthError {
     #[error(transparent)]
@@ -71,12 +88,39 @@ pub enum AuthError {
     Query,
     #[error("invalid redirect or chain `{0:?}`")]
     InvalidRedirect(Option<String>),
+    #[error("no account with the user ID `{0}` has logged in")]
+    UnknownAccount(String),
+    #[error("`{0}` is not a valid login port")]
+    InvalidLoginPort(String),
+    #[error("the login was cancelled or not finished in time")]
+    LoginCancelled,
+    #[error("the auth server refused the request: `{0}`")]
+    Refused(String),
+}
+
+impl AuthError {
+    /// Whether trying again could work. Cancelled logins and ones that need 2FA aren't.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AuthError::Request(err) => is_transient_request(err),
+            AuthError::Token(err) => err.is_transient(),
+            _ => false,
+        }
+    }
+
+    /// Whether the server refused one of the scopes that were asked for
+    pub fn is_invalid_scope(&self) -> bool {
+        matches!(self, AuthError::Refused(err) if err == "invalid_scope")
+    }
+}
+
+/// A network failure or server error, rather than the request being refused
+pub(crate) fn is_transient_request(err: &reqwest::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.status().is_some_and(|x| x.is_server_error())
 }
 
 #[derive(Default, Serialize, Deserialize)]
 pub struct AuthAccount {
-    #[serde(skip_serializing, skip_deserializing)]
-    client: Client,
     #[serde(skip_serializing, skip_deserializing)]
     dirty: bool,
 
@@ -120,8 +164,16 @@ impl AuthAccount {
         self.expires_at = expires_at;
 
         if self.user_id.is_empty() {
-            let token_info = NucleusTokenInfo::fetch(&self.client, &self.access_token).await?;
+            let token_info = NucleusTokenInfo::fetch(&http::client(), &self.access_token).await?;
             self.user_id = token_info.user_id().to_owned();
+
+            let missing = token_info.missing_scopes(&LOGIN_SCOPES);
+            if !missing.is_empty() {
+                warn!(
+                    "Logged in without the {} scopes, some requests may be refused",
+                    missing.join(", ")
+                );
+            }
         }
 
         self.dirty = true;
@@ -145,7 +197,7 @@ impl AuthAccount {
         }
 
         let access_token = access_token?.to_owned();
-        let token_info = NucleusTokenInfo::fetch(&self.client, &access_token).await;
+        let token_info = NucleusTokenInfo::fetch(&http::client(), &access_token).await;
         if token_info.is_err() {
             return Ok(false);
         }
@@ -173,10 +225,14 @@ impl AuthAccount {
 #[derive(Serialize, Deserialize)]
 pub struct AuthStorage {
     accounts: HashMap<String, AuthAccount>,
+    /// User ID of the account every frontend acts as
     selected: Option<String>,
 
     #[serde(skip_serializing, skip_deserializing)]
     can_save: bool,
+    /// Whether this process picked the selected account, rather than loading it
+    #[serde(skip_serializing, skip_deserializing)]
+    selection_changed: bool,
 }
 
 pub type LockedAuthStorage = Arc<Mutex<AuthStorage>>;
@@ -187,6 +243,7 @@ impl Default for AuthStorage {
             accounts: HashMap::new(),
             selected: None,
             can_save: true,
+            selection_changed: false,
         }
     }
 }
@@ -202,6 +259,7 @@ impl AuthStorage {
             accounts: HashMap::from([("direct".to_owned(), account)]),
             selected: Some("direct".to_owned()),
             can_save: false,
+            selection_changed: false,
         };
 
         Ok(Arc::new(Mutex::new(storage)))
@@ -218,6 +276,7 @@ impl AuthStorage {
             accounts: HashMap::from([("direct".to_owned(), account)]),
             selected: Some("direct".to_owned()),
             can_save: false,
+            selection_changed: false,
         };
 
         Arc::new(Mutex::new(storage))
@@ -228,11 +287,15 @@ impl AuthStorage {
             accounts: HashMap::new(),
             selected: None,
             can_save: false,
+            selection_changed: false,
         }))
     }
 
     pub fn load() -> Result<LockedAuthStorage, AuthError> {
-        let file = maxima_dir()?.join(FILE);
+        Self::load_from(&maxima_dir()?.join(FILE))
+    }
+
+    fn load_from(file: &Path) -> Result<LockedAuthStorage, AuthError> {
         if !file.exists() {
             return Ok(Arc::new(Mutex::new(Self::default())));
         }
@@ -248,8 +311,42 @@ impl AuthStorage {
     }
 
     pub fn save(&self) -> Result<(), TokenError> {
-        let file = maxima_dir()?.join(FILE);
-        fs::write(file, toml::to_string(&self)?)?;
+        self.save_to(&maxima_dir()?.join(FILE))
+    }
+
+    fn save_to(&self, file: &Path) -> Result<(), TokenError> {
+        let mut data = toml::Table::try_from(self)?;
+        let mut saved = fs::read_to_string(file)
+            .ok()
+            .and_then(|saved| saved.parse::<toml::Table>().ok())
+            .unwrap_or_default();
+
+        // Another frontend may have logged into accounts since this one loaded
+        let mut accounts = match data.remove("accounts") {
+            Some(toml::Value::Table(accounts)) => accounts,
+            _ => toml::Table::new(),
+        };
+        if let Some(toml::Value::Table(saved_accounts)) = saved.remove("accounts") {
+            for (id, account) in saved_accounts {
+                if !accounts.contains_key(&id) {
+                    accounts.insert(id, account);
+                }
+            }
+        }
+
+        // Or switched accounts. Unless this one switched too, keep their choice
+        // instead of putting back the one we loaded.
+        if !self.selection_changed {
+            let selected = saved
+                .remove("selected")
+                .filter(|id| id.as_str().is_some_and(|id| accounts.contains_key(id)));
+            if let Some(selected) = selected {
+                data.insert("selected".to_owned(), selected);
+            }
+        }
+
+        data.insert("accounts".to_owned(), toml::Value::Table(accounts));
+        fs::write(file, toml::to_string(&data)?)?;
         Ok(())
     }
 
@@ -260,6 +357,33 @@ impl AuthStorage {
         })
     }
 
+    /// User IDs of every account that has logged in
+    pub fn account_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.accounts.keys().map(|x| x.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    /// Act as another account that has already logged in. Saved right away, so
+    /// frontends started afterwards use it too.
+    pub fn select_account(&mut self, user_id: &str) -> Result<(), AuthError> {
+        if !self.accounts.contains_key(user_id) {
+            return Err(AuthError::UnknownAccount(user_id.to_owned()));
+        }
+
+        self.selected = Some(user_id.to_owned());
+        self.selection_changed = true;
+        if self.can_save {
+            self.save()?;
+        }
+
+        Ok(())
+    }
+
     pub fn current(&mut self) -> Option<&mut AuthAccount> {
         match &self.selected {
             Some(selected) => self.accounts.get_mut(selected),
@@ -291,6 +415,19 @@ impl AuthStorage {
         Ok(Some(access_token))
     }
 
+    /// What the server says about the current account's access token, like the scopes
+    /// it was granted
+    pub async fn token_info(&mut self) -> Result<Option<NucleusTokenInfo>, TokenError> {
+        let access_token = match self.access_token().await? {
+            Some(access_token) => access_token,
+            None => return Ok(None),
+        };
+
+        Ok(Some(
+            NucleusTokenInfo::fetch(&http::client(), &access_token).await?,
+        ))
+    }
+
     /// Add an account from a token response and set it as the currently selected one
     pub async fn add_account(&mut self, response: &TokenResponse) -> Result<(), AuthError> {
         let mut account = AuthAccount::from_token_response(response).await?;
@@ -303,6 +440,7 @@ impl AuthStorage {
 
         self.accounts.insert(user_id.to_owned(), account);
         self.selected = Some(user_id);
+        self.selection_changed = true;
 
         self.save_if_dirty()?;
         Ok(())
@@ -321,3 +459,78 @@ impl AuthStorage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    fn account(user_id: &str) -> AuthAccount {
+        AuthAccount {
+            access_token: format!("token-{}", user_id),
+            expires_at: u64::MAX,
+            user_id: user_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn storage(user_ids: &[&str], selected: &str) -> AuthStorage {
+        AuthStorage {
+            accounts: user_ids
+                .iter()
+                .map(|id| (id.to_string(), account(id)))
+                .collect(),
+            selected: Some(selected.to_owned()),
+            can_save: false,
+            selection_changed: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn save_keeps_accounts_added_elsewhere() {
+        let dir = TempDir::new("auth-merge");
+        let file = dir.join(FILE);
+        storage(&["a"], "a").save_to(&file).unwrap();
+
+        // Another frontend loaded before the first one saved, then logged into `b`
+        let mut other = storage(&["b"], "b");
+        other.selection_changed = true;
+        other.save_to(&file).unwrap();
+
+        let loaded = AuthStorage::load_from(&file).unwrap();
+        let mut loaded = loaded.lock().await;
+        assert_eq!(loaded.account_ids(), ["a", "b"]);
+        assert_eq!(loaded.selected(), Some("b"));
+        assert_eq!(loaded.access_token().await.unwrap().unwrap(), "token-b");
+    }
+
+    #[tokio::test]
+    async fn switching_accounts() {
+        let dir = TempDir::new("auth-switch");
+        let file = dir.join(FILE);
+        let mut first = storage(&["a", "b"], "a");
+        let mut second = storage(&["a", "b"], "a");
+
+        assert!(matches!(
+            first.select_account("c"),
+            Err(AuthError::UnknownAccount(id)) if id == "c"
+        ));
+        assert_eq!(first.selected(), Some("a"));
+
+        first.select_account("b").unwrap();
+        assert_eq!(first.access_token().await.unwrap().unwrap(), "token-b");
+        first.save_to(&file).unwrap();
+
+        // Saving without switching keeps the other frontend's choice
+        second.save_to(&file).unwrap();
+        let loaded = AuthStorage::load_from(&file).unwrap();
+        assert_eq!(loaded.lock().await.selected(), Some("b"));
+
+        // Switching back wins over it
+        second.select_account("a").unwrap();
+        second.save_to(&file).unwrap();
+        let loaded = AuthStorage::load_from(&file).unwrap();
+        assert_eq!(loaded.lock().await.selected(), Some("a"));
+    }
+}
@@ -17,9 +17,24 @@ pub struct NucleusTokenInfo {
 }
 
 impl NucleusTokenInfo {
+    /// Scopes the token was granted, which the server lists space-separated
+    pub fn scopes(&self) -> Vec<&str> {
+        self.scope.split_whitespace().collect()
+    }
+
+    /// Which of `wanted` the token wasn't granted
+    pub fn missing_scopes<'a>(&self, wanted: &[&'a str]) -> Vec<&'a str> {
+        let scopes = self.scopes();
+        wanted
+            .iter()
+            .filter(|x| !scopes.contains(x))
+            .copied()
+            .collect()
+    }
+
     pub async fn fetch(client: &Client, access_token: &str) -> Result<Self, TokenError> {
         let res = client
-            .get(API_NUCLEUS_TOKENINFO)
+            .get(API_NUCLEUS_TOKENINFO.as_str())
             .query(&[("access_token", access_token)])
             .send()
             .await?
@@ -29,3 +44,30 @@ impl NucleusTokenInfo {
         Ok(serde_json::from_str(text)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_scopes() {
+        let info: NucleusTokenInfo = serde_json::from_value(serde_json::json!({
+            "client_id": "JUNO_PC_CLIENT",
+            "scope": "basic.identity  basic.persona offline",
+            "expires_in": 3600,
+            "pid_id": "1",
+            "pid_type": "NUCLEUS",
+            "user_id": "1",
+        }))
+        .unwrap();
+
+        assert_eq!(
+            info.scopes(),
+            ["basic.identity", "basic.persona", "offline"]
+        );
+        assert_eq!(
+            info.missing_scopes(&["basic.persona", "basic.social"]),
+            ["basic.social"]
+        );
+    }
+}
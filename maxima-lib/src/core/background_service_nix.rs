@@ -48,6 +48,7 @@ pub async fn wine_get_pid(
         true,
         CommandType::RunInPrefix,
         slug,
+        None,
     )
     .await?;
 
@@ -91,6 +92,7 @@ pub async fn request_library_injection(
         false,
         CommandType::RunInPrefix,
         slug,
+        None,
     )
     .await?;
 
@@ -1,15 +1,231 @@
-use log::debug;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use derive_getters::Getters;
+use log::{debug, info, warn};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 
+use crate::build_info;
 use crate::core::error::BackgroundServiceClientError;
 use crate::util::dll_injector::DllInjector;
 use crate::util::native::NativeError;
-use crate::util::registry::{set_up_registry, RegistryError};
+use crate::util::registry::{check_registry_validity, set_up_registry, RegistryError};
+use crate::util::service::{
+    is_service_running, is_service_valid, register_service_user, start_service,
+};
+use crate::util::BackgroundServiceControlError;
 use is_elevated::is_elevated;
 
 pub const BACKGROUND_SERVICE_PORT: u16 = 13021;
 
+/// How long to wait for the service to answer a health check. It only reads some
+/// state, so one that takes longer is stuck.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a service that was just started gets to start answering health checks
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What the background service answers `GET /health` with
+#[derive(Clone, Debug, Getters, Serialize, Deserialize)]
+pub struct ServiceHealth {
+    /// Version of Maxima the service was built from
+    version: String,
+    /// Commit it was built from, which tells apart builds of the same version
+    git_sha: String,
+    uptime_secs: u64,
+    /// Whether the registry sends games to Maxima, see [`check_registry_validity`]
+    registry_set_up: bool,
+}
+
+impl ServiceHealth {
+    /// Health of the service running in this process, started `uptime` ago
+    pub fn current(uptime: Duration) -> Self {
+        let info = build_info();
+        Self {
+            version: info.version().to_string(),
+            git_sha: info.git_sha().to_string(),
+            uptime_secs: uptime.as_secs(),
+            registry_set_up: check_registry_validity().is_ok(),
+        }
+    }
+
+    /// Whether the service was built from the same code as this copy of Maxima
+    pub fn matches_build(&self) -> bool {
+        let info = build_info();
+        self.version == *info.version() && self.git_sha == *info.git_sha()
+    }
+}
+
+pub async fn request_health() -> Result<ServiceHealth, BackgroundServiceClientError> {
+    let client = reqwest::Client::builder().timeout(HEALTH_TIMEOUT).build()?;
+    let res = client
+        .get(format!(
+            "http://127.0.0.1:{}/health",
+            BACKGROUND_SERVICE_PORT
+        ))
+        .send()
+        .await?;
+    if res.status() != StatusCode::OK {
+        return Err(BackgroundServiceClientError::Request(res.text().await?));
+    }
+
+    Ok(res.json().await?)
+}
+
+/// Whether the running service is from this build of Maxima. A service from another
+/// build, or one too old to answer health checks, may not understand the requests this
+/// one sends, and should be reinstalled with
+/// [`crate::util::service::register_service_user`].
+pub async fn is_service_current() -> bool {
+    matches_this_build(request_health().await)
+}
+
+fn matches_this_build(health: Result<ServiceHealth, BackgroundServiceClientError>) -> bool {
+    match health {
+        Ok(health) if health.matches_build() => true,
+        Ok(health) => {
+            warn!(
+                "Service is from {} ({}), not this build",
+                health.version(),
+                health.git_sha()
+            );
+            false
+        }
+        Err(err) => {
+            warn!("Service health check failed: {}", err);
+            false
+        }
+    }
+}
+
+/// Health of a service that was just started, which takes a moment to answer
+async fn wait_for_health() -> Result<ServiceHealth, BackgroundServiceClientError> {
+    let started = Instant::now();
+    loop {
+        match request_health().await {
+            Err(_) if started.elapsed() < STARTUP_TIMEOUT => {
+                tokio::time::sleep(Duration::from_millis(250)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// A step of [`set_up_service`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServiceSetupStep {
+    /// Registering the service, which the user has to allow
+    Install,
+    /// Reinstalling a service from another build of Maxima, which the user has to allow too
+    Update,
+    Start,
+}
+
+/// How a step of [`set_up_service`] went
+#[derive(Debug)]
+pub enum ServiceSetupProgress<'a> {
+    Started(ServiceSetupStep),
+    Finished(ServiceSetupStep),
+    Failed(ServiceSetupStep, &'a BackgroundServiceControlError),
+}
+
+/// Gets the service from this build of Maxima running. It's installed if it isn't yet,
+/// started if it isn't running, and reinstalled if the one running is from another build,
+/// see [`is_service_current`].
+///
+/// `on_progress` is told when each step starts and how it went. Returning false when a
+/// step starts skips it and the rest of the setup, for when the user won't allow it, and
+/// then this returns false.
+pub async fn set_up_service(
+    mut on_progress: impl FnMut(ServiceSetupProgress) -> bool,
+) -> Result<bool, BackgroundServiceControlError> {
+    let installed = !is_service_valid()?;
+    if installed {
+        info!("Installing service...");
+        if !setup_step(
+            &mut on_progress,
+            ServiceSetupStep::Install,
+            install_service(),
+        )
+        .await?
+        {
+            return Ok(false);
+        }
+    }
+
+    let started = !is_service_running()?;
+    if started {
+        info!("Starting service...");
+        if !setup_step(&mut on_progress, ServiceSetupStep::Start, start_service()).await? {
+            return Ok(false);
+        }
+    }
+
+    // One that was just installed is from this build
+    if installed {
+        return Ok(true);
+    }
+
+    let health = match started {
+        true => wait_for_health().await,
+        false => request_health().await,
+    };
+    if matches_this_build(health) {
+        return Ok(true);
+    }
+
+    // Left over from an older Maxima, reinstalling restarts it from this one
+    info!("Updating service...");
+    if !setup_step(
+        &mut on_progress,
+        ServiceSetupStep::Update,
+        install_service(),
+    )
+    .await?
+    {
+        return Ok(false);
+    }
+
+    if !is_service_running()? {
+        info!("Starting service...");
+        return setup_step(&mut on_progress, ServiceSetupStep::Start, start_service()).await;
+    }
+
+    Ok(true)
+}
+
+/// Runs one step of [`set_up_service`], unless `on_progress` won't have it
+async fn setup_step(
+    on_progress: &mut impl FnMut(ServiceSetupProgress) -> bool,
+    step: ServiceSetupStep,
+    run: impl Future<Output = Result<(), BackgroundServiceControlError>>,
+) -> Result<bool, BackgroundServiceControlError> {
+    if !on_progress(ServiceSetupProgress::Started(step)) {
+        info!("{:?} step of the service setup was skipped", step);
+        return Ok(false);
+    }
+
+    match run.await {
+        Ok(()) => {
+            on_progress(ServiceSetupProgress::Finished(step));
+            Ok(true)
+        }
+        Err(err) => {
+            on_progress(ServiceSetupProgress::Failed(step, &err));
+            Err(err)
+        }
+    }
+}
+
+/// Registers the service through the bootstrap, which asks the user to allow it
+async fn install_service() -> Result<(), BackgroundServiceControlError> {
+    register_service_user()?;
+    // The bootstrap registers it in the background
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    Ok(())
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct ServiceLibraryInjectionRequest {
     pub pid: u32,
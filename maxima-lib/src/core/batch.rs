@@ -0,0 +1,653 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+
+use super::{
+    library::{LibraryError, OwnedOffer, OwnedTitle, Unavailable},
+    Maxima,
+};
+use crate::{
+    content::{
+        install_template::{resolve_install_template, InstallTemplateValues},
+        manager::{ContentManagerError, QueuedGameBuilder, RequiredSpace},
+    },
+    gameinfo::{channel::build_channel, load_game_info_from_json},
+};
+
+/// Why a game was left out of [`Maxima::queue_install_all`] or [`Maxima::update_all`]
+#[derive(Debug)]
+pub enum BatchSkipReason {
+    AlreadyInstalled,
+    /// Already downloading, or waiting to
+    AlreadyQueued,
+    UpToDate,
+    /// Its build channel has no build to download
+    NoBuild,
+    /// Can't be downloaded where the user is
+    Unavailable(Unavailable),
+    /// There isn't room for it alongside the downloads already queued
+    NoSpace(ContentManagerError),
+    Failed(String),
+}
+
+impl fmt::Display for BatchSkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchSkipReason::AlreadyInstalled => write!(f, "already installed"),
+            BatchSkipReason::AlreadyQueued => write!(f, "already queued"),
+            BatchSkipReason::UpToDate => write!(f, "up to date"),
+            BatchSkipReason::NoBuild => write!(f, "no build to download"),
+            BatchSkipReason::Unavailable(reason) => write!(f, "{}", reason),
+            BatchSkipReason::NoSpace(err) => write!(f, "{}", err),
+            BatchSkipReason::Failed(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// What a batch install or update did with each game
+#[derive(Debug, Default)]
+pub struct BatchQueueReport {
+    /// Slugs of the games that were queued
+    pub queued: Vec<String>,
+    /// Slugs of the games that weren't, and why
+    pub skipped: Vec<(String, BatchSkipReason)>,
+}
+
+impl BatchQueueReport {
+    fn skip(&mut self, slug: &str, reason: BatchSkipReason) {
+        info!("Not queuing {}: {}", slug, reason);
+        self.skipped.push((slug.to_owned(), reason));
+    }
+}
+
+/// Where a game's files go, and where they're stored if that's somewhere else
+struct BatchTarget {
+    path: PathBuf,
+    wine_prefix: Option<PathBuf>,
+    real_path: Option<PathBuf>,
+}
+
+#[cfg(unix)]
+fn default_wine_prefix(slug: &str) -> Option<PathBuf> {
+    crate::util::native::maxima_dir()
+        .ok()
+        .map(|x| x.join("wine/prefixes").join(slug))
+}
+
+#[cfg(not(unix))]
+fn default_wine_prefix(_slug: &str) -> Option<PathBuf> {
+    None
+}
+
+impl Maxima {
+    /// Queue every owned game that isn't installed, each in the folder `path_template`
+    /// resolves to inside `base`, see [`resolve_install_template`]. Games are queued as
+    /// long as the free space covers them together with the downloads in progress, and
+    /// download as many at a time as the content manager allows.
+    pub async fn queue_install_all(
+        &mut self,
+        base: &Path,
+        path_template: &str,
+    ) -> Result<BatchQueueReport, LibraryError> {
+        // Cloned, since queuing needs the content manager
+        let titles: Vec<OwnedTitle> = self
+            .library
+            .launchable_games()
+            .await?
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut report = BatchQueueReport::default();
+        let mut reserved = self.reserved_space().await;
+        for title in titles {
+            let offer = title.base_offer();
+            let slug = offer.slug();
+            if offer.is_installed().await {
+                report.skip(slug, BatchSkipReason::AlreadyInstalled);
+                continue;
+            }
+
+            let values = InstallTemplateValues {
+                base,
+                slug,
+                name: &title.name(),
+                publisher: title.publisher(),
+            };
+            let path = match resolve_install_template(path_template, &values) {
+                Ok(path) => path,
+                Err(err) => {
+                    report.skip(slug, BatchSkipReason::Failed(err.to_string()));
+                    continue;
+                }
+            };
+
+            let target = BatchTarget {
+                path,
+                wine_prefix: default_wine_prefix(slug),
+                real_path: None,
+            };
+            self.batch_queue(offer, target, &mut reserved, &mut report)
+                .await;
+        }
+
+        Ok(report)
+    }
+
+    /// Queue an update for every installed game whose version isn't the latest on its
+    /// build channel. Space is checked the same way as [`Maxima::queue_install_all`].
+    pub async fn update_all(&mut self) -> Result<BatchQueueReport, LibraryError> {
+        let titles: Vec<OwnedTitle> = self
+            .library
+            .launchable_games()
+            .await?
+            .into_iter()
+            .cloned()
+            .collect();
+
+        let mut report = BatchQueueReport::default();
+        let mut reserved = self.reserved_space().await;
+        for title in titles {
+            let offer = title.base_offer();
+            let slug = offer.slug();
+            if !offer.is_installed().await {
+                continue;
+            }
+
+            let latest = offer
+                .offer()
+                .channel_download(build_channel(slug))
+                .map(|x| x.version().to_owned());
+            if offer.installed_version().await.ok() == latest {
+                report.skip(slug, BatchSkipReason::UpToDate);
+                continue;
+            }
+
+            let info = match load_game_info_from_json(slug) {
+                Ok(info) => info,
+                Err(err) => {
+                    report.skip(slug, BatchSkipReason::Failed(err.to_string()));
+                    continue;
+                }
+            };
+
+            let target = BatchTarget {
+                path: info.path.clone(),
+                wine_prefix: info.wine_prefix.clone(),
+                real_path: info.real_path.clone(),
+            };
+            self.batch_queue(offer, target, &mut reserved, &mut report)
+                .await;
+        }
+
+        Ok(report)
+    }
+
+    /// Space the downloads in progress and the ones queued still need, where they're
+    /// stored. A queued download whose build can't be looked up isn't counted.
+    async fn reserved_space(&self) -> Vec<(PathBuf, RequiredSpace)> {
+        let mut reserved: Vec<(PathBuf, RequiredSpace)> = self
+            .content_manager
+            .active()
+            .iter()
+            .map(|x| (x.storage_path().to_owned(), x.required_space()))
+            .collect();
+
+        for game in self.content_manager.queue().queued() {
+            let path = game.real_path().as_ref().unwrap_or(game.path()).to_owned();
+            let space = self
+                .content_manager
+                .required_space(game.offer_id(), game.build_id(), game.slug(), Some(&path))
+                .await;
+            match space {
+                Ok(space) => reserved.push((path, space)),
+                Err(err) => warn!(
+                    "Couldn't tell how much space {} needs: {}",
+                    game.slug(),
+                    err
+                ),
+            }
+        }
+
+        reserved
+    }
+
+    /// Queue `offer`'s build into `target` if there's room for it next to `reserved`,
+    /// then count its space as reserved too
+    async fn batch_queue(
+        &mut self,
+        offer: &OwnedOffer,
+        target: BatchTarget,
+        reserved: &mut Vec<(PathBuf, RequiredSpace)>,
+        report: &mut BatchQueueReport,
+    ) {
+        let slug = offer.slug();
+        if self.content_manager.is_installing(offer.offer_id()) {
+            report.skip(slug, BatchSkipReason::AlreadyQueued);
+            return;
+        }
+
+        if let Some(reason) = offer.unavailable() {
+            report.skip(slug, BatchSkipReason::Unavailable(reason));
+            return;
+        }
+
+        let builds = match self
+            .content_manager
+            .service()
+            .available_builds(offer.offer_id())
+            .await
+        {
+            Ok(builds) => builds,
+            Err(err) => {
+                report.skip(slug, BatchSkipReason::Failed(err.to_string()));
+                return;
+            }
+        };
+        let build_id = match builds.channel_build(build_channel(slug)) {
+            Some(build) => build.build_id().to_owned(),
+            None => {
+                report.skip(slug, BatchSkipReason::NoBuild);
+                return;
+            }
+        };
+
+        let storage_path = target
+            .real_path
+            .clone()
+            .unwrap_or_else(|| target.path.clone());
+        let space = self
+            .content_manager
+            .required_space(offer.offer_id(), &build_id, slug, Some(&storage_path))
+            .await;
+        let space = match space {
+            Ok(space) => space,
+            Err(err) => {
+                report.skip(slug, BatchSkipReason::Failed(err.to_string()));
+                return;
+            }
+        };
+
+        let mut installs: Vec<(&Path, RequiredSpace)> = reserved
+            .iter()
+            .map(|(path, x)| (path.as_path(), *x))
+            .collect();
+        installs.push((&storage_path, space));
+        match RequiredSpace::check_combined(&installs) {
+            Ok(()) => {}
+            Err(err @ ContentManagerError::InsufficientSpace { .. }) => {
+                report.skip(slug, BatchSkipReason::NoSpace(err));
+                return;
+            }
+            Err(err) => {
+                report.skip(slug, BatchSkipReason::Failed(err.to_string()));
+                return;
+            }
+        }
+
+        let game = QueuedGameBuilder::default()
+            .offer_id(offer.offer_id().to_owned())
+            .build_id(build_id)
+            .path(target.path)
+            .slug(slug.to_owned())
+            .wine_prefix(target.wine_prefix)
+            .real_path(target.real_path)
+            .content_id(Some(offer.offer().content_id().to_owned()))
+            .build();
+        let result = match game {
+            Ok(game) => self.content_manager.add_install(game).await,
+            Err(err) => {
+                report.skip(slug, BatchSkipReason::Failed(err.to_string()));
+                return;
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                reserved.push((storage_path, space));
+                report.queued.push(slug.to_owned());
+            }
+            Err(err) => report.skip(slug, BatchSkipReason::Failed(err.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        content::{
+            manager::{
+                tests::{queued_game, write_queue_file},
+                ContentManager,
+            },
+            tests::cache_build_manifest,
+            zip::tests::stored_entry,
+        },
+        core::{
+            library::tests::{memory_library, offer_json, owned_offer, product_json},
+            service_layer::{
+                MockServiceLayer, SERVICE_REQUEST_AVAILABLEBUILDS,
+                SERVICE_REQUEST_GETLEGACYCATALOGDEFS, SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+            },
+            LockedMaxima, MaximaOptionsBuilder,
+        },
+        gameinfo::GameInstallInfo,
+        util::{native::volume_space, temp_dir::TempDataDir},
+    };
+
+    use super::*;
+
+    const WAITING_OFFER: &str = "Origin.OFR.50.700";
+
+    fn builds_json() -> serde_json::Value {
+        serde_json::json!([{
+            "buildId": "2",
+            "downloadType": "LIVE",
+            "gameVersion": "1.1",
+            "buildReleaseVersion": null,
+            "buildLiveDate": null,
+        }])
+    }
+
+    /// Maxima whose library and downloads are given to `service_layer`. `dir` is the test's
+    /// [`TempDataDir`], so the queue and request cache it loads aren't the real ones. Its
+    /// queue has a download of `waiting_size` bytes already waiting so nothing starts
+    /// downloading.
+    async fn test_maxima(
+        service_layer: Arc<MockServiceLayer>,
+        dir: &Path,
+        waiting_size: i64,
+    ) -> LockedMaxima {
+        let maxima_arc = Maxima::new_with_options(
+            MaximaOptionsBuilder::default()
+                .load_auth_storage(false)
+                .dummy_local_user(true)
+                .service_layer(service_layer.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let queue_file = dir.join("download_queue.json");
+        write_queue_file(
+            &queue_file,
+            vec![queued_game(WAITING_OFFER, "waiting", &dir.join("waiting"))],
+            Vec::new(),
+        );
+
+        {
+            let mut maxima = maxima_arc.lock().await;
+            maxima.library = memory_library(service_layer.clone());
+            maxima.content_manager = ContentManager::with_queue_file(service_layer, &queue_file)
+                .await
+                .unwrap();
+            cache_build_manifest(
+                maxima.content_manager.service(),
+                WAITING_OFFER,
+                "2",
+                vec![stored_entry("game.exe", waiting_size)],
+            );
+        }
+
+        maxima_arc
+    }
+
+    fn target(path: PathBuf) -> BatchTarget {
+        BatchTarget {
+            path,
+            wine_prefix: None,
+            real_path: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn queued_downloads_are_reserved() {
+        let dir = TempDataDir::new();
+        let service_layer = Arc::new(
+            MockServiceLayer::new().with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json()),
+        );
+        let maxima_arc = test_maxima(service_layer, dir.path(), 1024).await;
+        let mut maxima = maxima_arc.lock().await;
+
+        let offer = owned_offer("batch-reserved", "Origin.OFR.50.701", "ACTIVE");
+        cache_build_manifest(
+            maxima.content_manager.service(),
+            offer.offer_id(),
+            "2",
+            vec![stored_entry("game.exe", 2048)],
+        );
+
+        let mut reserved = maxima.reserved_space().await;
+        let mut report = BatchQueueReport::default();
+        maxima
+            .batch_queue(&offer, target(dir.join("game")), &mut reserved, &mut report)
+            .await;
+        assert_eq!(report.queued, ["batch-reserved"]);
+
+        // Both are waiting, so a later batch has to leave room for both
+        let space = |bytes| RequiredSpace {
+            install_bytes: bytes,
+            temp_bytes: 0,
+        };
+        assert_eq!(
+            maxima.reserved_space().await,
+            vec![
+                (dir.join("waiting"), space(1024)),
+                (dir.join("game"), space(2048)),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn batches_leave_room_for_queued_downloads() {
+        let dir = TempDataDir::new();
+        if volume_space(dir.path()).is_none() {
+            // Installs are let through when the free space can't be told
+            return;
+        }
+
+        let service_layer = Arc::new(
+            MockServiceLayer::new().with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json()),
+        );
+        // More than any disk holds
+        let maxima_arc = test_maxima(service_layer, dir.path(), i64::MAX / 2).await;
+        let mut maxima = maxima_arc.lock().await;
+
+        let offer = owned_offer("batch-room", "Origin.OFR.50.702", "ACTIVE");
+        cache_build_manifest(
+            maxima.content_manager.service(),
+            offer.offer_id(),
+            "2",
+            vec![stored_entry("game.exe", 1)],
+        );
+
+        let mut reserved = maxima.reserved_space().await;
+        let mut report = BatchQueueReport::default();
+        maxima
+            .batch_queue(&offer, target(dir.join("game")), &mut reserved, &mut report)
+            .await;
+
+        assert!(report.queued.is_empty());
+        assert!(matches!(
+            report.skipped.as_slice(),
+            [(_, BatchSkipReason::NoSpace(_))]
+        ));
+    }
+
+    #[tokio::test]
+    async fn batches_skip_what_cannot_be_queued() {
+        let dir = TempDataDir::new();
+        let service_layer = Arc::new(
+            MockServiceLayer::new().with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json()),
+        );
+        let maxima_arc = test_maxima(service_layer.clone(), dir.path(), 1024).await;
+        let mut maxima = maxima_arc.lock().await;
+
+        let waiting = owned_offer("waiting", WAITING_OFFER, "ACTIVE");
+        // Its build can be found, but its files can't be listed
+        let unlisted = owned_offer("batch-unlisted", "Origin.OFR.50.703", "ACTIVE");
+
+        let mut reserved = Vec::new();
+        let mut report = BatchQueueReport::default();
+        for offer in [&waiting, &unlisted] {
+            maxima
+                .batch_queue(offer, target(dir.join("game")), &mut reserved, &mut report)
+                .await;
+        }
+
+        service_layer.set_response(SERVICE_REQUEST_AVAILABLEBUILDS, serde_json::json!([]));
+        let unbuilt = owned_offer("batch-unbuilt", "Origin.OFR.50.704", "ACTIVE");
+        maxima
+            .batch_queue(
+                &unbuilt,
+                target(dir.join("game")),
+                &mut reserved,
+                &mut report,
+            )
+            .await;
+
+        assert!(report.queued.is_empty());
+        assert!(matches!(
+            report.skipped.as_slice(),
+            [
+                (_, BatchSkipReason::AlreadyQueued),
+                (_, BatchSkipReason::Failed(_)),
+                (_, BatchSkipReason::NoBuild),
+            ]
+        ));
+    }
+
+    fn library_responses(
+        service_layer: MockServiceLayer,
+        games: &[(&str, &str, serde_json::Value)],
+    ) -> MockServiceLayer {
+        let products: Vec<serde_json::Value> = games
+            .iter()
+            .map(|(slug, offer_id, _)| product_json(slug, offer_id, "ACTIVE"))
+            .collect();
+        let offers: Vec<serde_json::Value> = games
+            .iter()
+            .map(|(slug, offer_id, downloads)| {
+                let mut offer = offer_json(slug, offer_id);
+                offer["downloads"] = downloads.clone();
+                offer
+            })
+            .collect();
+
+        service_layer
+            .with_response(
+                SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+                serde_json::json!({
+                    "id": "0",
+                    "ownedGameProducts": {
+                        "next": null,
+                        "totalCount": products.len(),
+                        "items": products,
+                    },
+                }),
+            )
+            .with_response(SERVICE_REQUEST_GETLEGACYCATALOGDEFS, offers.into())
+    }
+
+    #[tokio::test]
+    async fn install_all_queues_games_that_are_not_installed() {
+        let dir = TempDataDir::new();
+        let installed = "batch-installed".to_owned();
+        let missing = "batch-missing".to_owned();
+        GameInstallInfo::new(dir.join(&installed), None).save_to_json(&installed);
+
+        let service_layer = library_responses(
+            MockServiceLayer::new().with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json()),
+            &[
+                (&installed, "Origin.OFR.50.705", serde_json::json!([])),
+                (&missing, "Origin.OFR.50.706", serde_json::json!([])),
+            ],
+        );
+        let maxima_arc = test_maxima(Arc::new(service_layer), dir.path(), 1024).await;
+        let mut maxima = maxima_arc.lock().await;
+        cache_build_manifest(
+            maxima.content_manager.service(),
+            "Origin.OFR.50.706",
+            "2",
+            vec![stored_entry("game.exe", 1024)],
+        );
+
+        let report = maxima.queue_install_all(dir.path(), "{base}/{slug}").await;
+        let queued_path = maxima
+            .content_manager
+            .queue()
+            .queued()
+            .last()
+            .map(|x| x.path().clone());
+
+        let report = report.unwrap();
+        assert_eq!(report.queued, [missing.clone()]);
+        assert!(matches!(
+            report.skipped.as_slice(),
+            [(slug, BatchSkipReason::AlreadyInstalled)] if slug == &installed
+        ));
+        assert_eq!(queued_path, Some(dir.join(&missing)));
+    }
+
+    #[tokio::test]
+    async fn update_all_queues_outdated_games_where_they_are() {
+        let dir = TempDataDir::new();
+        let outdated = "batch-outdated".to_owned();
+        let current = "batch-current".to_owned();
+        let missing = "batch-not-installed".to_owned();
+        GameInstallInfo::new(dir.join("outdated"), None).save_to_json(&outdated);
+        GameInstallInfo::new(dir.join("current"), None).save_to_json(&current);
+
+        let download = serde_json::json!([{
+            "igoApiEnabled": false,
+            "downloadType": "LIVE",
+            "version": "1.1",
+            "executeElevated": false,
+            "buildReleaseVersion": "",
+            "buildLiveDate": "",
+            "buildMetaData": "",
+            "gameVersion": null,
+            "treatUpdatesAsMandatory": false,
+            "enableDifferentialUpdate": false,
+        }]);
+        let service_layer = library_responses(
+            MockServiceLayer::new().with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json()),
+            &[
+                (&outdated, "Origin.OFR.50.707", download.clone()),
+                // Neither side knows a version, so there's nothing newer
+                (&current, "Origin.OFR.50.708", serde_json::json!([])),
+                (&missing, "Origin.OFR.50.709", download),
+            ],
+        );
+        let maxima_arc = test_maxima(Arc::new(service_layer), dir.path(), 1024).await;
+        let mut maxima = maxima_arc.lock().await;
+        cache_build_manifest(
+            maxima.content_manager.service(),
+            "Origin.OFR.50.707",
+            "2",
+            vec![stored_entry("game.exe", 1024)],
+        );
+
+        let report = maxima.update_all().await;
+        let queued_path = maxima
+            .content_manager
+            .queue()
+            .queued()
+            .last()
+            .map(|x| x.path().clone());
+
+        let report = report.unwrap();
+        assert_eq!(report.queued, [outdated.clone()]);
+        assert!(matches!(
+            report.skipped.as_slice(),
+            [(slug, BatchSkipReason::UpToDate)] if slug == &current
+        ));
+        assert_eq!(queued_path, Some(dir.join("outdated")));
+    }
+}
@@ -1,10 +1,35 @@
-use std::{any::Any, borrow::Borrow, hash::Hash, sync::Arc, time::Duration};
+use std::{
+    any::Any,
+    borrow::Borrow,
+    fs,
+    hash::Hash,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use log::{debug, warn};
 use moka::sync::Cache;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-/// Note that values are cloned when retrieved
+use crate::util::native::{maxima_dir, NativeError};
+
+/// On-disk wrapper for a persisted value, stamped with the time it stops being valid
+#[derive(Serialize, Deserialize)]
+struct PersistedEntry<T> {
+    expires_at: u64,
+    value: T,
+}
+
+/// Note that values are cloned when retrieved.
+///
+/// Backed by a concurrent moka cache, so it can be shared between tasks by reference
+/// without a lock around it. Reads don't block each other and writes only contend within
+/// a shard, with TTL and size eviction done as entries are touched.
 pub struct DynamicCache<K> {
     cache: Cache<K, Arc<dyn Any + Sync + Send>>,
+    time_to_live: Duration,
+    persist_dir: Option<PathBuf>,
 }
 
 impl<K: Eq + Hash + Sync + Send + 'static> DynamicCache<K> {
@@ -13,9 +38,14 @@ impl<K: Eq + Hash + Sync + Send + 'static> DynamicCache<K> {
             .max_capacity(capacity)
             .time_to_live(time_to_live)
             .time_to_idle(time_to_idle)
+            .support_invalidation_closures()
             .build();
 
-        Self { cache }
+        Self {
+            cache,
+            time_to_live,
+            persist_dir: None,
+        }
     }
 
     pub fn insert<T>(&self, key: K, request: T)
@@ -37,3 +67,251 @@ impl<K: Eq + Hash + Sync + Send + 'static> DynamicCache<K> {
         }
     }
 }
+
+impl DynamicCache<String> {
+    /// Back entries inserted with [`DynamicCache::insert_persisted`] with files in
+    /// `maxima_dir()/cache/<name>`, so they survive restarts until their TTL runs out.
+    /// If the directory can't be created, the cache stays memory-only.
+    pub fn persistent(mut self, name: &str) -> Self {
+        let result: Result<PathBuf, NativeError> = (|| {
+            let dir = maxima_dir()?.join("cache").join(name);
+            fs::create_dir_all(&dir)?;
+            Ok(dir)
+        })();
+
+        match result {
+            Ok(dir) => self.persist_dir = Some(dir),
+            Err(err) => warn!("Failed to set up persistent cache `{}`: {}", name, err),
+        }
+
+        self
+    }
+
+    /// Like [`DynamicCache::persistent`], with the files kept in `dir`
+    #[cfg(test)]
+    pub(crate) fn persistent_in(mut self, dir: PathBuf) -> Self {
+        self.persist_dir = Some(dir);
+        self
+    }
+
+    /// Like [`DynamicCache::insert`], but also writes the value to disk if this cache is persistent
+    pub fn insert_persisted<T>(&self, key: String, request: T)
+    where
+        T: Serialize + Sync + Send + Clone + 'static,
+    {
+        self.insert_persisted_for(key, request, self.time_to_live);
+    }
+
+    /// Same as [`DynamicCache::insert_persisted`], with the on-disk copy living for
+    /// `time_to_live` rather than the cache-wide TTL. Useful for rarely changing data.
+    pub fn insert_persisted_for<T>(&self, key: String, request: T, time_to_live: Duration)
+    where
+        T: Serialize + Sync + Send + Clone + 'static,
+    {
+        if let Some(path) = self.persisted_path(&key) {
+            let expires_at = unix_now() + time_to_live.as_secs();
+            let entry = PersistedEntry {
+                expires_at,
+                value: &request,
+            };
+
+            let result = serde_json::to_vec(&entry)
+                .map_err(NativeError::from)
+                .and_then(|data| Ok(fs::write(&path, data)?));
+            if let Err(err) = result {
+                debug!("Failed to persist cache entry `{}`: {}", key, err);
+            }
+        }
+
+        self.insert(key, request);
+    }
+
+    /// Like [`DynamicCache::get`], but falls back to the on-disk copy when the value
+    /// isn't in memory. Expired entries are removed and treated as missing.
+    pub fn get_persisted<T>(&self, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned + Sync + Send + Clone + 'static,
+    {
+        if let Some(cached) = self.get(key) {
+            return Some(cached);
+        }
+
+        let path = self.persisted_path(key)?;
+        let data = fs::read(&path).ok()?;
+
+        let entry = match serde_json::from_slice::<PersistedEntry<T>>(&data) {
+            Ok(entry) if entry.expires_at > unix_now() => entry,
+            _ => {
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+        };
+
+        self.insert(key.to_owned(), entry.value.clone());
+        Some(entry.value)
+    }
+
+    /// Removes a value from memory and, if persisted, from disk
+    pub fn invalidate(&self, key: &str) {
+        self.cache.invalidate(key);
+
+        if let Some(path) = self.persisted_path(key) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Like [`DynamicCache::invalidate_prefix`], but also removes the persisted copies
+    pub fn invalidate_prefix_persisted(&self, prefix: &str) {
+        self.invalidate_prefix(prefix);
+
+        let (dir, path) = match (&self.persist_dir, self.persisted_path(prefix)) {
+            (Some(dir), Some(path)) => (dir, path),
+            _ => return,
+        };
+
+        // Compared as file names, since keys are escaped a byte at a time and so keep
+        // their prefixes
+        let prefix = path
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or_default();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(prefix) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    /// Removes every value in memory whose key starts with `prefix`.
+    /// Persisted copies are left on disk.
+    pub fn invalidate_prefix(&self, prefix: &str) {
+        let prefix = prefix.to_owned();
+        if let Err(err) = self
+            .cache
+            .invalidate_entries_if(move |key, _| key.starts_with(&prefix))
+        {
+            warn!("Failed to invalidate cache entries: {}", err);
+        }
+    }
+
+    fn persisted_path(&self, key: &str) -> Option<PathBuf> {
+        let dir = self.persist_dir.as_ref()?;
+
+        // Every other byte is written as %XX, so no two keys share a file. Capitals are
+        // escaped too, as Windows and macOS don't tell file names apart by case.
+        let mut name = String::with_capacity(key.len());
+        for byte in key.bytes() {
+            match byte {
+                b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' => name.push(byte as char),
+                _ => name.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+
+        Some(dir.join(name + ".json"))
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn concurrent_inserts_and_gets() {
+        let cache =
+            DynamicCache::<String>::new(10_000, Duration::from_secs(60), Duration::from_secs(60));
+
+        thread::scope(|scope| {
+            for thread in 0..8 {
+                let cache = &cache;
+                scope.spawn(move || {
+                    for i in 0..500 {
+                        let key = format!("basic_player_{}_{}", thread, i);
+                        cache.insert(key.clone(), i);
+                        assert_eq!(cache.get::<_, i32>(&key), Some(i));
+                    }
+                });
+            }
+        });
+
+        assert_eq!(cache.get::<_, i32>("basic_player_7_499"), Some(499));
+    }
+
+    #[test]
+    fn persisted_entries_survive_restarts_until_they_expire() {
+        let dir = TempDir::new("cache");
+        let open = || {
+            DynamicCache::<String>::new(10, Duration::from_secs(60), Duration::from_secs(60))
+                .persistent_in(dir.path().to_owned())
+        };
+
+        let cache = open();
+        cache.insert_persisted("owned_games_1_en_US_1".to_owned(), vec![1, 2, 3]);
+        cache.insert_persisted_for("expired".to_owned(), 1, Duration::ZERO);
+
+        // A new instance has nothing in memory, so these come from disk
+        let cache = open();
+        assert_eq!(
+            cache.get_persisted::<Vec<i32>>("owned_games_1_en_US_1"),
+            Some(vec![1, 2, 3])
+        );
+        assert_eq!(
+            cache.get_persisted::<Vec<i32>>("owned_games_2_en_US_1"),
+            None
+        );
+        assert_eq!(cache.get_persisted::<i32>("expired"), None);
+        assert!(!dir.join("expired.json").exists());
+
+        cache.invalidate_prefix_persisted("owned_games_");
+        assert_eq!(
+            open().get_persisted::<Vec<i32>>("owned_games_1_en_US_1"),
+            None
+        );
+    }
+
+    #[test]
+    fn persisted_keys_get_their_own_files() {
+        let dir = TempDir::new("cache-keys");
+        let open = || {
+            DynamicCache::<String>::new(10, Duration::from_secs(60), Duration::from_secs(60))
+                .persistent_in(dir.path().to_owned())
+        };
+
+        let keys = [
+            "offer_a/b",
+            "offer_a_b",
+            "offer_a:b",
+            "offer_A_b",
+            "offer_a%2Fb",
+        ];
+        let cache = open();
+        for (i, key) in keys.iter().enumerate() {
+            cache.insert_persisted(key.to_string(), i);
+        }
+
+        let cache = open();
+        for (i, key) in keys.iter().enumerate() {
+            assert_eq!(cache.get_persisted::<usize>(key), Some(i));
+        }
+
+        cache.invalidate_prefix_persisted("offer_a/");
+        let cache = open();
+        assert_eq!(cache.get_persisted::<usize>("offer_a/b"), None);
+        assert_eq!(cache.get_persisted::<usize>("offer_a_b"), Some(1));
+        assert_eq!(cache.get_persisted::<usize>("offer_a%2Fb"), Some(4));
+    }
+}
@@ -14,19 +14,30 @@
 ///   - Push the files to the endpoints, along with a manifest outlining the files you uploaded and/or that are already there.
 /// - Call `/lock/delete`
 use super::{
-    auth::storage::LockedAuthStorage, endpoints::API_CLOUDSYNC, launch::LaunchMode,
-    library::OwnedOffer,
+    auth::storage::LockedAuthStorage,
+    concurrency::{CancellationToken, Cancelled},
+    endpoints::API_CLOUDSYNC,
+    launch::LaunchMode,
+    library::{LibraryError, OwnedOffer},
+    save_backup::{backup_saves, SaveBackupError, DEFAULT_SAVE_BACKUP_RETENTION},
+};
+use crate::{
+    gameinfo::load_game_info_from_json,
+    util::{
+        http,
+        native::{maxima_dir, NativeError, SafeParent, SafeStr},
+    },
 };
-use crate::util::native::{NativeError, SafeParent, SafeStr};
 use derive_getters::Getters;
 use futures::StreamExt;
-use log::{debug, error};
-use reqwest::{Client, ClientBuilder};
+use log::{debug, error, info, warn};
+use reqwest::{Client, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
     fmt::Debug,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use tokio::{
@@ -36,14 +47,23 @@ use tokio::{
 
 const AUTH_HEADER: &str = "X-Origin-AuthToken";
 const LOCK_HEADER: &str = "X-Origin-Sync-Lock";
+/// Where the lock we're holding is recorded, so it can be released if Maxima exits without doing so
+const HELD_LOCK_FILE: &str = "cloudsync_lock.json";
+/// How long to wait for someone else's lock to expire before giving up
+const LOCK_WAIT_TIMEOUT: Duration = Duration::from_secs(60);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Error, Debug)]
 pub enum CloudSyncError {
     #[error(transparent)]
     Auth(#[from] crate::core::auth::storage::AuthError),
     #[error(transparent)]
+    Cancelled(#[from] Cancelled),
+    #[error(transparent)]
     Request(#[from] reqwest::Error),
     #[error(transparent)]
+    SaveBackup(#[from] SaveBackupError),
+    #[error(transparent)]
     Token(#[from] crate::core::auth::storage::TokenError),
     #[error(transparent)]
     Xml(#[from] quick_xml::de::DeError),
@@ -59,15 +79,24 @@ pub enum CloudSyncError {
     Native(#[from] NativeError),
     #[error(transparent)]
     Library(#[from] crate::core::library::LibraryError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
 
     #[error("failed to acquire {0:?}")]
     LockAcquire(CloudSyncLockMode),
+    #[error("waited for a previous sync lock to expire, but it's still held. EA releases them after 5-10 minutes")]
+    LockHeld(CloudSyncLockMode),
     #[error("`{0}` has no cloudsync configuration")]
     NoConfig(String),
+    #[error("`{0}` has no multiplayer ID, so its cloudsync lock can't be named")]
+    NoMultiplayerId(String),
     #[error("cannot cloudsync when logged out")]
     NotSignedIn,
+    #[error("gave up syncing after {0} seconds")]
+    TimedOut(u64),
 }
 
+#[derive(Clone, Copy)]
 pub enum CloudSyncLockMode {
     Read,
     Write,
@@ -82,6 +111,74 @@ impl Debug for CloudSyncLockMode {
     }
 }
 
+/// A lock we've obtained and not yet released
+#[derive(Serialize, Deserialize)]
+struct HeldLock {
+    user_id: String,
+    lock: String,
+}
+
+impl HeldLock {
+    async fn load() -> Option<Self> {
+        let data = tokio::fs::read(maxima_dir().ok()?.join(HELD_LOCK_FILE))
+            .await
+            .ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    async fn save(&self) -> Result<(), CloudSyncError> {
+        let path = maxima_dir()?.join(HELD_LOCK_FILE);
+        tokio::fs::write(path, serde_json::to_vec(self)?).await?;
+        Ok(())
+    }
+
+    async fn clear() {
+        if let Ok(dir) = maxima_dir() {
+            let _ = tokio::fs::remove_file(dir.join(HELD_LOCK_FILE)).await;
+        }
+    }
+}
+
+/// Whether the server refused a lock because another one on the same save is still live
+fn is_lock_held(status: StatusCode) -> bool {
+    status == StatusCode::CONFLICT || status == StatusCode::LOCKED
+}
+
+/// What the server said to a request for a lock
+#[derive(Debug, PartialEq)]
+enum LockResponse {
+    /// Another lock on the same save is still live, try again once it expires
+    Held,
+    /// ID of the obtained lock. The save's manifest is in the body.
+    Obtained(String),
+}
+
+fn lock_response(res: &Response, mode: CloudSyncLockMode) -> Result<LockResponse, CloudSyncError> {
+    if is_lock_held(res.status()) {
+        return Ok(LockResponse::Held);
+    }
+
+    match res.headers().get("x-origin-sync-lock") {
+        Some(lock) => Ok(LockResponse::Obtained(lock.to_str()?.to_owned())),
+        None => Err(CloudSyncError::LockAcquire(mode)),
+    }
+}
+
+async fn delete_lock(
+    client: &Client,
+    token: String,
+    user_id: &str,
+    lock: &str,
+) -> Result<Response, CloudSyncError> {
+    Ok(client
+        .delete(format!("{}/lock/delete/{}", *API_CLOUDSYNC, user_id))
+        .header(AUTH_HEADER, token)
+        .header(LOCK_HEADER, lock)
+        .header("Content-Length", 0)
+        .send()
+        .await?)
+}
+
 async fn acquire_auth(auth: &LockedAuthStorage) -> Result<(String, String), CloudSyncError> {
     let mut auth = auth.lock().await;
 
@@ -129,6 +226,96 @@ fn substitute_paths<P: AsRef<str>>(path: P, slug: Option<&str>) -> Result<PathBu
     Ok(result)
 }
 
+/// Extra save file globs from the user, on top of the ones EA provides. Paths can use
+/// the same `%Documents%` and `%SavedGames%` variables as EA's.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CloudSyncFilter {
+    /// Files EA's configuration misses
+    pub include: Vec<String>,
+    /// Files to leave out even when they're included, like logs or screenshots
+    pub exclude: Vec<String>,
+}
+
+/// Files matching EA's `includes` or the user's, minus anything the user excluded
+fn collect_allowed_files(
+    includes: &[String],
+    filter: &CloudSyncFilter,
+    slug: &str,
+) -> Result<Vec<PathBuf>, CloudSyncError> {
+    let excludes = filter
+        .exclude
+        .iter()
+        .map(|x| {
+            let path = substitute_paths(x, Some(slug))?;
+            Ok(glob::Pattern::new(path.safe_str()?)?)
+        })
+        .collect::<Result<Vec<_>, CloudSyncError>>()?;
+
+    let mut allowed_files = Vec::new();
+    for include in includes.iter().chain(&filter.include) {
+        let path = substitute_paths(include, Some(slug))?;
+        for path in glob::glob(path.safe_str()?)? {
+            let path = path?;
+            if path.is_dir()
+                || allowed_files.contains(&path)
+                || excludes.iter().any(|x| x.matches_path(&path))
+            {
+                continue;
+            }
+
+            allowed_files.push(path);
+        }
+    }
+
+    Ok(allowed_files)
+}
+
+/// The part of `pattern` before its first wildcard. A pattern without any is taken
+/// to be a file, so the folder it's in is returned.
+fn glob_base(pattern: &Path) -> PathBuf {
+    let is_glob = |x: &std::path::Component| {
+        x.as_os_str()
+            .to_string_lossy()
+            .contains(&['*', '?', '['][..])
+    };
+
+    match pattern.components().any(|x| is_glob(&x)) {
+        true => pattern.components().take_while(|x| !is_glob(x)).collect(),
+        false => pattern
+            .parent()
+            .map(|x| x.to_path_buf())
+            .unwrap_or_default(),
+    }
+}
+
+/// Folders the game keeps its saves in, going by its cloudsync configuration. They
+/// may not exist until the game has been played. On unix they're in the game's wine
+/// prefix, so the game has to be installed.
+pub fn save_folders(offer: &OwnedOffer) -> Result<Vec<PathBuf>, CloudSyncError> {
+    let slug = offer.slug();
+    let info =
+        load_game_info_from_json(slug).map_err(|_| LibraryError::NotInstalled(slug.to_owned()))?;
+    if cfg!(unix) && info.wine_prefix.is_none() {
+        return Err(LibraryError::NotInstalled(slug.to_owned()).into());
+    }
+
+    let criteria: CloudSyncSaveFileCriteria =
+        match offer.offer().cloud_save_configuration_override() {
+            Some(config) => quick_xml::de::from_str(config)?,
+            None => return Err(CloudSyncError::NoConfig(offer.offer_id().clone())),
+        };
+
+    let mut folders = Vec::new();
+    for include in criteria.include {
+        let folder = glob_base(&substitute_paths(&include.value, Some(slug))?);
+        if !folder.as_os_str().is_empty() && !folders.contains(&folder) {
+            folders.push(folder);
+        }
+    }
+
+    Ok(folders)
+}
+
 fn unsubstitute_paths<P: AsRef<Path>>(path: P, slug: Option<&str>) -> Result<String, NativeError> {
     let path = path.as_ref();
     let home = home_dir(slug)?;
@@ -150,12 +337,30 @@ fn unsubstitute_paths<P: AsRef<Path>>(path: P, slug: Option<&str>) -> Result<Str
     }
 }
 
-enum HashMode {
-    Hex,
-    Base62,
+/// MD5 of a save file. Manifests may key files by a few encodings of it.
+struct FileDigest(md5::Digest);
+
+impl FileDigest {
+    fn hex(&self) -> String {
+        format!("{:x}", self.0)
+    }
+
+    /// The digest as a big-endian number in base62, padded to the 22 digits any digest fits in
+    fn base62(&self) -> String {
+        format!("{:0>22}", base62::encode(u128::from_be_bytes(self.0 .0)))
+    }
+
+    /// What older versions of Maxima uploaded as the base62 key
+    fn legacy(&self) -> String {
+        format!("{:=<24}", u128::from_le_bytes(self.0 .0))
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        key == self.hex() || key == self.base62() || key == self.legacy()
+    }
 }
 
-async fn calc_file_md5(file: File, mode: HashMode) -> Result<String, CloudSyncError> {
+async fn calc_file_md5(file: File) -> Result<FileDigest, CloudSyncError> {
     let len = file.metadata().await?.len();
 
     let buf_len = len.min(1_000_000) as usize;
@@ -174,20 +379,18 @@ async fn calc_file_md5(file: File, mode: HashMode) -> Result<String, CloudSyncEr
         buf.consume(len);
     }
 
-    let digest = context.compute();
-
-    match mode {
-        HashMode::Hex => Ok(format!("{:x}", digest)),
-        HashMode::Base62 => {
-            let mut b = format!("{}", u128::from_le_bytes(digest.0));
-
-            while b.len() < 24 {
-                b = format!("{}=", b)
-            }
+    Ok(FileDigest(context.compute()))
+}
 
-            Ok(b)
-        }
-    }
+/// Files a sync transferred, or would have in a dry run
+#[derive(Clone, Debug, Default)]
+pub struct CloudSyncPlan {
+    /// Local files sent to the cloud
+    pub upload: Vec<PathBuf>,
+    /// Local files replaced with the cloud's copy
+    pub download: Vec<PathBuf>,
+    /// Files left alone, because both copies match or there's no local file to replace
+    pub skipped: Vec<PathBuf>,
 }
 
 #[derive(Getters)]
@@ -199,6 +402,8 @@ pub struct CloudSyncLock<'a> {
     mode: CloudSyncLockMode,
     allowed_files: Vec<PathBuf>,
     slug: String,
+    /// Backups of local saves to keep, taken before a read replaces them. None are taken at 0.
+    backup_retention: usize,
 }
 
 impl<'a> CloudSyncLock<'a> {
@@ -210,6 +415,7 @@ impl<'a> CloudSyncLock<'a> {
         mode: CloudSyncLockMode,
         allowed_files: Vec<PathBuf>,
         slug: &str,
+        backup_retention: usize,
     ) -> Result<Self, CloudSyncError> {
         let res = client.get(manifest_url).send().await?;
 
@@ -242,22 +448,16 @@ impl<'a> CloudSyncLock<'a> {
             mode,
             allowed_files,
             slug: slug.to_owned(),
+            backup_retention,
         })
     }
 
     pub async fn release(&self) -> Result<(), CloudSyncError> {
         let (token, user_id) = acquire_auth(self.auth).await?;
 
-        let res = self
-            .client
-            .delete(format!("{}/lock/delete/{}", API_CLOUDSYNC, user_id))
-            .header(AUTH_HEADER, token)
-            .header(LOCK_HEADER, &self.lock)
-            .header("Content-Length", 0)
-            .send()
-            .await?;
-
+        let res = delete_lock(self.client, token, &user_id, &self.lock).await?;
         res.error_for_status()?;
+        HeldLock::clear().await;
 
         debug!("Released CloudSync {:?} {}", self.mode, self.lock);
         Ok(())
@@ -265,15 +465,30 @@ impl<'a> CloudSyncLock<'a> {
 
     /// The file syncing functions are some real hastily written code at the moment.
     /// Lots of stuff could be better and merged between them. TODO: Clean it up.
-    pub async fn sync_files(&self) -> Result<(), CloudSyncError> {
+    ///
+    /// With `dry_run`, nothing is transferred, and the returned plan is what would have been.
+    ///
+    /// Cancelling `cancel` stops a download between files. Uploads can only be stopped before
+    /// anything is sent, since the manifest goes up first and has to match the files after it.
+    /// The lock is still held either way, and should be released.
+    pub async fn sync_files(
+        &self,
+        dry_run: bool,
+        cancel: &CancellationToken,
+    ) -> Result<CloudSyncPlan, CloudSyncError> {
         Ok(match self.mode {
-            CloudSyncLockMode::Read => self.sync_read_files().await,
-            CloudSyncLockMode::Write => self.sync_write_files().await,
+            CloudSyncLockMode::Read => self.sync_read_files(dry_run, cancel).await,
+            CloudSyncLockMode::Write => self.sync_write_files(dry_run, cancel).await,
         }?)
     }
 
-    async fn sync_read_files(&self) -> Result<(), CloudSyncError> {
+    async fn sync_read_files(
+        &self,
+        dry_run: bool,
+        cancel: &CancellationToken,
+    ) -> Result<CloudSyncPlan, CloudSyncError> {
         let mut value = CloudSyncRequests::default();
+        let mut plan = CloudSyncPlan::default();
 
         let mut paths = HashMap::new();
         for i in 0..self.manifest.file.len() {
@@ -283,13 +498,15 @@ impl<'a> CloudSyncLock<'a> {
             let file = OpenOptions::new().read(true).open(path.clone()).await;
 
             let md5 = if let Ok(file) = file {
-                let md5 = calc_file_md5(file, HashMode::Hex).await?;
-                if let Some(_) = self.manifest.file_by_md5(&md5) {
+                let md5 = calc_file_md5(file).await?;
+                if let Some(_) = self.manifest.file_by_digest(&md5) {
                     debug!("Skipping CloudSync read {}", &path.display());
+                    plan.skipped.push(path);
                     continue;
                 }
                 md5
             } else {
+                plan.skipped.push(path);
                 continue;
             };
 
@@ -301,11 +518,18 @@ impl<'a> CloudSyncLock<'a> {
                 md5: None,
             });
 
+            plan.download.push(path.clone());
             paths.insert(i.to_string(), path);
         }
 
-        if value.request.is_empty() {
-            return Ok(());
+        if dry_run || value.request.is_empty() {
+            return Ok(plan);
+        }
+
+        // The cloud's copy could be older than ours, or broken. Nothing is replaced
+        // unless the local saves could be backed up first.
+        if self.backup_retention > 0 {
+            backup_saves(&self.slug, &plan.download, self.backup_retention).await?;
         }
 
         let (token, user_id) = acquire_auth(self.auth).await?;
@@ -313,7 +537,7 @@ impl<'a> CloudSyncLock<'a> {
 
         let res = self
             .client
-            .put(format!("{}/lock/authorize/{}", API_CLOUDSYNC, user_id))
+            .put(format!("{}/lock/authorize/{}", *API_CLOUDSYNC, user_id))
             .header(AUTH_HEADER, token)
             .header(LOCK_HEADER, &self.lock)
             .header("Content-Type", "application/xml")
@@ -325,6 +549,10 @@ impl<'a> CloudSyncLock<'a> {
         let authorizations: CloudSyncAuthorizationResponses = quick_xml::de::from_str(&text)?;
 
         for i in 0..authorizations.request.len() {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
             let auth_req = &authorizations.request[i];
             let mut req = self.client.get(&auth_req.url);
             let res = req.send().await?;
@@ -361,11 +589,16 @@ impl<'a> CloudSyncLock<'a> {
             }
         }
 
-        Ok(())
+        Ok(plan)
     }
 
-    async fn sync_write_files(&self) -> Result<(), CloudSyncError> {
+    async fn sync_write_files(
+        &self,
+        dry_run: bool,
+        cancel: &CancellationToken,
+    ) -> Result<CloudSyncPlan, CloudSyncError> {
         let mut auth_reqs = CloudSyncRequests::default();
+        let mut plan = CloudSyncPlan::default();
 
         enum WriteData {
             File {
@@ -404,22 +637,28 @@ impl<'a> CloudSyncLock<'a> {
 
         let mut i = 1;
         for path in &self.allowed_files {
+            if cancel.is_cancelled() {
+                return Err(Cancelled.into());
+            }
+
             let file = OpenOptions::new().read(true).open(path.clone()).await?;
 
-            let md5 = calc_file_md5(file.try_clone().await?, HashMode::Hex).await?;
-            let base62 = calc_file_md5(file.try_clone().await?, HashMode::Base62).await?;
-            if let Some(file) = self.manifest.file_by_md5(&md5) {
+            let md5 = calc_file_md5(file.try_clone().await?).await?;
+            if let Some(file) = self.manifest.file_by_digest(&md5) {
                 debug!("Skipping CloudSync write {}", &path.display());
                 skipped.push(file);
+                plan.skipped.push(path.clone());
                 continue;
             }
 
+            plan.upload.push(path.clone());
+
             let name = unsubstitute_paths(&path, Some(&self.slug))?;
             let write_data = WriteData::File {
                 name,
                 file,
-                hex: md5,
-                base62: base62.clone(),
+                hex: md5.hex(),
+                base62: md5.base62(),
             };
 
             // TODO(headassbtw): DELETE if already present
@@ -429,7 +668,7 @@ impl<'a> CloudSyncLock<'a> {
                 verb: "PUT".to_owned(),
                 resource: write_data.file_key().await?,
                 content_type: None,
-                md5: Some(base62),
+                md5: Some(md5.base62()),
             });
 
             data.insert(i, write_data);
@@ -437,8 +676,8 @@ impl<'a> CloudSyncLock<'a> {
         }
 
         // Don't bother uploading/updating the cloudsave data if there's no changes.
-        if data.is_empty() {
-            return Ok(());
+        if dry_run || data.is_empty() {
+            return Ok(plan);
         }
 
         // Create a manifest that tells the cloud what files it does and is going to have.
@@ -477,12 +716,17 @@ impl<'a> CloudSyncLock<'a> {
             );
         }
 
+        // Last chance, once the manifest is up the files have to follow it
+        if cancel.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+
         let (token, user_id) = acquire_auth(self.auth).await?;
         let body = quick_xml::se::to_string(&auth_reqs)?.replace("CloudSyncRequests", "requests");
 
         let res = self
             .client
-            .put(format!("{}/lock/authorize/{}", API_CLOUDSYNC, user_id))
+            .put(format!("{}/lock/authorize/{}", *API_CLOUDSYNC, user_id))
             .header(AUTH_HEADER, token)
             .header(LOCK_HEADER, &self.lock)
             .header("Content-Type", "application/xml")
@@ -535,53 +779,157 @@ impl<'a> CloudSyncLock<'a> {
             );
         }
 
-        Ok(())
+        Ok(plan)
     }
 }
 
+#[derive(Clone)]
 pub struct CloudSyncClient {
     auth: LockedAuthStorage,
     client: Client,
+    backup_retention: usize,
+    automatic_sync: bool,
 }
 
 impl CloudSyncClient {
     pub fn new(auth: LockedAuthStorage) -> Self {
         Self {
             auth,
-            client: ClientBuilder::default().gzip(true).build().unwrap(),
+            client: http::client_builder().gzip(true).build().unwrap(),
+            backup_retention: DEFAULT_SAVE_BACKUP_RETENTION,
+            automatic_sync: true,
         }
     }
 
+    /// Whether saves are synced when games launch and stop, on by default. Turned off,
+    /// [`CloudSyncClient::sync_if_enabled`] never syncs whatever a game's own settings say,
+    /// so Maxima sends no save traffic on its own. Locks obtained directly with
+    /// [`CloudSyncClient::obtain_lock`] still work.
+    pub fn set_automatic_sync(&mut self, enabled: bool) {
+        self.automatic_sync = enabled;
+    }
+
+    pub fn automatic_sync(&self) -> bool {
+        self.automatic_sync
+    }
+
+    /// Keep up to `count` backups of each game's local saves, taken before a read
+    /// replaces them. 0 turns backups off.
+    pub fn set_backup_retention(&mut self, count: usize) {
+        self.backup_retention = count;
+    }
+
+    /// Release a lock left behind by a previous session that exited without releasing it,
+    /// so syncing doesn't have to wait for it to expire. Returns whether there was one.
+    /// Called before obtaining a lock, but can be called earlier, e.g. after logging in.
+    pub async fn release_orphaned_lock(&self) -> Result<bool, CloudSyncError> {
+        let held = match HeldLock::load().await {
+            Some(held) => held,
+            None => return Ok(false),
+        };
+
+        // Locks of other accounts can't be released, they'll expire on their own
+        let (token, user_id) = acquire_auth(&self.auth).await?;
+        if held.user_id == user_id {
+            info!("Releasing a CloudSync lock left behind by a previous session");
+            let res = delete_lock(&self.client, token, &user_id, &held.lock).await?;
+            if !res.status().is_success() {
+                debug!(
+                    "Leftover CloudSync lock was already released: {}",
+                    res.status()
+                );
+            }
+        }
+
+        HeldLock::clear().await;
+        Ok(true)
+    }
+
+    /// Obtains a lock and syncs an offer's saves, as long as automatic syncing and cloud
+    /// saves for this launch are turned on, and the game has them. Turned off, CloudSync isn't contacted at all, not
+    /// even for a lock. Returns whether a sync happened.
+    pub async fn sync_if_enabled(
+        &self,
+        enabled: bool,
+        offer: &OwnedOffer,
+        mode: CloudSyncLockMode,
+        filter: &CloudSyncFilter,
+        cancel: &CancellationToken,
+    ) -> Result<bool, CloudSyncError> {
+        self.sync_if_enabled_within(enabled, offer, mode, filter, cancel, None)
+            .await
+    }
+
+    /// Like [`CloudSyncClient::sync_if_enabled`], but gives up on the sync once `timeout`
+    /// runs out. The lock is still released, though an upload cut short may have only sent
+    /// some of the files.
+    pub async fn sync_if_enabled_within(
+        &self,
+        enabled: bool,
+        offer: &OwnedOffer,
+        mode: CloudSyncLockMode,
+        filter: &CloudSyncFilter,
+        cancel: &CancellationToken,
+        timeout: Option<Duration>,
+    ) -> Result<bool, CloudSyncError> {
+        if !enabled || !self.automatic_sync || !offer.offer().has_cloud_save() {
+            return Ok(false);
+        }
+
+        let lock = self.obtain_lock(offer, mode, filter).await?;
+        let result = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, lock.sync_files(false, cancel))
+                .await
+                .unwrap_or(Err(CloudSyncError::TimedOut(timeout.as_secs()))),
+            None => lock.sync_files(false, cancel).await,
+        };
+
+        // Released whether or not the sync worked, or it's held until it expires
+        let released = lock.release().await;
+        result?;
+        released?;
+        Ok(true)
+    }
+
+    /// The cloud's manifest of `offer`'s saves, without transferring any of them. Holds a
+    /// read lock only as long as it takes to fetch it.
+    pub async fn fetch_manifest(
+        &self,
+        offer: &OwnedOffer,
+    ) -> Result<CloudSyncManifest, CloudSyncError> {
+        let lock = self
+            .obtain_lock(offer, CloudSyncLockMode::Read, &CloudSyncFilter::default())
+            .await?;
+        let manifest = lock.manifest().clone();
+        lock.release().await?;
+        Ok(manifest)
+    }
+
     pub async fn obtain_lock<'a>(
         &self,
         offer: &OwnedOffer,
         mode: CloudSyncLockMode,
+        filter: &CloudSyncFilter,
     ) -> Result<CloudSyncLock, CloudSyncError> {
+        let multiplayer_id = match offer.offer().multiplayer_id() {
+            Some(id) => id,
+            None => return Err(CloudSyncError::NoMultiplayerId(offer.offer_id().clone())),
+        };
         let id = format!(
             "{}_{}",
             offer.offer().primary_master_title_id(),
-            offer.offer().multiplayer_id().as_ref().unwrap()
+            multiplayer_id
         );
 
         let slug = offer.slug().to_string();
-        let mut allowed_files = Vec::new();
-        if let Some(config) = offer.offer().cloud_save_configuration_override() {
-            let criteria: CloudSyncSaveFileCriteria = quick_xml::de::from_str(config)?;
-            for include in criteria.include {
-                let path = substitute_paths(include.value, Some(&slug))?;
-                let paths = glob::glob(path.safe_str()?)?;
-                for path in paths {
-                    let path = path?;
-                    if path.is_dir() {
-                        continue;
-                    }
-
-                    allowed_files.push(path);
-                }
+        let allowed_files = match offer.offer().cloud_save_configuration_override() {
+            Some(config) => {
+                let criteria: CloudSyncSaveFileCriteria = quick_xml::de::from_str(config)?;
+                let includes: Vec<String> = criteria.include.into_iter().map(|x| x.value).collect();
+                collect_allowed_files(&includes, filter, &slug)?
             }
-        } else {
-            return Err(CloudSyncError::NoConfig(offer.offer_id().clone()));
-        }
+            None => return Err(CloudSyncError::NoConfig(offer.offer_id().clone())),
+        };
 
         Ok(self
             .obtain_lock_raw(&id, mode, allowed_files, &slug)
@@ -595,23 +943,43 @@ impl CloudSyncClient {
         allowed_files: Vec<PathBuf>,
         slug: &str,
     ) -> Result<CloudSyncLock, CloudSyncError> {
-        let (token, user_id) = acquire_auth(&self.auth).await?;
+        if let Err(err) = self.release_orphaned_lock().await {
+            warn!("Failed to release a leftover CloudSync lock: {}", err);
+        }
 
-        let res = self
-            .client
-            .post(format!("{}/{:?}/{}/{}", API_CLOUDSYNC, mode, user_id, id))
-            .header(AUTH_HEADER, token)
-            .header("Content-Length", 0)
-            .send()
-            .await?;
-        let lock = match res.headers().get("x-origin-sync-lock") {
-            Some(lock) => lock.to_str()?.to_owned(),
-            None => {
-                return Err(CloudSyncError::LockAcquire(mode));
+        let started = Instant::now();
+        let (res, lock, user_id) = loop {
+            let (token, user_id) = acquire_auth(&self.auth).await?;
+
+            let res = self
+                .client
+                .post(format!("{}/{:?}/{}/{}", *API_CLOUDSYNC, mode, user_id, id))
+                .header(AUTH_HEADER, token)
+                .header("Content-Length", 0)
+                .send()
+                .await?;
+            if let LockResponse::Obtained(lock) = lock_response(&res, mode)? {
+                break (res, lock, user_id);
+            }
+
+            if started.elapsed() >= LOCK_WAIT_TIMEOUT {
+                return Err(CloudSyncError::LockHeld(mode));
             }
+
+            info!("Waiting for previous sync lock to expire...");
+            tokio::time::sleep(LOCK_RETRY_INTERVAL).await;
         };
+
         debug!("Obtained CloudSync {:?}: {}", mode, lock);
 
+        let held = HeldLock {
+            user_id,
+            lock: lock.clone(),
+        };
+        if let Err(err) = held.save().await {
+            warn!("Failed to record CloudSync lock: {}", err);
+        }
+
         let text = res.text().await?;
         let sync: CloudSyncSync = quick_xml::de::from_str(&text)?;
         Ok(CloudSyncLock::new(
@@ -622,6 +990,7 @@ impl CloudSyncClient {
             mode,
             allowed_files,
             slug,
+            self.backup_retention,
         )
         .await?)
     }
@@ -629,10 +998,130 @@ impl CloudSyncClient {
 
 #[cfg(test)]
 mod tests {
-    use crate::core::{auth::storage::AuthStorage, library::GameLibrary};
+    use std::sync::Arc;
+
+    use crate::{
+        core::{
+            auth::storage::AuthStorage,
+            library::{tests::owned_offer_with_cloud_save, GameLibrary},
+            locale::Locale,
+            service_layer::ServiceLayerClient,
+        },
+        util::temp_dir::TempDir,
+    };
 
     use super::*;
 
+    #[test]
+    fn digest_encodings() {
+        let digest = FileDigest(md5::compute(b"abc"));
+        assert_eq!(digest.hex(), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(digest.base62(), "4NjUkTaz87FZto8hbWIO6E");
+
+        // Small digests keep their width
+        let digest = FileDigest(md5::Digest([0; 16]));
+        assert_eq!(digest.base62(), "0".repeat(22));
+        assert!(digest.matches("0000000000000000000000"));
+        assert!(digest.matches(&"0".repeat(32)));
+    }
+
+    #[test]
+    fn user_excludes_win_over_includes() {
+        let dir = TempDir::new("cloudsync");
+        std::fs::create_dir_all(dir.join("logs")).unwrap();
+        for file in ["1.sav", "2.sav", "settings.ini", "logs/game.log"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        let root = dir.path().to_str().unwrap();
+        let includes = [format!("{}/*.sav", root), format!("{}/logs/*", root)];
+        let filter = CloudSyncFilter {
+            include: vec![format!("{}/settings.ini", root)],
+            exclude: vec![format!("{}/2.sav", root), format!("{}/logs/*", root)],
+        };
+
+        let mut files = collect_allowed_files(&includes, &filter, "test").unwrap();
+        files.sort();
+
+        assert_eq!(files, vec![dir.join("1.sav"), dir.join("settings.ini")]);
+    }
+
+    fn response(status: u16, lock: Option<&str>) -> Response {
+        let mut res = ::http::Response::builder().status(status);
+        if let Some(lock) = lock {
+            res = res.header("x-origin-sync-lock", lock);
+        }
+        Response::from(res.body("").unwrap())
+    }
+
+    #[test]
+    fn live_locks_are_waited_for() {
+        for status in [409, 423] {
+            let res = response(status, None);
+            let result = lock_response(&res, CloudSyncLockMode::Write).unwrap();
+            assert_eq!(result, LockResponse::Held);
+        }
+    }
+
+    #[test]
+    fn lock_comes_from_the_header() {
+        let res = response(200, Some("lock-636"));
+        let result = lock_response(&res, CloudSyncLockMode::Read).unwrap();
+        assert_eq!(result, LockResponse::Obtained("lock-636".to_owned()));
+
+        for status in [200, 403, 500] {
+            let res = response(status, None);
+            let result = lock_response(&res, CloudSyncLockMode::Read);
+            assert!(matches!(
+                result,
+                Err(CloudSyncError::LockAcquire(CloudSyncLockMode::Read))
+            ));
+        }
+    }
+
+    #[test]
+    fn save_folder_is_the_pattern_before_wildcards() {
+        let base = Path::new("/saves/Game");
+        assert_eq!(glob_base(&base.join("*.sav")), base);
+        assert_eq!(glob_base(&base.join("slot?/data/*")), base);
+        assert_eq!(glob_base(&base.join("settings.ini")), base);
+    }
+
+    #[tokio::test]
+    async fn disabled_cloud_saves_skip_the_lock() {
+        // Not signed in, so obtaining a lock would fail
+        let client = CloudSyncClient::new(AuthStorage::new());
+        let offer = owned_offer_with_cloud_save("test", "Origin.OFR.50.1", Some("1"));
+        let filter = CloudSyncFilter::default();
+        let cancel = CancellationToken::new();
+
+        for mode in [CloudSyncLockMode::Read, CloudSyncLockMode::Write] {
+            let synced = client
+                .sync_if_enabled(false, &offer, mode, &filter, &cancel)
+                .await
+                .unwrap();
+            assert!(!synced);
+        }
+
+        let result = client
+            .sync_if_enabled(true, &offer, CloudSyncLockMode::Read, &filter, &cancel)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn lock_needs_a_multiplayer_id() {
+        let client = CloudSyncClient::new(AuthStorage::new());
+        let offer = owned_offer_with_cloud_save("test", "Origin.OFR.50.1", None);
+
+        let result = client
+            .obtain_lock(&offer, CloudSyncLockMode::Read, &CloudSyncFilter::default())
+            .await;
+        assert!(
+            matches!(result, Err(CloudSyncError::NoMultiplayerId(id)) if id == "Origin.OFR.50.1")
+        );
+    }
+
     #[tokio::test]
     async fn read_files() -> Result<(), CloudSyncError> {
         let auth = AuthStorage::load()?;
@@ -640,18 +1129,22 @@ mod tests {
             return Err(CloudSyncError::NotSignedIn);
         }
 
-        let mut library = GameLibrary::new(auth.clone()).await;
-        let offer = library
-            .game_by_base_slug("star-wars-battlefront-2")
-            .await?
-            .unwrap();
+        let mut library = GameLibrary::new(
+            Arc::new(ServiceLayerClient::new(auth.clone())),
+            auth.clone(),
+            Locale::EnUs,
+        )
+        .await;
+        let offer = library.game_by_base_slug("star-wars-battlefront-2").await?;
 
         println!("Got offer");
 
         let client = CloudSyncClient::new(auth);
 
-        let lock = client.obtain_lock(offer, CloudSyncLockMode::Read).await?;
-        //lock.sync_read_files().await?;
+        let lock = client
+            .obtain_lock(offer, CloudSyncLockMode::Read, &CloudSyncFilter::default())
+            .await?;
+        //lock.sync_read_files(false, &CancellationToken::new()).await?;
         lock.release().await?;
         Ok(())
     }
@@ -663,18 +1156,24 @@ mod tests {
             return Err(CloudSyncError::NotSignedIn);
         }
 
-        let mut library = GameLibrary::new(auth.clone()).await;
-        let offer = library
-            .game_by_base_slug("star-wars-battlefront-2")
-            .await?
-            .unwrap();
+        let mut library = GameLibrary::new(
+            Arc::new(ServiceLayerClient::new(auth.clone())),
+            auth.clone(),
+            Locale::EnUs,
+        )
+        .await;
+        let offer = library.game_by_base_slug("star-wars-battlefront-2").await?;
 
         println!("Got offer");
 
         let client = CloudSyncClient::new(auth);
 
-        let lock = client.obtain_lock(offer, CloudSyncLockMode::Write).await?;
-        let res = lock.sync_write_files().await;
+        let lock = client
+            .obtain_lock(offer, CloudSyncLockMode::Write, &CloudSyncFilter::default())
+            .await?;
+        let res = lock
+            .sync_write_files(false, &CancellationToken::new())
+            .await;
         lock.release().await?;
         res?;
         Ok(())
@@ -684,7 +1183,7 @@ mod tests {
 macro_rules! cloudsync_type {
     (
         $(#[$message_attr:meta])*
-        $message_name:ident;
+        $vis:vis $message_name:ident;
         attr {
             $(
                 $(#[$attr_field_attr:meta])*
@@ -703,7 +1202,7 @@ macro_rules! cloudsync_type {
             $(#[$message_attr])*
             #[derive(Default, Debug, Clone, Serialize, Deserialize, Getters, PartialEq)]
             #[serde(rename_all = "camelCase")]
-            struct [<CloudSync $message_name>] {
+            $vis struct [<CloudSync $message_name>] {
                 $(
                     $(#[$attr_field_attr])*
                     #[serde(rename = "@" $attr_field)]
@@ -719,7 +1218,9 @@ macro_rules! cloudsync_type {
 }
 
 cloudsync_type!(
-    File;
+    /// A save in the cloud. `local_name` is where it goes, with the path variables
+    /// CloudSync uses in place of the user's folders.
+    pub File;
     attr {
         href: String,
         size: String,
@@ -731,7 +1232,8 @@ cloudsync_type!(
 );
 
 cloudsync_type!(
-    Manifest;
+    /// What's in the cloud for a game
+    pub Manifest;
     attr {
         xmlns: String,
     },
@@ -741,18 +1243,10 @@ cloudsync_type!(
 );
 
 impl CloudSyncManifest {
-    pub fn file_by_md5(&self, md5: &str) -> Option<&CloudSyncFile> {
-        for ele in &self.file {
-            if let Some(ele_md5) = &ele.attr_md5 {
-                if ele_md5 != md5 {
-                    continue;
-                }
-
-                return Some(&ele);
-            }
-        }
-
-        None
+    fn file_by_digest(&self, digest: &FileDigest) -> Option<&CloudSyncFile> {
+        self.file
+            .iter()
+            .find(|x| x.attr_md5.as_ref().is_some_and(|md5| digest.matches(md5)))
     }
 }
 
@@ -1,4 +1,175 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+
 use futures::{stream, Future, StreamExt};
+use lazy_static::lazy_static;
+use thiserror::Error;
+use tokio::sync::{Notify, OwnedMutexGuard};
+
+/// Re-exported so frontends can cancel operations without depending on tokio-util themselves
+pub use tokio_util::sync::CancellationToken;
+
+/// Returned by long-running operations that were aborted through a [`CancellationToken`]
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("operation was cancelled")]
+pub struct Cancelled;
+
+/// Run `future` to completion, or stop early with [`Cancelled`] once `cancel` is cancelled.
+/// Whatever `future` was doing is dropped at its next await point.
+pub async fn cancellable<F, T, E>(cancel: &CancellationToken, future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: From<Cancelled>,
+{
+    tokio::select! {
+        result = future => result,
+        _ = cancel.cancelled() => Err(Cancelled.into()),
+    }
+}
+
+/// Default limit of [`IMAGE_DOWNLOADS`]
+pub const DEFAULT_IMAGE_DOWNLOADS: usize = 4;
+
+/// Default limit of [`FILE_VERIFICATION`], one file per CPU core
+pub fn default_file_verification() -> usize {
+    std::thread::available_parallelism().map_or(4, |x| x.get())
+}
+
+lazy_static! {
+    /// Shared by every image download, from avatars Maxima caches to art frontends load,
+    /// so a slow connection isn't flooded when a frontend starts
+    pub static ref IMAGE_DOWNLOADS: PriorityLimiter = PriorityLimiter::new(DEFAULT_IMAGE_DOWNLOADS);
+
+    /// Shared by every installed file being hashed against its manifest, across all
+    /// downloads, so checking a large game keeps the CPU busy without thrashing the disk
+    pub static ref FILE_VERIFICATION: PriorityLimiter =
+        PriorityLimiter::new(default_file_verification());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Needed for what's on screen, like game art
+    High,
+    /// Can fill in later, like friend avatars
+    Low,
+}
+
+struct LimiterState {
+    limit: usize,
+    running: usize,
+    high_waiting: usize,
+}
+
+/// Limits how many tasks run at once. Waiting [`Priority::High`] tasks go before any
+/// waiting [`Priority::Low`] ones.
+pub struct PriorityLimiter {
+    state: Mutex<LimiterState>,
+    notify: Notify,
+}
+
+/// Held while a task runs, letting the next one start when dropped
+pub struct LimiterPermit<'a> {
+    limiter: &'a PriorityLimiter,
+}
+
+/// Counts a high priority task as waiting, until it starts or stops waiting
+struct HighWaiting<'a> {
+    limiter: &'a PriorityLimiter,
+}
+
+impl PriorityLimiter {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                limit: limit.max(1),
+                running: 0,
+                high_waiting: 0,
+            }),
+            notify: Notify::new(),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Change how many tasks run at once. Running tasks aren't stopped when it goes down.
+    pub fn set_limit(&self, limit: usize) {
+        self.state.lock().unwrap().limit = limit.max(1);
+        self.notify.notify_waiters();
+    }
+
+    /// Wait for a free slot
+    pub async fn acquire(&self, priority: Priority) -> LimiterPermit<'_> {
+        let mut waiting = None;
+        loop {
+            // Made before checking, so a slot freed in between still wakes us
+            let notified = self.notify.notified();
+            {
+                let mut state = self.state.lock().unwrap();
+                let free = state.running < state.limit;
+                if free && (priority == Priority::High || state.high_waiting == 0) {
+                    state.running += 1;
+                    drop(state);
+                    // No longer waiting, let low priority tasks have any other free slots
+                    drop(waiting);
+                    return LimiterPermit { limiter: self };
+                }
+
+                if priority == Priority::High && waiting.is_none() {
+                    state.high_waiting += 1;
+                    waiting = Some(HighWaiting { limiter: self });
+                }
+            }
+
+            notified.await;
+        }
+    }
+}
+
+impl Drop for LimiterPermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().running -= 1;
+        self.limiter.notify.notify_waiters();
+    }
+}
+
+impl Drop for HighWaiting<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().high_waiting -= 1;
+        self.limiter.notify.notify_waiters();
+    }
+}
+
+/// One lock per key, so only one task at a time fetches a given thing. Tasks asking for
+/// the same key wait for the first, then find what it cached rather than asking again.
+#[derive(Default)]
+pub struct KeyedLock {
+    locks: Mutex<HashMap<String, Weak<tokio::sync::Mutex<()>>>>,
+}
+
+impl KeyedLock {
+    /// Wait until no other task holds `key`. Locks nobody holds are dropped as keys
+    /// are taken.
+    pub async fn lock(&self, key: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            locks.retain(|_, x| x.strong_count() > 0);
+            match locks.get(key).and_then(Weak::upgrade) {
+                Some(lock) => lock,
+                None => {
+                    let lock = Arc::new(tokio::sync::Mutex::new(()));
+                    locks.insert(key.to_owned(), Arc::downgrade(&lock));
+                    lock
+                }
+            }
+        };
+
+        lock.lock_owned().await
+    }
+}
 
 pub async fn execute_batch_concurrent<T, F, Fut, R>(
     buffer: usize,
@@ -17,3 +188,53 @@ where
 
     tasks.collect::<Vec<_>>().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn cancellable_stops_early() {
+        let cancel = CancellationToken::new();
+        let result: Result<u32, Cancelled> = cancellable(&cancel, async { Ok(5) }).await;
+        assert_eq!(result, Ok(5));
+
+        cancel.cancel();
+        let result: Result<u32, Cancelled> = cancellable(&cancel, futures::future::pending()).await;
+        assert_eq!(result, Err(Cancelled));
+    }
+
+    #[tokio::test]
+    async fn high_priority_goes_first() {
+        let limiter = PriorityLimiter::new(1);
+        let permit = limiter.acquire(Priority::Low).await;
+
+        let mut low = Box::pin(limiter.acquire(Priority::Low));
+        let mut high = Box::pin(limiter.acquire(Priority::High));
+        assert!(futures::poll!(&mut low).is_pending());
+        assert!(futures::poll!(&mut high).is_pending());
+
+        drop(permit);
+        assert!(futures::poll!(&mut low).is_pending());
+        let permit = match futures::poll!(&mut high) {
+            std::task::Poll::Ready(permit) => permit,
+            std::task::Poll::Pending => panic!("high priority task didn't start"),
+        };
+
+        drop(permit);
+        assert!(futures::poll!(&mut low).is_ready());
+    }
+
+    #[tokio::test]
+    async fn keyed_lock_waits_for_the_same_key() {
+        let locks = KeyedLock::default();
+        let guard = locks.lock("builds_1").await;
+
+        let mut same = Box::pin(locks.lock("builds_1"));
+        assert!(futures::poll!(&mut same).is_pending());
+        assert!(futures::poll!(Box::pin(locks.lock("builds_2"))).is_ready());
+
+        drop(guard);
+        assert!(futures::poll!(&mut same).is_ready());
+    }
+}
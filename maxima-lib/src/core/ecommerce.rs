@@ -1,8 +1,9 @@
 #![allow(non_snake_case)]
 
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 
 use super::endpoints::API_ECOMMERCE;
+use crate::util::http;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -28,8 +29,8 @@ pub async fn request_entitlements(
         query.push(("groupName", group_name));
     }
 
-    let res = Client::new()
-        .get(format!("{}/entitlements/{}", API_ECOMMERCE, user_id))
+    let res = http::client()
+        .get(format!("{}/entitlements/{}", *API_ECOMMERCE, user_id))
         .query(&query)
         .header("AuthToken", access_token)
         .header("Accept", "application/json")
@@ -49,8 +50,8 @@ pub async fn request_offer_data(
     offer: &str,
     locale: &str,
 ) -> Result<CommerceOffer, ECommerceError> {
-    let res = Client::new()
-        .get(&format!("{}/public/{}/{}", API_ECOMMERCE, offer, locale))
+    let res = http::client()
+        .get(&format!("{}/public/{}/{}", *API_ECOMMERCE, offer, locale))
         .header("AuthToken", access_token)
         .send()
         .await?;
@@ -1,9 +1,61 @@
-pub const API_SERVICE_AGGREGATION_LAYER: &str =
-    "https://service-aggregation-layer.juno.ea.com/graphql";
-pub const API_CONTENTFUL_PROXY: &str = "https://contentful-proxy.juno.ea.com/graphql";
-pub const API_PROXY_NOVAFUSION_LICENSES: &str = "https://proxy.novafusion.ea.com/licenses";
-pub const API_ECOMMERCE: &str = "https://ecommerce2.dm.origin.com/ecommerce2";
-pub const API_NUCLEUS_AUTH: &str = "https://accounts.ea.com/connect/auth";
-pub const API_NUCLEUS_TOKEN: &str = "https://accounts.ea.com/connect/token";
-pub const API_NUCLEUS_TOKENINFO: &str = "https://accounts.ea.com/connect/tokeninfo";
-pub const API_CLOUDSYNC: &str = "https://cloudsync.juno.ea.com";
+//! EA API endpoints. Each can be pointed somewhere else through an environment variable,
+//! for testing against a staging or mock server:
+//!
+//! | Endpoint                         | Variable                            |
+//! |----------------------------------|-------------------------------------|
+//! | [`API_SERVICE_AGGREGATION_LAYER`] | `MAXIMA_ENDPOINT_SERVICE_LAYER`     |
+//! | [`API_CONTENTFUL_PROXY`]          | `MAXIMA_ENDPOINT_CONTENTFUL_PROXY`  |
+//! | [`API_PROXY_NOVAFUSION_LICENSES`] | `MAXIMA_ENDPOINT_LICENSES`          |
+//! | [`API_ECOMMERCE`]                 | `MAXIMA_ENDPOINT_ECOMMERCE`         |
+//! | [`API_NUCLEUS_AUTH`]              | `MAXIMA_ENDPOINT_NUCLEUS_AUTH`      |
+//! | [`API_NUCLEUS_TOKEN`]             | `MAXIMA_ENDPOINT_NUCLEUS_TOKEN`     |
+//! | [`API_NUCLEUS_TOKENINFO`]         | `MAXIMA_ENDPOINT_NUCLEUS_TOKENINFO` |
+//! | [`API_CLOUDSYNC`]                 | `MAXIMA_ENDPOINT_CLOUDSYNC`         |
+
+use lazy_static::lazy_static;
+use log::info;
+
+lazy_static! {
+    pub static ref API_SERVICE_AGGREGATION_LAYER: String = endpoint(
+        "MAXIMA_ENDPOINT_SERVICE_LAYER",
+        "https://service-aggregation-layer.juno.ea.com/graphql"
+    );
+    pub static ref API_CONTENTFUL_PROXY: String = endpoint(
+        "MAXIMA_ENDPOINT_CONTENTFUL_PROXY",
+        "https://contentful-proxy.juno.ea.com/graphql"
+    );
+    pub static ref API_PROXY_NOVAFUSION_LICENSES: String = endpoint(
+        "MAXIMA_ENDPOINT_LICENSES",
+        "https://proxy.novafusion.ea.com/licenses"
+    );
+    pub static ref API_ECOMMERCE: String = endpoint(
+        "MAXIMA_ENDPOINT_ECOMMERCE",
+        "https://ecommerce2.dm.origin.com/ecommerce2"
+    );
+    pub static ref API_NUCLEUS_AUTH: String = endpoint(
+        "MAXIMA_ENDPOINT_NUCLEUS_AUTH",
+        "https://accounts.ea.com/connect/auth"
+    );
+    pub static ref API_NUCLEUS_TOKEN: String = endpoint(
+        "MAXIMA_ENDPOINT_NUCLEUS_TOKEN",
+        "https://accounts.ea.com/connect/token"
+    );
+    pub static ref API_NUCLEUS_TOKENINFO: String = endpoint(
+        "MAXIMA_ENDPOINT_NUCLEUS_TOKENINFO",
+        "https://accounts.ea.com/connect/tokeninfo"
+    );
+    pub static ref API_CLOUDSYNC: String =
+        endpoint("MAXIMA_ENDPOINT_CLOUDSYNC", "https://cloudsync.juno.ea.com");
+}
+
+/// The endpoint from `var` if it's set, otherwise the default
+fn endpoint(var: &str, default: &str) -> String {
+    match std::env::var(var) {
+        Ok(url) if !url.trim().is_empty() => {
+            let url = url.trim().trim_end_matches('/').to_owned();
+            info!("Using {} from {}", url, var);
+            url
+        }
+        _ => default.to_owned(),
+    }
+}
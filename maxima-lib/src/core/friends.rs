@@ -0,0 +1,154 @@
+use log::info;
+use thiserror::Error;
+
+use super::{
+    service_layer::{
+        ServiceFriendInvitation, ServiceFriendInvitationRequestBuilder, ServiceFriendInvitations,
+        ServiceGetMyFriendInvitationsRequestBuilder, ServiceLayerError, ServiceLayerGraphQLRequest,
+        ServiceMutationResult, SERVICE_REQUEST_ACCEPTFRIENDINVITATION,
+        SERVICE_REQUEST_CANCELFRIENDINVITATION, SERVICE_REQUEST_GETMYFRIENDINVITATIONS,
+        SERVICE_REQUEST_REJECTFRIENDINVITATION, SERVICE_REQUEST_SENDFRIENDINVITATION,
+    },
+    CachedData, Maxima,
+};
+
+#[derive(Error, Debug)]
+pub enum FriendRequestError {
+    #[error(transparent)]
+    ServiceLayer(#[from] ServiceLayerError),
+
+    #[error("`{0}` is already a friend")]
+    AlreadyFriends(String),
+    #[error("a friend request to `{0}` is already pending")]
+    AlreadyPending(String),
+    #[error("there's no pending friend request with `{0}`")]
+    NoRequest(String),
+    #[error("the friend request was refused")]
+    Refused,
+    #[error("a dummy user can't have friends")]
+    DummyUser,
+}
+
+/// What [`Maxima::send_friend_request`] ended up doing
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FriendRequestOutcome {
+    /// A request was sent, and is pending until they accept it
+    Sent,
+    /// They had already sent one, so theirs was accepted instead
+    Accepted,
+}
+
+impl Maxima {
+    /// Friend requests sent to and by the local user that haven't been answered yet
+    pub async fn friend_invitations(&self) -> Result<ServiceFriendInvitations, FriendRequestError> {
+        if self.dummy_local_user.is_some() {
+            return Err(FriendRequestError::DummyUser);
+        }
+
+        Ok(self
+            .service_layer
+            .request(
+                SERVICE_REQUEST_GETMYFRIENDINVITATIONS,
+                ServiceGetMyFriendInvitationsRequestBuilder::default()
+                    .is_mutual_friends_enabled(false)
+                    .build()
+                    .unwrap(),
+            )
+            .await?)
+    }
+
+    /// Ask `user_id` to be friends. If they've already asked the local user, their request
+    /// is accepted rather than sending another one.
+    pub async fn send_friend_request(
+        &self,
+        user_id: &str,
+    ) -> Result<FriendRequestOutcome, FriendRequestError> {
+        if self
+            .all_friends()
+            .await?
+            .friends()
+            .iter()
+            .any(|x| x.pd() == user_id)
+        {
+            return Err(FriendRequestError::AlreadyFriends(user_id.to_owned()));
+        }
+
+        let invitations = self.friend_invitations().await?;
+        let has_request = |page: &[ServiceFriendInvitation]| page.iter().any(|x| x.pd() == user_id);
+
+        if has_request(invitations.outbound_friend_invitations().items()) {
+            return Err(FriendRequestError::AlreadyPending(user_id.to_owned()));
+        }
+
+        if has_request(invitations.inbound_friend_invitations().items()) {
+            self.accept_friend_request(user_id).await?;
+            return Ok(FriendRequestOutcome::Accepted);
+        }
+
+        if !self
+            .friend_invitation_mutation(SERVICE_REQUEST_SENDFRIENDINVITATION, user_id)
+            .await?
+        {
+            return Err(FriendRequestError::Refused);
+        }
+
+        info!("Sent a friend request to {}", user_id);
+        Ok(FriendRequestOutcome::Sent)
+    }
+
+    /// Accept a friend request `user_id` sent to the local user
+    pub async fn accept_friend_request(&self, user_id: &str) -> Result<(), FriendRequestError> {
+        self.answer_friend_request(SERVICE_REQUEST_ACCEPTFRIENDINVITATION, user_id)
+            .await
+    }
+
+    /// Turn down a friend request `user_id` sent to the local user
+    pub async fn reject_friend_request(&self, user_id: &str) -> Result<(), FriendRequestError> {
+        self.answer_friend_request(SERVICE_REQUEST_REJECTFRIENDINVITATION, user_id)
+            .await
+    }
+
+    /// Take back a friend request the local user sent to `user_id`
+    pub async fn cancel_friend_request(&self, user_id: &str) -> Result<(), FriendRequestError> {
+        self.answer_friend_request(SERVICE_REQUEST_CANCELFRIENDINVITATION, user_id)
+            .await
+    }
+
+    /// Accepting, rejecting or cancelling only fails when there's nothing to answer
+    async fn answer_friend_request(
+        &self,
+        operation: &ServiceLayerGraphQLRequest,
+        user_id: &str,
+    ) -> Result<(), FriendRequestError> {
+        match self.friend_invitation_mutation(operation, user_id).await? {
+            true => Ok(()),
+            false => Err(FriendRequestError::NoRequest(user_id.to_owned())),
+        }
+    }
+
+    /// Send one of the friend invitation mutations, then drop the cached friend list
+    /// since it may have changed. Returns whether the service went through with it.
+    async fn friend_invitation_mutation(
+        &self,
+        operation: &ServiceLayerGraphQLRequest,
+        user_id: &str,
+    ) -> Result<bool, FriendRequestError> {
+        if self.dummy_local_user.is_some() {
+            return Err(FriendRequestError::DummyUser);
+        }
+
+        let result: ServiceMutationResult = self
+            .service_layer
+            .request(
+                operation,
+                ServiceFriendInvitationRequestBuilder::default()
+                    .friend_pd(user_id.to_owned())
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+        self.invalidate(CachedData::Friends);
+
+        Ok(*result.success())
+    }
+}
@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+
+use derive_getters::Getters;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    library::OwnedOffer,
+    manifest::{self, MANIFEST_RELATIVE_PATH},
+};
+#[cfg(unix)]
+use crate::unix::fs::case_insensitive_path;
+
+/// Folders the EA app and Origin install to unless told otherwise
+#[cfg(windows)]
+const DEFAULT_EA_FOLDERS: [&str; 2] = [
+    "C:\\Program Files\\EA Games",
+    "C:\\Program Files (x86)\\Origin Games",
+];
+
+/// Why a found install can't be used as it is
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InstallProblem {
+    /// The folder is there, but not the manifest every finished install has
+    NoManifest,
+    UnreadableManifest,
+    /// The manifest is there, but not the game's executable, like after an interrupted download
+    MissingExecutable(PathBuf),
+}
+
+/// An owned game found on disk by [`super::Maxima::scan_installed_games`]
+#[derive(Clone, Debug, Getters, Serialize, Deserialize)]
+pub struct FoundInstall {
+    slug: String,
+    offer_id: String,
+    path: PathBuf,
+    /// Set when the install is broken, so it needs repairing rather than locating
+    problem: Option<InstallProblem>,
+}
+
+impl FoundInstall {
+    pub fn is_complete(&self) -> bool {
+        self.problem.is_none()
+    }
+}
+
+/// Where `offer` may have been installed by something other than Maxima
+async fn candidate_paths(offer: &OwnedOffer, folders: &[PathBuf]) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(path) = offer.existing_install().await {
+        paths.push(path);
+    }
+
+    #[cfg(windows)]
+    let defaults = DEFAULT_EA_FOLDERS
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    #[cfg(not(windows))]
+    let defaults: Vec<PathBuf> = Vec::new();
+
+    for folder in folders.iter().chain(defaults.iter()) {
+        for name in [offer.offer().display_name(), offer.slug()] {
+            let path = folder.join(name);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+
+    paths
+}
+
+/// Look for `offer` at its candidate paths. An install of a different game is skipped,
+/// a broken one of this game is returned with what's wrong with it.
+pub(crate) async fn find_install(offer: &OwnedOffer, folders: &[PathBuf]) -> Option<FoundInstall> {
+    let found = |path: PathBuf, problem| FoundInstall {
+        slug: offer.slug().to_owned(),
+        offer_id: offer.offer_id().to_owned(),
+        path,
+        problem,
+    };
+
+    let mut broken = None;
+    for path in candidate_paths(offer, folders).await {
+        if !path.is_dir() {
+            continue;
+        }
+
+        let manifest_path = path.join(MANIFEST_RELATIVE_PATH);
+        if !manifest_path.exists() {
+            broken.get_or_insert_with(|| found(path, Some(InstallProblem::NoManifest)));
+            continue;
+        }
+
+        let manifest = match manifest::read(manifest_path).await {
+            Ok(manifest) => manifest,
+            Err(_) => {
+                broken.get_or_insert_with(|| found(path, Some(InstallProblem::UnreadableManifest)));
+                continue;
+            }
+        };
+
+        if !manifest.is_for(offer.offer().content_id()) {
+            continue;
+        }
+
+        // Paths look like [HKEY_LOCAL_MACHINE\SOFTWARE\...\Install Dir]Game.exe, where
+        // the key holds the install folder
+        let executable = manifest
+            .execute_path(offer.is_trial())
+            .map(|x| x.rsplit(']').next().unwrap_or_default().replace('\\', "/"))
+            .or_else(|| offer.offer().execute_path())
+            .map(|x| path.join(x));
+        if let Some(executable) = executable {
+            #[cfg(unix)]
+            let executable = case_insensitive_path(executable);
+            if !executable.exists() {
+                return Some(found(
+                    path,
+                    Some(InstallProblem::MissingExecutable(executable)),
+                ));
+            }
+        }
+
+        return Some(found(path, None));
+    }
+
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{core::library::tests::owned_offer_with_cloud_save, util::temp_dir::TempDir};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn folder_without_manifest_needs_repair() {
+        let root = TempDir::new("scan");
+        std::fs::create_dir_all(root.join("scan-test")).unwrap();
+        let offer = owned_offer_with_cloud_save("scan-test", "Origin.OFR.50.1", None);
+
+        let found = find_install(&offer, &[root.path().to_owned()]).await;
+        let missing = find_install(&offer, &[root.join("elsewhere")]).await;
+
+        let found = found.unwrap();
+        assert_eq!(found.path(), &root.join("scan-test"));
+        assert_eq!(found.problem(), &Some(InstallProblem::NoManifest));
+        assert!(missing.is_none());
+    }
+}
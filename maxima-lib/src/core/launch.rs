@@ -1,7 +1,14 @@
 use base64::{engine::general_purpose, Engine};
 use derive_getters::Getters;
-use log::{error, info};
-use std::{env, fmt::Display, path::PathBuf, sync::Arc};
+use log::{error, info, warn};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Display,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 use tokio::{
     process::{Child, Command},
     sync::Mutex,
@@ -16,12 +23,18 @@ use crate::{
             storage::{AuthError, TokenError},
         },
         clients::JUNO_PC_CLIENT_ID,
-        cloudsync::{CloudSyncError, CloudSyncLockMode},
-        library::{LibraryError, OwnedOffer},
+        cloudsync::{CloudSyncError, CloudSyncFilter, CloudSyncLockMode},
+        concurrency::CancellationToken,
+        library::{
+            AccessEnded, InstallState, LibraryError, OwnedOffer, PlatformSupport, Unavailable,
+        },
         service_layer::ServiceLayerError,
-        Maxima,
+        LockedMaxima, Maxima,
+    },
+    ooa::{
+        needs_license_update, request_and_save_license, LicenseAuth, LicenseError,
+        LicenseRequestToken,
     },
-    ooa::{needs_license_update, request_and_save_license, LicenseAuth, LicenseError},
     util::{
         native::{NativeError, SafeParent, SafeStr},
         registry::bootstrap_path,
@@ -33,7 +46,7 @@ use thiserror::Error;
 #[cfg(unix)]
 use crate::{
     core::manifest::{self, MANIFEST_RELATIVE_PATH},
-    gameinfo::load_game_info_from_json,
+    gameinfo::{load_game_info_from_json, stats::record_played},
     unix::fs::case_insensitive_path,
 };
 
@@ -69,12 +82,34 @@ pub enum LaunchError {
     GamePath,
     #[error("`{0}` is not installed")]
     NotInstalled(String),
+    #[error("`{0}` didn't finish installing, resume the install before playing")]
+    PartialInstall(String),
+    #[error("your access to `{0}` has ended: {1}")]
+    AccessEnded(String, AccessEnded),
+    #[error("`{0}` can't be played: {1}")]
+    Unavailable(String, Unavailable),
     #[error("bootstrap was not found! Please re-install maxima")]
     BootstrapMissing,
     #[error(
         "content ID (`{0}`) was specified as an offer ID when launching in OnlineOffline mode"
     )]
     ContentIdAsOfferId(String),
+    #[error("failed to open Battlelog in the browser: {0}")]
+    Battlelog(std::io::Error),
+    #[error("`{0}` isn't playing anything")]
+    FriendNotPlaying(String),
+    #[error("`{0}` is in a session that can't be joined")]
+    NotJoinable(String),
+    #[error("the game `{0}` is playing isn't owned by this account")]
+    JoinNotOwned(String),
+    #[error("`{0}` has to be installed to join the session")]
+    JoinNotInstalled(String),
+    #[error("`{0}` is still installing, wait for it to finish before playing")]
+    StillInstalling(String),
+    #[error("`{0}` can't be used as an environment variable override")]
+    InvalidEnvOverride(String),
+    #[error("the working directory `{0}` doesn't exist")]
+    InvalidWorkingDir(PathBuf),
 }
 
 pub enum StartupStage {
@@ -91,6 +126,102 @@ pub struct LaunchOptions {
     pub path_override: Option<String>,
     pub arguments: Vec<String>,
     pub cloud_saves: bool,
+    /// The user's additions to EA's list of save files
+    pub cloud_sync_filter: CloudSyncFilter,
+    /// Stops the cloud save download before the game starts. The game is launched anyway.
+    pub cancel: CancellationToken,
+    /// The friend whose session the game should join
+    pub join: Option<JoinTarget>,
+    /// Extra environment variables for the game. They're set after Maxima's own, so they
+    /// win over its defaults, including the wine ones the bootstrap sets on Linux. The
+    /// variables the launch is handed to the game with can't be overridden, see
+    /// [`check_env_override`].
+    pub env: HashMap<String, String>,
+    /// Sent with the license request, for games that need the handshake before they start.
+    /// A license is always requested when this is set, since a saved one won't have a game
+    /// token for it.
+    pub license_request: Option<LicenseRequestToken>,
+    /// Folder the game runs in, for games that only start from their install root or some
+    /// other folder. Relative paths are taken from the executable's folder, which is used
+    /// when this isn't set.
+    pub working_dir: Option<PathBuf>,
+}
+
+/// Variables Maxima hands the launch to the game with, on top of everything starting with `EA`
+const RESERVED_ENV_VARS: [&str; 5] = [
+    "MXLaunchId",
+    "OriginSessionKey",
+    "ContentId",
+    "KYBER_INTERFACE_PORT",
+    "MAXIMA_LAUNCH_ARGS",
+];
+
+/// Variables holding tokens or passwords, left out of a [`LaunchPlan`]
+const SECRET_ENV_VARS: [&str; 4] = [
+    "EAGenericAuthToken",
+    "EAAccessTokenJWS",
+    "EALaunchUserAuthToken",
+    "EALaunchOOAUserPass",
+];
+
+/// What a launch would run, worked out by [`dry_run_game`] without running it
+#[derive(Clone, Debug, Getters, Serialize)]
+pub struct LaunchPlan {
+    /// The game's executable
+    path: String,
+    args: Vec<String>,
+    /// Every variable the game gets, tokens and passwords blanked out
+    env: BTreeMap<String, String>,
+    working_dir: PathBuf,
+    /// Whether a new license would be requested, rather than the saved one being used
+    requests_license: bool,
+    /// The Battlelog page opened instead of the game, when Battlelog hasn't handed over
+    /// a server to join yet. Nothing else is run then.
+    battlelog: Option<String>,
+    /// Port the game is told to reach Maxima's LSX server on
+    lsx_port: u16,
+    /// Whether anything was listening on `lsx_port`. Without the LSX server, games
+    /// either fail to start or close right away.
+    lsx_listening: bool,
+}
+
+/// Where the executable at `path` runs from: `working_dir` taken from the executable's
+/// folder if it's set, otherwise that folder itself
+fn resolve_working_dir(path: &Path, working_dir: Option<&Path>) -> Result<PathBuf, LaunchError> {
+    let exe_dir = path.safe_parent()?;
+    let dir = match working_dir {
+        Some(dir) => exe_dir.join(dir),
+        None => exe_dir.to_path_buf(),
+    };
+
+    if !dir.is_dir() {
+        return Err(LaunchError::InvalidWorkingDir(dir));
+    }
+
+    Ok(dir)
+}
+
+/// Whether `key` can be given in [`LaunchOptions::env`]. It has to be a plain variable
+/// name, made of ASCII letters, digits and underscores, and not one Maxima sets for the launch.
+pub fn check_env_override(key: &str) -> Result<(), LaunchError> {
+    let valid_name = key.chars().next().is_some_and(|x| !x.is_ascii_digit())
+        && key.chars().all(|x| x.is_ascii_alphanumeric() || x == '_');
+    let reserved = key.starts_with("EA") || RESERVED_ENV_VARS.contains(&key);
+
+    if !valid_name || reserved {
+        return Err(LaunchError::InvalidEnvOverride(key.to_owned()));
+    }
+
+    Ok(())
+}
+
+/// A friend's session, taken from their rich presence. Handed to the game over LSX
+/// when it asks for the friend's presence.
+#[derive(Clone, Debug, Getters)]
+pub struct JoinTarget {
+    user_id: String,
+    session: String,
+    multiplayer_id: Option<String>,
 }
 
 pub enum LaunchMode {
@@ -123,6 +254,8 @@ pub struct ActiveGameContext {
     mode: LaunchMode,
     injections: Vec<LibraryInjection>,
     cloud_saves: bool,
+    cloud_sync_filter: CloudSyncFilter,
+    join: Option<JoinTarget>,
     process: Child,
     started: bool,
 }
@@ -132,6 +265,8 @@ impl ActiveGameContext {
         launch_id: &str,
         game_path: &str,
         cloud_saves: bool,
+        cloud_sync_filter: CloudSyncFilter,
+        join: Option<JoinTarget>,
         content_id: &str,
         offer: Option<OwnedOffer>,
         mode: LaunchMode,
@@ -147,6 +282,8 @@ impl ActiveGameContext {
             mode,
             injections: Vec::new(),
             cloud_saves,
+            cloud_sync_filter,
+            join,
             process,
             started: false,
         }
@@ -161,11 +298,35 @@ impl ActiveGameContext {
     }
 }
 
+/// Games that are started from their Battlelog page, which hands the launch back to us
+/// through an `origin2://` link with the arguments for the server to join
+const BATTLELOG_GAMES: [(&str, &str); 2] = [
+    ("battlefield-3", "https://battlelog.battlefield.com/bf3/"),
+    ("battlefield-4", "https://battlelog.battlefield.com/bf4/"),
+];
+
+/// Interface port given to games launched from Battlelog
+pub const BATTLELOG_INTERFACE_PORT: u16 = 3005;
+
+/// Battlelog page for games that have to be launched from there
+pub fn battlelog_url(slug: &str) -> Option<&'static str> {
+    BATTLELOG_GAMES
+        .iter()
+        .find(|(game, _)| *game == slug)
+        .map(|(_, url)| *url)
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct BootstrapLaunchArgs {
     pub path: String,
     pub args: Vec<String>,
     pub slug: String,
+    /// Set on the game's process last, see [`LaunchOptions::env`]
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    /// Folder to run the game in, see [`LaunchOptions::working_dir`]
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
 }
 
 impl Display for LaunchMode {
@@ -183,9 +344,39 @@ pub async fn start_game(
     mode: LaunchMode,
     options: LaunchOptions,
 ) -> Result<(), LaunchError> {
+    launch(maxima_arc, mode, options, false).await.map(|_| ())
+}
+
+/// Go through a launch without starting the game, requesting a license or syncing saves,
+/// and report what would be run. For finding out why a game won't start.
+pub async fn dry_run_game(
+    maxima_arc: Arc<Mutex<Maxima>>,
+    mode: LaunchMode,
+    options: LaunchOptions,
+) -> Result<LaunchPlan, LaunchError> {
+    launch(maxima_arc, mode, options, true).await
+}
+
+async fn launch(
+    maxima_arc: Arc<Mutex<Maxima>>,
+    mode: LaunchMode,
+    options: LaunchOptions,
+    dry_run: bool,
+) -> Result<LaunchPlan, LaunchError> {
     let mut maxima = maxima_arc.lock().await;
     info!("Initiating game launch with {}...", mode);
 
+    for key in options.env.keys() {
+        check_env_override(key)?;
+    }
+
+    // Checked under the lock, so an install can't be queued between this and the launch
+    if let LaunchMode::Online(ref offer_id) = mode {
+        if maxima.content_manager().is_installing(offer_id) {
+            return Err(LaunchError::StillInstalling(offer_id.clone()));
+        }
+    }
+
     if let LaunchMode::OnlineOffline(ref content_id, _, _) = mode {
         if options.path_override.is_none() {
             return Err(LaunchError::GamePathOffline);
@@ -199,23 +390,57 @@ pub async fn start_game(
     let (content_id, online_offline, offer, access_token) =
         if let LaunchMode::Online(ref offer_id) = mode {
             let access_token = &maxima.access_token().await?;
-            let offer = match maxima.mut_library().game_by_base_offer(offer_id).await? {
-                Some(offer) => offer,
-                None => return Err(LaunchError::NoOfferFound(offer_id.clone())),
-            };
+            let offer = maxima
+                .mut_library()
+                .game_by_base_offer(offer_id)
+                .await?
+                .clone();
+
+            if let Some(reason) = offer.access_ended() {
+                return Err(LaunchError::AccessEnded(
+                    offer.offer().display_name().to_owned(),
+                    reason,
+                ));
+            }
 
-            if !offer.is_installed().await {
-                return Err(LaunchError::NotInstalled(offer.offer_id().clone()));
+            if let Some(reason) = offer.unavailable().filter(|x| x.blocks_launch()) {
+                return Err(LaunchError::Unavailable(
+                    offer.offer().display_name().to_owned(),
+                    reason,
+                ));
+            }
+
+            match offer.install_state(maxima.content_manager()).await {
+                InstallState::Installed => {}
+                InstallState::Partial { .. } => {
+                    return Err(LaunchError::PartialInstall(offer.offer_id().clone()))
+                }
+                InstallState::External(_) | InstallState::NotInstalled => {
+                    return Err(LaunchError::NotInstalled(offer.offer_id().clone()))
+                }
+            }
+
+            if offer.platform_support() == PlatformSupport::Unsupported {
+                let platforms: Vec<&str> = offer.platforms().iter().map(|x| x.label()).collect();
+                warn!(
+                    "{} is made for {}, not {}, and may not run",
+                    offer.offer().display_name(),
+                    platforms.join(" and "),
+                    env::consts::OS
+                );
+            }
+
+            if let Some(storefront) = offer.ownership_method().and_then(|x| x.storefront()) {
+                warn!(
+                    "{} is owned through {:?}, which may need to be running for it to launch",
+                    offer.offer().display_name(),
+                    storefront
+                );
             }
 
             let content_id = offer.offer().content_id().to_owned();
 
-            (
-                content_id,
-                false,
-                Some(offer.clone()),
-                access_token.to_owned(),
-            )
+            (content_id, false, Some(offer), access_token.to_owned())
         } else if let LaunchMode::OnlineOffline(ref content_id, _, _) = mode {
             (content_id.to_owned(), true, None, String::new())
         } else {
@@ -227,7 +452,7 @@ pub async fn start_game(
         PathBuf::from(&game_path_override)
     } else if !online_offline {
         match offer {
-            Some(ref offer) => offer.execute_path(false).await?.clone(),
+            Some(ref offer) => offer.execute_path(offer.is_trial()).await?.clone(),
             None => return Err(LaunchError::NoOfferFound("Unknown".to_string())),
         }
     } else {
@@ -237,8 +462,10 @@ pub async fn start_game(
     let dir = path.safe_parent()?.safe_str()?;
     #[cfg(unix)]
     let path = case_insensitive_path(path.clone());
+    let working_dir = resolve_working_dir(&path, options.working_dir.as_deref())?;
     let path = path.safe_str()?;
     info!("Game path: {}", path);
+    info!("Working directory: {}", working_dir.display());
 
     let slug = if let LaunchMode::Online(ref _offer_id) = mode {
         offer.as_ref().map(|o| o.slug().to_owned())
@@ -246,16 +473,56 @@ pub async fn start_game(
         None
     };
 
+    // Launch arguments handed over by the bootstrap, e.g. from Battlelog
+    let handoff_args = env::var("MAXIMA_LAUNCH_ARGS").ok();
+
+    let lsx_port = maxima.lsx_port;
+    // Binding only fails if the LSX server, or something else, already has the port
+    let lsx_listening = dry_run && TcpListener::bind(("127.0.0.1", lsx_port)).is_err();
+    let plan = |args, env, requests_license, battlelog: Option<&str>| LaunchPlan {
+        path: path.to_owned(),
+        args,
+        env,
+        working_dir: working_dir.clone(),
+        requests_license,
+        battlelog: battlelog.map(str::to_owned),
+        lsx_port,
+        lsx_listening,
+    };
+
+    let battlelog = slug.as_deref().and_then(battlelog_url);
+    if let (Some(url), None) = (battlelog, &handoff_args) {
+        // Without the arguments from Battlelog, the game has nothing to do. Send the
+        // user there instead, and pick the launch back up when they click play.
+        if !dry_run {
+            info!("Opening Battlelog at {}", url);
+            open::that(url).map_err(LaunchError::Battlelog)?;
+        }
+        return Ok(plan(Vec::new(), BTreeMap::new(), false, Some(url)));
+    }
+
     #[cfg(unix)]
-    mx_linux_setup(slug.as_deref()).await?;
+    if !dry_run {
+        mx_linux_setup(slug.as_deref()).await?;
+    }
+
+    let requests_license = match mode {
+        LaunchMode::Offline(_) => false,
+        _ => {
+            options.license_request.is_some()
+                || needs_license_update(&content_id, slug.as_deref()).await?
+        }
+    };
 
     match mode {
         LaunchMode::Offline(_) => {}
+        _ if dry_run => {}
         LaunchMode::Online(_) => {
             let auth = LicenseAuth::AccessToken(maxima.access_token().await?);
 
             let offer = offer.as_ref().unwrap();
-            if needs_license_update(&content_id, slug.as_deref()).await? {
+            let request_token = options.license_request.as_ref();
+            if requests_license {
                 info!(
                     "Requesting new game license for {}...",
                     offer.offer().display_name()
@@ -266,44 +533,42 @@ pub async fn start_game(
                     &content_id,
                     path.to_owned().into(),
                     slug.as_deref(),
+                    request_token,
                 )
                 .await?;
             } else {
                 info!("Existing game license is still valid, not updating");
             }
 
-            if options.cloud_saves && offer.offer().has_cloud_save() {
-                info!("Syncing with cloud save...");
-
-                let result = maxima
-                    .cloud_sync()
-                    .obtain_lock(offer, CloudSyncLockMode::Read)
-                    .await;
-                if let Err(err) = result {
-                    error!("Failed to obtain CloudSync read lock: {}", err);
-                } else {
-                    let lock = result?;
-
-                    let result = lock.sync_files().await;
-                    if let Err(err) = result {
-                        error!("Failed to sync cloud save: {}", err);
-                    } else {
-                        info!("Cloud save synced");
-                    }
-
-                    lock.release().await?;
-                }
+            // Saves still uploading from the last game that was played would be read stale
+            maxima.finish_saves_upload().await;
+            let result = maxima
+                .cloud_sync()
+                .sync_if_enabled(
+                    options.cloud_saves,
+                    offer,
+                    CloudSyncLockMode::Read,
+                    &options.cloud_sync_filter,
+                    &options.cancel,
+                )
+                .await;
+            match result {
+                Ok(true) => info!("Cloud save synced"),
+                Ok(false) => {}
+                Err(err) => error!("Cloud saves weren't synced: {}", err),
             }
         }
         LaunchMode::OnlineOffline(_, ref persona, ref password) => {
             let auth = LicenseAuth::Direct(persona.to_owned(), password.to_owned());
 
-            if needs_license_update(&content_id, slug.as_deref()).await? {
+            let request_token = options.license_request.as_ref();
+            if requests_license {
                 request_and_save_license(
                     &auth,
                     &content_id,
                     path.to_owned().into(),
                     slug.as_deref(),
+                    request_token,
                 )
                 .await?;
             } else {
@@ -315,7 +580,7 @@ pub async fn start_game(
     let mut game_args = options.arguments.clone();
 
     // Append args from env
-    if let Ok(args) = env::var("MAXIMA_LAUNCH_ARGS") {
+    if let Some(args) = handoff_args {
         game_args.append(&mut parse_arguments(args.as_str()));
     }
 
@@ -323,79 +588,117 @@ pub async fn start_game(
         return Err(LaunchError::BootstrapMissing);
     }
 
-    let mut child = Command::new(bootstrap_path()?);
-    child.arg("launch");
-
-    let bootstrap_args = BootstrapLaunchArgs {
-        path: path.to_string(),
-        args: game_args,
-        slug: slug.clone().unwrap_or_default(),
-    };
-
-    let b64 = general_purpose::STANDARD.encode(serde_json::to_string(&bootstrap_args)?);
-    child.arg(b64);
-
     let user = maxima.local_user().await?;
     let launch_id = Uuid::new_v4().to_string();
 
-    child
-        .current_dir(PathBuf::from(path).safe_parent()?)
-        .env("MXLaunchId", launch_id.to_owned())
-        .env("EAAuthCode", "unavailable")
-        .env("EAEgsProxyIpcPort", "0")
-        .env("EAEntitlementSource", "EA")
-        .env("EAExternalSource", "EA")
-        .env("EAFreeTrialGame", "false")
-        .env("EAGameLocale", maxima.locale.full_str())
-        .env("EAGenericAuthToken", access_token.to_owned())
-        .env("EALaunchCode", "unavailable")
-        .env("EALaunchOwner", "EA")
-        .env(
+    let mut launch_env = vec![
+        ("MXLaunchId", launch_id.to_owned()),
+        ("EAAuthCode", "unavailable".to_owned()),
+        ("EAEgsProxyIpcPort", "0".to_owned()),
+        ("EAEntitlementSource", "EA".to_owned()),
+        ("EAExternalSource", "EA".to_owned()),
+        (
+            "EAFreeTrialGame",
+            offer.as_ref().is_some_and(|x| x.is_trial()).to_string(),
+        ),
+        ("EAGameLocale", maxima.locale.full_str().to_owned()),
+        ("EAGenericAuthToken", access_token.to_owned()),
+        ("EALaunchCode", "unavailable".to_owned()),
+        ("EALaunchOwner", "EA".to_owned()),
+        (
             "EALaunchEAID",
             user.player()
                 .as_ref()
                 .ok_or(ServiceLayerError::MissingField)?
-                .display_name(),
-        )
-        .env("EALaunchEnv", "production")
-        .env("EALaunchOfflineMode", "false")
-        .env("EALsxPort", maxima.lsx_port.to_string())
-        .env(
+                .display_name()
+                .to_owned(),
+        ),
+        ("EALaunchEnv", "production".to_owned()),
+        ("EALaunchOfflineMode", "false".to_owned()),
+        ("EALsxPort", lsx_port.to_string()),
+        (
             "EARtPLaunchCode",
             simple_crypto::rtp_handshake().to_string(),
-        )
-        .env("EASecureLaunchTokenTemp", user.id())
-        .env("EASteamProxyIpcPort", "0")
-        .env("OriginSessionKey", launch_id.clone())
-        .env("ContentId", content_id.clone())
-        .env("EAOnErrorExitRetCode", "1");
+        ),
+        ("EASecureLaunchTokenTemp", user.id().to_owned()),
+        ("EASteamProxyIpcPort", "0".to_owned()),
+        ("OriginSessionKey", launch_id.clone()),
+        ("ContentId", content_id.clone()),
+        ("EAOnErrorExitRetCode", "1".to_owned()),
+    ];
+
+    if battlelog.is_some() {
+        launch_env.push(("KYBER_INTERFACE_PORT", BATTLELOG_INTERFACE_PORT.to_string()));
+    }
 
     match mode {
         LaunchMode::Offline(_) => todo!(),
         LaunchMode::Online(ref offer_id) => {
-            let short_token = request_opaque_ooa_token(&access_token).await?;
+            // Left out of the plan anyway, so not worth a request
+            let short_token = if dry_run {
+                String::new()
+            } else {
+                request_opaque_ooa_token(&access_token).await?
+            };
 
-            child
-                .env("EAConnectionId", offer_id.clone())
-                .env("EALicenseToken", offer_id.clone())
-                .env("EALaunchUserAuthToken", short_token)
-                .env("EAAccessTokenJWS", access_token);
+            launch_env.push(("EAConnectionId", offer_id.clone()));
+            launch_env.push(("EALicenseToken", offer_id.clone()));
+            launch_env.push(("EALaunchUserAuthToken", short_token));
+            launch_env.push(("EAAccessTokenJWS", access_token.clone()));
         }
         LaunchMode::OnlineOffline(_, ref persona, ref password) => {
-            child
-                .env("EALaunchOOAUserEmail", persona)
-                .env("EALaunchOOAUserPass", password)
-                // Given this is probably running headlessly, don't show a UI on error
-                .env("EAOnErrorExitRetCode", "1");
+            launch_env.push(("EALaunchOOAUserEmail", persona.clone()));
+            launch_env.push(("EALaunchOOAUserPass", password.clone()));
+            // Given this is probably running headlessly, don't show a UI on error
+            launch_env.push(("EAOnErrorExitRetCode", "1".to_owned()));
         }
     };
 
+    // The game gets the bootstrap's environment, then the overrides on top
+    let mut env: BTreeMap<String, String> = launch_env
+        .iter()
+        .map(|(key, value)| match SECRET_ENV_VARS.contains(key) {
+            true => (key.to_string(), "<hidden>".to_owned()),
+            false => (key.to_string(), value.clone()),
+        })
+        .collect();
+    env.extend(options.env.clone());
+
+    if dry_run {
+        return Ok(plan(game_args, env, requests_license, None));
+    }
+
+    let mut child = Command::new(bootstrap_path()?);
+    child.arg("launch");
+
+    let bootstrap_args = BootstrapLaunchArgs {
+        path: path.to_string(),
+        args: game_args.clone(),
+        slug: slug.clone().unwrap_or_default(),
+        env: options.env,
+        working_dir: Some(working_dir.clone()),
+    };
+
+    let b64 = general_purpose::STANDARD.encode(serde_json::to_string(&bootstrap_args)?);
+    child.arg(b64);
+    child.current_dir(&working_dir).envs(launch_env);
+
     let child = child.spawn().expect("Failed to start child");
 
+    // Offline launches of owned games count too, unlike the slug handed to the bootstrap
+    if let Some(offer) = &offer {
+        let slug = offer.slug();
+        if let Err(err) = record_played(slug) {
+            warn!("Failed to record when {} was played: {}", slug, err);
+        }
+    }
+
     maxima.playing = Some(ActiveGameContext::new(
         &launch_id,
         dir,
         options.cloud_saves,
+        options.cloud_sync_filter,
+        options.join,
         &content_id,
         offer,
         mode,
@@ -403,7 +706,67 @@ pub async fn start_game(
         slug,
     ));
 
-    Ok(())
+    Ok(plan(game_args, env, requests_license, None))
+}
+
+impl Maxima {
+    /// Launches the game a friend is playing, joining their session. Their rich presence
+    /// has to say the session is open to anyone.
+    pub async fn join_friend(
+        maxima_arc: LockedMaxima,
+        user_id: &str,
+        options: LaunchOptions,
+    ) -> Result<(), LaunchError> {
+        let (offer_id, join) = {
+            let mut maxima = maxima_arc.lock().await;
+
+            let presence = maxima.rtm().presence_store().lock().await.get(user_id);
+            let activity = match presence.as_ref().and_then(|x| x.activity().as_ref()) {
+                Some(activity) => activity.clone(),
+                None => return Err(LaunchError::FriendNotPlaying(user_id.to_owned())),
+            };
+
+            let session = match activity.session() {
+                Some(session) if activity.can_join() => session.to_owned(),
+                _ => return Err(LaunchError::NotJoinable(user_id.to_owned())),
+            };
+
+            let offer_id = activity.product_id().to_owned();
+            let offer = match maxima.mut_library().game_by_base_offer(&offer_id).await {
+                Ok(offer) => offer.clone(),
+                Err(LibraryError::NotOwned(_)) => {
+                    return Err(LaunchError::JoinNotOwned(
+                        activity.title().clone().unwrap_or(offer_id),
+                    ))
+                }
+                Err(err) => return Err(err.into()),
+            };
+            if !offer.is_installed().await {
+                return Err(LaunchError::JoinNotInstalled(
+                    offer.offer().display_name().to_owned(),
+                ));
+            }
+
+            info!("Joining {}'s session in {}", user_id, offer_id);
+            let join = JoinTarget {
+                user_id: user_id.to_owned(),
+                session,
+                multiplayer_id: activity.multiplayer_id().clone(),
+            };
+
+            (offer_id, join)
+        };
+
+        start_game(
+            maxima_arc,
+            LaunchMode::Online(offer_id),
+            LaunchOptions {
+                join: Some(join),
+                ..options
+            },
+        )
+        .await
+    }
 }
 
 async fn request_opaque_ooa_token(access_token: &str) -> Result<String, AuthError> {
@@ -489,3 +852,100 @@ pub fn parse_arguments(input: &str) -> Vec<String> {
 
     args
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        content::manager::{
+            tests::{queued_game, write_queue_file},
+            ContentManager,
+        },
+        core::{service_layer::MockServiceLayer, MaximaOptionsBuilder},
+        util::temp_dir::{TempDataDir, TempDir},
+    };
+
+    use super::*;
+
+    fn options() -> LaunchOptions {
+        LaunchOptions {
+            path_override: None,
+            arguments: Vec::new(),
+            cloud_saves: false,
+            cloud_sync_filter: CloudSyncFilter::default(),
+            cancel: CancellationToken::new(),
+            join: None,
+            env: HashMap::new(),
+            license_request: None,
+            working_dir: None,
+        }
+    }
+
+    #[test]
+    fn working_dir_is_relative_to_executable() {
+        let root = TempDir::new("cwd");
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        let exe = root.join("bin").join("game.exe");
+
+        let default = resolve_working_dir(&exe, None);
+        let parent = resolve_working_dir(&exe, Some(Path::new("..")));
+        let missing = resolve_working_dir(&exe, Some(Path::new("missing")));
+
+        assert_eq!(default.unwrap(), root.join("bin"));
+        assert_eq!(parent.unwrap(), root.join("bin").join(".."));
+        assert!(matches!(missing, Err(LaunchError::InvalidWorkingDir(_))));
+    }
+
+    #[tokio::test]
+    async fn launch_waits_for_install() {
+        let dir = TempDataDir::new();
+        let service_layer = Arc::new(MockServiceLayer::new());
+        let maxima_arc = Maxima::new_with_options(
+            MaximaOptionsBuilder::default()
+                .load_auth_storage(false)
+                .dummy_local_user(true)
+                .service_layer(service_layer.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let offer_id = "Origin.OFR.50.672";
+        let queue_file = dir.join("download_queue.json");
+        write_queue_file(
+            &queue_file,
+            vec![queued_game(offer_id, "install-race", dir.path())],
+            Vec::new(),
+        );
+
+        maxima_arc.lock().await.content_manager =
+            ContentManager::with_queue_file(service_layer.clone(), &queue_file)
+                .await
+                .unwrap();
+        let installing = start_game(
+            maxima_arc.clone(),
+            LaunchMode::Online(offer_id.to_owned()),
+            options(),
+        )
+        .await;
+
+        // Once the install is gone, the launch goes on, up to needing a dummy user's token
+        std::fs::remove_file(&queue_file).unwrap();
+        maxima_arc.lock().await.content_manager =
+            ContentManager::with_queue_file(service_layer, &queue_file)
+                .await
+                .unwrap();
+        let installed = start_game(
+            maxima_arc,
+            LaunchMode::Online(offer_id.to_owned()),
+            options(),
+        )
+        .await;
+
+        assert!(matches!(installing, Err(LaunchError::StillInstalling(id)) if id == offer_id));
+        assert!(matches!(
+            installed,
+            Err(LaunchError::Token(TokenError::Absent))
+        ));
+    }
+}
@@ -1,24 +1,38 @@
 use super::{
-    auth::storage::LockedAuthStorage,
+    auth::storage::{AuthError, LockedAuthStorage},
+    cache::DynamicCache,
+    concurrency::{cancellable, CancellationToken, Cancelled},
     locale::Locale,
     manifest::{self, GameManifest, ManifestError, MANIFEST_RELATIVE_PATH},
     service_layer::{
         ServiceGameProductType, ServiceGetLegacyCatalogDefsRequestBuilder,
         ServiceGetPreloadedOwnedGamesRequest, ServiceGetPreloadedOwnedGamesRequestBuilder,
-        ServiceGetPreloadedOwnedGamesRequestBuilderError, ServiceLayerClient, ServiceLayerError,
-        ServiceLegacyOffer, ServicePlatform, ServiceStorefront, ServiceUser,
-        ServiceUserGameProduct, SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
-        SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+        ServiceGetPreloadedOwnedGamesRequestBuilderError, ServiceLayerError, ServiceLegacyOffer,
+        ServiceOwnershipMethod, ServiceOwnershipStatus, ServicePlatform, ServiceStorefront,
+        ServiceTrialType, ServiceUser, ServiceUserGameProduct, SharedServiceLayer,
+        SERVICE_REQUEST_GETLEGACYCATALOGDEFS, SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
     },
 };
 use crate::util::registry::{parse_registry_path_json, RegistryError};
 use crate::{
-    gameinfo::load_game_info_from_json,
+    content::manager::ContentManager,
+    gameinfo::{
+        load_game_info_from_json,
+        stats::{installed_size, GameStats},
+        GameInstallInfo, GameVersionError,
+    },
     util::native::{maxima_dir, NativeError, SafeStr},
 };
+use chrono::{DateTime, Utc};
 use derive_getters::Getters;
-use log::info;
-use std::{collections::HashMap, path::PathBuf, time::SystemTimeError};
+use log::{info, warn};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTimeError},
+};
 use thiserror::Error;
 
 #[cfg(windows)]
@@ -26,6 +40,10 @@ use winapi::shared::cfg;
 
 #[derive(Error, Debug)]
 pub enum LibraryError {
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
     #[error(transparent)]
     Manifest(#[from] ManifestError),
     #[error(transparent)]
@@ -40,6 +58,8 @@ pub enum LibraryError {
     ServiceLayer(#[from] ServiceLayerError),
     #[error(transparent)]
     Time(#[from] SystemTimeError),
+    #[error(transparent)]
+    GameInfo(#[from] GameVersionError),
 
     #[error("`{0}` has no manifest found")]
     NoManifest(String),
@@ -49,6 +69,10 @@ pub enum LibraryError {
     NoPath(String),
     #[error("`{0}`'s version info is unavailable")]
     NoVersion(String),
+    #[error("you don't own `{0}`")]
+    NotOwned(String),
+    #[error("`{0}` can't be installed: {1}")]
+    Unavailable(String, Unavailable),
 }
 
 #[derive(Clone, Getters)]
@@ -56,47 +80,232 @@ pub struct OwnedOffer {
     slug: String,
     product: ServiceUserGameProduct,
     offer: ServiceLegacyOffer,
+    /// Platforms the game is made for, going by which platforms' libraries list it
+    platforms: Vec<ServicePlatform>,
 }
 
-impl OwnedOffer {
-    #[cfg(windows)]
-    pub async fn check_install_win_registry(&self) -> bool {
-        use crate::util::registry::parse_registry_path_regkey;
+/// Whether a game can run on the platform Maxima is running on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlatformSupport {
+    Native,
+    /// A Windows game on Linux
+    Wine,
+    /// Not made for this platform, like a Mac-only game on Windows or Linux, or a
+    /// Windows-only one on Mac
+    Unsupported,
+}
 
-        let path = match self.offer.install_check_override() {
-            Some(path) => path,
-            None => return false,
-        };
-        if let Ok(manifest_path) = parse_registry_path_regkey(path).await {
-            use crate::gameinfo::GameInstallInfo;
+/// How much of a game is on disk, see [`OwnedOffer::install_state`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InstallState {
+    Installed,
+    /// The EA app or Origin installed it at this folder. It isn't Maxima's to launch
+    /// until the user has it adopted with [`OwnedOffer::adopt`].
+    External(PathBuf),
+    /// Some of its files were downloaded, but the install never finished. It can't be
+    /// launched until it's resumed with [`ContentManager::resume_install`].
+    Partial {
+        /// Whether it's still in the download queue, and resumes on its own
+        queued: bool,
+    },
+    NotInstalled,
+}
 
-            let gamedir = manifest_path.ancestors().nth(2).unwrap().to_path_buf(); // Strip off the manifest and just leave the game directory
-            let game_install_info: GameInstallInfo = GameInstallInfo::new(gamedir, None);
-            game_install_info.save_to_json(&self.slug);
-            manifest_path.exists()
-        } else {
-            false
+/// Trial entitlement of an offer, like an EA Play "Play First" trial
+#[derive(Clone, Debug, Getters)]
+pub struct TrialStatus {
+    trial_type: Option<ServiceTrialType>,
+    /// Playtime left, if the trial is time limited
+    seconds_remaining: Option<u32>,
+}
+
+/// Where to renew EA Play after a subscription lapses
+pub const EA_PLAY_URL: &str = "https://www.ea.com/ea-play";
+
+/// Why an owned game can't be played anymore
+#[derive(Clone, Debug, PartialEq)]
+pub enum AccessEnded {
+    /// It came with a subscription, like EA Play, that has since ended
+    SubscriptionLapsed,
+    /// Taken away for another reason, e.g. a refund
+    Revoked,
+    TrialExpired,
+}
+
+impl AccessEnded {
+    /// Page to get the game back at, where there's a single one for every game
+    pub fn store_url(&self) -> Option<&'static str> {
+        match self {
+            AccessEnded::SubscriptionLapsed => Some(EA_PLAY_URL),
+            AccessEnded::Revoked | AccessEnded::TrialExpired => None,
         }
     }
+}
 
-    pub async fn is_installed(&self) -> bool {
-        let maxima_dir = match maxima_dir() {
-            Ok(dir) => dir,
-            Err(_) => return false,
-        };
+impl Display for AccessEnded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessEnded::SubscriptionLapsed => write!(
+                f,
+                "the subscription it came with has ended. Renew it at {} to keep playing",
+                EA_PLAY_URL
+            ),
+            AccessEnded::Revoked => write!(f, "it may have been refunded or revoked"),
+            AccessEnded::TrialExpired => {
+                write!(f, "the trial is over. Buy the full game to keep playing")
+            }
+        }
+    }
+}
+
+/// Why an owned game can't be downloaded or played where the user is
+#[derive(Clone, Debug, PartialEq)]
+pub enum Unavailable {
+    /// Not offered in the user's country, if the catalog says which that is
+    RegionLocked(Option<String>),
+    /// Can't be downloaded before this date, e.g. a pre-order
+    NotReleased(DateTime<Utc>),
+    /// EA stopped offering it on this date
+    Retired(DateTime<Utc>),
+    /// The catalog doesn't offer a download, without saying why
+    NotDownloadable,
+}
 
-        let game_info_path = maxima_dir
-            .join("gameinfo")
-            .join(format!("{}.json", &self.slug));
+impl Unavailable {
+    /// Whether an existing install can't be played either. One that can't be downloaded
+    /// for no stated reason may still run.
+    pub fn blocks_launch(&self) -> bool {
+        !matches!(self, Unavailable::NotDownloadable)
+    }
+}
+
+impl Display for Unavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unavailable::RegionLocked(Some(country)) => {
+                write!(f, "it isn't available in your region ({})", country)
+            }
+            Unavailable::RegionLocked(None) => write!(f, "it isn't available in your region"),
+            Unavailable::NotReleased(date) => write!(
+                f,
+                "it can't be downloaded until {}",
+                date.format("%Y-%m-%d")
+            ),
+            Unavailable::Retired(date) => {
+                write!(f, "it stopped being offered on {}", date.format("%Y-%m-%d"))
+            }
+            Unavailable::NotDownloadable => write!(f, "it isn't offered as a download"),
+        }
+    }
+}
+
+/// Catalog dates are RFC 3339, but empty or missing when they don't apply
+fn parse_catalog_date(date: &Option<String>) -> Option<DateTime<Utc>> {
+    date.as_deref()
+        .and_then(|x| DateTime::parse_from_rfc3339(x).ok())
+        .map(|x| x.with_timezone(&Utc))
+}
+
+impl TrialStatus {
+    pub fn is_expired(&self) -> bool {
+        self.seconds_remaining == Some(0)
+    }
+}
 
+impl OwnedOffer {
+    /// Where the EA app or Origin installed this game, going by the registry. Only
+    /// Windows has one to read.
+    pub async fn existing_install(&self) -> Option<PathBuf> {
         #[cfg(windows)]
-        match game_info_path.exists() {
-            true => return true,
-            false => return self.check_install_win_registry().await,
+        {
+            use crate::util::registry::{parse_registry_path_regkey, read_game_path};
+
+            if let Some(key) = self.offer.install_check_override() {
+                if let Ok(manifest_path) = parse_registry_path_regkey(key).await {
+                    if manifest_path.exists() {
+                        // Strip off the manifest and just leave the game directory
+                        return manifest_path.ancestors().nth(2).map(|x| x.to_path_buf());
+                    }
+                }
+            }
+
+            read_game_path(self.offer.display_name())
+                .ok()
+                .filter(|path| path.join(MANIFEST_RELATIVE_PATH).exists())
         }
 
         #[cfg(not(windows))]
-        game_info_path.exists()
+        None
+    }
+
+    /// Take over an install Maxima didn't make, like one from the EA app, once its
+    /// manifest shows it's this game. Nothing is downloaded.
+    pub async fn adopt(
+        &self,
+        path: &PathBuf,
+        wine_prefix: Option<PathBuf>,
+    ) -> Result<(), LibraryError> {
+        let manifest = manifest::read(path.join(MANIFEST_RELATIVE_PATH)).await?;
+        manifest.check_for(self.offer.content_id())?;
+
+        GameInstallInfo::new(path.to_owned(), wine_prefix).save_to_json(&self.slug);
+        manifest.run_touchup(path, &self.slug).await?;
+
+        info!("Adopted {} at {}", self.slug, path.display());
+        Ok(())
+    }
+
+    /// Whether Maxima knows where the game is installed, without looking for installs
+    /// it didn't make
+    pub fn has_install_info(&self) -> bool {
+        match maxima_dir() {
+            Ok(dir) => dir
+                .join("gameinfo")
+                .join(format!("{}.json", &self.slug))
+                .exists(),
+            Err(_) => false,
+        }
+    }
+
+    /// Whether Maxima installed the game or has adopted it. Installs by the EA app or
+    /// Origin don't count until they're adopted, see [`InstallState::External`].
+    pub async fn is_installed(&self) -> bool {
+        self.has_install_info()
+    }
+
+    /// Check the install info and the files on disk against the download queue.
+    /// Installs are only recorded once they finish, so one that's queued, gave up, or
+    /// lost its manifest is partial. Nothing is written, an install found in the registry
+    /// is only reported.
+    pub async fn install_state(&self, content: &ContentManager) -> InstallState {
+        let offer_id = self.offer_id();
+        if !self.has_install_info() {
+            if content.is_installing(offer_id) {
+                return InstallState::Partial { queued: true };
+            }
+
+            if content
+                .queue()
+                .failed()
+                .iter()
+                .any(|x| x.offer_id() == offer_id)
+            {
+                return InstallState::Partial { queued: false };
+            }
+
+            return match self.existing_install().await {
+                Some(path) => InstallState::External(path),
+                None => InstallState::NotInstalled,
+            };
+        }
+
+        let manifest_exists = load_game_info_from_json(&self.slug)
+            .map(|info| info.real_path().join(MANIFEST_RELATIVE_PATH).exists())
+            .unwrap_or(false);
+        match manifest_exists || content.is_installing(offer_id) {
+            true => InstallState::Installed,
+            false => InstallState::Partial { queued: false },
+        }
     }
 
     // This is unused
@@ -115,21 +324,25 @@ impl OwnedOffer {
     }
 
     pub async fn execute_path(&self, trial: bool) -> Result<PathBuf, LibraryError> {
-        let manifest = match self.local_manifest().await? {
-            Some(manifest) => manifest,
-            None => return Err(LibraryError::NoManifest(self.slug.clone())),
-        };
-
-        let path = if let Some(path) = manifest.execute_path(trial) {
-            &Some(path)
-        } else {
-            self.offer.execute_path_override()
+        let info = match load_game_info_from_json(&self.slug) {
+            Ok(info) => info,
+            Err(_) => return Err(LibraryError::NotInstalled(self.slug.clone())),
         };
 
+        // Not every trial has its own launcher, some run the full game's
+        let manifest = self.local_manifest().await?;
+        let path = manifest
+            .as_ref()
+            .and_then(|x| x.execute_path(trial).or_else(|| x.execute_path(false)));
         if let Some(path) = path {
-            Ok(parse_registry_path_json(path, Some(&self.slug)).await?)
-        } else {
-            Err(LibraryError::NoPath(self.slug.clone()))
+            return Ok(parse_registry_path_json(&path, Some(&self.slug)).await?);
+        }
+
+        // Pre-DiP games, which may not have a manifest at all, get it from the offer
+        match self.offer.execute_path() {
+            Some(path) => Ok(info.path().join(path)),
+            None if manifest.is_none() => Err(LibraryError::NoManifest(self.slug.clone())),
+            None => Err(LibraryError::NoPath(self.slug.clone())),
         }
     }
 
@@ -157,7 +370,7 @@ impl OwnedOffer {
             Err(_) => return Ok(None), // No info file yet, placeholder for now
         };
 
-        let path = game_install_info.path().join(MANIFEST_RELATIVE_PATH);
+        let path = game_install_info.real_path().join(MANIFEST_RELATIVE_PATH);
         info!("Checking for manifest at `{}`", path.display());
         if !path.exists() {
             return Ok(None);
@@ -168,6 +381,133 @@ impl OwnedOffer {
     pub fn offer_id(&self) -> &String {
         self.offer.offer_id()
     }
+
+    /// Last launch and the last measured install size, without measuring anything
+    pub fn stats(&self) -> GameStats {
+        GameStats::load(&self.slug)
+    }
+
+    /// Bytes the game's files take up, measuring them if that hasn't been done since
+    /// the install last changed. `None` if Maxima doesn't know where it's installed.
+    pub async fn installed_size(&self) -> Result<Option<u64>, LibraryError> {
+        let info = match load_game_info_from_json(&self.slug) {
+            Ok(info) => info,
+            Err(_) => return Ok(None),
+        };
+
+        Ok(Some(installed_size(&self.slug, &info).await?))
+    }
+
+    /// Trial details, if this is a trial rather than the full game
+    pub fn trial(&self) -> Option<TrialStatus> {
+        let product = self.product.product();
+        let user_trial = product
+            .game_product_user()
+            .game_product_user_trial()
+            .as_ref()?;
+
+        Some(TrialStatus {
+            trial_type: product
+                .trial_details()
+                .as_ref()
+                .map(|x| x.trial_type().clone()),
+            seconds_remaining: *user_trial.trial_time_remaining_seconds(),
+        })
+    }
+
+    pub fn is_trial(&self) -> bool {
+        self.trial().is_some()
+    }
+
+    /// Whether the game can run here, going by [`Self::platforms`]
+    pub fn platform_support(&self) -> PlatformSupport {
+        let has = |platform| self.platforms.contains(&platform);
+
+        if cfg!(target_os = "macos") {
+            if has(ServicePlatform::MacPc) {
+                return PlatformSupport::Native;
+            }
+        } else if has(ServicePlatform::Pc) {
+            return if cfg!(windows) {
+                PlatformSupport::Native
+            } else {
+                PlatformSupport::Wine
+            };
+        }
+
+        PlatformSupport::Unsupported
+    }
+
+    /// How the game was obtained. When there are several, owning it outright wins over a
+    /// subscription, and EA over other storefronts.
+    pub fn ownership_method(&self) -> Option<&ServiceOwnershipMethod> {
+        self.product
+            .product()
+            .game_product_user()
+            .ownership_methods()
+            .iter()
+            .filter(|x| **x != ServiceOwnershipMethod::Unknown)
+            .min_by_key(|x| (x.is_subscription(), x.storefront().is_some()))
+    }
+
+    /// Whether the entitlement was taken away, e.g. after a refund. These are still listed,
+    /// but can't be installed or launched.
+    pub fn is_disabled(&self) -> bool {
+        self.product.status() == &ServiceOwnershipStatus::Disabled
+            || self.product.product().game_product_user().status()
+                == &ServiceOwnershipStatus::Disabled
+    }
+
+    /// Why the game can't be played anymore, if it can't. Checked before launching, as the
+    /// license request fails without saying why.
+    pub fn access_ended(&self) -> Option<AccessEnded> {
+        if self.is_disabled() {
+            let methods = self
+                .product
+                .product()
+                .game_product_user()
+                .ownership_methods();
+            let subscription_only =
+                !methods.is_empty() && methods.iter().all(|x| x.is_subscription());
+            return Some(if subscription_only {
+                AccessEnded::SubscriptionLapsed
+            } else {
+                AccessEnded::Revoked
+            });
+        }
+
+        if self.trial().is_some_and(|trial| trial.is_expired()) {
+            return Some(AccessEnded::TrialExpired);
+        }
+
+        None
+    }
+
+    /// Why the game can't be downloaded or played where the user is, if it can't. The
+    /// content service only answers these with a failed request.
+    pub fn unavailable(&self) -> Option<Unavailable> {
+        let now = Utc::now();
+        if let Some(date) = parse_catalog_date(self.offer.use_end_date()) {
+            if date <= now {
+                return Some(Unavailable::Retired(date));
+            }
+        }
+
+        if let Some(date) = parse_catalog_date(self.offer.download_start_date()) {
+            if date > now {
+                return Some(Unavailable::NotReleased(date));
+            }
+        }
+
+        if !self.offer.is_downloadable() {
+            return Some(match self.offer.grey_market_controls() {
+                Some(true) => Unavailable::RegionLocked(self.offer.applied_country_code().clone()),
+                _ => Unavailable::NotDownloadable,
+            });
+        }
+
+        None
+    }
 }
 
 #[derive(Clone, Getters)]
@@ -207,30 +547,30 @@ fn group_offers(products: Vec<OwnedOffer>) -> Vec<OwnedTitle> {
                 return false;
             }
 
-            // Ensure it isn't a trial
-            if product
-                .product()
-                .product()
-                .game_product_user()
-                .game_product_user_trial()
-                .is_some()
-            {
-                return false;
-            }
-
             true
         })();
 
         if full_game {
             match slug {
                 Some(slug) => {
-                    product_map
-                        .entry(slug.clone())
-                        .or_insert_with(Vec::new)
-                        .push(product);
+                    // A trial isn't extra content for the game it's a trial of
+                    if !product.is_trial() {
+                        product_map
+                            .entry(slug.clone())
+                            .or_insert_with(Vec::new)
+                            .push(product);
+                    }
                 }
                 None => {
-                    base_products.insert(product.slug.clone(), product.clone());
+                    // When the same game is owned more than once, prefer an active entitlement
+                    // over a disabled one, and the full game over a trial
+                    let rank = |x: &OwnedOffer| (x.is_disabled(), x.is_trial());
+                    let replace = base_products
+                        .get(&product.slug)
+                        .map_or(true, |x: &OwnedOffer| rank(&product) <= rank(x));
+                    if replace {
+                        base_products.insert(product.slug.clone(), product.clone());
+                    }
                 }
             }
         }
@@ -268,6 +608,11 @@ impl OwnedTitle {
         }
     }
 
+    /// Publisher of the base game, if the catalog names one
+    pub fn publisher(&self) -> Option<&str> {
+        self.base_offer.offer.publisher_facet_key().as_deref()
+    }
+
     pub fn base_game(&self) -> Option<OwnedOffer> {
         for offer in self.offers.iter() {
             if offer
@@ -299,61 +644,164 @@ impl OwnedTitle {
     pub fn extra_offers(&self) -> &Vec<OwnedOffer> {
         &self.offers
     }
+
+    pub fn trial(&self) -> Option<TrialStatus> {
+        self.base_offer.trial()
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.base_offer.is_disabled()
+    }
+
+    pub fn access_ended(&self) -> Option<AccessEnded> {
+        self.base_offer.access_ended()
+    }
+
+    pub fn unavailable(&self) -> Option<Unavailable> {
+        self.base_offer.unavailable()
+    }
+
+    pub fn ownership_method(&self) -> Option<&ServiceOwnershipMethod> {
+        self.base_offer.ownership_method()
+    }
+
+    pub fn platforms(&self) -> &Vec<ServicePlatform> {
+        self.base_offer.platforms()
+    }
+
+    pub fn platform_support(&self) -> PlatformSupport {
+        self.base_offer.platform_support()
+    }
 }
 
+/// How long catalog definitions stay on disk. They rarely change, unlike the owned games list.
+pub(crate) const CATALOG_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct GameLibrary {
-    service_layer: ServiceLayerClient,
+    service_layer: SharedServiceLayer,
+    /// Whose library is fetched, so the persisted pages of one account aren't shown to another
+    auth_storage: LockedAuthStorage,
+    request_cache: DynamicCache<String>,
+    /// Language titles are fetched in
+    locale: Locale,
     library: Vec<OwnedTitle>,
-    last_request: u64,
+    /// When the library was last fetched, zero to fetch it again on the next access
+    last_request: AtomicU64,
 }
 
 impl GameLibrary {
-    pub async fn new(auth: LockedAuthStorage) -> Self {
+    pub async fn new(
+        service_layer: SharedServiceLayer,
+        auth_storage: LockedAuthStorage,
+        locale: Locale,
+    ) -> Self {
+        let request_cache = DynamicCache::new(
+            100,
+            Duration::from_secs(20 * 60),
+            Duration::from_secs(20 * 60),
+        )
+        .persistent("library");
+
         Self {
-            service_layer: ServiceLayerClient::new(auth),
+            service_layer,
+            auth_storage,
+            request_cache,
+            locale,
             library: Vec::new(),
-            last_request: 0,
+            last_request: AtomicU64::new(0),
+        }
+    }
+
+    /// Fetch titles in `locale` from now on. The library is fetched again the next time
+    /// it's asked for, pages already fetched in `locale` are still cached.
+    pub fn set_locale(&mut self, locale: Locale) {
+        if self.locale != locale {
+            self.locale = locale;
+            self.last_request.store(0, Ordering::Relaxed);
         }
     }
 
+    /// Fetch the owned games again the next time they're asked for, like after
+    /// switching to another account
+    pub(crate) fn invalidate(&self) {
+        self.last_request.store(0, Ordering::Relaxed);
+        self.request_cache
+            .invalidate_prefix_persisted("owned_games_");
+        self.request_cache
+            .invalidate_prefix_persisted("legacy_catalog_");
+    }
+
+    /// Fetch the library now if it's out of date, stopping early if `cancel` is cancelled.
+    /// Afterwards the other accessors answer from what was fetched.
+    pub async fn load(&mut self, cancel: &CancellationToken) -> Result<(), LibraryError> {
+        cancellable(cancel, self.update_if_needed()).await
+    }
+
+    /// Every owned game, including disabled ones
     pub async fn games(&mut self) -> Result<&Vec<OwnedTitle>, LibraryError> {
         self.update_if_needed().await?;
         Ok(&self.library)
     }
 
+    /// Owned games that can be installed and launched, leaving out disabled entitlements
+    pub async fn launchable_games(&mut self) -> Result<Vec<&OwnedTitle>, LibraryError> {
+        self.update_if_needed().await?;
+        Ok(self.library.iter().filter(|x| !x.is_disabled()).collect())
+    }
+
+    /// Base offers of the launchable games Maxima doesn't know an install of, for looking
+    /// for ones installed some other way
+    pub async fn offers_without_install_info(&mut self) -> Result<Vec<OwnedOffer>, LibraryError> {
+        Ok(self
+            .launchable_games()
+            .await?
+            .into_iter()
+            .map(|x| x.base_offer().clone())
+            .filter(|x| !x.has_install_info())
+            .collect())
+    }
+
+    /// Lookups fail with [`LibraryError::NotOwned`] when no owned game matches, so
+    /// that can be told apart from the library failing to load
     pub async fn title_by_base_offer(
         &mut self,
         offer_id: &str,
-    ) -> Result<Option<&OwnedTitle>, LibraryError> {
+    ) -> Result<&OwnedTitle, LibraryError> {
         self.update_if_needed().await?;
-        Ok(self
-            .library
+        self.library
             .iter()
-            .find(|x| x.base_offer.offer.offer_id() == offer_id))
+            .find(|x| x.base_offer.offer.offer_id() == offer_id)
+            .ok_or_else(|| LibraryError::NotOwned(offer_id.to_owned()))
     }
 
     pub async fn game_by_base_offer(
         &mut self,
         offer_id: &str,
-    ) -> Result<Option<&OwnedOffer>, LibraryError> {
+    ) -> Result<&OwnedOffer, LibraryError> {
+        Ok(&self.title_by_base_offer(offer_id).await?.base_offer)
+    }
+
+    pub async fn game_by_base_slug(&mut self, slug: &str) -> Result<&OwnedOffer, LibraryError> {
         self.update_if_needed().await?;
-        Ok(self
-            .library
+        self.library
             .iter()
-            .find(|x| x.base_offer.offer.offer_id() == offer_id)
-            .map(|x| &x.base_offer))
+            .find(|x| x.base_offer.product.product().game_slug() == slug)
+            .map(|x| &x.base_offer)
+            .ok_or_else(|| LibraryError::NotOwned(slug.to_owned()))
     }
 
-    pub async fn game_by_base_slug(
+    /// The first owned game matching any of `offer_ids`. Battlelog sends every offer
+    /// ID a game has been sold under, and we may only own one of them.
+    pub async fn game_by_any_base_offer(
         &mut self,
-        slug: &str,
-    ) -> Result<Option<&OwnedOffer>, LibraryError> {
+        offer_ids: &[&str],
+    ) -> Result<&OwnedOffer, LibraryError> {
         self.update_if_needed().await?;
-        Ok(self
-            .library
+        self.library
             .iter()
-            .find(|x| x.base_offer.product.product().game_slug() == slug)
-            .map(|x| &x.base_offer))
+            .find(|x| offer_ids.contains(&x.base_offer.offer.offer_id().as_str()))
+            .map(|x| &x.base_offer)
+            .ok_or_else(|| LibraryError::NotOwned(offer_ids.join(", ")))
     }
 
     async fn update_if_needed(&mut self) -> Result<(), LibraryError> {
@@ -361,7 +809,7 @@ impl GameLibrary {
             .duration_since(std::time::UNIX_EPOCH)?
             .as_secs();
 
-        if now - self.last_request > 1200 {
+        if now - self.last_request.load(Ordering::Relaxed) > 1200 {
             self.request_owned_games().await?;
         }
 
@@ -369,7 +817,7 @@ impl GameLibrary {
     }
 
     async fn request_owned_games(&mut self) -> Result<(), LibraryError> {
-        self.request_page_concurrent(Locale::EnUs, 1).await?;
+        self.request_page_concurrent(self.locale.clone(), 1).await?;
 
         Ok(())
     }
@@ -379,41 +827,86 @@ impl GameLibrary {
         locale: Locale,
         page: u32,
     ) -> Result<(), LibraryError> {
-        let responses: Vec<ServiceUserGameProduct> = {
-            let request = GameLibrary::library_request(
-                &locale,
-                ServiceGameProductType::DigitalFullGame,
-                true,
-                page,
-            )?;
-
-            let user: ServiceUser = self
-                .service_layer
-                .request(SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES, request)
-                .await?;
-            user.owned_game_products()
-                .as_ref()
-                .ok_or(ServiceLayerError::MissingField)?
-                .items()
-                .clone()
+        let user_id = self
+            .auth_storage
+            .lock()
+            .await
+            .user_id()
+            .await?
+            .unwrap_or_default();
+
+        let pc_products = self
+            .request_owned_products(&user_id, &locale, ServicePlatform::Pc, page)
+            .await?;
+        // Only tells which games are made for Mac, so the library still loads without it
+        let mac_products = match self
+            .request_owned_products(&user_id, &locale, ServicePlatform::MacPc, page)
+            .await
+        {
+            Ok(products) => products,
+            Err(err) => {
+                warn!("Couldn't check which games are made for Mac: {}", err);
+                Vec::new()
+            }
         };
 
-        let offer_ids = responses
+        let ids = |products: &[ServiceUserGameProduct]| -> HashSet<String> {
+            products
+                .iter()
+                .map(|x| x.origin_offer_id().to_owned())
+                .collect()
+        };
+        let pc_ids = ids(&pc_products);
+        let mac_ids = ids(&mac_products);
+
+        let mut responses = pc_products;
+        responses.extend(
+            mac_products
+                .into_iter()
+                .filter(|x| !pc_ids.contains(x.origin_offer_id())),
+        );
+
+        if responses.is_empty() {
+            info!("No owned games found for this account");
+            self.library = Vec::new();
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs();
+            self.last_request.store(now, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        let offer_ids: Vec<String> = responses
             .iter()
             .map(|x| x.origin_offer_id().to_owned())
             .collect();
 
-        let defs: Vec<ServiceLegacyOffer> = self
-            .service_layer
-            .request(
-                SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
-                ServiceGetLegacyCatalogDefsRequestBuilder::default()
-                    .offer_ids(offer_ids)
-                    .locale(locale)
-                    .build()
-                    .unwrap(),
-            )
-            .await?;
+        let cache_key = format!(
+            "legacy_catalog_{}_{}_{:x}",
+            user_id,
+            locale.full_str(),
+            md5::compute(offer_ids.join(","))
+        );
+        let defs: Vec<ServiceLegacyOffer> = match self.request_cache.get_persisted(&cache_key) {
+            Some(cached) => cached,
+            None => {
+                let defs: Vec<ServiceLegacyOffer> = self
+                    .service_layer
+                    .request(
+                        SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+                        ServiceGetLegacyCatalogDefsRequestBuilder::default()
+                            .offer_ids(offer_ids)
+                            .locale(locale)
+                            .build()
+                            .unwrap(),
+                    )
+                    .await?;
+
+                self.request_cache
+                    .insert_persisted_for(cache_key, defs.clone(), CATALOG_CACHE_TTL);
+                defs
+            }
+        };
 
         let mut offers: Vec<OwnedOffer> = Vec::new();
         for product in responses {
@@ -426,10 +919,19 @@ impl GameLibrary {
                 }
                 Some(def) => def.clone(),
             };
+            let platforms = [
+                (ServicePlatform::Pc, &pc_ids),
+                (ServicePlatform::MacPc, &mac_ids),
+            ]
+            .into_iter()
+            .filter(|(_, ids)| ids.contains(product.origin_offer_id()))
+            .map(|(platform, _)| platform)
+            .collect();
             offers.push(OwnedOffer {
                 slug: product.product().game_slug().to_owned(),
                 product: product.clone(),
                 offer: def,
+                platforms,
             });
         }
 
@@ -441,18 +943,61 @@ impl GameLibrary {
             .as_secs();
 
         self.library = titles;
-        self.last_request = now;
+        self.last_request.store(now, Ordering::Relaxed);
         Ok(())
     }
 
+    /// Owned games made for `platform`, cached for `user_id`
+    async fn request_owned_products(
+        &mut self,
+        user_id: &str,
+        locale: &Locale,
+        platform: ServicePlatform,
+        page: u32,
+    ) -> Result<Vec<ServiceUserGameProduct>, LibraryError> {
+        let platform_key = match platform {
+            ServicePlatform::Pc => "",
+            ServicePlatform::MacPc => "mac_",
+        };
+        let cache_key = format!(
+            "owned_games_{}{}_{}_{}",
+            platform_key,
+            user_id,
+            locale.full_str(),
+            page
+        );
+        if let Some(cached) = self.request_cache.get_persisted(&cache_key) {
+            return Ok(cached);
+        }
+
+        let request = GameLibrary::library_request(
+            locale,
+            ServiceGameProductType::DigitalFullGame,
+            true,
+            platform,
+            page,
+        )?;
+
+        let user: ServiceUser = self
+            .service_layer
+            .request(SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES, request)
+            .await?;
+        let products = user.owned_game_items().to_vec();
+
+        self.request_cache
+            .insert_persisted(cache_key, products.clone());
+        Ok(products)
+    }
+
     fn library_request(
         locale: &Locale,
         r#type: ServiceGameProductType,
         entitlement_enabled: bool,
+        platform: ServicePlatform,
         page: u32,
     ) -> Result<ServiceGetPreloadedOwnedGamesRequest, LibraryError> {
         Ok(ServiceGetPreloadedOwnedGamesRequestBuilder::default()
-            .is_mac(false)
+            .is_mac(platform == ServicePlatform::MacPc)
             .locale(locale.to_owned())
             .limit(1000)
             .next(((page - 1) * 1000).to_string())
@@ -463,7 +1008,475 @@ impl GameLibrary {
                 ServiceStorefront::Steam,
                 ServiceStorefront::Epic,
             ])
-            .platforms(vec![ServicePlatform::Pc])
+            .platforms(vec![platform])
             .build()?)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        content::manager::tests::{queued_game, write_queue_file},
+        core::{
+            auth::storage::AuthStorage,
+            service_layer::{
+                MockServiceLayer, ServiceUserBuilder, ServiceUserGameProductCursorPageBuilder,
+            },
+        },
+        util::temp_dir::{TempDataDir, TempDir},
+    };
+
+    use super::*;
+
+    #[test]
+    fn empty_owned_games_page() {
+        let page = ServiceUserGameProductCursorPageBuilder::default()
+            .next(None)
+            .total_count(0)
+            .items(Vec::new())
+            .build()
+            .unwrap();
+
+        let user = ServiceUserBuilder::default()
+            .id("0".to_owned())
+            .pd(None)
+            .player(None)
+            .owned_game_products(Some(page))
+            .build()
+            .unwrap();
+
+        assert!(user.owned_game_items().is_empty());
+        assert!(group_offers(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn missing_owned_games_page() {
+        let user = ServiceUserBuilder::default()
+            .id("0".to_owned())
+            .pd(None)
+            .player(None)
+            .owned_game_products(None)
+            .build()
+            .unwrap();
+
+        assert!(user.owned_game_items().is_empty());
+    }
+
+    pub(crate) fn product_json(slug: &str, offer_id: &str, status: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": offer_id,
+            "originOfferId": offer_id,
+            "status": status,
+            "product": {
+                "id": offer_id,
+                "name": slug,
+                "downloadable": true,
+                "gameSlug": slug,
+                "baseItem": { "id": slug, "gameType": "BASE_GAME" },
+                "gameProductUser": {
+                    "ownershipMethods": ["PURCHASE"],
+                    "initialEntitlementDate": "2024-01-01T00:00:00Z",
+                    "status": status,
+                },
+                "purchaseStatus": { "repurchasable": false },
+            },
+        })
+    }
+
+    pub(crate) fn offer_json(slug: &str, offer_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "offerId": offer_id,
+            "contentId": offer_id,
+            "primaryMasterTitleId": offer_id,
+            "displayName": slug,
+            "displayType": "FullGame",
+            "downloads": [],
+            "isDownloadable": true,
+        })
+    }
+
+    /// A library that isn't persisted, for a logged out user
+    pub(crate) fn memory_library(service_layer: SharedServiceLayer) -> GameLibrary {
+        GameLibrary {
+            service_layer,
+            auth_storage: AuthStorage::new(),
+            request_cache: DynamicCache::new(100, Duration::from_secs(60), Duration::from_secs(60)),
+            locale: Locale::EnUs,
+            library: Vec::new(),
+            last_request: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn owned_offer(slug: &str, offer_id: &str, status: &str) -> OwnedOffer {
+        OwnedOffer {
+            slug: slug.to_owned(),
+            product: serde_json::from_value(product_json(slug, offer_id, status)).unwrap(),
+            offer: serde_json::from_value(offer_json(slug, offer_id)).unwrap(),
+            platforms: vec![ServicePlatform::Pc],
+        }
+    }
+
+    pub(crate) fn owned_offer_with_cloud_save(
+        slug: &str,
+        offer_id: &str,
+        multiplayer_id: Option<&str>,
+    ) -> OwnedOffer {
+        let mut offer = offer_json(slug, offer_id);
+        if let Some(multiplayer_id) = multiplayer_id {
+            offer["multiplayerId"] = multiplayer_id.into();
+        }
+        offer["cloudSaveConfigurationOverride"] =
+            "<saveFileCriteria><include order=\"0\">%Documents%/Test/*</include></saveFileCriteria>"
+                .into();
+
+        OwnedOffer {
+            slug: slug.to_owned(),
+            product: serde_json::from_value(product_json(slug, offer_id, "ACTIVE")).unwrap(),
+            offer: serde_json::from_value(offer).unwrap(),
+            platforms: vec![ServicePlatform::Pc],
+        }
+    }
+
+    #[test]
+    fn access_ended_reasons() {
+        let offer = |status: &str, method: &str, trial_seconds: Option<u32>| {
+            let mut product = product_json("game", "Origin.OFR.50.1", status);
+            let user = &mut product["product"]["gameProductUser"];
+            user["ownershipMethods"] = serde_json::json!([method]);
+            if let Some(seconds) = trial_seconds {
+                user["gameProductUserTrial"] =
+                    serde_json::json!({ "trialTimeRemainingSeconds": seconds });
+            }
+
+            OwnedOffer {
+                slug: "game".to_owned(),
+                product: serde_json::from_value(product).unwrap(),
+                offer: serde_json::from_value(offer_json("game", "Origin.OFR.50.1")).unwrap(),
+                platforms: vec![ServicePlatform::Pc],
+            }
+        };
+
+        assert_eq!(offer("ACTIVE", "PURCHASE", None).access_ended(), None);
+        assert_eq!(offer("ACTIVE", "PURCHASE", Some(600)).access_ended(), None);
+        assert_eq!(
+            offer("ACTIVE", "PURCHASE", Some(0)).access_ended(),
+            Some(AccessEnded::TrialExpired)
+        );
+        assert_eq!(
+            offer("DISABLED", "PURCHASE", None).access_ended(),
+            Some(AccessEnded::Revoked)
+        );
+
+        let lapsed = offer("DISABLED", "VAULT", None).access_ended();
+        assert_eq!(lapsed, Some(AccessEnded::SubscriptionLapsed));
+        assert_eq!(lapsed.unwrap().store_url(), Some(EA_PLAY_URL));
+    }
+
+    #[test]
+    fn unavailable_reasons() {
+        let offer = |fields: serde_json::Value| {
+            let mut offer = offer_json("game", "Origin.OFR.50.1");
+            for (key, value) in fields.as_object().unwrap() {
+                offer[key] = value.clone();
+            }
+
+            OwnedOffer {
+                slug: "game".to_owned(),
+                product: serde_json::from_value(product_json("game", "Origin.OFR.50.1", "ACTIVE"))
+                    .unwrap(),
+                offer: serde_json::from_value(offer).unwrap(),
+                platforms: vec![ServicePlatform::Pc],
+            }
+        };
+
+        assert_eq!(offer(serde_json::json!({})).unavailable(), None);
+        assert_eq!(
+            offer(serde_json::json!({ "downloadStartDate": "", "useEndDate": null })).unavailable(),
+            None
+        );
+        assert_eq!(
+            offer(serde_json::json!({
+                "isDownloadable": false,
+                "greyMarketControls": true,
+                "appliedCountryCode": "RU",
+            }))
+            .unavailable(),
+            Some(Unavailable::RegionLocked(Some("RU".to_owned())))
+        );
+        assert_eq!(
+            offer(serde_json::json!({ "isDownloadable": false })).unavailable(),
+            Some(Unavailable::NotDownloadable)
+        );
+
+        let retired = offer(serde_json::json!({ "useEndDate": "2020-01-01T00:00:00Z" }));
+        assert!(matches!(
+            retired.unavailable(),
+            Some(Unavailable::Retired(_))
+        ));
+        let upcoming = offer(serde_json::json!({ "downloadStartDate": "2999-01-01T00:00:00Z" }));
+        assert!(matches!(
+            upcoming.unavailable(),
+            Some(Unavailable::NotReleased(_))
+        ));
+    }
+
+    #[test]
+    fn disabled_products_are_listed_but_flagged() {
+        let titles = group_offers(vec![
+            owned_offer("active-game", "Origin.OFR.50.1", "ACTIVE"),
+            owned_offer("refunded-game", "Origin.OFR.50.2", "DISABLED"),
+            // Bought again after a refund
+            owned_offer("rebought-game", "Origin.OFR.50.3", "DISABLED"),
+            owned_offer("rebought-game", "Origin.OFR.50.4", "ACTIVE"),
+        ]);
+        assert_eq!(titles.len(), 3);
+
+        let title = |slug: &str| {
+            titles
+                .iter()
+                .find(|x| x.base_offer().slug() == slug)
+                .unwrap()
+        };
+        assert!(!title("active-game").is_disabled());
+        assert!(title("refunded-game").is_disabled());
+        assert!(!title("rebought-game").is_disabled());
+        assert_eq!(
+            title("rebought-game").base_offer().offer_id(),
+            "Origin.OFR.50.4"
+        );
+    }
+
+    #[test]
+    fn platform_support() {
+        let mut offer = owned_offer("game", "Origin.OFR.50.1", "ACTIVE");
+        offer.platforms = vec![ServicePlatform::Pc, ServicePlatform::MacPc];
+        assert_ne!(offer.platform_support(), PlatformSupport::Unsupported);
+
+        let only = if cfg!(target_os = "macos") {
+            ServicePlatform::Pc
+        } else {
+            ServicePlatform::MacPc
+        };
+        offer.platforms = vec![only];
+        assert_eq!(offer.platform_support(), PlatformSupport::Unsupported);
+    }
+
+    #[tokio::test]
+    async fn library_from_mock_service_layer() {
+        let service_layer = MockServiceLayer::new()
+            .with_response(
+                SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+                serde_json::json!({
+                    "id": "0",
+                    "ownedGameProducts": {
+                        "next": null,
+                        "totalCount": 3,
+                        "items": [
+                            product_json("zuma", "Origin.OFR.50.1", "ACTIVE"),
+                            product_json("apex", "Origin.OFR.50.2", "ACTIVE"),
+                            product_json("uncataloged", "Origin.OFR.50.3", "ACTIVE"),
+                        ],
+                    },
+                }),
+            )
+            .with_response(
+                SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+                serde_json::json!([
+                    offer_json("zuma", "Origin.OFR.50.1"),
+                    offer_json("apex", "Origin.OFR.50.2"),
+                ]),
+            );
+
+        let mut library = memory_library(Arc::new(service_layer));
+
+        // Sorted by name, and products without a catalog definition are left out
+        let slugs: Vec<&str> = library
+            .games()
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| x.base_offer().slug().as_str())
+            .collect();
+        assert_eq!(slugs, ["apex", "zuma"]);
+    }
+
+    #[tokio::test]
+    async fn changing_the_locale_fetches_the_library_again() {
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(
+                    SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+                    serde_json::json!({
+                        "id": "0",
+                        "ownedGameProducts": {
+                            "next": null,
+                            "totalCount": 1,
+                            "items": [product_json("zuma", "Origin.OFR.50.1", "ACTIVE")],
+                        },
+                    }),
+                )
+                .with_response(
+                    SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+                    serde_json::json!([offer_json("zuma", "Origin.OFR.50.1")]),
+                ),
+        );
+        let mut library = memory_library(service_layer.clone());
+
+        library.games().await.unwrap();
+        let fetched = service_layer.requests().len();
+        assert!(fetched > 0);
+
+        // Same locale, nothing to fetch
+        library.set_locale(Locale::EnUs);
+        library.games().await.unwrap();
+        assert_eq!(service_layer.requests().len(), fetched);
+
+        library.set_locale(Locale::DeDe);
+        library.games().await.unwrap();
+        assert!(service_layer.requests().len() > fetched);
+    }
+
+    #[tokio::test]
+    async fn install_queries_have_no_side_effects() {
+        let data = TempDataDir::new();
+        let offer = owned_offer("install-query", "Origin.OFR.50.1", "ACTIVE");
+        let content = ContentManager::new(Arc::new(MockServiceLayer::new()), AuthStorage::new())
+            .await
+            .unwrap();
+
+        assert!(!offer.is_installed().await);
+        assert_eq!(
+            offer.install_state(&content).await,
+            InstallState::NotInstalled
+        );
+        assert!(!offer.has_install_info());
+        assert!(!data.join("gameinfo").join("install-query.json").exists());
+    }
+
+    #[tokio::test]
+    async fn unfinished_installs_are_partial() {
+        let _data = TempDataDir::new();
+        let dir = TempDir::new("install-state");
+        let offer = |name: &str, offer_id: &str| {
+            owned_offer(&format!("install-state-{}", name), offer_id, "ACTIVE")
+        };
+        let queued = offer("queued", "Origin.OFR.50.11");
+        let failed = offer("failed", "Origin.OFR.50.12");
+        let no_manifest = offer("no-manifest", "Origin.OFR.50.13");
+        let installed = offer("installed", "Origin.OFR.50.14");
+
+        for offer in [&no_manifest, &installed] {
+            GameInstallInfo::new(dir.join(offer.slug()), None).save_to_json(offer.slug());
+        }
+        let manifest = dir.join(installed.slug()).join(MANIFEST_RELATIVE_PATH);
+        std::fs::create_dir_all(manifest.parent().unwrap()).unwrap();
+        std::fs::write(&manifest, "").unwrap();
+
+        let queue_file = dir.join("download_queue.json");
+        let queued_at =
+            |offer: &OwnedOffer| queued_game(offer.offer_id(), offer.slug(), dir.path());
+        write_queue_file(
+            &queue_file,
+            vec![queued_at(&queued)],
+            vec![queued_at(&failed)],
+        );
+        let content =
+            ContentManager::with_queue_file(Arc::new(MockServiceLayer::new()), &queue_file)
+                .await
+                .unwrap();
+
+        let states = [
+            queued.install_state(&content).await,
+            failed.install_state(&content).await,
+            no_manifest.install_state(&content).await,
+            installed.install_state(&content).await,
+        ];
+
+        assert_eq!(
+            states,
+            [
+                InstallState::Partial { queued: true },
+                InstallState::Partial { queued: false },
+                InstallState::Partial { queued: false },
+                InstallState::Installed,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn offers_without_install_info() {
+        let data = TempDataDir::new();
+        let known = "known-install".to_owned();
+        let unknown = "unknown-install".to_owned();
+        let service_layer = MockServiceLayer::new()
+            .with_response(
+                SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+                serde_json::json!({
+                    "id": "0",
+                    "ownedGameProducts": {
+                        "next": null,
+                        "totalCount": 3,
+                        "items": [
+                            product_json(&known, "Origin.OFR.50.1", "ACTIVE"),
+                            product_json(&unknown, "Origin.OFR.50.2", "ACTIVE"),
+                            product_json("refunded", "Origin.OFR.50.3", "DISABLED"),
+                        ],
+                    },
+                }),
+            )
+            .with_response(
+                SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+                serde_json::json!([
+                    offer_json(&known, "Origin.OFR.50.1"),
+                    offer_json(&unknown, "Origin.OFR.50.2"),
+                    offer_json("refunded", "Origin.OFR.50.3"),
+                ]),
+            );
+
+        let mut library = memory_library(Arc::new(service_layer));
+
+        GameInstallInfo::new(data.join(&known), None).save_to_json(&known);
+        let offers = library.offers_without_install_info().await;
+
+        let slugs: Vec<String> = offers.unwrap().iter().map(|x| x.slug().clone()).collect();
+        assert_eq!(slugs, [unknown]);
+    }
+
+    #[tokio::test]
+    async fn invalidate_fetches_library_again() {
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(
+                    SERVICE_REQUEST_GETPRELOADEDOWNEDGAMES,
+                    serde_json::json!({
+                        "id": "0",
+                        "ownedGameProducts": {
+                            "next": null,
+                            "totalCount": 1,
+                            "items": [product_json("zuma", "Origin.OFR.50.1", "ACTIVE")],
+                        },
+                    }),
+                )
+                .with_response(
+                    SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+                    serde_json::json!([offer_json("zuma", "Origin.OFR.50.1")]),
+                ),
+        );
+
+        let mut library = memory_library(service_layer.clone());
+
+        library.games().await.unwrap();
+        let fetched = service_layer.requests().len();
+        assert!(fetched > 0);
+
+        library.games().await.unwrap();
+        assert_eq!(service_layer.requests().len(), fetched);
+
+        // Nothing cached for the previous account is reused
+        library.invalidate();
+        library.games().await.unwrap();
+        assert_eq!(service_layer.requests().len(), fetched * 2);
+    }
+}
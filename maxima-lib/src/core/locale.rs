@@ -1,20 +1,103 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Languages EA serves content in, with the region where it differs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Locale {
+    ArSa,
+    DeDe,
     EnUs,
+    EsEs,
+    EsMx,
+    FrFr,
+    ItIt,
+    JaJp,
+    KoKr,
+    PlPl,
+    PtBr,
+    RuRu,
+    ZhCn,
+    ZhTw,
 }
 
 impl Locale {
-    pub fn short_str(&self) -> &'static str {
+    pub const ALL: [Locale; 14] = [
+        Locale::ArSa,
+        Locale::DeDe,
+        Locale::EnUs,
+        Locale::EsEs,
+        Locale::EsMx,
+        Locale::FrFr,
+        Locale::ItIt,
+        Locale::JaJp,
+        Locale::KoKr,
+        Locale::PlPl,
+        Locale::PtBr,
+        Locale::RuRu,
+        Locale::ZhCn,
+        Locale::ZhTw,
+    ];
+
+    /// Code for requests that take a short locale, like system requirements and hero
+    /// backgrounds. Just the language, unless it's spoken in more than one region.
+    pub fn short_code(&self) -> &'static str {
         match self {
+            Locale::ArSa => "ar",
+            Locale::DeDe => "de",
             Locale::EnUs => "en",
+            Locale::EsEs => "es",
+            Locale::EsMx => "es-mx",
+            Locale::FrFr => "fr",
+            Locale::ItIt => "it",
+            Locale::JaJp => "ja",
+            Locale::KoKr => "ko",
+            Locale::PlPl => "pl",
+            Locale::PtBr => "pt-br",
+            Locale::RuRu => "ru",
+            Locale::ZhCn => "zh-cn",
+            Locale::ZhTw => "zh-tw",
         }
     }
 
+    /// The locale with `code` as its [`Locale::short_code`], ignoring case and whether
+    /// the region is split off with `-` or `_`
+    pub fn from_short_code(code: &str) -> Option<Locale> {
+        let code = code.to_ascii_lowercase().replace('_', "-");
+        Locale::ALL.into_iter().find(|x| x.short_code() == code)
+    }
+
     pub fn full_str(&self) -> &'static str {
         match self {
+            Locale::ArSa => "ar_SA",
+            Locale::DeDe => "de_DE",
             Locale::EnUs => "en_US",
+            Locale::EsEs => "es_ES",
+            Locale::EsMx => "es_MX",
+            Locale::FrFr => "fr_FR",
+            Locale::ItIt => "it_IT",
+            Locale::JaJp => "ja_JP",
+            Locale::KoKr => "ko_KR",
+            Locale::PlPl => "pl_PL",
+            Locale::PtBr => "pt_BR",
+            Locale::RuRu => "ru_RU",
+            Locale::ZhCn => "zh_CN",
+            Locale::ZhTw => "zh_TW",
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_codes_round_trip() {
+        for locale in Locale::ALL {
+            let code = locale.short_code();
+            assert!(!code.is_empty() && code.chars().all(|x| x.is_ascii_lowercase() || x == '-'));
+            assert_eq!(Locale::from_short_code(code), Some(locale));
+        }
+
+        assert_eq!(Locale::from_short_code("PT_BR"), Some(Locale::PtBr));
+        assert_eq!(Locale::from_short_code("xx"), None);
+    }
+}
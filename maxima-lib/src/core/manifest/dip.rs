@@ -1,8 +1,8 @@
 #![allow(non_snake_case)]
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::{core::manifest::ManifestError, util::native::platform_path};
+use crate::core::manifest::{run_touchup_actions, touchup_action, ManifestError, TouchupAction};
 use derive_getters::Getters;
 use serde::Deserialize;
 
@@ -120,20 +120,21 @@ fn remove_leading_slash(path: &str) -> &str {
     path.strip_prefix('/').unwrap_or(path)
 }
 
-fn remove_trailing_slash(path: &str) -> &str {
-    path.strip_suffix('/').unwrap_or(path)
-}
-
-fn remove_trailing_backslash(path: &str) -> &str {
-    path.strip_suffix('\\').unwrap_or(path)
-}
-
 impl DiPTouchup {
     pub fn path(&self) -> &str {
         remove_leading_slash(&self.file_path)
     }
 }
 
+dip_type!(
+    ContentIDs;
+    attr {},
+    data {
+        #[serde(default)]
+        contentID: Vec<String>,
+    }
+);
+
 dip_type!(
     Manifest;
     attr {
@@ -141,6 +142,8 @@ dip_type!(
     },
     data {
         buildMetaData: DiPBuildMetaData,
+        #[serde(default)]
+        contentIDs: DiPContentIDs,
         runtime: DiPRuntime,
         touchup: DiPTouchup,
     }
@@ -190,69 +193,29 @@ impl DiPManifest {
         Some(self.buildMetaData.gameVersion.attr_version().clone())
     }
 
-    #[cfg(unix)]
-    pub async fn run_touchup(
-        &self,
-        install_path: &PathBuf,
-        slug: &str,
-    ) -> Result<(), ManifestError> {
-        use crate::unix::{
-            fs::case_insensitive_path,
-            wine::{invalidate_mx_wine_registry, run_wine_command, CommandType},
-        };
-
-        let install_path = PathBuf::from(remove_trailing_slash(
-            install_path.to_str().ok_or(ManifestError::Decode)?,
-        ));
-        let args = self.collect_touchup_args(&install_path)?;
-        let path = install_path.join(&self.touchup.path());
-        let path = case_insensitive_path(path);
-        run_wine_command(path, Some(args), None, true, CommandType::Run, Some(slug)).await?;
+    pub fn content_ids(&self) -> &[String] {
+        &self.contentIDs.contentID
+    }
 
-        invalidate_mx_wine_registry().await;
-        Ok(())
+    /// What [`DiPManifest::run_touchup`] runs for the game installed at `install_path`
+    pub fn touchup_actions(
+        &self,
+        install_path: &Path,
+    ) -> Result<Vec<TouchupAction>, ManifestError> {
+        let action = touchup_action(
+            &self.touchup.file_path,
+            &self.touchup.parameters,
+            install_path,
+        )?;
+        Ok(action.into_iter().collect())
     }
 
-    #[cfg(windows)]
     pub async fn run_touchup(
         &self,
         install_path: &PathBuf,
-        _slug: &str,
+        slug: &str,
     ) -> Result<(), ManifestError> {
-        use crate::util::native::NativeError;
-        use tokio::process::Command;
-
-        let args = self.collect_touchup_args(install_path)?;
-        let path = install_path.join(&self.touchup.path());
-
-        let mut binding = Command::new(path);
-        let child = binding.args(args);
-
-        let status = child.spawn()?.wait().await?;
-        if !status.success() {
-            return Err(ManifestError::Native(NativeError::Command(
-                status.code().unwrap_or(0),
-            )));
-        }
-
-        Ok(())
-    }
-
-    fn collect_touchup_args(&self, install_path: &PathBuf) -> Result<Vec<PathBuf>, ManifestError> {
-        let mut args = Vec::new();
-        for arg in self.touchup.parameters.split(" ") {
-            let arg = arg.replace("{locale}", "en_US").replace(
-                "\"{installLocation}\"",
-                platform_path(
-                    remove_trailing_backslash(install_path.to_str().ok_or(ManifestError::Decode)?)
-                        .replace("/", "\\"),
-                )
-                .to_str()
-                .ok_or(ManifestError::Decode)?,
-            );
-
-            args.push(PathBuf::from(arg));
-        }
-        Ok(args)
+        let actions = self.touchup_actions(install_path)?;
+        run_touchup_actions(&actions, install_path, slug).await
     }
 }
@@ -4,9 +4,11 @@ pub mod pre_dip;
 use dip::DiPManifest;
 use pre_dip::PreDiPManifest;
 use quick_xml::DeError;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+use crate::util::native::platform_path;
+
 #[derive(Error, Debug)]
 pub enum ManifestError {
     #[error(transparent)]
@@ -27,15 +29,150 @@ pub enum ManifestError {
     },
     #[error("could not find install path for `{0}`")]
     NoInstallPath(String),
+    #[error(
+        "this folder contains a different game (content IDs {found:?}, expected `{expected}`)"
+    )]
+    WrongGame {
+        expected: String,
+        found: Vec<String>,
+    },
 }
 
 pub const MANIFEST_RELATIVE_PATH: &str = "__Installer/installerdata.xml";
 
+/// A program run by a game's touchup, after it's installed, moved or repaired
+#[derive(Debug, Clone, PartialEq)]
+pub struct TouchupAction {
+    /// Relative to the install folder
+    pub file_path: String,
+    /// With the install folder and locale filled in
+    pub args: Vec<PathBuf>,
+}
+
+fn remove_trailing_backslash(path: &str) -> &str {
+    path.strip_suffix('\\').unwrap_or(path)
+}
+
+/// The action running `file_path` with `parameters` for the game installed at
+/// `install_path`, or None if the manifest has nothing to run
+fn touchup_action(
+    file_path: &str,
+    parameters: &str,
+    install_path: &Path,
+) -> Result<Option<TouchupAction>, ManifestError> {
+    let file_path = file_path.trim();
+    let file_path = file_path.strip_prefix('/').unwrap_or(file_path);
+    if file_path.is_empty() {
+        return Ok(None);
+    }
+
+    let install_path = install_path.to_str().ok_or(ManifestError::Decode)?;
+    let install_path = install_path.strip_suffix('/').unwrap_or(install_path);
+    let install_location =
+        platform_path(remove_trailing_backslash(install_path).replace("/", "\\"));
+    let install_location = install_location.to_str().ok_or(ManifestError::Decode)?;
+
+    let args = parameters
+        .split(' ')
+        .filter(|x| !x.is_empty())
+        .map(|arg| {
+            let arg = arg
+                .replace("{locale}", "en_US")
+                .replace("\"{installLocation}\"", install_location);
+            PathBuf::from(arg)
+        })
+        .collect();
+
+    Ok(Some(TouchupAction {
+        file_path: file_path.to_owned(),
+        args,
+    }))
+}
+
+/// Run each of `actions` for the game installed at `install_path`, stopping at the first
+/// that fails
+#[cfg(unix)]
+async fn run_touchup_actions(
+    actions: &[TouchupAction],
+    install_path: &Path,
+    slug: &str,
+) -> Result<(), ManifestError> {
+    use crate::unix::{
+        fs::case_insensitive_path,
+        wine::{invalidate_mx_wine_registry, run_wine_command, CommandType},
+    };
+
+    for action in actions {
+        let path = case_insensitive_path(install_path.join(&action.file_path));
+        run_wine_command(
+            &path,
+            Some(&action.args),
+            None,
+            true,
+            CommandType::Run,
+            Some(slug),
+            None,
+        )
+        .await?;
+    }
+
+    invalidate_mx_wine_registry().await;
+    Ok(())
+}
+
+#[cfg(windows)]
+async fn run_touchup_actions(
+    actions: &[TouchupAction],
+    install_path: &Path,
+    _slug: &str,
+) -> Result<(), ManifestError> {
+    use crate::util::native::NativeError;
+    use tokio::process::Command;
+
+    for action in actions {
+        let status = Command::new(install_path.join(&action.file_path))
+            .args(&action.args)
+            .spawn()?
+            .wait()
+            .await?;
+        if !status.success() {
+            return Err(ManifestError::Native(NativeError::Command(
+                status.code().unwrap_or(0),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 #[async_trait::async_trait]
 pub trait GameManifest: Send + std::fmt::Debug {
     async fn run_touchup(&self, install_path: &PathBuf, slug: &str) -> Result<(), ManifestError>;
+    /// What [`GameManifest::run_touchup`] runs for the game installed at `install_path`,
+    /// in order, without running anything
+    fn touchup_actions(&self, install_path: &Path) -> Result<Vec<TouchupAction>, ManifestError>;
     fn execute_path(&self, trial: bool) -> Option<String>;
     fn version(&self) -> Option<String>;
+    /// Content IDs of the game this manifest belongs to
+    fn content_ids(&self) -> &[String];
+
+    /// Whether this is the manifest of the game with `content_id`.
+    /// Manifests that don't list any content IDs can't be told apart, so they always match.
+    fn is_for(&self, content_id: &str) -> bool {
+        let ids = self.content_ids();
+        ids.is_empty() || ids.iter().any(|x| x == content_id)
+    }
+
+    fn check_for(&self, content_id: &str) -> Result<(), ManifestError> {
+        if self.is_for(content_id) {
+            return Ok(());
+        }
+
+        Err(ManifestError::WrongGame {
+            expected: content_id.to_owned(),
+            found: self.content_ids().to_vec(),
+        })
+    }
 }
 #[async_trait::async_trait]
 impl GameManifest for DiPManifest {
@@ -43,6 +180,10 @@ impl GameManifest for DiPManifest {
         self.run_touchup(install_path, slug).await
     }
 
+    fn touchup_actions(&self, install_path: &Path) -> Result<Vec<TouchupAction>, ManifestError> {
+        self.touchup_actions(install_path)
+    }
+
     fn execute_path(&self, trial: bool) -> Option<String> {
         self.execute_path(trial)
     }
@@ -50,6 +191,10 @@ impl GameManifest for DiPManifest {
     fn version(&self) -> Option<String> {
         self.version()
     }
+
+    fn content_ids(&self) -> &[String] {
+        self.content_ids()
+    }
 }
 
 #[async_trait::async_trait]
@@ -58,6 +203,10 @@ impl GameManifest for PreDiPManifest {
         self.run_touchup(install_path, slug).await
     }
 
+    fn touchup_actions(&self, install_path: &Path) -> Result<Vec<TouchupAction>, ManifestError> {
+        self.touchup_actions(install_path)
+    }
+
     fn execute_path(&self, _: bool) -> Option<String> {
         None // pre-dip games don't have an exe field, most if not all just use info in the offer
     }
@@ -65,6 +214,10 @@ impl GameManifest for PreDiPManifest {
     fn version(&self) -> Option<String> {
         self.version()
     }
+
+    fn content_ids(&self) -> &[String] {
+        self.content_ids()
+    }
 }
 
 pub async fn read(path: PathBuf) -> Result<Box<dyn GameManifest>, ManifestError> {
@@ -82,3 +235,93 @@ pub async fn read(path: PathBuf) -> Result<Box<dyn GameManifest>, ManifestError>
         pre_dip_attempt: pre_dip_attempt.unwrap_err().into(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    const DIP_MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<DiPManifest version="4.0">
+  <buildMetaData>
+    <featureFlags allowMultipleInstances="false" autoUpdateEnabled="true" dynamicContentSupportEnabled="false" enableDifferentialUpdate="true" enableOriginInGameAPI="true" forceTouchupInstallerAfterUpdate="false" languageChangeSupportEnabled="true" treatUpdatesAsMandatory="true" useGameVersionFromManifest="true" />
+    <gameVersion version="1.0.80.38447" />
+    <requirements osMinVersion="6.1.7601" osReqs64Bit="true" />
+  </buildMetaData>
+  <contentIDs>
+    <contentID>1035052</contentID>
+  </contentIDs>
+  <runtime>
+    <launcher uid="{9c4d1d14-1d1e-4b6f-8d1c-0b3c3b1c2a11}">
+      <filePath>[HKEY_LOCAL_MACHINE\SOFTWARE\Respawn\Titanfall2\Install Dir]Titanfall2.exe</filePath>
+      <executeElevated>false</executeElevated>
+      <trial>false</trial>
+    </launcher>
+  </runtime>
+  <touchup>
+    <filePath>/__Installer/Touchup.exe</filePath>
+    <parameters>install -locale {locale} -installpath "{installLocation}" -autologging</parameters>
+  </touchup>
+</DiPManifest>"#;
+
+    const PRE_DIP_MANIFEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<game gameVersion="1.0.3.0" manifestVersion="2.0">
+  <contentIDs>
+    <contentID>71067</contentID>
+  </contentIDs>
+  <executable>
+    <filePath>__Installer/Cleanup.exe</filePath>
+    <parameters>/silent /dir="{installLocation}"</parameters>
+  </executable>
+</game>"#;
+
+    async fn read_str(name: &str, xml: &str) -> Box<dyn GameManifest> {
+        let dir = TempDir::new("manifest");
+        let path = dir.join(format!("{}.xml", name));
+        tokio::fs::write(&path, xml).await.unwrap();
+        read(path).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn parses_touchup_actions() {
+        let install_path = Path::new("/games/Titanfall 2/");
+        let install_location = platform_path("\\games\\Titanfall 2");
+
+        let manifest = read_str("dip", DIP_MANIFEST).await;
+        assert_eq!(
+            manifest.touchup_actions(install_path).unwrap(),
+            vec![TouchupAction {
+                file_path: "__Installer/Touchup.exe".to_owned(),
+                args: vec![
+                    PathBuf::from("install"),
+                    PathBuf::from("-locale"),
+                    PathBuf::from("en_US"),
+                    PathBuf::from("-installpath"),
+                    install_location.clone(),
+                    PathBuf::from("-autologging"),
+                ],
+            }]
+        );
+
+        let manifest = read_str("pre-dip", PRE_DIP_MANIFEST).await;
+        assert_eq!(manifest.version().as_deref(), Some("1.0.3.0"));
+        assert_eq!(
+            manifest.touchup_actions(install_path).unwrap(),
+            vec![TouchupAction {
+                file_path: "__Installer/Cleanup.exe".to_owned(),
+                args: vec![
+                    PathBuf::from("/silent"),
+                    PathBuf::from(format!("/dir={}", install_location.display())),
+                ],
+            }]
+        );
+
+        let manifest = read_str(
+            "no-touchup",
+            &DIP_MANIFEST.replace("/__Installer/Touchup.exe", ""),
+        )
+        .await;
+        assert_eq!(manifest.touchup_actions(install_path).unwrap(), vec![]);
+    }
+}
@@ -1,17 +1,24 @@
 pub mod auth;
+pub mod batch;
 pub mod cache;
 pub mod clients;
 pub mod cloudsync;
 pub mod concurrency;
+pub mod diagnostics;
 pub mod ecommerce;
 pub mod endpoints;
 pub mod error;
+pub mod friends;
+pub mod install_scan;
 pub mod launch;
 pub mod library;
 pub mod locale;
 pub mod manifest;
+pub mod save_backup;
 pub mod service_layer;
 pub mod settings;
+pub mod system_requirements;
+mod update_poll;
 
 #[cfg(target_os = "windows")]
 mod background_service_win;
@@ -32,43 +39,70 @@ use std::{
     fs::{create_dir_all, File},
     io,
     os::raw::c_char,
-    path::PathBuf,
-    time::Duration,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use cloudsync::{CloudSyncClient, CloudSyncLockMode};
+use chrono::{DateTime, Utc};
+use cloudsync::{CloudSyncClient, CloudSyncError, CloudSyncLockMode};
+use concurrency::{Priority, IMAGE_DOWNLOADS};
 use derive_builder::Builder;
 use derive_getters::Getters;
+use futures::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
 use log::{error, info, warn};
 use strum_macros::IntoStaticStr;
 
 use lazy_static::lazy_static;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use thiserror::Error;
-use tokio::sync::Mutex;
+use tokio::{
+    sync::{oneshot, Mutex},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
 
 use self::{
     auth::storage::{AuthError, AuthStorage, LockedAuthStorage, TokenError},
     cache::DynamicCache,
-    launch::ActiveGameContext,
-    library::GameLibrary,
+    install_scan::{find_install, FoundInstall},
+    launch::{ActiveGameContext, LaunchError},
+    library::{GameLibrary, InstallState, LibraryError, OwnedOffer, OwnedTitle, CATALOG_CACHE_TTL},
     locale::Locale,
     service_layer::{
-        ServiceAvatarListBuilder, ServiceAvatarListBuilderError, ServiceFriends,
-        ServiceGetBasicPlayerRequestBuilder, ServiceGetMyFriendsRequestBuilder,
-        ServiceGetUserPlayerRequest, ServiceImage, ServiceImageBuilder, ServiceImageBuilderError,
-        ServiceLayerClient, ServiceLayerError, ServicePlayer, ServicePlayerBuilder,
-        ServicePlayerBuilderError, ServiceUser, ServiceUserBuilder, ServiceUserBuilderError,
-        SERVICE_REQUEST_GETBASICPLAYER, SERVICE_REQUEST_GETMYFRIENDS,
-        SERVICE_REQUEST_GETUSERPLAYER,
+        ServiceAvailableBuild, ServiceAvatarListBuilder, ServiceAvatarListBuilderError,
+        ServiceFriends, ServiceFriendsOffsetPage, ServiceGameHubCollection,
+        ServiceGameSystemRequirements, ServiceGameSystemRequirementsRequestBuilder,
+        ServiceGetBasicPlayerRequestBuilder, ServiceGetInitUserDataRequest,
+        ServiceGetLegacyCatalogDefsRequestBuilder, ServiceGetMyFriendsRequestBuilder,
+        ServiceGetUserPlayerRequest, ServiceHeroBackgroundImageRequestBuilder, ServiceImage,
+        ServiceImageBuilder, ServiceImageBuilderError, ServiceInitUserData, ServiceLayerClient,
+        ServiceLayerError, ServiceLegacyOffer, ServicePlatform, ServicePlayer,
+        ServicePlayerBuilder, ServicePlayerBuilderError, ServiceUser, ServiceUserBuilder,
+        ServiceUserBuilderError, SharedServiceLayer, LARGE_AVATAR_SIZE, MEDIUM_AVATAR_SIZE,
+        SERVICE_REQUEST_GAMESYSTEMREQUIREMENTS, SERVICE_REQUEST_GETBASICPLAYER,
+        SERVICE_REQUEST_GETHEROBACKGROUNDIMAGE, SERVICE_REQUEST_GETINITUSERDATA,
+        SERVICE_REQUEST_GETLEGACYCATALOGDEFS, SERVICE_REQUEST_GETMYFRIENDS,
+        SERVICE_REQUEST_GETUSERPLAYER, SMALL_AVATAR_SIZE,
     },
+    system_requirements::{RequirementsVerdict, SystemSpecs},
 };
 use crate::{
-    content::manager::{ContentManager, ContentManagerError},
+    content::{
+        manager::{ContentManager, ContentManagerError, DownloadQueue, DownloaderError},
+        zip::ZipFileEntry,
+    },
+    gameinfo::load_game_info_from_json,
     lsx::{self, service::LSXServerError, types::LSXRequestType},
-    rtm::client::{BasicPresence, RtmClient},
-    util::native::{maxima_dir, NativeError},
+    ooa::{needs_license_update, request_and_save_license, LicenseAuth},
+    rtm::{
+        client::{BasicPresence, LocalPresence, RtmClient},
+        RtmError,
+    },
+    util::{
+        http,
+        native::{maxima_dir, NativeError},
+    },
 };
 
 #[derive(Clone, IntoStaticStr)]
@@ -77,6 +111,68 @@ pub enum MaximaEvent {
     ReceivedLSXRequest(u32, LSXRequestType),
     /// Offer ID. Use `maxima.mut_library().title_by_base_offer(id)` for details
     InstallFinished(String),
+    /// A file failed to download. It's retried, so the install may still finish.
+    InstallEntryFailed {
+        offer_id: String,
+        file: String,
+        reason: String,
+    },
+    /// Offer ID, and the files that couldn't be downloaded. Retry with
+    /// [`crate::content::manager::ContentManager::retry_failed`].
+    InstallFailed(String, Vec<String>),
+    /// Something was queued, started, finished, failed or cancelled. Holds the whole
+    /// queue, so frontends don't need to keep track of it themselves.
+    QueueChanged(DownloadQueue),
+    /// Offer ID of a game whose install was interrupted, found by
+    /// [`Maxima::reconcile_installs`]. Games still queued resume on their own, the
+    /// rest with [`crate::content::manager::ContentManager::resume_install`].
+    PartialInstallFound { offer_id: String, queued: bool },
+    /// Offer ID of a game that stopped, whose saves are being uploaded to CloudSync
+    SavesUploadStarted(String),
+    /// Offer ID, and why the upload failed if it did
+    SavesUploadFinished(String, Option<String>),
+    /// A CloudSync lock left behind by a session that exited without releasing it was
+    /// released by [`Maxima::release_orphaned_lock`]
+    OrphanedSyncLockReleased,
+    /// An installed game has a newer build on its channel than the one installed, found
+    /// by the poll enabled with [`Maxima::set_update_poll_interval`]. Sent once per build.
+    UpdateAvailable {
+        offer_id: String,
+        build: ServiceAvailableBuild,
+    },
+}
+
+/// Progress of [`Maxima::bootstrap`], one for each fetch as it finishes
+pub enum BootstrapEvent {
+    LocalUser(Result<ServiceUser, ServiceLayerError>),
+    /// Every owned game, including disabled ones
+    Library(Result<Vec<OwnedTitle>, LibraryError>),
+    /// Every friend, all pages of them
+    Friends(Result<FriendList, ServiceLayerError>),
+    /// Logged into RTM, and subscribed to friends' presence if they loaded
+    Presence(Result<(), RtmError>),
+}
+
+/// The local user's friends, see [`Maxima::all_friends`]
+#[derive(Clone, Debug, Default, Getters)]
+pub struct FriendList {
+    friends: Vec<ServicePlayer>,
+    /// How many friends the service reported. Can be off from the length of `friends`
+    /// if someone was added or removed while the pages were being fetched.
+    total_count: u32,
+}
+
+/// Data kept in the request cache that can go stale, see [`Maxima::invalidate`]
+#[derive(Clone, Debug)]
+pub enum CachedData {
+    /// [`Maxima::local_user`] and [`Maxima::region`]
+    LocalUser,
+    /// [`Maxima::friends`] and [`Maxima::all_friends`]
+    Friends,
+    /// [`Maxima::player_by_id`] for the given user ID
+    Player(String),
+    /// Everything about the logged in account, for after switching to another one
+    Account,
 }
 
 pub type MaximaLSXEventCallback = extern "C" fn(*const c_char);
@@ -86,7 +182,7 @@ pub struct Maxima {
     locale: Locale,
 
     auth_storage: LockedAuthStorage,
-    service_layer: ServiceLayerClient,
+    service_layer: SharedServiceLayer,
 
     #[getter(skip)]
     library: GameLibrary,
@@ -96,6 +192,8 @@ pub struct Maxima {
     lsx_port: u16,
     lsx_event_callback: Option<MaximaLSXEventCallback>,
     lsx_connections: u16,
+    #[getter(skip)]
+    lsx_shutdown: CancellationToken,
 
     cloud_sync: CloudSyncClient,
 
@@ -113,12 +211,36 @@ pub struct Maxima {
 
     #[getter(skip)]
     pending_events: Vec<MaximaEvent>,
+
+    /// When the last [`RECENT_EVENTS_KEPT`] events happened, for diagnostics bundles
+    #[getter(skip)]
+    recent_events: VecDeque<(DateTime<Utc>, &'static str)>,
+
+    /// Offer ID and the save upload of a game that has stopped, run in the background so
+    /// it doesn't hold up updates
+    #[getter(skip)]
+    saves_upload: Option<(String, JoinHandle<Result<bool, CloudSyncError>>)>,
+
+    /// How often installed games are checked for new builds, if at all
+    #[getter(skip)]
+    update_poll_interval: Option<Duration>,
+    #[getter(skip)]
+    last_update_poll: Option<Instant>,
+    /// Offer ID and build of the games the running poll found updates for
+    #[getter(skip)]
+    update_poll: Option<JoinHandle<Vec<(String, ServiceAvailableBuild)>>>,
+    /// Offer ID and the build last sent in [`MaximaEvent::UpdateAvailable`]
+    #[getter(skip)]
+    notified_builds: HashMap<String, String>,
 }
 
 #[derive(Builder)]
 pub struct MaximaOptions {
     load_auth_storage: bool,
     dummy_local_user: bool,
+    /// Where service layer requests go instead of EA's servers, for testing
+    #[builder(default, setter(strip_option))]
+    service_layer: Option<SharedServiceLayer>,
 }
 
 #[derive(Error, Debug)]
@@ -141,6 +263,22 @@ pub enum MaximaCreationError {
     ServiceUserBuilder(#[from] ServiceUserBuilderError),
 }
 
+/// How long [`Maxima::shutdown`] waits for cloud saves and downloads to be flushed
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the save upload after a game stops is given before it's abandoned and its
+/// CloudSync lock released
+pub const SAVES_UPLOAD_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Friends fetched per request, the most the service hands out at once
+pub const FRIENDS_PAGE_SIZE: u32 = 100;
+
+/// Shortest time allowed between checks for new builds of installed games
+pub const MIN_UPDATE_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Events remembered for [`Maxima::write_diagnostics`]
+pub const RECENT_EVENTS_KEPT: usize = 100;
+
 pub type LockedMaxima = Arc<Mutex<Maxima>>;
 
 impl Maxima {
@@ -157,7 +295,8 @@ impl Maxima {
             10_000,
             Duration::from_secs(30 * 60),
             Duration::from_secs(5 * 60),
-        );
+        )
+        .persistent("requests");
 
         let auth_storage = if options.load_auth_storage {
             AuthStorage::load()?
@@ -203,21 +342,33 @@ impl Maxima {
             None
         };
 
+        let service_layer = options
+            .service_layer
+            .unwrap_or_else(|| Arc::new(ServiceLayerClient::new(auth_storage.clone())));
+
         Ok(Arc::new(Mutex::new(Self {
             locale: Locale::EnUs,
             auth_storage: auth_storage.clone(),
-            service_layer: ServiceLayerClient::new(auth_storage.clone()),
-            library: GameLibrary::new(auth_storage.clone()).await,
+            service_layer: service_layer.clone(),
+            library: GameLibrary::new(service_layer.clone(), auth_storage.clone(), Locale::EnUs)
+                .await,
             playing: None,
             lsx_port,
             lsx_event_callback: None,
             lsx_connections: 0,
+            lsx_shutdown: CancellationToken::new(),
             cloud_sync: CloudSyncClient::new(auth_storage.clone()),
-            content_manager: ContentManager::new(auth_storage.clone(), false).await?,
-            rtm: RtmClient::new(auth_storage),
+            content_manager: ContentManager::new(service_layer, auth_storage.clone()).await?,
+            rtm: RtmClient::new(auth_storage, dummy_local_user.is_some()),
             request_cache,
             dummy_local_user,
             pending_events: Vec::new(),
+            recent_events: VecDeque::with_capacity(RECENT_EVENTS_KEPT),
+            saves_upload: None,
+            update_poll_interval: None,
+            last_update_poll: None,
+            update_poll: None,
+            notified_builds: HashMap::new(),
         })))
     }
 
@@ -233,9 +384,10 @@ impl Maxima {
 
     pub async fn start_lsx(&self, maxima: LockedMaxima) -> Result<(), LSXServerError> {
         let lsx_port = self.lsx_port;
+        let shutdown = self.lsx_shutdown.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = lsx::service::start_server(lsx_port, maxima).await {
+            if let Err(e) = lsx::service::start_server(lsx_port, maxima, shutdown).await {
                 error!("Error starting LSX server: {}", e);
             }
         });
@@ -257,55 +409,379 @@ impl Maxima {
             return Ok(user);
         }
 
-        let cache_key = "user_player";
-        if let Some(cached) = self.request_cache.get(cache_key) {
+        fetch_local_user(&self.service_layer, &self.request_cache).await
+    }
+
+    /// The storefront region of the logged in account, eg "US". Store prices
+    /// are only correct in the account's currency when requested with this.
+    pub async fn region(&self) -> Result<String, ServiceLayerError> {
+        if self.dummy_local_user.is_some() {
+            return Ok("US".to_owned());
+        }
+
+        let user_id = self.auth_storage.lock().await.user_id().await?;
+        let cache_key = format!("init_user_data_{}", user_id.unwrap_or_default());
+        let data: ServiceInitUserData = match self.request_cache.get(&cache_key) {
+            Some(cached) => cached,
+            None => {
+                let data: ServiceInitUserData = self
+                    .service_layer
+                    .request(
+                        SERVICE_REQUEST_GETINITUSERDATA,
+                        ServiceGetInitUserDataRequest {},
+                    )
+                    .await?;
+
+                self.request_cache.insert(cache_key, data.clone());
+                data
+            }
+        };
+
+        data.storefront_region()
+            .clone()
+            .ok_or(ServiceLayerError::MissingField)
+    }
+
+    /// Legacy catalog definition of an offer, with its content ID, multiplayer ID and
+    /// download metadata. Cached per offer.
+    pub async fn legacy_offer(
+        &self,
+        offer_id: &str,
+    ) -> Result<ServiceLegacyOffer, ServiceLayerError> {
+        self.legacy_offer_in(offer_id, &self.locale).await
+    }
+
+    /// Like [`Maxima::legacy_offer`], with names and descriptions in `locale` rather than
+    /// Maxima's own
+    pub async fn legacy_offer_in(
+        &self,
+        offer_id: &str,
+        locale: &Locale,
+    ) -> Result<ServiceLegacyOffer, ServiceLayerError> {
+        let cache_key = format!("legacy_offer_{}_{}", locale.full_str(), offer_id);
+        if let Some(cached) = self.request_cache.get_persisted(&cache_key) {
             return Ok(cached);
         }
 
-        let user: ServiceUser = self
+        let defs: Vec<ServiceLegacyOffer> = self
             .service_layer
             .request(
-                SERVICE_REQUEST_GETUSERPLAYER,
-                ServiceGetUserPlayerRequest {},
+                SERVICE_REQUEST_GETLEGACYCATALOGDEFS,
+                ServiceGetLegacyCatalogDefsRequestBuilder::default()
+                    .offer_ids(vec![offer_id.to_owned()])
+                    .locale(locale.clone())
+                    .build()?,
             )
             .await?;
 
+        let def = defs
+            .into_iter()
+            .find(|x| x.offer_id() == offer_id)
+            .ok_or(ServiceLayerError::MissingField)?;
+
         self.request_cache
-            .insert(cache_key.to_owned(), user.clone());
-        Ok(user)
+            .insert_persisted_for(cache_key, def.clone(), CATALOG_CACHE_TTL);
+        Ok(def)
+    }
+
+    /// Content ID of an offer, which licenses and cloud saves are keyed by
+    pub async fn content_id(&self, offer_id: &str) -> Result<String, ServiceLayerError> {
+        Ok(self.legacy_offer(offer_id).await?.content_id().to_owned())
+    }
+
+    /// Drop cached data that's known to have changed, like the friend list after adding
+    /// someone. It's fetched again the next time it's asked for.
+    pub fn invalidate(&self, data: CachedData) {
+        match data {
+            CachedData::LocalUser => {
+                self.request_cache.invalidate("user_player");
+                self.request_cache.invalidate_prefix("init_user_data_");
+            }
+            CachedData::Friends => self.request_cache.invalidate_prefix_persisted("friends_"),
+            CachedData::Player(id) => self
+                .request_cache
+                .invalidate(&format!("basic_player_{}", id)),
+            CachedData::Account => {
+                self.invalidate(CachedData::LocalUser);
+                self.invalidate(CachedData::Friends);
+                self.request_cache
+                    .invalidate_prefix_persisted("basic_player_");
+                self.request_cache
+                    .invalidate_prefix_persisted("system_requirements_");
+                self.library.invalidate();
+            }
+        }
     }
 
+    /// One page of friends, [`FRIENDS_PAGE_SIZE`] at a time. Empty for a dummy user.
     pub async fn friends(&self, page: u32) -> Result<Vec<ServicePlayer>, ServiceLayerError> {
-        let cache_key = format!("friends_{}", page);
-        if let Some(cached) = self.request_cache.get(&cache_key) {
+        if self.dummy_local_user.is_some() {
+            return Ok(Vec::new());
+        }
+
+        fetch_friends(&self.service_layer, &self.request_cache, page).await
+    }
+
+    /// Every friend, going through the pages until there are no more
+    pub async fn all_friends(&self) -> Result<FriendList, ServiceLayerError> {
+        if self.dummy_local_user.is_some() {
+            return Ok(FriendList::default());
+        }
+
+        fetch_all_friends(&self.service_layer, &self.request_cache).await
+    }
+
+    /// Fetch the local user, owned games and friends, and log into RTM, all at once rather
+    /// than one after another. `on_event` is called as each one finishes, so games can be
+    /// shown before friends have loaded. Everything fetched here is cached, so asking for it
+    /// again afterwards doesn't go back to the network.
+    pub async fn bootstrap<F>(&mut self, mut on_event: F)
+    where
+        F: FnMut(BootstrapEvent) + Send,
+    {
+        let Maxima {
+            service_layer,
+            request_cache,
+            dummy_local_user,
+            library,
+            rtm,
+            ..
+        } = self;
+        let service_layer = &*service_layer;
+        let request_cache = &*request_cache;
+        let dummy_local_user = &*dummy_local_user;
+
+        // Presence subscriptions need the friend list, but RTM can log in while it loads
+        let (friend_ids_tx, friend_ids_rx) = oneshot::channel::<Vec<String>>();
+
+        let mut fetches: FuturesUnordered<BoxFuture<'_, BootstrapEvent>> = FuturesUnordered::new();
+        fetches.push(Box::pin(async move {
+            BootstrapEvent::LocalUser(match dummy_local_user {
+                Some(user) => Ok(user.clone()),
+                None => fetch_local_user(service_layer, request_cache).await,
+            })
+        }));
+        fetches.push(Box::pin(async move {
+            BootstrapEvent::Library(library.games().await.cloned())
+        }));
+        fetches.push(Box::pin(async move {
+            let friends = match dummy_local_user {
+                Some(_) => Ok(FriendList::default()),
+                None => fetch_all_friends(service_layer, request_cache).await,
+            };
+            if let Ok(friends) = &friends {
+                let ids = friends.friends.iter().map(|x| x.id().to_owned()).collect();
+                let _ = friend_ids_tx.send(ids);
+            }
+
+            BootstrapEvent::Friends(friends)
+        }));
+        fetches.push(Box::pin(async move {
+            let result = async {
+                rtm.login().await?;
+                // Invisible in particular has to be sent before anyone sees us online
+                rtm.update_presence().await?;
+
+                // If friends failed to load there's no one to subscribe to
+                if let Ok(players) = friend_ids_rx.await {
+                    rtm.subscribe(&players).await?;
+                    info!("Subscribed to {} players", players.len());
+                }
+
+                Ok(())
+            }
+            .await;
+
+            BootstrapEvent::Presence(result)
+        }));
+
+        while let Some(event) = fetches.next().await {
+            on_event(event);
+        }
+    }
+
+    /// Work out which owned games are installed, going by their install info, their files,
+    /// and the download queue. Meant to be called on startup, once the library has loaded.
+    /// A [`MaximaEvent::PartialInstallFound`] is sent for each install that didn't finish,
+    /// so frontends can offer to resume it. Returns the state of each game by slug.
+    pub async fn reconcile_installs(
+        &mut self,
+    ) -> Result<HashMap<String, InstallState>, LibraryError> {
+        let offers: Vec<OwnedOffer> = self
+            .library
+            .launchable_games()
+            .await?
+            .into_iter()
+            .map(|x| x.base_offer().clone())
+            .collect();
+
+        let mut states = HashMap::new();
+        for offer in offers {
+            let state = offer.install_state(&self.content_manager).await;
+            if let InstallState::Partial { queued } = state {
+                info!("Found partial install of {}", offer.slug());
+                self.call_event(MaximaEvent::PartialInstallFound {
+                    offer_id: offer.offer_id().to_owned(),
+                    queued,
+                });
+            }
+
+            states.insert(offer.slug().to_owned(), state);
+        }
+
+        Ok(states)
+    }
+
+    /// Folder the game's files are in, following the link if it was moved to another drive
+    pub async fn install_path(&mut self, offer_id: &str) -> Result<PathBuf, LibraryError> {
+        let slug = self
+            .library
+            .game_by_base_offer(offer_id)
+            .await?
+            .slug()
+            .clone();
+        match load_game_info_from_json(&slug) {
+            Ok(info) => Ok(info.real_path()),
+            Err(_) => Err(LibraryError::NotInstalled(slug)),
+        }
+    }
+
+    /// Folders the game keeps its saves in, see [`cloudsync::save_folders`]
+    pub async fn save_paths(&mut self, offer_id: &str) -> Result<Vec<PathBuf>, CloudSyncError> {
+        let offer = self.library.game_by_base_offer(offer_id).await?;
+        cloudsync::save_folders(offer)
+    }
+
+    /// System requirements for a game. These rarely change, so they are kept on disk for a day.
+    pub async fn system_requirements(
+        &self,
+        slug: &str,
+    ) -> Result<ServiceGameSystemRequirements, ServiceLayerError> {
+        self.system_requirements_in(slug, &self.locale).await
+    }
+
+    /// Like [`Maxima::system_requirements`], written up in `locale` rather than Maxima's own
+    pub async fn system_requirements_in(
+        &self,
+        slug: &str,
+        locale: &Locale,
+    ) -> Result<ServiceGameSystemRequirements, ServiceLayerError> {
+        let cache_key = format!("system_requirements_{}_{}", slug, locale.short_code());
+        if let Some(cached) = self.request_cache.get_persisted(&cache_key) {
             return Ok(cached);
         }
 
-        let friends: ServiceFriends = self
+        let requirements: ServiceGameSystemRequirements = self
             .service_layer
             .request(
-                SERVICE_REQUEST_GETMYFRIENDS,
-                ServiceGetMyFriendsRequestBuilder::default()
-                    .limit(100)
-                    .offset(page)
-                    .is_mutual_friends_enabled(false)
+                SERVICE_REQUEST_GAMESYSTEMREQUIREMENTS,
+                ServiceGameSystemRequirementsRequestBuilder::default()
+                    .slug(slug.to_owned())
+                    .locale(locale.short_code().to_owned())
                     .build()
                     .unwrap(),
             )
             .await?;
 
-        let friends: Vec<ServicePlayer> = friends
-            .friends()
-            .items()
-            .into_iter()
-            .map(|x| x.player().clone())
-            .collect();
+        self.request_cache.insert_persisted_for(
+            cache_key,
+            requirements.clone(),
+            Duration::from_secs(24 * 60 * 60),
+        );
+        Ok(requirements)
+    }
+
+    /// Ask EA for titles, descriptions and art in `locale` from now on. Lookups are cached
+    /// per locale, so switching back doesn't fetch them again.
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.library.set_locale(locale.clone());
+        self.locale = locale;
+    }
+
+    /// Background art for a game's page. Not every game has one. Like system requirements,
+    /// it's kept on disk for a day.
+    pub async fn hero_background(
+        &self,
+        slug: &str,
+    ) -> Result<Option<ServiceGameHubCollection>, ServiceLayerError> {
+        self.hero_background_in(slug, &self.locale).await
+    }
+
+    /// Like [`Maxima::hero_background`], for the art of a region other than Maxima's own
+    pub async fn hero_background_in(
+        &self,
+        slug: &str,
+        locale: &Locale,
+    ) -> Result<Option<ServiceGameHubCollection>, ServiceLayerError> {
+        let cache_key = format!("hero_background_{}_{}", slug, locale.short_code());
+        if let Some(cached) = self.request_cache.get_persisted(&cache_key) {
+            return Ok(cached);
+        }
+
+        let background: Option<ServiceGameHubCollection> = self
+            .service_layer
+            .request(
+                SERVICE_REQUEST_GETHEROBACKGROUNDIMAGE,
+                ServiceHeroBackgroundImageRequestBuilder::default()
+                    .game_slug(slug.to_owned())
+                    .locale(locale.short_code().to_owned())
+                    .build()
+                    .unwrap(),
+            )
+            .await?;
+
+        self.request_cache.insert_persisted_for(
+            cache_key,
+            background.clone(),
+            Duration::from_secs(24 * 60 * 60),
+        );
+        Ok(background)
+    }
+
+    /// Compare this machine against a game's minimum and recommended requirements. Free
+    /// space isn't checked for a game that's installed already, and is checked on the
+    /// drive of Maxima's folder for one that isn't. Frontends that know where it'll be
+    /// installed should use [`Maxima::meets_requirements_at`].
+    pub async fn meets_requirements(
+        &self,
+        slug: &str,
+    ) -> Result<RequirementsVerdict, ServiceLayerError> {
+        let install_path = match load_game_info_from_json(slug) {
+            Ok(_) => None,
+            Err(_) => maxima_dir().ok(),
+        };
+        self.meets_requirements_at(slug, install_path.as_deref())
+            .await
+    }
+
+    /// Like [`Maxima::meets_requirements`], checking free space on the drive of `install_path`
+    /// instead, if given
+    pub async fn meets_requirements_at(
+        &self,
+        slug: &str,
+        install_path: Option<&Path>,
+    ) -> Result<RequirementsVerdict, ServiceLayerError> {
+        let requirements = self.system_requirements(slug).await?;
+        let requirements = requirements.system_requirements();
+
+        let requirements = match requirements
+            .iter()
+            .find(|x| x.platform() == &ServicePlatform::Pc)
+            .or(requirements.first())
+        {
+            Some(requirements) => requirements.parsed(),
+            None => return Ok(RequirementsVerdict::Unknown),
+        };
 
-        self.request_cache.insert(cache_key, friends.clone());
-        Ok(friends)
+        Ok(requirements.verdict(&SystemSpecs::current(install_path)))
     }
 
     pub fn call_event(&mut self, event: MaximaEvent) {
+        if self.recent_events.len() == RECENT_EVENTS_KEPT {
+            self.recent_events.pop_front();
+        }
+        self.recent_events.push_back((Utc::now(), (&event).into()));
+
         self.pending_events.push(event);
     }
 
@@ -343,30 +819,37 @@ impl Maxima {
         let avatars = data.avatar();
 
         let avatars = avatars.as_ref().ok_or(ServiceLayerError::MissingField)?;
-        let _ = self.cache_avatar_image(&id, avatars.large()).await;
-        let _ = self.cache_avatar_image(&id, avatars.medium()).await;
-        let _ = self.cache_avatar_image(&id, avatars.small()).await;
+        let _ = self
+            .cache_avatar_image(&id, avatars.large(), LARGE_AVATAR_SIZE)
+            .await;
+        let _ = self
+            .cache_avatar_image(&id, avatars.medium(), MEDIUM_AVATAR_SIZE)
+            .await;
+        let _ = self
+            .cache_avatar_image(&id, avatars.small(), SMALL_AVATAR_SIZE)
+            .await;
 
         self.request_cache.insert(cache_key, data.clone());
         Ok(data)
     }
 
+    /// Download `image` into the avatar cache, under the size it'll be looked up by.
+    /// `standard` is the size of the rendition, for when the service leaves it out.
     async fn cache_avatar_image(
         &self,
         id: &str,
         image: &ServiceImage,
+        standard: u16,
     ) -> Result<(), error::CacheRetrievalError> {
-        let path = self.cached_avatar_path(
-            id,
-            image.width().unwrap_or(727),
-            image.height().unwrap_or(727),
-        )?;
+        let (width, height) = image.dimensions_or(standard);
+        let path = self.cached_avatar_path(id, width, height)?;
 
         if path.exists() {
             return Ok(());
         }
 
-        let response = reqwest::get(image.path()).await?;
+        let _permit = IMAGE_DOWNLOADS.acquire(Priority::Low).await;
+        let response = http::client().get(image.path()).send().await?;
         let body: Vec<u8> = response.bytes().await?.to_vec();
 
         let mut file = File::create(path)?;
@@ -417,6 +900,100 @@ impl Maxima {
         &mut self.library
     }
 
+    /// Take over every owned game the EA app or Origin installed, so they don't have to be
+    /// downloaded again. Each is checked against its manifest first, and gets a license
+    /// if one can be requested now. Returns the slugs and paths of the games adopted.
+    pub async fn adopt_existing_installs(
+        &mut self,
+    ) -> Result<Vec<(String, PathBuf)>, LibraryError> {
+        let offers = self.library.offers_without_install_info().await?;
+        let mut adopted = Vec::new();
+        for offer in offers {
+            let path = match offer.existing_install().await {
+                Some(path) => path,
+                None => continue,
+            };
+
+            if let Err(err) = offer.adopt(&path, None).await {
+                warn!(
+                    "Not adopting {} from {}: {}",
+                    offer.slug(),
+                    path.display(),
+                    err
+                );
+                continue;
+            }
+
+            // Launching requests it anyway, this only saves a step
+            if let Err(err) = self.adopt_license(&offer).await {
+                warn!("Couldn't get a license for {} yet: {}", offer.slug(), err);
+            }
+            adopted.push((offer.slug().to_owned(), path));
+        }
+
+        Ok(adopted)
+    }
+
+    /// Every file in a build of `offer_id`, see
+    /// [`crate::content::ContentService::build_manifest`]
+    pub async fn build_manifest(
+        &self,
+        offer_id: &str,
+        build_id: &str,
+    ) -> Result<Vec<ZipFileEntry>, DownloaderError> {
+        self.content_manager
+            .service()
+            .build_manifest(offer_id, build_id)
+            .await
+    }
+
+    /// Look for owned games that Maxima doesn't know are installed, in the registry, the
+    /// EA app's default folders and subfolders of `folders`. Broken installs are returned
+    /// too, flagged so they can be repaired instead of located. Nothing is adopted.
+    pub async fn scan_installed_games(
+        &mut self,
+        folders: &[PathBuf],
+    ) -> Result<Vec<FoundInstall>, LibraryError> {
+        let offers = self.library.offers_without_install_info().await?;
+        let mut found = Vec::new();
+        for offer in offers {
+            if let Some(install) = find_install(&offer, folders).await {
+                found.push(install);
+            }
+        }
+
+        Ok(found)
+    }
+
+    async fn adopt_license(&mut self, offer: &OwnedOffer) -> Result<(), LaunchError> {
+        let content_id = self.content_id(offer.offer_id()).await?;
+        let slug = Some(offer.slug().as_str());
+        if !needs_license_update(&content_id, slug).await? {
+            return Ok(());
+        }
+
+        let auth = LicenseAuth::AccessToken(self.access_token().await?);
+        let path = offer.execute_path(offer.is_trial()).await?;
+        request_and_save_license(&auth, &content_id, path, slug, None).await?;
+        Ok(())
+    }
+
+    pub fn mut_cloud_sync(&mut self) -> &mut CloudSyncClient {
+        &mut self.cloud_sync
+    }
+
+    /// Release a CloudSync lock a previous session left behind, so the next sync doesn't
+    /// wait for it to expire. Best called after logging in. Sends
+    /// [`MaximaEvent::OrphanedSyncLockReleased`] if there was one, and returns whether
+    /// there was.
+    pub async fn release_orphaned_lock(&mut self) -> Result<bool, CloudSyncError> {
+        let released = self.cloud_sync.release_orphaned_lock().await?;
+        if released {
+            self.call_event(MaximaEvent::OrphanedSyncLockReleased);
+        }
+        Ok(released)
+    }
+
     pub fn content_manager(&mut self) -> &mut ContentManager {
         &mut self.content_manager
     }
@@ -425,6 +1002,28 @@ impl Maxima {
         &mut self.rtm
     }
 
+    /// Appear online, away, or invisible with [`BasicPresence::Offline`], with `status_text`
+    /// for friends to see. Kept across game launches and restarts, and while invisible the
+    /// game being played isn't shown.
+    pub async fn set_local_presence(
+        &mut self,
+        basic: BasicPresence,
+        status_text: &str,
+    ) -> Result<(), RtmError> {
+        self.rtm.set_local_presence(basic, status_text).await
+    }
+
+    pub fn local_presence(&self) -> &LocalPresence {
+        self.rtm.local_presence()
+    }
+
+    /// Check installed games for new builds every `interval` while [`Maxima::update`] is
+    /// being called, sending [`MaximaEvent::UpdateAvailable`]. Off by default, and with
+    /// None. Intervals shorter than [`MIN_UPDATE_POLL_INTERVAL`] are raised to it.
+    pub fn set_update_poll_interval(&mut self, interval: Option<Duration>) {
+        self.update_poll_interval = interval.map(|x| x.max(MIN_UPDATE_POLL_INTERVAL));
+    }
+
     pub fn set_lsx_port(&mut self, port: u16) {
         self.lsx_port = port;
     }
@@ -444,15 +1043,52 @@ impl Maxima {
     pub async fn update(&mut self) {
         self.update_playing_status().await;
 
+        let upload_done = self
+            .saves_upload
+            .as_ref()
+            .is_some_and(|(_, upload)| upload.is_finished());
+        if upload_done {
+            self.finish_saves_upload().await;
+        }
+
         let result = self.content_manager.update().await;
         match result {
             Err(err) => warn!("Failed to update content manager: {}", err),
-            Ok(result) => {
-                if let Some(event) = result {
+            Ok(events) => {
+                for event in events {
                     self.call_event(event);
                 }
             }
         }
+
+        self.poll_updates().await;
+    }
+
+    /// Get ready for the process to exit. Saves of a game that has just closed are synced,
+    /// downloads are stopped with their progress saved so they resume on the next start,
+    /// and the LSX server is stopped. Gives up after [`SHUTDOWN_TIMEOUT`].
+    pub async fn shutdown(&mut self) {
+        info!("Shutting down...");
+
+        let result = tokio::time::timeout(SHUTDOWN_TIMEOUT, async {
+            // A game that exited since the last update hasn't had its saves written yet
+            self.update_playing_status().await;
+            self.finish_saves_upload().await;
+
+            if let Err(err) = self.content_manager.shutdown().await {
+                error!("Failed to save download queue: {}", err);
+            }
+
+            self.lsx_shutdown.cancel();
+            if let Some(poll) = self.update_poll.take() {
+                poll.abort();
+            }
+        })
+        .await;
+
+        if result.is_err() {
+            warn!("Timed out while shutting down, some state may not have been saved");
+        }
     }
 
     async fn update_playing_status(&mut self) {
@@ -461,41 +1097,72 @@ impl Maxima {
         }
 
         let playing = self.playing.as_mut().unwrap();
+        // The bootstrap exits with the game's code, or 128 plus the signal that killed it
         match playing.process_mut().try_wait() {
             Ok(None) => return,
-            _ => (),
-        }
-
-        info!("Game stopped");
-
-        if let Some(offer) = playing.offer() {
-            if *playing.cloud_saves() && offer.offer().has_cloud_save() {
-                let result = self
-                    .cloud_sync
-                    .obtain_lock(offer, CloudSyncLockMode::Write)
-                    .await;
-                match result {
-                    Err(err) => error!("Failed to obtain CloudSync write lock: {}", err),
-                    Ok(lock) => {
-                        let result = lock.sync_files().await;
-                        if let Err(err) = result {
-                            error!("Failed to write to CloudSync: {}", err);
-                        }
-
-                        lock.release().await.ok();
-                    }
-                }
-            }
+            Ok(Some(status)) if status.success() => info!("Game stopped"),
+            Ok(Some(status)) => warn!("Game stopped with an error ({})", status),
+            Err(err) => warn!("Game stopped, but its exit status is unknown: {}", err),
         }
 
-        // We need to store your BasicPresence somewhere
-        self.rtm
-            .set_presence(BasicPresence::Online, "", "")
-            .await
-            .ok();
+        let automatic_sync = self.cloud_sync.automatic_sync();
+        let upload = playing
+            .offer()
+            .clone()
+            .filter(|x| automatic_sync && *playing.cloud_saves() && x.offer().has_cloud_save())
+            .map(|x| (x, playing.cloud_sync_filter().clone()));
+        if let Some((offer, filter)) = upload {
+            // Uploads would release each other's locks, so only one runs at a time
+            self.finish_saves_upload().await;
+
+            // Not cancelled, or progress made since the last sync is lost
+            let offer_id = offer.offer_id().to_owned();
+            let cloud_sync = self.cloud_sync.clone();
+            let upload = tokio::spawn(async move {
+                cloud_sync
+                    .sync_if_enabled_within(
+                        true,
+                        &offer,
+                        CloudSyncLockMode::Write,
+                        &filter,
+                        &CancellationToken::new(),
+                        Some(SAVES_UPLOAD_TIMEOUT),
+                    )
+                    .await
+            });
+
+            self.call_event(MaximaEvent::SavesUploadStarted(offer_id.clone()));
+            self.saves_upload = Some((offer_id, upload));
+        }
+
+        self.rtm.set_game_presence("", "").await.ok();
         self.playing = None;
     }
 
+    /// Wait for the save upload of a game that has stopped, if there is one. Call this before
+    /// anything else uses CloudSync, since a new lock would release the upload's, and before
+    /// exiting. [`Maxima::update`] finishes it on its own once it's done.
+    pub async fn finish_saves_upload(&mut self) {
+        let (offer_id, upload) = match self.saves_upload.take() {
+            Some(upload) => upload,
+            None => return,
+        };
+
+        let error = match upload.await {
+            Ok(Ok(_)) => {
+                info!("Uploaded saves of {}", offer_id);
+                None
+            }
+            Ok(Err(err)) => Some(err.to_string()),
+            Err(err) => Some(err.to_string()),
+        };
+        if let Some(err) = &error {
+            error!("Failed to write to CloudSync: {}", err);
+        }
+
+        self.call_event(MaximaEvent::SavesUploadFinished(offer_id, error));
+    }
+
     /// Returns whether this Maxima instance was constructed with a dummy
     /// user. This is usually paired with not loading/interacting with auth
     /// storage.
@@ -503,3 +1170,325 @@ impl Maxima {
         self.dummy_local_user.is_some()
     }
 }
+
+async fn fetch_local_user(
+    service_layer: &SharedServiceLayer,
+    request_cache: &DynamicCache<String>,
+) -> Result<ServiceUser, ServiceLayerError> {
+    let cache_key = "user_player";
+    if let Some(cached) = request_cache.get(cache_key) {
+        return Ok(cached);
+    }
+
+    let user: ServiceUser = service_layer
+        .request(
+            SERVICE_REQUEST_GETUSERPLAYER,
+            ServiceGetUserPlayerRequest {},
+        )
+        .await?;
+
+    request_cache.insert(cache_key.to_owned(), user.clone());
+    Ok(user)
+}
+
+async fn fetch_friends_page(
+    service_layer: &SharedServiceLayer,
+    offset: u32,
+) -> Result<ServiceFriendsOffsetPage, ServiceLayerError> {
+    let friends: ServiceFriends = service_layer
+        .request(
+            SERVICE_REQUEST_GETMYFRIENDS,
+            ServiceGetMyFriendsRequestBuilder::default()
+                .limit(FRIENDS_PAGE_SIZE)
+                .offset(offset)
+                .is_mutual_friends_enabled(false)
+                .build()
+                .unwrap(),
+        )
+        .await?;
+
+    Ok(friends.friends().clone())
+}
+
+async fn fetch_friends(
+    service_layer: &SharedServiceLayer,
+    request_cache: &DynamicCache<String>,
+    page: u32,
+) -> Result<Vec<ServicePlayer>, ServiceLayerError> {
+    let cache_key = format!("friends_{}", page);
+    if let Some(cached) = request_cache.get(&cache_key) {
+        return Ok(cached);
+    }
+
+    let friends: Vec<ServicePlayer> = fetch_friends_page(service_layer, page * FRIENDS_PAGE_SIZE)
+        .await?
+        .items()
+        .into_iter()
+        .map(|x| x.player().clone())
+        .collect();
+
+    request_cache.insert(cache_key, friends.clone());
+    Ok(friends)
+}
+
+async fn fetch_all_friends(
+    service_layer: &SharedServiceLayer,
+    request_cache: &DynamicCache<String>,
+) -> Result<FriendList, ServiceLayerError> {
+    let cache_key = "friends_all";
+    if let Some(cached) = request_cache.get(cache_key) {
+        return Ok(cached);
+    }
+
+    let mut friends = Vec::new();
+    let mut seen = HashSet::new();
+    let mut offset = 0;
+    let total_count = loop {
+        let page = fetch_friends_page(service_layer, offset).await?;
+        offset += page.items().len() as u32;
+
+        // Offsets shift if the list changes between requests, which can repeat someone
+        for friend in page.items() {
+            if seen.insert(friend.player().id().to_owned()) {
+                friends.push(friend.player().clone());
+            }
+        }
+
+        if !page.has_next_page() || page.items().is_empty() {
+            break *page.total_count();
+        }
+    };
+
+    let list = FriendList {
+        friends,
+        total_count,
+    };
+    request_cache.insert(cache_key.to_owned(), list.clone());
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::{core::service_layer::MockServiceLayer, util::temp_dir::TempDataDir};
+
+    use super::*;
+
+    /// Serves `body` to every request on a local port, like the avatar service would.
+    /// Returns the address to put in image paths.
+    async fn serve_image(body: &'static [u8]) -> String {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = [0; 4096];
+                let _ = socket.read(&mut request).await;
+
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                let _ = socket.write_all(header.as_bytes()).await;
+                let _ = socket.write_all(body).await;
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    fn friends_page(ids: &[&str], has_next_page: bool) -> serde_json::Value {
+        let items: Vec<serde_json::Value> = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "id": id,
+                    "pd": id,
+                    "player": {
+                        "id": id,
+                        "pd": id,
+                        "psd": id,
+                        "displayName": id,
+                        "uniqueName": id,
+                        "nickname": id,
+                        "avatar": null,
+                        "relationship": "FRIEND",
+                    },
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "id": "0",
+            "pd": "0",
+            "friends": {
+                "totalCount": 4,
+                "hasNextPage": has_next_page,
+                "hasPreviousPage": false,
+                "items": items,
+            },
+            "blockedPlayers": { "items": [] },
+        })
+    }
+
+    #[tokio::test]
+    async fn all_friend_pages_are_fetched_once_each() {
+        // Someone was added while paging, so the second page starts with who ended the first
+        let mock = Arc::new(MockServiceLayer::new().with_responses(
+            SERVICE_REQUEST_GETMYFRIENDS,
+            vec![
+                friends_page(&["a", "b"], true),
+                friends_page(&["b", "c"], true),
+                friends_page(&["d"], false),
+            ],
+        ));
+        let service_layer: SharedServiceLayer = mock.clone();
+        let request_cache =
+            DynamicCache::new(100, Duration::from_secs(60), Duration::from_secs(60));
+
+        let list = fetch_all_friends(&service_layer, &request_cache)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = list.friends().iter().map(|x| x.id().as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+        assert_eq!(*list.total_count(), 4);
+
+        assert_eq!(mock.requests().len(), 3);
+
+        // The whole list is cached, so the pages aren't asked for again
+        let cached = fetch_all_friends(&service_layer, &request_cache)
+            .await
+            .unwrap();
+        assert_eq!(cached.friends().len(), 4);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn avatars_are_found_where_they_were_cached() {
+        let host = serve_image(b"avatar").await;
+        let id = format!("avatar-test-{}", std::process::id());
+        let player = serde_json::json!({
+            "id": id,
+            "pd": id,
+            "psd": id,
+            "displayName": "Avatar",
+            "uniqueName": "Avatar",
+            "nickname": "Avatar",
+            "avatar": {
+                "large": {
+                    "height": null,
+                    "width": null,
+                    "path": format!("{}/avatar/1/416x416.JPEG", host),
+                },
+                "medium": {
+                    "height": null,
+                    "width": null,
+                    "path": format!("{}/avatar/1/default.JPEG", host),
+                },
+                "small": {
+                    "height": 40,
+                    "width": 40,
+                    "path": format!("{}/avatar/1/small.JPEG", host),
+                },
+            },
+            "relationship": "FRIEND",
+        });
+
+        let service_layer =
+            Arc::new(MockServiceLayer::new().with_response(SERVICE_REQUEST_GETBASICPLAYER, player));
+        let _data = TempDataDir::new();
+        let maxima_arc = Maxima::new_with_options(
+            MaximaOptionsBuilder::default()
+                .load_auth_storage(false)
+                .dummy_local_user(false)
+                .service_layer(service_layer.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut maxima = maxima_arc.lock().await;
+        maxima.request_cache =
+            DynamicCache::new(100, Duration::from_secs(60), Duration::from_secs(60));
+
+        let mut contents = Vec::new();
+        for size in [LARGE_AVATAR_SIZE, MEDIUM_AVATAR_SIZE, SMALL_AVATAR_SIZE] {
+            let path = maxima.avatar_image(&id, size, size).await.unwrap();
+            contents.push(std::fs::read(&path).unwrap());
+        }
+
+        assert!(contents.iter().all(|x| x == b"avatar"));
+        // Every size was cached by the first lookup
+        assert_eq!(service_layer.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn hero_backgrounds_are_cached_per_locale() {
+        let service_layer = Arc::new(MockServiceLayer::new().with_response(
+            SERVICE_REQUEST_GETHEROBACKGROUNDIMAGE,
+            serde_json::json!({ "items": [] }),
+        ));
+        let _data = TempDataDir::new();
+        let maxima_arc = Maxima::new_with_options(
+            MaximaOptionsBuilder::default()
+                .load_auth_storage(false)
+                .dummy_local_user(true)
+                .service_layer(service_layer.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut maxima = maxima_arc.lock().await;
+        maxima.request_cache =
+            DynamicCache::new(100, Duration::from_secs(60), Duration::from_secs(60));
+
+        for _ in 0..2 {
+            let background = maxima.hero_background("hero-test").await.unwrap();
+            assert!(background.unwrap().items().is_empty());
+        }
+        assert_eq!(service_layer.requests().len(), 1);
+
+        maxima
+            .hero_background_in("hero-test", &Locale::DeDe)
+            .await
+            .unwrap();
+        assert_eq!(service_layer.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn offline_mode_stays_offline() {
+        let service_layer = Arc::new(MockServiceLayer::new());
+        let _data = TempDataDir::new();
+        let maxima_arc = Maxima::new_with_options(
+            MaximaOptionsBuilder::default()
+                .load_auth_storage(false)
+                .dummy_local_user(true)
+                .service_layer(service_layer.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut maxima = maxima_arc.lock().await;
+
+        let user = maxima.local_user().await.unwrap();
+        maxima.player_by_id(user.id()).await.unwrap();
+        maxima.region().await.unwrap();
+        assert!(maxima.friends(0).await.unwrap().is_empty());
+        assert!(maxima.all_friends().await.unwrap().friends().is_empty());
+
+        let rtm = maxima.rtm();
+        rtm.login().await.unwrap();
+        rtm.subscribe(&vec!["1234".to_owned()]).await.unwrap();
+        rtm.set_presence(BasicPresence::Online, "", "")
+            .await
+            .unwrap();
+        rtm.heartbeat().await.unwrap();
+
+        assert!(service_layer.requests().is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use derive_getters::Getters;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::fs;
+
+use crate::util::native::{maxima_dir, NativeError, SafeParent};
+
+/// How many backups are kept per game, unless set with
+/// [`super::cloudsync::CloudSyncClient::set_backup_retention`]
+pub const DEFAULT_SAVE_BACKUP_RETENTION: usize = 5;
+
+const SAVE_BACKUP_DIR: &str = "save_backups";
+/// Lists where each file in a backup came from
+const BACKUP_INDEX_FILE: &str = "backup.json";
+
+#[derive(Error, Debug)]
+pub enum SaveBackupError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Native(#[from] NativeError),
+
+    #[error("`{0}` has no save backups")]
+    NoBackups(String),
+    #[error("`{0}` has no save backup called `{1}`")]
+    NotFound(String, String),
+}
+
+/// Copies of a game's local saves, taken before a cloud save read replaced them
+#[derive(Clone, Debug, Getters, Serialize, Deserialize)]
+pub struct SaveBackup {
+    /// When the backup was taken, in UTC. Also the name of its folder.
+    id: String,
+    /// Where each file was copied from. They're stored by their position in this list.
+    files: Vec<PathBuf>,
+}
+
+/// `maxima_dir()/save_backups`, holding a folder per game
+pub fn save_backup_root() -> Result<PathBuf, NativeError> {
+    Ok(maxima_dir()?.join(SAVE_BACKUP_DIR))
+}
+
+/// `maxima_dir()/save_backups/<slug>`, holding a folder per backup
+pub fn save_backup_dir(slug: &str) -> Result<PathBuf, NativeError> {
+    Ok(save_backup_root()?.join(slug))
+}
+
+/// Copies `files` into a new backup, then removes the oldest ones so no more than
+/// `retention` are left
+pub async fn backup_saves(
+    slug: &str,
+    files: &[PathBuf],
+    retention: usize,
+) -> Result<SaveBackup, SaveBackupError> {
+    backup_saves_in(&save_backup_root()?, slug, files, retention).await
+}
+
+/// Like [`backup_saves`], with the backups of each game kept in `root`
+async fn backup_saves_in(
+    root: &Path,
+    slug: &str,
+    files: &[PathBuf],
+    retention: usize,
+) -> Result<SaveBackup, SaveBackupError> {
+    let id = Utc::now().format("%Y-%m-%d_%H-%M-%S-%3f").to_string();
+    let dir = root.join(slug).join(&id);
+    fs::create_dir_all(&dir).await?;
+
+    for (i, file) in files.iter().enumerate() {
+        fs::copy(file, dir.join(i.to_string())).await?;
+    }
+
+    let backup = SaveBackup {
+        id,
+        files: files.to_vec(),
+    };
+    fs::write(
+        dir.join(BACKUP_INDEX_FILE),
+        serde_json::to_vec_pretty(&backup)?,
+    )
+    .await?;
+    debug!("Backed up {} save files to {}", files.len(), dir.display());
+
+    for old in save_backups_in(root, slug).await?.iter().skip(retention) {
+        debug!("Removing old save backup {}", old.id);
+        fs::remove_dir_all(root.join(slug).join(&old.id)).await?;
+    }
+
+    Ok(backup)
+}
+
+/// Backups of a game's saves, newest first
+pub async fn save_backups(slug: &str) -> Result<Vec<SaveBackup>, SaveBackupError> {
+    save_backups_in(&save_backup_root()?, slug).await
+}
+
+async fn save_backups_in(root: &Path, slug: &str) -> Result<Vec<SaveBackup>, SaveBackupError> {
+    let mut backups = Vec::new();
+
+    let mut entries = match fs::read_dir(root.join(slug)).await {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(backups),
+        Err(err) => return Err(err.into()),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let index = fs::read(entry.path().join(BACKUP_INDEX_FILE)).await;
+        match index.map(|data| serde_json::from_slice::<SaveBackup>(&data)) {
+            Ok(Ok(backup)) => backups.push(backup),
+            _ => warn!("Ignoring unreadable save backup {}", entry.path().display()),
+        }
+    }
+
+    // IDs are timestamps, so they sort by age
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(backups)
+}
+
+/// Copies a backup's files back to where they were taken from, replacing the saves
+/// that are there now. Restores the newest backup when `id` is `None`.
+pub async fn restore_save_backup(
+    slug: &str,
+    id: Option<&str>,
+) -> Result<SaveBackup, SaveBackupError> {
+    restore_save_backup_in(&save_backup_root()?, slug, id).await
+}
+
+async fn restore_save_backup_in(
+    root: &Path,
+    slug: &str,
+    id: Option<&str>,
+) -> Result<SaveBackup, SaveBackupError> {
+    let backups = save_backups_in(root, slug).await?;
+    let backup = match id {
+        Some(id) => backups
+            .into_iter()
+            .find(|x| x.id == id)
+            .ok_or_else(|| SaveBackupError::NotFound(slug.to_owned(), id.to_owned()))?,
+        None => backups
+            .into_iter()
+            .next()
+            .ok_or_else(|| SaveBackupError::NoBackups(slug.to_owned()))?,
+    };
+
+    let dir = root.join(slug).join(&backup.id);
+    for (i, file) in backup.files.iter().enumerate() {
+        fs::create_dir_all(file.safe_parent()?).await?;
+        fs::copy(dir.join(i.to_string()), file).await?;
+    }
+
+    info!(
+        "Restored {} save files of {} from {}",
+        backup.files.len(),
+        slug,
+        backup.id
+    );
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn old_backups_are_pruned() {
+        let slug = "save-backup-test";
+        let dir = TempDir::new("save-backup");
+        let root = dir.join("save_backups");
+        let save = dir.join("save.dat");
+
+        let mut ids = Vec::new();
+        for i in 0..4 {
+            std::fs::write(&save, i.to_string()).unwrap();
+            let backup = backup_saves_in(&root, slug, &[save.clone()], 2)
+                .await
+                .unwrap();
+            ids.push(backup.id);
+            // Backups are named after the millisecond they're taken in
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let kept: Vec<String> = save_backups_in(&root, slug)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|x| x.id)
+            .collect();
+
+        std::fs::write(&save, "changed").unwrap();
+        let restored = restore_save_backup_in(&root, slug, None).await.unwrap();
+        let contents = std::fs::read_to_string(&save).unwrap();
+
+        assert_eq!(kept, vec![ids[3].clone(), ids[2].clone()]);
+        assert_eq!(restored.id, ids[3]);
+        assert_eq!(contents, "3");
+    }
+}
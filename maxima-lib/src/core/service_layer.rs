@@ -1,8 +1,16 @@
 #![allow(non_snake_case)]
 
-use log::debug;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use log::{debug, warn};
 use reqwest::{Client, StatusCode};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 use sha2_const::Sha256;
 use thiserror::Error;
@@ -11,12 +19,19 @@ use derive_builder::Builder;
 use derive_getters::Getters;
 
 use super::{
-    auth::storage::{LockedAuthStorage, TokenError},
+    auth::storage::{AuthError, LockedAuthStorage, TokenError},
     ecommerce::CommerceEntitlementType,
     endpoints::API_CONTENTFUL_PROXY,
     endpoints::API_SERVICE_AGGREGATION_LAYER,
     locale::Locale,
+    system_requirements::{ParsedSystemRequirements, RequirementSpec},
 };
+use crate::{gameinfo::channel::BuildChannel, util::http};
+
+/// Sizes of the avatar renditions EA serves, in pixels. Avatars are square.
+pub const LARGE_AVATAR_SIZE: u16 = 416;
+pub const MEDIUM_AVATAR_SIZE: u16 = 208;
+pub const SMALL_AVATAR_SIZE: u16 = 40;
 
 const LARGE_AVATAR_PATH: &str =
     "https://eaavatarservice.akamaized.net/production/avatar/prod/1/599/416x416.JPEG";
@@ -25,6 +40,19 @@ const MEDIUM_AVATAR_PATH: &str =
 const SMALL_AVATAR_PATH: &str =
     "https://eaavatarservice.akamaized.net/production/avatar/prod/1/599/40x40.JPEG";
 
+/// Errors the server returns when it doesn't recognize a persisted query's hash
+const STALE_QUERY_ERRORS: [&str; 3] = [
+    "PersistedQueryNotFound",
+    "PERSISTED_QUERY_NOT_FOUND",
+    "provided sha does not match query",
+];
+
+lazy_static! {
+    /// Operations whose persisted query hash was rejected. They're sent as full queries
+    /// for the rest of the session.
+    static ref STALE_QUERIES: Mutex<HashSet<&'static str>> = Mutex::new(HashSet::new());
+}
+
 #[derive(Error, Debug)]
 pub enum ServiceLayerError {
     #[error(transparent)]
@@ -32,6 +60,8 @@ pub enum ServiceLayerError {
     #[error(transparent)]
     AuthToken(#[from] TokenError),
     #[error(transparent)]
+    Auth(#[from] AuthError),
+    #[error(transparent)]
     Json(#[from] serde_json::Error),
     #[error(transparent)]
     ServiceAvailableBuildsRequestBuilder(#[from] ServiceAvailableBuildsRequestBuilderError),
@@ -54,6 +84,10 @@ pub enum ServiceLayerError {
         operation: String,
         error: Option<String>,
     },
+    #[error("there is no service layer operation called `{0}`")]
+    UnknownOperation(String),
+    #[error("persisted query for operation `{0}` was not found on the server")]
+    PersistedQueryNotFound(String),
     #[error("Request did not return a `data` key")]
     NoData,
     #[error("Request did not return a response containing the requested field")]
@@ -76,7 +110,8 @@ pub struct ServiceExtensions {
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
 struct FullServiceRequest<'a, T: Serialize> {
-    extensions: ServiceExtensions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    extensions: Option<ServiceExtensions>,
     variables: T,
     operation_name: &'a str,
     query: &'static str,
@@ -87,6 +122,17 @@ pub enum ServiceLayerRequestType {
     ContentfulProxy,
 }
 
+/// How an operation is sent to the server
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum QueryMode {
+    /// GET with only the hash of the bundled query
+    Persisted,
+    /// POST with the full query, and its hash so the server can cache it
+    Full,
+    /// POST with the full query and no hash, for when the server rejects our hash
+    FullUnhashed,
+}
+
 pub struct ServiceLayerGraphQLRequest {
     query: &'static str,
     operation: &'static str,
@@ -95,6 +141,12 @@ pub struct ServiceLayerGraphQLRequest {
     r#type: ServiceLayerRequestType,
 }
 
+impl ServiceLayerGraphQLRequest {
+    pub fn is_mutation(&self) -> bool {
+        self.query.trim_start().starts_with("mutation")
+    }
+}
+
 macro_rules! load_graphql_request {
     ($type:ident, $operation:expr, $key:expr) => {{
         let content = include_str!(concat!("graphql/", $operation, ".gql"));
@@ -109,28 +161,96 @@ macro_rules! load_graphql_request {
     }};
 }
 
-macro_rules! define_graphql_request {
-    ($type:ident, $operation:expr, $key:expr) => { paste::paste! {
-        pub const [<SERVICE_REQUEST_ $operation:upper>]: &ServiceLayerGraphQLRequest = &load_graphql_request!($type, stringify!($operation), stringify!($key));
+macro_rules! define_graphql_requests {
+    ($($type:ident, $operation:ident, $key:ident;)*) => { paste::paste! {
+        $(
+            pub const [<SERVICE_REQUEST_ $operation:upper>]: &ServiceLayerGraphQLRequest = &load_graphql_request!($type, stringify!($operation), stringify!($key));
+        )*
+
+        /// Every operation above, so they can be looked up by name
+        const SERVICE_REQUESTS: &[&ServiceLayerGraphQLRequest] = &[$([<SERVICE_REQUEST_ $operation:upper>]),*];
     }}
 }
 
-define_graphql_request!(ServiceAggregationLayer, addonSearch, me); // Input: ServiceAddonSearchRequest, Output: AddonSearchResult
-define_graphql_request!(ServiceAggregationLayer, availableBuilds, availableBuilds); // Input: ServiceAvailableBuildsRequest, Output: ServiceAvailableBuild[]
-define_graphql_request!(ServiceAggregationLayer, downloadUrl, downloadUrl); // Input: ServiceDownloadUrlRequest, Output: ServiceDownloadUrlMetadata
-define_graphql_request!(ServiceAggregationLayer, GameImages, game); // Input: ServiceGameImagesRequest, Output: ServiceGame
-define_graphql_request!(ServiceAggregationLayer, GetBasicPlayer, playerByPd); // Input: ServiceGetBasicPlayerRequest, Output: ServicePlayer
-define_graphql_request!(ServiceAggregationLayer, getPreloadedOwnedGames, me); // Input: ServiceGetPreloadedOwnedGamesRequest, Output: ServiceUser (with owned_game_products field set)
-define_graphql_request!(ServiceAggregationLayer, GetUserPlayer, me); // Input: ServiceGetUserPlayerRequest, Output: ServiceUser
-define_graphql_request!(ServiceAggregationLayer, GameSystemRequirements, game); // Input: ServiceGameSystemRequirementsRequest, Output: ServiceGameSystemRequirements
-define_graphql_request!(ServiceAggregationLayer, GetMyFriends, me); // Input: ServiceGetMyFriendsRequest, Output: ServiceFriends
-define_graphql_request!(ServiceAggregationLayer, SearchPlayer, players); // Input: ServiceSearchPlayerRequest, Output: ServicePlayersPage
-define_graphql_request!(ServiceAggregationLayer, getLegacyCatalogDefs, legacyOffers); // Input: ServiceGetLegacyCatalogDefsRequest, Output: Vec<ServiceLegacyOffer>
-define_graphql_request!(ServiceAggregationLayer, getLegacyEntitlements, me); // Input: ServiceGetLegacyEntitlementsRequest, Output: UserLegacyEntitlementsCursorPage
-define_graphql_request!(ServiceAggregationLayer, sdkEntitlements, me); // Input: ServiceSdkEntitlementsRequest, Output: SdkEntitlementsResult
-define_graphql_request!(ServiceAggregationLayer, getGameProducts, gameProducts); // Input: ServiceGetLegacyCatalogDefsRequest, Output: Vec<ServiceLegacyProduct>
-define_graphql_request!(ServiceAggregationLayer, GetGamePlayTimes, me); // Input: ServiceGetLegacyCatalogDefsRequest, Output: Vec<ServiceLegacyProduct>
-define_graphql_request!(ContentfulProxy, GetHeroBackgroundImage, gameHubCollection); // Input: ServiceHeroBackgroundImageRequest, Output: ServiceGameHubCollection
+define_graphql_requests! {
+    ServiceAggregationLayer, addonSearch, me; // Input: ServiceAddonSearchRequest, Output: AddonSearchResult
+    ServiceAggregationLayer, availableBuilds, availableBuilds; // Input: ServiceAvailableBuildsRequest, Output: ServiceAvailableBuild[]
+    ServiceAggregationLayer, downloadUrl, downloadUrl; // Input: ServiceDownloadUrlRequest, Output: ServiceDownloadUrlMetadata
+    ServiceAggregationLayer, GameImages, game; // Input: ServiceGameImagesRequest, Output: ServiceGame
+    ServiceAggregationLayer, GetBasicPlayer, playerByPd; // Input: ServiceGetBasicPlayerRequest, Output: ServicePlayer
+    ServiceAggregationLayer, getPreloadedOwnedGames, me; // Input: ServiceGetPreloadedOwnedGamesRequest, Output: ServiceUser (with owned_game_products field set)
+    ServiceAggregationLayer, GetUserPlayer, me; // Input: ServiceGetUserPlayerRequest, Output: ServiceUser
+    ServiceAggregationLayer, GetInitUserData, me; // Input: ServiceGetInitUserDataRequest, Output: ServiceInitUserData
+    ServiceAggregationLayer, GameSystemRequirements, game; // Input: ServiceGameSystemRequirementsRequest, Output: ServiceGameSystemRequirements
+    ServiceAggregationLayer, GetMyFriends, me; // Input: ServiceGetMyFriendsRequest, Output: ServiceFriends
+    ServiceAggregationLayer, GetMyFriendInvitations, me; // Input: ServiceGetMyFriendInvitationsRequest, Output: ServiceFriendInvitations
+    ServiceAggregationLayer, SendFriendInvitation, friendInvitationSend; // Input: ServiceFriendInvitationRequest, Output: ServiceMutationResult
+    ServiceAggregationLayer, AcceptFriendInvitation, friendInvitationAccept; // Input: ServiceFriendInvitationRequest, Output: ServiceMutationResult
+    ServiceAggregationLayer, RejectFriendInvitation, friendInvitationReject; // Input: ServiceFriendInvitationRequest, Output: ServiceMutationResult
+    ServiceAggregationLayer, CancelFriendInvitation, friendInvitationCancel; // Input: ServiceFriendInvitationRequest, Output: ServiceMutationResult
+    ServiceAggregationLayer, SearchPlayer, players; // Input: ServiceSearchPlayerRequest, Output: ServicePlayersPage
+    ServiceAggregationLayer, getLegacyCatalogDefs, legacyOffers; // Input: ServiceGetLegacyCatalogDefsRequest, Output: Vec<ServiceLegacyOffer>
+    ServiceAggregationLayer, getLegacyEntitlements, me; // Input: ServiceGetLegacyEntitlementsRequest, Output: UserLegacyEntitlementsCursorPage
+    ServiceAggregationLayer, sdkEntitlements, me; // Input: ServiceSdkEntitlementsRequest, Output: SdkEntitlementsResult
+    ServiceAggregationLayer, getGameProducts, gameProducts; // Input: ServiceGetLegacyCatalogDefsRequest, Output: Vec<ServiceLegacyProduct>
+    ServiceAggregationLayer, GetGamePlayTimes, me; // Input: ServiceGetLegacyCatalogDefsRequest, Output: Vec<ServiceLegacyProduct>
+    ContentfulProxy, GetHeroBackgroundImage, gameHubCollection; // Input: ServiceHeroBackgroundImageRequest, Output: ServiceGameHubCollection
+}
+
+/// The operation called `name`, eg "GetUserPlayer", for requests that aren't known ahead
+/// of time, like ones coming from outside Rust
+pub fn operation_by_name(name: &str) -> Option<&'static ServiceLayerGraphQLRequest> {
+    SERVICE_REQUESTS
+        .iter()
+        .copied()
+        .find(|x| x.operation == name)
+}
+
+/// Somewhere to send service layer operations. [`ServiceLayerClient`] talks to EA's servers,
+/// while [`MockServiceLayer`] answers with canned data so code using it can be tested offline.
+#[async_trait]
+pub trait ServiceLayerBackend: Send + Sync {
+    /// Send `operation`, returning the value under its key in the response's `data`
+    async fn request_json(
+        &self,
+        operation: &ServiceLayerGraphQLRequest,
+        variables: Value,
+    ) -> Result<Value, ServiceLayerError>;
+}
+
+pub type SharedServiceLayer = Arc<dyn ServiceLayerBackend>;
+
+impl dyn ServiceLayerBackend {
+    pub async fn request<T, R>(
+        &self,
+        operation: &ServiceLayerGraphQLRequest,
+        variables: T,
+    ) -> Result<R, ServiceLayerError>
+    where
+        T: Serialize,
+        R: DeserializeOwned,
+    {
+        let data = self
+            .request_json(operation, serde_json::to_value(variables)?)
+            .await?;
+        Ok(serde_json::from_value::<R>(data)?)
+    }
+
+    /// Send the operation called `operation_name` with `variables` as a JSON string,
+    /// returning the response as one too
+    pub async fn request_raw(
+        &self,
+        operation_name: &str,
+        variables: &str,
+    ) -> Result<String, ServiceLayerError> {
+        let operation = operation_by_name(operation_name)
+            .ok_or_else(|| ServiceLayerError::UnknownOperation(operation_name.to_owned()))?;
+        let data = self
+            .request_json(operation, serde_json::from_str(variables)?)
+            .await?;
+        Ok(data.to_string())
+    }
+}
 
 #[derive(Clone)]
 pub struct ServiceLayerClient {
@@ -138,43 +258,84 @@ pub struct ServiceLayerClient {
     client: Client,
 }
 
+#[async_trait]
+impl ServiceLayerBackend for ServiceLayerClient {
+    async fn request_json(
+        &self,
+        operation: &ServiceLayerGraphQLRequest,
+        variables: Value,
+    ) -> Result<Value, ServiceLayerError> {
+        let variables = &variables;
+        Self::send_with_fallback(operation, move |mode| {
+            self.request2(operation, variables, mode)
+        })
+        .await
+    }
+}
+
 impl ServiceLayerClient {
     pub fn new(auth: LockedAuthStorage) -> Self {
         Self {
             auth,
-            client: Client::new(),
+            client: http::client(),
         }
     }
 
-    pub async fn request<T, R>(
-        &self,
+    /// Send `operation` with `send`, falling back to the full query when the persisted
+    /// one fails
+    async fn send_with_fallback<F, Fut>(
         operation: &ServiceLayerGraphQLRequest,
-        variables: T,
-    ) -> Result<R, ServiceLayerError>
+        send: F,
+    ) -> Result<Value, ServiceLayerError>
     where
-        T: Serialize,
-        R: for<'a> Deserialize<'a>,
+        F: Fn(QueryMode) -> Fut,
+        Fut: Future<Output = Result<Value, ServiceLayerError>>,
     {
-        let mut result = self.request2(operation, &variables, false).await;
+        // Mutations can't be sent as a GET, and shouldn't be sent twice when one is refused
+        let stale = STALE_QUERIES.lock().unwrap().contains(operation.operation);
+        if stale || operation.is_mutation() {
+            return send(QueryMode::FullUnhashed).await;
+        }
+
+        let mut result = send(QueryMode::Persisted).await;
 
         // On first error, try sending the full query
-        if result.is_err() {
-            result = self.request2(operation, variables, true).await;
+        if let Err(err) = &result {
+            let mode = if matches!(err, ServiceLayerError::PersistedQueryNotFound(_)) {
+                Self::mark_stale(operation);
+                QueryMode::FullUnhashed
+            } else {
+                QueryMode::Full
+            };
+
+            result = send(mode).await;
+        }
+
+        // The hash may also be rejected alongside the full query
+        if let Err(ServiceLayerError::PersistedQueryNotFound(_)) = &result {
+            Self::mark_stale(operation);
+            result = send(QueryMode::FullUnhashed).await;
         }
 
         result
     }
 
-    async fn request2<T, R>(
+    fn mark_stale(operation: &ServiceLayerGraphQLRequest) {
+        if STALE_QUERIES.lock().unwrap().insert(operation.operation) {
+            warn!(
+                "The server doesn't recognize the query for `{}`, the bundled query may be outdated. \
+                Sending the full query for the rest of the session",
+                operation.operation
+            );
+        }
+    }
+
+    async fn request2(
         &self,
         operation: &ServiceLayerGraphQLRequest,
-        variables: T,
-        full_query: bool,
-    ) -> Result<R, ServiceLayerError>
-    where
-        T: Serialize,
-        R: for<'a> Deserialize<'a>,
-    {
+        variables: &Value,
+        mode: QueryMode,
+    ) -> Result<Value, ServiceLayerError> {
         let extensions = ServiceExtensions {
             persisted_query: PersistedQuery {
                 version: 1,
@@ -183,14 +344,16 @@ impl ServiceLayerClient {
         };
 
         let host = match operation.r#type {
-            ServiceLayerRequestType::ServiceAggregationLayer => API_SERVICE_AGGREGATION_LAYER,
-            ServiceLayerRequestType::ContentfulProxy => API_CONTENTFUL_PROXY,
+            ServiceLayerRequestType::ServiceAggregationLayer => {
+                API_SERVICE_AGGREGATION_LAYER.as_str()
+            }
+            ServiceLayerRequestType::ContentfulProxy => API_CONTENTFUL_PROXY.as_str(),
         };
 
-        let mut request = if full_query {
-            self.client.post(host)
-        } else {
+        let mut request = if mode == QueryMode::Persisted {
             self.client.get(host)
+        } else {
+            self.client.post(host)
         };
 
         let access_token = self.auth.lock().await.access_token().await?;
@@ -198,9 +361,15 @@ impl ServiceLayerClient {
             request = request.header("Authorization", &("Bearer ".to_owned() + &access_token));
         }
 
-        let res = if full_query {
+        let res = if mode == QueryMode::Persisted {
+            request.query(&[
+                ("extensions", serde_json::to_string(&extensions)?.as_str()),
+                ("operationName", operation.operation),
+                ("variables", serde_json::to_string(&variables)?.as_str()),
+            ])
+        } else {
             let data = FullServiceRequest {
-                extensions,
+                extensions: (mode == QueryMode::Full).then_some(extensions),
                 variables,
                 operation_name: operation.operation,
                 query: operation.query,
@@ -209,18 +378,18 @@ impl ServiceLayerClient {
             request
                 .header("Content-Type", "application/json")
                 .body(serde_json::to_string(&data)?)
-        } else {
-            request.query(&[
-                ("extensions", serde_json::to_string(&extensions)?.as_str()),
-                ("operationName", operation.operation),
-                ("variables", serde_json::to_string(&variables)?.as_str()),
-            ])
         }
         .send()
         .await?;
 
         let status = res.status();
         let text = res.text().await?;
+        if mode != QueryMode::FullUnhashed && is_stale_query_error(&text) {
+            return Err(ServiceLayerError::PersistedQueryNotFound(
+                operation.operation.to_string(),
+            ));
+        }
+
         if status != StatusCode::OK {
             return Err(ServiceLayerError::Http {
                 status_code: status,
@@ -265,10 +434,89 @@ impl ServiceLayerClient {
             .ok_or(ServiceLayerError::NoData)?
             .to_owned();
 
-        Ok(serde_json::from_value::<R>(data)?)
+        Ok(data)
+    }
+}
+
+/// Answers operations with data registered up front, in place of EA's servers.
+/// Operations without a response fail with [`ServiceLayerError::NoData`].
+#[derive(Default)]
+pub struct MockServiceLayer {
+    responses: Mutex<HashMap<&'static str, Value>>,
+    /// Answers used once each, in order, before `responses`
+    queued: Mutex<HashMap<&'static str, VecDeque<Value>>>,
+    /// Every operation sent, answered or not
+    requests: Mutex<Vec<&'static str>>,
+}
+
+impl MockServiceLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer `operation` with `data`, which is what the server would return under the
+    /// operation's key, e.g. the `me` object for `getPreloadedOwnedGames`
+    pub fn with_response(self, operation: &ServiceLayerGraphQLRequest, data: Value) -> Self {
+        self.set_response(operation, data);
+        self
+    }
+
+    pub fn set_response(&self, operation: &ServiceLayerGraphQLRequest, data: Value) {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(operation.operation, data);
+    }
+
+    /// Answer `operation` with each of `data` in turn, like the pages of a list. Once
+    /// they run out, the response set with [`MockServiceLayer::set_response`] is used.
+    pub fn with_responses(self, operation: &ServiceLayerGraphQLRequest, data: Vec<Value>) -> Self {
+        self.queued
+            .lock()
+            .unwrap()
+            .insert(operation.operation, data.into());
+        self
+    }
+
+    /// Names of the operations sent so far, in order
+    pub fn requests(&self) -> Vec<&'static str> {
+        self.requests.lock().unwrap().clone()
     }
 }
 
+#[async_trait]
+impl ServiceLayerBackend for MockServiceLayer {
+    async fn request_json(
+        &self,
+        operation: &ServiceLayerGraphQLRequest,
+        _variables: Value,
+    ) -> Result<Value, ServiceLayerError> {
+        self.requests.lock().unwrap().push(operation.operation);
+
+        let queued = self
+            .queued
+            .lock()
+            .unwrap()
+            .get_mut(operation.operation)
+            .and_then(|x| x.pop_front());
+        if let Some(data) = queued {
+            return Ok(data);
+        }
+
+        self.responses
+            .lock()
+            .unwrap()
+            .get(operation.operation)
+            .cloned()
+            .ok_or(ServiceLayerError::NoData)
+    }
+}
+
+/// Whether a response says the server didn't accept a persisted query's hash
+fn is_stale_query_error(response: &str) -> bool {
+    STALE_QUERY_ERRORS.iter().any(|x| response.contains(x))
+}
+
 macro_rules! service_layer_type {
     ($name:ident, { $($field:tt)* }) => {
         paste::paste! {
@@ -328,6 +576,15 @@ service_layer_enum!(Storefront, {
 
 service_layer_enum!(Platform, { Pc, MacPc });
 
+impl ServicePlatform {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Pc => "Windows",
+            Self::MacPc => "Mac",
+        }
+    }
+}
+
 service_layer_type!(GetPreloadedOwnedGamesRequest, {
     is_mac: bool,
     locale: Locale,
@@ -345,11 +602,14 @@ service_layer_type!(GetUserPlayerRequest, {
     // but I'm not sure what they are.
 });
 
+service_layer_type!(GetInitUserDataRequest, {});
+
 service_layer_type!(AddonSearchRequest, {
     master_title_id: String,
     category_id: String,
     offer_ids: Vec<String>,
     platform: String,
+    override_country_code: Option<String>, // Storefront region, eg "US", or the service's pick
 });
 
 // Responses
@@ -430,6 +690,48 @@ service_layer_enum!(OwnershipMethod, {
     EpicVault,
 });
 
+impl ServiceOwnershipMethod {
+    /// Storefront other than EA that the game came from. Launching it may need that
+    /// storefront installed and logged in.
+    pub fn storefront(&self) -> Option<ServiceStorefront> {
+        match self {
+            Self::Steam | Self::SteamVault | Self::SteamSubscription => {
+                Some(ServiceStorefront::Steam)
+            }
+            Self::Epic | Self::EpicVault => Some(ServiceStorefront::Epic),
+            _ => None,
+        }
+    }
+
+    /// Whether the game is only available through a subscription, rather than owned outright
+    pub fn is_subscription(&self) -> bool {
+        matches!(
+            self,
+            Self::Vault
+                | Self::XgpVault
+                | Self::SteamVault
+                | Self::SteamSubscription
+                | Self::EpicVault
+        )
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Association | Self::EntitlementGrant | Self::DirectEntitlement => "Granted",
+            Self::Purchase | Self::PreOrderPurchase => "Purchased",
+            Self::Redemption => "Redeemed code",
+            Self::GiftReceipt | Self::GiftPurchase => "Gift",
+            Self::Vault => "EA Play Vault",
+            Self::XgpVault => "Xbox Game Pass",
+            Self::Steam => "via Steam",
+            Self::SteamVault | Self::SteamSubscription => "EA Play via Steam",
+            Self::Epic => "via Epic Games",
+            Self::EpicVault => "EA Play via Epic Games",
+        }
+    }
+}
+
 service_layer_enum!(OwnershipStatus, {
     Active,
     Disabled,
@@ -492,6 +794,11 @@ service_layer_type!(UserGameProductCursorPage, {
     items: Vec<ServiceUserGameProduct>,
 });
 
+service_layer_type!(InitUserData, {
+    id: String,
+    storefront_region: Option<String>,
+});
+
 service_layer_type!(User, {
     id: String,
     pd: Option<String>, // Persona ID
@@ -499,6 +806,17 @@ service_layer_type!(User, {
     owned_game_products: Option<ServiceUserGameProductCursorPage>,
 });
 
+impl ServiceUser {
+    /// Owned products from the first page, treating a missing page as an empty library.
+    /// Accounts that own nothing may not get a product page back at all.
+    pub fn owned_game_items(&self) -> &[ServiceUserGameProduct] {
+        match &self.owned_game_products {
+            Some(page) => page.items(),
+            None => &[],
+        }
+    }
+}
+
 service_layer_enum!(DownloadType, {
     Staged,
     Live,
@@ -537,6 +855,13 @@ impl ServiceAvailableBuild {
 
         str
     }
+
+    /// Day the build went live, without the time of day
+    pub fn live_day(&self) -> Option<&str> {
+        self.build_live_date
+            .as_deref()
+            .and_then(|date| date.split('T').next())
+    }
 }
 
 service_layer_type!(AvailableBuilds, {
@@ -553,6 +878,15 @@ impl ServiceAvailableBuilds {
         })
     }
 
+    /// The build of `channel`, or the live build when the channel has none right now
+    pub fn channel_build(&self, channel: BuildChannel) -> Option<&ServiceAvailableBuild> {
+        let download_type = channel.download_type();
+        self.builds
+            .iter()
+            .find(|b| b.download_type().as_ref() == Some(&download_type))
+            .or_else(|| self.live_build())
+    }
+
     pub fn build(&self, id: &str) -> Option<&ServiceAvailableBuild> {
         self.builds
             .iter()
@@ -574,6 +908,20 @@ service_layer_type!(DownloadUrlMetadata, {
     sync_url: Option<String>,
 });
 
+impl ServiceDownloadUrlMetadata {
+    /// Every URL the build can be downloaded from, the preferred one first
+    pub fn mirrors(&self) -> Vec<String> {
+        let mut urls = vec![self.url.clone()];
+        match &self.sync_url {
+            Some(sync_url) if !sync_url.is_empty() && *sync_url != self.url => {
+                urls.push(sync_url.clone())
+            }
+            _ => {}
+        }
+        urls
+    }
+}
+
 service_layer_type!(GrantEntitlementInput, {
     offer_id: String,
     source: Option<String>,
@@ -621,6 +969,17 @@ service_layer_type!(SystemRequirements, {
     platform: ServicePlatform,
 });
 
+impl ServiceSystemRequirements {
+    /// Best-effort structured view of `minimum` and `recommended`. Fields that
+    /// couldn't be recognized are left empty rather than failing.
+    pub fn parsed(&self) -> ParsedSystemRequirements {
+        ParsedSystemRequirements {
+            minimum: RequirementSpec::parse(&self.minimum),
+            recommended: RequirementSpec::parse(&self.recommended),
+        }
+    }
+}
+
 service_layer_type!(GameSystemRequirements, {
     id: String,
     game_type: ServiceGameProductType,
@@ -663,6 +1022,38 @@ service_layer_type!(Friends, {
     blocked_players: ServiceBlockedPlayerOffsetPage,
 });
 
+service_layer_type!(GetMyFriendInvitationsRequest, {
+    is_mutual_friends_enabled: bool,
+});
+
+service_layer_type!(FriendInvitation, {
+    id: String,
+    pd: String,
+    date: String,
+    player: ServicePlayer,
+});
+
+service_layer_type!(FriendInvitationPage, {
+    items: Vec<ServiceFriendInvitation>,
+});
+
+service_layer_type!(FriendInvitations, {
+    id: String,
+    pd: String,
+    /// Sent to the local user
+    inbound_friend_invitations: ServiceFriendInvitationPage,
+    /// Sent by the local user
+    outbound_friend_invitations: ServiceFriendInvitationPage,
+});
+
+service_layer_type!(FriendInvitationRequest, {
+    friend_pd: String,
+});
+
+service_layer_type!(MutationResult, {
+    success: bool,
+});
+
 service_layer_type!(SearchPlayerRequest, {
     is_mutual_friends_enabled: bool,
     page_number: u32,
@@ -722,9 +1113,19 @@ service_layer_type!(LegacyOffer, {
     monitor_play: Option<bool>,
     display_name: String,
     display_type: String,
+    /// Who published the game, as the catalog's publisher facet names it
+    publisher_facet_key: Option<String>,
     dip_manifest_relative_path: Option<String>,
     downloads: Vec<ServiceLegacyDownloadMetadata>,
     is_downloadable: bool,
+    /// Whether the offer is restricted to the countries it's sold in
+    grey_market_controls: Option<bool>,
+    /// Country the catalog was looked up for, the user's
+    applied_country_code: Option<String>,
+    download_start_date: Option<String>,
+    release_date: Option<String>,
+    /// When the offer stops being playable
+    use_end_date: Option<String>,
     cloud_save_configuration_override: Option<String>,
 });
 
@@ -736,6 +1137,62 @@ impl ServiceLegacyOffer {
             .unwrap_or_default()
             .is_empty()
     }
+
+    /// Where the executable is, relative to the install folder, for pre-DiP games whose
+    /// manifest doesn't say. The overrides start with the registry key the EA app keeps the
+    /// install folder in, like `[HKEY_LOCAL_MACHINE\SOFTWARE\EA Games\Crysis\Install Dir]Bin32\Crysis.exe`,
+    /// which Maxima already knows. The install check is used when there's no execute path
+    /// and it's an exe.
+    pub fn execute_path(&self) -> Option<String> {
+        let path = self
+            .execute_path_override
+            .as_deref()
+            .filter(|x| !x.trim().is_empty())
+            .or_else(|| {
+                self.install_check_override
+                    .as_deref()
+                    .filter(|x| x.to_ascii_lowercase().ends_with(".exe"))
+            })?;
+
+        let path = match path.rfind(']') {
+            Some(idx) => path[idx + 1..].replace('\\', "/"),
+            // Without a key it's relative to the folder games are installed into
+            None => {
+                let path = path.replace('\\', "/");
+                let dir = self
+                    .installation_directory
+                    .as_deref()
+                    .unwrap_or_default()
+                    .replace('\\', "/");
+                match path.strip_prefix(&(dir.trim_matches('/').to_owned() + "/")) {
+                    Some(path) if !dir.is_empty() => path.to_owned(),
+                    _ => path,
+                }
+            }
+        };
+
+        let path = path.trim_start_matches('/');
+        (!path.is_empty()).then(|| path.to_owned())
+    }
+
+    /// The download of `channel`, falling back to the live one. An offer with a single
+    /// download may not label it, so that one is used whatever the channel.
+    pub fn channel_download(
+        &self,
+        channel: BuildChannel,
+    ) -> Option<&ServiceLegacyDownloadMetadata> {
+        if self.downloads.len() == 1 {
+            return self.downloads.first();
+        }
+
+        let find = |download_type: &str| {
+            self.downloads
+                .iter()
+                .find(|x| x.download_type() == download_type)
+        };
+        find(channel.legacy_download_type())
+            .or_else(|| find(BuildChannel::Live.legacy_download_type()))
+    }
 }
 
 service_layer_type!(AddonOffer, {
@@ -769,6 +1226,26 @@ service_layer_type!(AddonOffer, {
     }
 );
 
+impl ServiceAddonOffer {
+    /// The current price, formatted in the offer's currency
+    pub fn formatted_price(&self) -> String {
+        Self::format_price(&self.display_price, self.price as f64, &self.currency)
+    }
+
+    /// The price before any discount, formatted in the offer's currency
+    pub fn formatted_list_price(&self) -> String {
+        Self::format_price(&self.display_list_price, self.list_price, &self.currency)
+    }
+
+    fn format_price(display: &str, raw: f64, currency: &str) -> String {
+        if !display.is_empty() {
+            return display.to_owned();
+        }
+
+        format!("{:.2} {}", raw, currency)
+    }
+}
+
 service_layer_type!(AddonSearchResultRoot, {
     addonSearch: ServiceAddonSearchResult,
 });
@@ -845,14 +1322,33 @@ service_layer_type!(SdkEntitlementsResult, {
 
 // Serde treats a field being null differently from the field not being there, so we need to do custom deserialization to handle this.
 impl ServiceImage {
+    /// The image's width and height. When the service leaves them out, they're taken from
+    /// the `<width>x<height>` file name the avatar service uses, or else `standard`.
+    pub fn dimensions_or(&self, standard: u16) -> (u16, u16) {
+        if let (Some(width), Some(height)) = (self.width, self.height) {
+            return (width, height);
+        }
+
+        let from_path = self
+            .path
+            .rsplit('/')
+            .next()
+            .and_then(|name| name.split('.').next())
+            .and_then(|name| name.split_once('x'))
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)));
+
+        let (width, height) = from_path.unwrap_or((standard, standard));
+        (self.width.unwrap_or(width), self.height.unwrap_or(height))
+    }
+
     fn deserialize_large_avatar<'de, D>(deserializer: D) -> Result<ServiceImage, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
         Ok(
             ServiceImage::deserialize(deserializer).unwrap_or(ServiceImage {
-                height: Some(416),
-                width: Some(416),
+                height: Some(LARGE_AVATAR_SIZE),
+                width: Some(LARGE_AVATAR_SIZE),
                 path: LARGE_AVATAR_PATH.to_owned(),
             }),
         )
@@ -864,8 +1360,8 @@ impl ServiceImage {
     {
         Ok(
             ServiceImage::deserialize(deserializer).unwrap_or(ServiceImage {
-                width: Some(208),
-                height: Some(208),
+                width: Some(MEDIUM_AVATAR_SIZE),
+                height: Some(MEDIUM_AVATAR_SIZE),
                 path: MEDIUM_AVATAR_PATH.to_owned(),
             }),
         )
@@ -877,10 +1373,135 @@ impl ServiceImage {
     {
         Ok(
             ServiceImage::deserialize(deserializer).unwrap_or(ServiceImage {
-                width: Some(40),
-                height: Some(40),
+                width: Some(SMALL_AVATAR_SIZE),
+                height: Some(SMALL_AVATAR_SIZE),
                 path: SMALL_AVATAR_PATH.to_owned(),
             }),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn legacy_offer_execute_path() {
+        let offer = |fields: serde_json::Value| {
+            let mut offer = json!({
+                "offerId": "OFB-EAST:48217",
+                "contentId": "71067",
+                "primaryMasterTitleId": "71067",
+                "displayName": "Crysis",
+                "displayType": "FullGame",
+                "downloads": [],
+                "isDownloadable": true,
+                "installationDirectory": "Crysis",
+            });
+            for (key, value) in fields.as_object().unwrap() {
+                offer[key] = value.clone();
+            }
+            serde_json::from_value::<ServiceLegacyOffer>(offer).unwrap()
+        };
+
+        let offer_with_key = offer(json!({
+            "executePathOverride": "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Crytek\\Crysis\\Install Dir]\\Bin32\\Crysis.exe",
+            "installCheckOverride": "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Crytek\\Crysis\\Install Dir]\\Game\\Config.pak",
+        }));
+        assert_eq!(
+            offer_with_key.execute_path().as_deref(),
+            Some("Bin32/Crysis.exe")
+        );
+
+        let install_check = offer(json!({
+            "installCheckOverride": "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Crytek\\Crysis\\Install Dir]Bin64\\Crysis64.EXE",
+        }));
+        assert_eq!(
+            install_check.execute_path().as_deref(),
+            Some("Bin64/Crysis64.EXE")
+        );
+
+        let relative = offer(json!({ "executePathOverride": "Crysis\\Bin32\\Crysis.exe" }));
+        assert_eq!(relative.execute_path().as_deref(), Some("Bin32/Crysis.exe"));
+
+        let not_an_exe = offer(json!({
+            "installCheckOverride": "[HKEY_LOCAL_MACHINE\\SOFTWARE\\Crytek\\Crysis\\Install Dir]\\Game\\Config.pak",
+        }));
+        assert_eq!(not_an_exe.execute_path(), None);
+    }
+
+    #[test]
+    fn avatar_dimensions_without_size() {
+        let image = |path: &str| ServiceImage {
+            width: None,
+            height: None,
+            path: path.to_owned(),
+        };
+
+        let named = image("https://eaavatarservice.akamaized.net/avatar/1/208x208.PNG?v=2");
+        let unnamed = image("https://eaavatarservice.akamaized.net/avatar/1/default.JPEG");
+        assert_eq!(named.dimensions_or(LARGE_AVATAR_SIZE), (208, 208));
+        assert_eq!(unnamed.dimensions_or(MEDIUM_AVATAR_SIZE), (208, 208));
+        assert_eq!(unnamed.dimensions_or(SMALL_AVATAR_SIZE), (40, 40));
+    }
+
+    #[tokio::test]
+    async fn raw_request_by_name() {
+        let service_layer: SharedServiceLayer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(SERVICE_REQUEST_GETUSERPLAYER, json!({ "id": "1234" })),
+        );
+
+        let response = service_layer
+            .request_raw("GetUserPlayer", "{}")
+            .await
+            .unwrap();
+        assert_eq!(response, r#"{"id":"1234"}"#);
+
+        let err = service_layer
+            .request_raw("NotAnOperation", "{}")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, ServiceLayerError::UnknownOperation(name) if name == "NotAnOperation")
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_queries_are_sent_in_full() {
+        assert!(is_stale_query_error(
+            r#"{"errors":[{"message":"PersistedQueryNotFound"}]}"#
+        ));
+        assert!(!is_stale_query_error(r#"{"data":{"me":null}}"#));
+
+        // Like the server once the bundled query is outdated: the hash is rejected, and
+        // only the full query gets an answer
+        let sent = Mutex::new(Vec::new());
+        let send = |mode: QueryMode| {
+            sent.lock().unwrap().push(mode);
+            async move {
+                match mode {
+                    QueryMode::FullUnhashed => Ok(json!({ "id": "1234" })),
+                    _ => Err(ServiceLayerError::PersistedQueryNotFound(
+                        "GetGamePlayTimes".to_owned(),
+                    )),
+                }
+            }
+        };
+
+        let operation = SERVICE_REQUEST_GETGAMEPLAYTIMES;
+        let response = ServiceLayerClient::send_with_fallback(operation, send).await;
+        assert_eq!(response.unwrap(), json!({ "id": "1234" }));
+        assert_eq!(
+            *sent.lock().unwrap(),
+            [QueryMode::Persisted, QueryMode::FullUnhashed]
+        );
+
+        // Remembered for the rest of the session
+        sent.lock().unwrap().clear();
+        let response = ServiceLayerClient::send_with_fallback(operation, send).await;
+        assert_eq!(response.unwrap(), json!({ "id": "1234" }));
+        assert_eq!(*sent.lock().unwrap(), [QueryMode::FullUnhashed]);
+    }
+}
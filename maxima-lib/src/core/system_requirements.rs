@@ -0,0 +1,478 @@
+//! Best-effort parsing of the EasyMark/HTML system requirement blurbs EA returns.
+//! The formatting varies wildly between titles, so every field is optional.
+
+use std::{arch::x86_64::CpuidResult, path::Path};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use sysinfo::{System, SystemExt};
+
+use crate::{core::auth::hardware::HardwareInfo, util::native::volume_space};
+
+lazy_static! {
+    static ref TAG_PATTERN: Regex = Regex::new(r"<[^>]*>").unwrap();
+    static ref LINE_BREAK_PATTERN: Regex =
+        Regex::new(r"(?i)<br\s*/?>|</li>|</p>|</div>|<li>").unwrap();
+    static ref SIZE_PATTERN: Regex =
+        Regex::new(r"(?i)(\d+(?:[.,]\d+)?)\s*(TB|GB|MB|G|M)\b").unwrap();
+    static ref CORES_PATTERN: Regex =
+        Regex::new(r"(?i)\b(dual|quad|hexa|six|octa|eight|\d+)[\s-]*cores?\b").unwrap();
+    static ref SSE41_PATTERN: Regex = Regex::new(r"(?i)\bSSE\s*4[.,]1\b").unwrap();
+    static ref SSE42_PATTERN: Regex = Regex::new(r"(?i)\bSSE\s*4[.,]2\b").unwrap();
+    // AVX2 and AVX-512 need AVX, so they count as asking for it
+    static ref AVX_PATTERN: Regex = Regex::new(r"(?i)\bAVX").unwrap();
+}
+
+/// Installed RAM is usually reported slightly under its nominal size, so allow some slack
+const RAM_TOLERANCE: f64 = 0.9;
+
+/// One tier (minimum or recommended) of a game's system requirements
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RequirementSpec {
+    pub os: Option<String>,
+    /// Some games list several processors, usually one per vendor
+    pub cpu: Vec<String>,
+    pub ram_mb: Option<u64>,
+    /// Some games list several graphics cards, usually one per vendor
+    pub gpu: Vec<String>,
+    pub vram_mb: Option<u64>,
+    pub storage_mb: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ParsedSystemRequirements {
+    pub minimum: RequirementSpec,
+    pub recommended: RequirementSpec,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequirementsVerdict {
+    /// Meets the recommended requirements
+    Recommended,
+    /// Meets the minimum requirements, but not the recommended ones
+    Minimum,
+    /// Falls short of the minimum requirements
+    BelowMinimum,
+    /// Nothing comparable could be parsed from the requirements
+    Unknown,
+}
+
+/// Instruction set extensions games list among their processor requirements
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CpuFeatures {
+    pub sse41: bool,
+    pub sse42: bool,
+    pub avx: bool,
+}
+
+impl CpuFeatures {
+    /// Read from leaf 1 of CPUID, or `None` if it couldn't be read and is all zeroes
+    pub fn from_cpuid(leaf1: &CpuidResult) -> Option<Self> {
+        if leaf1.ecx == 0 && leaf1.edx == 0 {
+            return None;
+        }
+
+        Some(Self {
+            sse41: leaf1.ecx & (1 << 19) != 0,
+            sse42: leaf1.ecx & (1 << 20) != 0,
+            avx: leaf1.ecx & (1 << 28) != 0,
+        })
+    }
+
+    /// Whether every extension in `required` is here
+    pub fn has_all(&self, required: &CpuFeatures) -> bool {
+        (self.sse41 || !required.sse41)
+            && (self.sse42 || !required.sse42)
+            && (self.avx || !required.avx)
+    }
+}
+
+/// The parts of this machine's hardware that can be compared against [`RequirementSpec`]
+#[derive(Clone, Debug, Default)]
+pub struct SystemSpecs {
+    pub ram_mb: u64,
+    pub cpu_cores: Option<u32>,
+    /// Extensions the processor supports, from [`HardwareInfo`]
+    pub cpu_features: Option<CpuFeatures>,
+    /// Free space on the drive the game is, or will be, installed to
+    pub free_disk_mb: Option<u64>,
+}
+
+impl SystemSpecs {
+    /// Query the current machine. Free disk space is only filled in when `install_path` is given.
+    pub fn current(install_path: Option<&Path>) -> Self {
+        let mut system = System::new();
+        system.refresh_memory();
+
+        let free_disk_mb = install_path
+            .and_then(volume_space)
+            .map(|(_, available)| available / 1024 / 1024);
+
+        Self {
+            ram_mb: system.total_memory() / 1024 / 1024,
+            cpu_cores: system.physical_core_count().map(|x| x as u32),
+            cpu_features: CpuFeatures::from_cpuid(&HardwareInfo::cached(1, None).cpu_details.flags),
+            free_disk_mb,
+        }
+    }
+}
+
+impl ParsedSystemRequirements {
+    /// Compare against `specs`. CPU and GPU model names can't be ranked reliably, so
+    /// only RAM, core counts, the CPU extensions asked for and disk space are taken into
+    /// account.
+    pub fn verdict(&self, specs: &SystemSpecs) -> RequirementsVerdict {
+        let minimum = self.minimum.met_by(specs);
+        let recommended = self.recommended.met_by(specs);
+
+        match (minimum, recommended) {
+            (Some(false), _) => RequirementsVerdict::BelowMinimum,
+            (_, Some(true)) => RequirementsVerdict::Recommended,
+            (Some(true), _) => RequirementsVerdict::Minimum,
+            _ => RequirementsVerdict::Unknown,
+        }
+    }
+}
+
+enum RequirementField {
+    Os,
+    Cpu,
+    Ram,
+    Gpu,
+    Vram,
+    Storage,
+}
+
+impl RequirementField {
+    fn from_label(label: &str) -> Option<Self> {
+        let label = label.to_lowercase();
+
+        // Order matters here, "video memory" would otherwise be caught as a GPU or RAM line
+        if label.contains("vram")
+            || label.contains("video memory")
+            || label.contains("video ram")
+            || label.contains("graphics memory")
+        {
+            Some(Self::Vram)
+        } else if label == "os" || label.starts_with("os ") || label.contains("operating system") {
+            Some(Self::Os)
+        } else if label.contains("processor") || label.contains("cpu") {
+            Some(Self::Cpu)
+        } else if label.contains("memory") || label.contains("ram") {
+            Some(Self::Ram)
+        } else if label.contains("graphics")
+            || label.contains("video")
+            || label.contains("gpu")
+            || label.contains("display")
+        {
+            Some(Self::Gpu)
+        } else if label.contains("storage")
+            || label.contains("disk")
+            || label.contains("drive")
+            || label.contains("space")
+        {
+            Some(Self::Storage)
+        } else {
+            None
+        }
+    }
+}
+
+impl RequirementSpec {
+    /// Parse a single tier. Lines that can't be understood are skipped.
+    pub fn parse(text: &str) -> Self {
+        let mut spec = Self::default();
+
+        for line in plain_lines(text) {
+            let (label, value) = match line.split_once(':') {
+                Some((label, value)) => (label.trim(), value.trim()),
+                None => continue,
+            };
+
+            if value.is_empty() {
+                continue;
+            }
+
+            let field = match RequirementField::from_label(label) {
+                Some(field) => field,
+                None => continue,
+            };
+
+            match field {
+                RequirementField::Os => {
+                    if spec.os.is_none() {
+                        spec.os = Some(value.to_owned());
+                    }
+                }
+                RequirementField::Cpu => spec.cpu.push(value.to_owned()),
+                RequirementField::Ram => spec.ram_mb = spec.ram_mb.or(parse_size_mb(value)),
+                RequirementField::Gpu => spec.gpu.push(value.to_owned()),
+                RequirementField::Vram => spec.vram_mb = spec.vram_mb.or(parse_size_mb(value)),
+                RequirementField::Storage => {
+                    spec.storage_mb = spec.storage_mb.or(parse_size_mb(value))
+                }
+            }
+        }
+
+        spec
+    }
+
+    /// A one-line summary, eg "Windows 10 64-bit · 8 GB RAM · 100 GB storage"
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(os) = &self.os {
+            parts.push(os.clone());
+        }
+
+        if let Some(ram) = self.ram_mb {
+            parts.push(format!("{} RAM", format_size_mb(ram)));
+        }
+
+        if let Some(gpu) = self.gpu.first() {
+            parts.push(gpu.clone());
+        }
+
+        if let Some(storage) = self.storage_mb {
+            parts.push(format!("{} storage", format_size_mb(storage)));
+        }
+
+        parts.join(" · ")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Whether `specs` satisfies this tier, or `None` if nothing could be compared
+    pub fn met_by(&self, specs: &SystemSpecs) -> Option<bool> {
+        let mut checks = Vec::new();
+
+        if let Some(ram) = self.ram_mb {
+            checks.push(specs.ram_mb as f64 >= ram as f64 * RAM_TOLERANCE);
+        }
+
+        if let (Some(required), Some(cores)) = (self.cpu_cores(), specs.cpu_cores) {
+            checks.push(cores >= required);
+        }
+
+        if let (Some(required), Some(features)) = (self.cpu_features(), specs.cpu_features) {
+            checks.push(features.has_all(&required));
+        }
+
+        if let (Some(storage), Some(free)) = (self.storage_mb, specs.free_disk_mb) {
+            checks.push(free >= storage);
+        }
+
+        if checks.is_empty() {
+            None
+        } else {
+            Some(checks.into_iter().all(|x| x))
+        }
+    }
+
+    /// The lowest core count mentioned across the listed processors, eg "Quad-core"
+    pub fn cpu_cores(&self) -> Option<u32> {
+        self.cpu
+            .iter()
+            .filter_map(|cpu| {
+                let captures = CORES_PATTERN.captures(cpu)?;
+                match captures[1].to_lowercase().as_str() {
+                    "dual" => Some(2),
+                    "quad" => Some(4),
+                    "hexa" | "six" => Some(6),
+                    "octa" | "eight" => Some(8),
+                    count => count.parse().ok(),
+                }
+            })
+            .min()
+    }
+
+    /// The extensions the listed processors mention, eg "AVX support required", or
+    /// `None` if there aren't any
+    pub fn cpu_features(&self) -> Option<CpuFeatures> {
+        let mentions = |pattern: &Regex| self.cpu.iter().any(|cpu| pattern.is_match(cpu));
+        let features = CpuFeatures {
+            sse41: mentions(&SSE41_PATTERN),
+            sse42: mentions(&SSE42_PATTERN),
+            avx: mentions(&AVX_PATTERN),
+        };
+
+        if features == CpuFeatures::default() {
+            None
+        } else {
+            Some(features)
+        }
+    }
+}
+
+/// Strip markup and split into trimmed, non-empty lines
+fn plain_lines(text: &str) -> Vec<String> {
+    let text = LINE_BREAK_PATTERN.replace_all(text, "\n");
+    let text = TAG_PATTERN.replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .replace('*', "");
+
+    text.lines()
+        .map(|line| line.trim().trim_start_matches(['-', '•']).trim().to_owned())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Extract the first size in a value, in megabytes
+pub fn parse_size_mb(value: &str) -> Option<u64> {
+    let captures = SIZE_PATTERN.captures(value)?;
+    let amount: f64 = captures[1].replace(',', ".").parse().ok()?;
+
+    let multiplier = match captures[2].to_uppercase().as_str() {
+        "TB" => 1024.0 * 1024.0,
+        "GB" | "G" => 1024.0,
+        _ => 1.0,
+    };
+
+    Some((amount * multiplier) as u64)
+}
+
+fn format_size_mb(mb: u64) -> String {
+    if mb >= 1024 && mb % 1024 == 0 {
+        format!("{} GB", mb / 1024)
+    } else if mb >= 1024 {
+        format!("{:.1} GB", mb as f64 / 1024.0)
+    } else {
+        format!("{} MB", mb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_html_requirements() {
+        let spec = RequirementSpec::parse(
+            "<b>OS:</b> 64-bit Windows 10<br><b>Processor (AMD):</b> AMD FX-8350<br>\
+             <b>Processor (Intel):</b> Core i5 6600K<br><b>Memory:</b> 8GB<br>\
+             <b>Graphics card (NVIDIA):</b> NVIDIA GeForce GTX 1050 Ti<br>\
+             <b>Hard-drive space:</b> 100 GB",
+        );
+
+        assert_eq!(spec.os.as_deref(), Some("64-bit Windows 10"));
+        assert_eq!(spec.cpu.len(), 2);
+        assert_eq!(spec.ram_mb, Some(8 * 1024));
+        assert_eq!(spec.gpu, vec!["NVIDIA GeForce GTX 1050 Ti".to_owned()]);
+        assert_eq!(spec.storage_mb, Some(100 * 1024));
+    }
+
+    #[test]
+    fn parse_list_requirements() {
+        let spec = RequirementSpec::parse(
+            "<ul><li>Operating System: Windows 11</li><li>RAM: 16 GB</li>\
+             <li>Video Memory: 4096 MB</li><li>Storage: 1.5 TB</li></ul>",
+        );
+
+        assert_eq!(spec.os.as_deref(), Some("Windows 11"));
+        assert_eq!(spec.ram_mb, Some(16 * 1024));
+        assert_eq!(spec.vram_mb, Some(4096));
+        assert_eq!(spec.storage_mb, Some(1536 * 1024));
+    }
+
+    #[test]
+    fn verdict() {
+        let requirements = ParsedSystemRequirements {
+            minimum: RequirementSpec::parse("Processor: Quad-core CPU<br>Memory: 8 GB"),
+            recommended: RequirementSpec::parse("Memory: 16 GB<br>Storage: 100 GB"),
+        };
+
+        let specs = SystemSpecs {
+            ram_mb: 15 * 1024 + 800,
+            cpu_cores: Some(6),
+            cpu_features: None,
+            free_disk_mb: Some(200 * 1024),
+        };
+        assert_eq!(
+            requirements.verdict(&specs),
+            RequirementsVerdict::Recommended
+        );
+
+        let specs = SystemSpecs {
+            ram_mb: 8 * 1024,
+            cpu_cores: Some(4),
+            cpu_features: None,
+            free_disk_mb: None,
+        };
+        assert_eq!(requirements.verdict(&specs), RequirementsVerdict::Minimum);
+
+        let specs = SystemSpecs {
+            ram_mb: 8 * 1024,
+            cpu_cores: Some(2),
+            cpu_features: None,
+            free_disk_mb: None,
+        };
+        assert_eq!(
+            requirements.verdict(&specs),
+            RequirementsVerdict::BelowMinimum
+        );
+
+        let unparsable = ParsedSystemRequirements::default();
+        assert_eq!(unparsable.verdict(&specs), RequirementsVerdict::Unknown);
+    }
+
+    #[test]
+    fn cpu_extensions() {
+        let spec = RequirementSpec::parse(
+            "Processor (Intel): Core i5 4690 with AVX2<br>Processor (AMD): Ryzen 3 1200",
+        );
+        assert_eq!(
+            spec.cpu_features(),
+            Some(CpuFeatures {
+                avx: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            RequirementSpec::parse("Processor: SSE 4.2 support").cpu_features(),
+            Some(CpuFeatures {
+                sse42: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            RequirementSpec::parse("Processor: Quad-core").cpu_features(),
+            None
+        );
+
+        let leaf1 = |ecx| CpuidResult {
+            eax: 0,
+            ebx: 0,
+            ecx,
+            edx: 0,
+        };
+        let without_avx = SystemSpecs {
+            ram_mb: 16 * 1024,
+            cpu_cores: Some(4),
+            cpu_features: CpuFeatures::from_cpuid(&leaf1(1 << 19 | 1 << 20)),
+            free_disk_mb: None,
+        };
+        let with_avx = SystemSpecs {
+            cpu_features: CpuFeatures::from_cpuid(&leaf1(1 << 19 | 1 << 20 | 1 << 28)),
+            ..without_avx.clone()
+        };
+        assert_eq!(spec.met_by(&without_avx), Some(false));
+        assert_eq!(spec.met_by(&with_avx), Some(true));
+
+        // CPUID that couldn't be read doesn't count against the machine
+        let unread = SystemSpecs {
+            cpu_features: CpuFeatures::from_cpuid(&leaf1(0)),
+            ..without_avx
+        };
+        assert_eq!(unread.cpu_features, None);
+        assert_eq!(spec.met_by(&unread), None);
+    }
+
+    #[test]
+    fn parse_garbage() {
+        assert!(RequirementSpec::parse("Coming soon").is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+use std::time::Instant;
+
+use log::{info, warn};
+
+use crate::{
+    content::{manager::ContentManager, ContentService},
+    gameinfo::channel::build_channel,
+};
+
+use super::{library::OwnedOffer, service_layer::ServiceAvailableBuild, Maxima, MaximaEvent};
+
+impl Maxima {
+    /// Look for new builds of installed games, if it's been long enough since the last
+    /// look. The look runs in the background so it doesn't hold up the caller, and what
+    /// it found is sent on the first update after it finishes.
+    pub(super) async fn poll_updates(&mut self) {
+        if self.update_poll.as_ref().is_some_and(|x| x.is_finished()) {
+            let poll = self.update_poll.take().unwrap();
+            match poll.await {
+                Ok(found) => self.notify_updates(found),
+                Err(err) => warn!("Failed to check for game updates: {}", err),
+            }
+        }
+
+        if self.update_poll.is_some() {
+            return;
+        }
+
+        let interval = match self.update_poll_interval {
+            Some(interval) => interval,
+            None => return,
+        };
+        if self
+            .last_update_poll
+            .is_some_and(|x| x.elapsed() < interval)
+        {
+            return;
+        }
+        self.last_update_poll = Some(Instant::now());
+
+        let offers: Vec<OwnedOffer> = match self.library.launchable_games().await {
+            Ok(titles) => titles.into_iter().map(|x| x.base_offer().clone()).collect(),
+            Err(err) => {
+                warn!("Failed to check for game updates: {}", err);
+                return;
+            }
+        };
+
+        let offers = update_candidates(offers, &self.content_manager);
+        if offers.is_empty() {
+            return;
+        }
+
+        let service = self.content_manager.service().clone();
+        self.update_poll = Some(tokio::spawn(find_updates(service, offers)));
+    }
+
+    /// Send [`MaximaEvent::UpdateAvailable`] for builds that haven't been sent yet, unless
+    /// the game started installing while they were being looked up
+    fn notify_updates(&mut self, found: Vec<(String, ServiceAvailableBuild)>) {
+        for (offer_id, build) in found {
+            if self.notified_builds.get(&offer_id) == Some(build.build_id())
+                || self.content_manager.is_installing(&offer_id)
+            {
+                continue;
+            }
+
+            self.notified_builds
+                .insert(offer_id.clone(), build.build_id().to_owned());
+            self.call_event(MaximaEvent::UpdateAvailable { offer_id, build });
+        }
+    }
+}
+
+/// Offers Maxima has installed and isn't installing right now. Installs it didn't make
+/// aren't looked for, that's left to the user.
+fn update_candidates(offers: Vec<OwnedOffer>, content: &ContentManager) -> Vec<OwnedOffer> {
+    offers
+        .into_iter()
+        .filter(|x| x.has_install_info() && !content.is_installing(x.offer_id()))
+        .collect()
+}
+
+/// Offer ID and build of the games with a newer build on their channel than the one
+/// installed. Builds go through the content service's cache, so most games aren't
+/// requested again on every poll, and the look stops at the first failed request.
+async fn find_updates(
+    service: ContentService,
+    offers: Vec<OwnedOffer>,
+) -> Vec<(String, ServiceAvailableBuild)> {
+    let mut found = Vec::new();
+
+    for offer in offers {
+        let builds = match service.available_builds(offer.offer_id()).await {
+            Ok(builds) => builds,
+            Err(err) => {
+                warn!("Failed to check {} for updates: {}", offer.slug(), err);
+                break;
+            }
+        };
+        let build = match builds.channel_build(build_channel(offer.slug())) {
+            Some(build) => build.clone(),
+            None => continue,
+        };
+
+        let installed = offer.installed_version().await.ok();
+        if build.game_version().is_none() || build.game_version() == &installed {
+            continue;
+        }
+
+        info!(
+            "Build {} of {} is available",
+            build.to_string(),
+            offer.slug()
+        );
+        found.push((offer.offer_id().to_owned(), build));
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        content::{
+            manager::tests::{queued_game, write_queue_file},
+            tests::memory_service,
+        },
+        core::{
+            library::tests::owned_offer,
+            service_layer::{MockServiceLayer, SERVICE_REQUEST_AVAILABLEBUILDS},
+            MaximaOptionsBuilder,
+        },
+        gameinfo::GameInstallInfo,
+        util::temp_dir::TempDataDir,
+    };
+
+    use super::*;
+
+    fn builds_json(build_id: &str, version: &str) -> serde_json::Value {
+        serde_json::json!([{
+            "buildId": build_id,
+            "downloadType": "LIVE",
+            "gameVersion": version,
+            "buildReleaseVersion": null,
+            "buildLiveDate": null,
+        }])
+    }
+
+    #[tokio::test]
+    async fn updates_are_sent_once_per_build() {
+        // Maxima loads its download queue and request cache from here
+        let _data = TempDataDir::new();
+        let service_layer = Arc::new(
+            MockServiceLayer::new()
+                .with_response(SERVICE_REQUEST_AVAILABLEBUILDS, builds_json("2", "1.1")),
+        );
+        let maxima_arc = Maxima::new_with_options(
+            MaximaOptionsBuilder::default()
+                .load_auth_storage(false)
+                .dummy_local_user(true)
+                .service_layer(service_layer.clone())
+                .build()
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+        let mut maxima = maxima_arc.lock().await;
+
+        let service = memory_service(service_layer.clone());
+        let offer = owned_offer("update-poll-test", "Origin.OFR.50.716", "ACTIVE");
+
+        for _ in 0..2 {
+            let found = find_updates(service.clone(), vec![offer.clone()]).await;
+            assert_eq!(found.len(), 1);
+            maxima.notify_updates(found);
+        }
+
+        let updates: Vec<MaximaEvent> = maxima
+            .consume_pending_events()
+            .into_iter()
+            .filter(|x| matches!(x, MaximaEvent::UpdateAvailable { .. }))
+            .collect();
+        assert_eq!(updates.len(), 1);
+        // The second look was served from the cache
+        assert_eq!(service_layer.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn installing_games_are_skipped() {
+        let dir = TempDataDir::new();
+        let slug = "update-poll-test";
+        let offer = owned_offer(slug, "Origin.OFR.50.717", "ACTIVE");
+        GameInstallInfo::new(dir.join(slug), None).save_to_json(slug);
+
+        let queue_file = dir.join("download_queue.json");
+        write_queue_file(
+            &queue_file,
+            vec![queued_game(offer.offer_id(), slug, &dir.join(slug))],
+            Vec::new(),
+        );
+
+        let service_layer = Arc::new(MockServiceLayer::new());
+        let installing = ContentManager::with_queue_file(service_layer.clone(), &queue_file)
+            .await
+            .unwrap();
+        let candidates = update_candidates(vec![offer.clone()], &installing);
+
+        std::fs::remove_file(&queue_file).unwrap();
+        let idle = ContentManager::with_queue_file(service_layer, &queue_file)
+            .await
+            .unwrap();
+        let idle_candidates = update_candidates(vec![offer], &idle);
+
+        assert!(candidates.is_empty());
+        assert_eq!(idle_candidates.len(), 1);
+    }
+}
@@ -0,0 +1,95 @@
+use std::{collections::HashMap, fs, path::PathBuf, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::GameVersionError;
+use crate::{
+    core::service_layer::ServiceDownloadType,
+    util::native::{maxima_dir, NativeError},
+};
+
+const CHANNELS_FILE: &str = "build_channels.json";
+
+/// Which of a game's builds gets installed, and updated to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BuildChannel {
+    /// The build everyone plays
+    #[default]
+    Live,
+    /// The build EA is about to release. May be unstable, and may not exist at all.
+    Staged,
+}
+
+impl BuildChannel {
+    pub fn download_type(&self) -> ServiceDownloadType {
+        match self {
+            BuildChannel::Live => ServiceDownloadType::Live,
+            BuildChannel::Staged => ServiceDownloadType::Staged,
+        }
+    }
+
+    /// How the offer's download list labels the channel
+    pub fn legacy_download_type(&self) -> &'static str {
+        match self {
+            BuildChannel::Live => "LIVE",
+            BuildChannel::Staged => "STAGED",
+        }
+    }
+}
+
+impl FromStr for BuildChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "live" => Ok(BuildChannel::Live),
+            "staged" => Ok(BuildChannel::Staged),
+            _ => Err(format!(
+                "unknown build channel `{}`, expected live or staged",
+                s
+            )),
+        }
+    }
+}
+
+fn channels_path() -> Result<PathBuf, NativeError> {
+    Ok(maxima_dir()?.join(CHANNELS_FILE))
+}
+
+fn load_channels() -> HashMap<String, BuildChannel> {
+    channels_path()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// The channel chosen for `slug`, [`BuildChannel::Live`] unless it was changed
+pub fn build_channel(slug: &str) -> BuildChannel {
+    load_channels().get(slug).copied().unwrap_or_default()
+}
+
+/// Install and update `slug` from `channel` from now on. Only saved when it isn't live.
+pub fn set_build_channel(slug: &str, channel: BuildChannel) -> Result<(), GameVersionError> {
+    let mut channels = load_channels();
+    if channel == BuildChannel::Live {
+        channels.remove(slug);
+    } else {
+        channels.insert(slug.to_owned(), channel);
+    }
+
+    fs::write(channels_path()?, serde_json::to_string_pretty(&channels)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_channel_names() {
+        assert_eq!("Staged".parse(), Ok(BuildChannel::Staged));
+        assert_eq!("live".parse(), Ok(BuildChannel::Live));
+        assert!("beta".parse::<BuildChannel>().is_err());
+    }
+}
@@ -5,6 +5,9 @@ use std::path::PathBuf;
 use crate::util::native::maxima_dir;
 use thiserror::Error;
 
+pub mod channel;
+pub mod stats;
+
 #[derive(Error, Debug)]
 pub enum GameVersionError {
     #[error(transparent)]
@@ -50,17 +53,35 @@ pub struct GameInstallInfo {
         serialize_with = "prefix_to_string"
     )]
     pub wine_prefix: Option<PathBuf>,
+    /// Where the files actually live, when `path` is a junction/symlink to another drive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub real_path: Option<PathBuf>,
 }
 
 impl GameInstallInfo {
     pub fn new(path: PathBuf, wine_prefix: Option<PathBuf>) -> Self {
-        Self { path, wine_prefix }
+        Self {
+            path,
+            wine_prefix,
+            real_path: None,
+        }
+    }
+
+    pub fn with_real_path(mut self, real_path: Option<PathBuf>) -> Self {
+        self.real_path = real_path;
+        self
     }
 
+    /// The location the game expects to be installed at. May be a link, see [`Self::real_path`].
     pub fn path(&self) -> PathBuf {
         self.path.clone()
     }
 
+    /// The folder the game's files are stored in, following the link if there is one
+    pub fn real_path(&self) -> PathBuf {
+        self.real_path.clone().unwrap_or_else(|| self.path.clone())
+    }
+
     pub fn wine_prefix(&self) -> Option<PathBuf> {
         self.wine_prefix.clone()
     }
@@ -73,6 +94,7 @@ impl GameInstallInfo {
             if let Ok(_) = std::fs::create_dir_all(&path.as_ref().unwrap()) {
                 path.as_mut().unwrap().push(format!("{}.json", slug));
                 fs::write(path.unwrap(), json).unwrap();
+                stats::forget_installed_size(slug);
             }
         }
     }
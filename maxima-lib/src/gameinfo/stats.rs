@@ -0,0 +1,121 @@
+use std::{fs, io, path::Path, path::PathBuf};
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{GameInstallInfo, GameVersionError};
+use crate::util::native::{maxima_dir, NativeError};
+
+const STATS_DIR: &str = "gamestats";
+
+/// Install size and last launch of a game, in `maxima_dir()/gamestats/<slug>.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GameStats {
+    /// Bytes the game's files take up. Cleared when the install info changes, and
+    /// measured again the next time it's needed.
+    #[serde(default)]
+    installed_size: Option<u64>,
+    /// When the game was last launched, in seconds since epoch
+    #[serde(default)]
+    last_played: Option<u64>,
+}
+
+fn stats_path(slug: &str) -> Result<PathBuf, NativeError> {
+    Ok(maxima_dir()?.join(STATS_DIR).join(format!("{}.json", slug)))
+}
+
+impl GameStats {
+    /// The stats saved for `slug`, empty if there are none yet
+    pub fn load(slug: &str) -> Self {
+        stats_path(slug)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, slug: &str) -> Result<(), GameVersionError> {
+        let path = stats_path(slug)?;
+        fs::create_dir_all(maxima_dir()?.join(STATS_DIR))?;
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The last measured install size, see [`installed_size`] to measure it if needed
+    pub fn installed_size(&self) -> Option<u64> {
+        self.installed_size
+    }
+
+    pub fn last_played(&self) -> Option<DateTime<Utc>> {
+        self.last_played
+            .and_then(|secs| Utc.timestamp_opt(secs as i64, 0).single())
+    }
+}
+
+/// Remember that `slug` was launched just now
+pub fn record_played(slug: &str) -> Result<(), GameVersionError> {
+    let mut stats = GameStats::load(slug);
+    stats.last_played = Some(Utc::now().timestamp() as u64);
+    stats.save(slug)
+}
+
+/// Drop the measured size of `slug`, since its files have changed
+pub(crate) fn forget_installed_size(slug: &str) {
+    let mut stats = GameStats::load(slug);
+    if stats.installed_size.take().is_some() {
+        let _ = stats.save(slug);
+    }
+}
+
+/// Bytes the files of the install at `info` take up. Measured once, then kept until
+/// the install info changes.
+pub async fn installed_size(slug: &str, info: &GameInstallInfo) -> Result<u64, GameVersionError> {
+    let mut stats = GameStats::load(slug);
+    if let Some(size) = stats.installed_size {
+        return Ok(size);
+    }
+
+    let path = info.real_path();
+    let size = tokio::task::spawn_blocking(move || dir_size(&path))
+        .await
+        .map_err(io::Error::from)??;
+
+    stats.installed_size = Some(size);
+    stats.save(slug)?;
+    Ok(size)
+}
+
+/// Total size of the files under `path`. Links to folders aren't followed.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += entry.metadata()?.len();
+        }
+    }
+
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn dir_size_counts_nested_files() {
+        let root = TempDir::new("size");
+        fs::create_dir_all(root.join("data/maps")).unwrap();
+        fs::write(root.join("game.exe"), vec![0; 100]).unwrap();
+        fs::write(root.join("data/maps/level.bin"), vec![0; 250]).unwrap();
+
+        let size = dir_size(root.path()).unwrap();
+
+        assert_eq!(size, 350);
+    }
+}
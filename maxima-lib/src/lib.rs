@@ -15,5 +15,7 @@ pub mod util;
 #[cfg(unix)]
 pub mod unix;
 
+pub use util::build_info::build_info;
+
 #[cfg(not(target_arch = "x86_64"))]
 compile_error!("Only x86_64 is supported at the moment");
@@ -9,7 +9,7 @@ use crate::{
         types::{LSXRequestLicense, LSXRequestLicenseResponse, LSXResponseType},
     },
     make_lsx_handler_response,
-    ooa::{request_license, LicenseAuth},
+    ooa::{request_license, LicenseAuth, LicenseRequestToken},
 };
 
 pub async fn handle_license_request(
@@ -41,13 +41,16 @@ pub async fn handle_license_request(
     };
 
     // TODO: how to get version
-    let hw_info = HardwareInfo::new(2, slug.as_deref());
+    let hw_info = HardwareInfo::cached(2, slug.as_deref());
+    let request_token = LicenseRequestToken {
+        token: request.attr_RequestTicket,
+        kind: request.attr_TicketEngine,
+    };
     let license = request_license(
         &content_id,
         &hw_info.generate_hardware_hash(),
         &auth,
-        Some(request.attr_RequestTicket.as_str()),
-        Some(request.attr_TicketEngine.as_str()),
+        Some(&request_token),
     )
     .await?;
 
@@ -1,3 +1,5 @@
+use log::warn;
+
 use crate::{
     core::service_layer::{
         ServiceAddonSearchRequestBuilder, ServiceAddonSearchResultRoot, SERVICE_REQUEST_ADDONSEARCH,
@@ -24,6 +26,15 @@ pub async fn handle_query_offers_request(
 
     let mut conn = conn.write().await;
     let maxima = conn.maxima().await;
+    // Without it prices come in whatever currency the service picks, like before
+    // they were requested in the account's region
+    let region = match maxima.region().await {
+        Ok(region) => Some(region),
+        Err(err) => {
+            warn!("Failed to look up the storefront region: {}", err);
+            None
+        }
+    };
     let offers: ServiceAddonSearchResultRoot = maxima
         .service_layer()
         .request(
@@ -33,6 +44,7 @@ pub async fn handle_query_offers_request(
                 .category_id(category)
                 .master_title_id(String::new())
                 .offer_ids(Vec::new())
+                .override_country_code(region)
                 .build()
                 .unwrap(),
         )
@@ -41,17 +53,17 @@ pub async fn handle_query_offers_request(
     for offer in offers.addonSearch().addonOffers() {
         rtn.push(LSXOffer {
             attr_InventorySold: 0,
-            attr_LocalizedPrice: offer.display_list_price().to_string(),
-            attr_OriginalPrice: offer.price().to_string(),
+            attr_LocalizedPrice: offer.formatted_price(),
+            attr_OriginalPrice: offer.list_price().to_string(),
             attr_DownloadDate: "0000-00-00T00:00:00".to_string(),
             attr_Currency: offer.currency().to_string(),
             attr_InventoryAvailable: 0,
             attr_PurchaseDate: "0000-00-00T00:00:00".to_string(),
             attr_DownloadSize: 0,
             attr_bCanPurchase: offer.user_can_purchase().clone(),
-            attr_Price: offer.list_price().to_string(),
+            attr_Price: offer.price().to_string(),
             attr_Type: offer.origin_display_type().to_string(),
-            attr_LocalizedOriginalPrice: offer.display_price().to_string(),
+            attr_LocalizedOriginalPrice: offer.formatted_list_price(),
             attr_InventoryCap: 0,
             attr_Description: offer.long_description().to_string(),
             attr_bHidden: !offer.is_published().clone(),
@@ -1,4 +1,4 @@
-use log::{debug, info};
+use log::{debug, info, warn};
 
 use crate::core::service_layer::{
     ServiceFriends, ServiceGetMyFriendsRequestBuilder, ServiceLayerError,
@@ -17,7 +17,7 @@ use crate::{
         },
     },
     make_lsx_handler_response,
-    rtm::client::{BasicPresence, RichPresenceBuilder},
+    rtm::client::{BasicPresence, RichPresence, RichPresenceBuilder},
     util::native::{platform_path, NativeError, SafeStr},
 };
 
@@ -55,19 +55,79 @@ pub async fn handle_profile_request(
     })
 }
 
+fn lsx_presence(presence: &RichPresence) -> LSXPresence {
+    if let Some(activity) = presence.activity() {
+        if activity.can_join() {
+            return LSXPresence::Joinable;
+        } else if *activity.joinable() && *activity.joinable_invite_only() {
+            return LSXPresence::JoinableInviteOnly;
+        }
+    }
+
+    if presence.game().is_some() {
+        return LSXPresence::Ingame;
+    }
+
+    match presence.basic() {
+        BasicPresence::Unknown => LSXPresence::Unknown,
+        BasicPresence::Offline => LSXPresence::Offline,
+        BasicPresence::Dnd => LSXPresence::Busy,
+        BasicPresence::Away => LSXPresence::Idle,
+        BasicPresence::Online => LSXPresence::Online,
+    }
+}
+
 pub async fn handle_presence_request(
-    _: LockedConnectionState,
-    _: LSXGetPresence,
+    state: LockedConnectionState,
+    request: LSXGetPresence,
 ) -> Result<Option<LSXResponseType>, LSXRequestError> {
+    let mut state = state.write().await;
+    let mut maxima = state.maxima().await;
+
+    let user_id = request.attr_UserId.to_string();
+    // Not knowing who we are only matters for our own presence, so it doesn't fail the request
+    let local = match maxima.local_user().await {
+        Ok(user) => user.id() == &user_id,
+        Err(err) => {
+            warn!("Failed to get the local user for GetPresence: {}", err);
+            false
+        }
+    };
+    let join = maxima
+        .playing()
+        .as_ref()
+        .and_then(|x| x.join().clone())
+        .filter(|x| *x.user_id() == user_id);
+
+    let presence_store = maxima.rtm().presence_store().lock().await;
+    let presence = presence_store.get(&user_id);
+    let activity = presence.as_ref().and_then(|x| x.activity().as_ref());
+
+    // The game we were launched to join asks for the friend's session. Their presence
+    // may have expired from the store since, so fall back to what we launched with.
+    let mut status = match presence.as_ref() {
+        Some(presence) => lsx_presence(presence),
+        // Our own presence isn't in the store, but we know what we're doing
+        None if local => LSXPresence::Ingame,
+        None => LSXPresence::Offline,
+    };
+    let mut session = activity.and_then(|x| x.session().clone());
+    let mut multiplayer_id = activity.and_then(|x| x.multiplayer_id().clone());
+    if let Some(join) = join {
+        status = LSXPresence::Joinable;
+        session = session.or_else(|| Some(join.session().to_owned()));
+        multiplayer_id = multiplayer_id.or_else(|| join.multiplayer_id().clone());
+    }
+
     make_lsx_handler_response!(Response, GetPresenceResponse, {
-       attr_UserId: 1005663144213,
-       attr_Presence: LSXPresence::Ingame,
-       attr_Title: None,
-       attr_TitleId: None,
-       attr_MultiplayerId: None,
-       attr_RichPresence: None,
-       attr_GamePresence: None,
-       attr_SessionId: None,
+       attr_UserId: request.attr_UserId,
+       attr_Presence: status,
+       attr_Title: activity.and_then(|x| x.title().clone()),
+       attr_TitleId: presence.as_ref().and_then(|x| x.game().clone()),
+       attr_MultiplayerId: multiplayer_id,
+       attr_RichPresence: activity.and_then(|x| x.rich_presence().clone()),
+       attr_GamePresence: session.clone(),
+       attr_SessionId: session,
        attr_Group: None,
        attr_GroupId: None,
     })
@@ -102,11 +162,7 @@ pub async fn handle_set_presence_request(
     if let Some(presence) = request.attr_RichPresence {
         maxima
             .rtm()
-            .set_presence(
-                BasicPresence::Online,
-                &format!("{}: {}", name, presence),
-                &offer_id,
-            )
+            .set_game_presence(&format!("{}: {}", name, presence), &offer_id)
             .await?;
     }
 
@@ -162,11 +218,11 @@ pub async fn handle_query_friends_request(
     let mut state = state.write().await;
     let mut maxima = state.maxima().await;
 
-    let friends = maxima.friends(0).await?;
+    let friends = maxima.all_friends().await?;
     let presence_store = maxima.rtm().presence_store().lock().await;
 
     let mut lsx_friends = Vec::new();
-    for ele in friends {
+    for ele in friends.friends() {
         if ele.relationship() != "FRIEND" {
             continue;
         }
@@ -180,24 +236,12 @@ pub async fn handle_query_friends_request(
                 .unwrap()
         });
 
-        let mut lsx_presence = match presence.basic() {
-            BasicPresence::Unknown => LSXPresence::Unknown,
-            BasicPresence::Offline => LSXPresence::Offline,
-            BasicPresence::Dnd => LSXPresence::Busy,
-            BasicPresence::Away => LSXPresence::Idle,
-            BasicPresence::Online => LSXPresence::Online,
-        };
-
         let game = if let Some(game) = presence.game() {
             game.to_owned()
         } else {
             String::new()
         };
 
-        if !game.is_empty() {
-            lsx_presence = LSXPresence::Ingame;
-        }
-
         lsx_friends.push(LSXFriend {
             attr_TitleId: "".to_string(),
             attr_MultiplayerId: "".to_string(),
@@ -210,7 +254,7 @@ pub async fn handle_query_friends_request(
             attr_AvatarId: format!("user:{}", ele.id()).to_string(),
             attr_Group: "".to_string(),
             attr_GroupId: "".to_string(),
-            attr_Presence: lsx_presence,
+            attr_Presence: lsx_presence(&presence),
             attr_State: LSXFriendState::Mutual,
         });
     }
@@ -226,6 +270,11 @@ pub async fn handle_get_block_list_request(
 
     let mut maxima = state.write().await;
     let maxima = maxima.maxima().await;
+    // There's no one to have blocked without an account
+    if maxima.dummy_local_user() {
+        return make_lsx_handler_response!(Response, GetBlockListResponse, { attr_Return: "Success".to_string(), User: list});
+    }
+
     let friends: ServiceFriends = maxima
         .service_layer()
         .request(
@@ -2,6 +2,7 @@ use std::{io::ErrorKind, net::TcpListener, time::Duration};
 
 use log::{info, warn};
 use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
 
 use crate::lsx::connection::LSXConnectionError;
 use crate::{core::LockedMaxima, lsx::connection::Connection};
@@ -15,7 +16,12 @@ pub enum LSXServerError {
     Io(#[from] std::io::Error),
 }
 
-pub async fn start_server(port: u16, maxima: LockedMaxima) -> Result<(), LSXServerError> {
+/// Serve LSX connections until `shutdown` is cancelled
+pub async fn start_server(
+    port: u16,
+    maxima: LockedMaxima,
+    shutdown: CancellationToken,
+) -> Result<(), LSXServerError> {
     let addr = "127.0.0.1:".to_string() + port.to_string().as_str();
 
     let listener = TcpListener::bind(&addr)?;
@@ -25,6 +31,11 @@ pub async fn start_server(port: u16, maxima: LockedMaxima) -> Result<(), LSXServ
     let mut connections: Vec<Connection> = Vec::new();
 
     loop {
+        if shutdown.is_cancelled() {
+            info!("Stopping LSX server");
+            return Ok(());
+        }
+
         let mut idx = 0 as usize;
         while idx < connections.len() {
             let connection = &mut connections[idx];
@@ -17,12 +17,14 @@ use base64::{engine::general_purpose, DecodeError, Engine};
 use crate::core::{auth::hardware::HardwareInfo, endpoints::API_PROXY_NOVAFUSION_LICENSES};
 #[cfg(unix)]
 use crate::unix::fs::case_insensitive_path;
-use crate::util::native::{NativeError, SafeParent, SafeStr};
-use lazy_static::lazy_static;
+use crate::util::{
+    account::AccountIdentifier,
+    http,
+    native::{program_data_dir, NativeError, SafeParent},
+};
 use quick_xml::DeError;
-use regex::Regex;
 use reqwest::header::ToStrError;
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -33,14 +35,9 @@ pub const OOA_CRYPTO_KEY: [u8; 16] = [
 type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
 type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
 
-lazy_static! {
-    static ref EMAIL_PATTERN: Regex = Regex::new(
-        r"^([a-z0-9_+]([a-z0-9_+.]*[a-z0-9_+])?)@([a-z0-9]+([\-\.]{1}[a-z0-9]+)*\.[a-z]{2,6})"
-    )
-    .unwrap();
-}
-
-const LICENSE_PATH: &str = "ProgramData/Electronic Arts/EA Services/License";
+pub(crate) const LICENSE_PATH: &str = "Electronic Arts/EA Services/License";
+/// Saved licenses start with their signature, padded with zeroes to this size
+const LICENSE_SIGNATURE_SIZE: usize = 65;
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "PascalCase")]
@@ -96,6 +93,8 @@ pub enum LicenseError {
 
     #[error("license request failed: `{0}`")]
     Http(String),
+    #[error("license file is too short to hold a signature")]
+    Truncated,
 }
 
 pub fn detect_ooa_state(game_path: PathBuf) -> OOAState {
@@ -172,6 +171,21 @@ pub enum LicenseAuth {
     Direct(String, String),
 }
 
+/// A ticket from a game's DRM, sent with a license request so the license comes back with
+/// a game token for it.
+///
+/// Games with Denuvo hand one over themselves once running, through LSX's `RequestLicense`,
+/// which Maxima's LSX server answers on its own. The license written before the launch
+/// doesn't need one for games that work that way, so only pass one for a game known to
+/// check its license for a game token before it gets to LSX.
+#[derive(Clone, Debug)]
+pub struct LicenseRequestToken {
+    /// The ticket itself, `requestToken`
+    pub token: String,
+    /// What made the ticket, `requestType`, like the DRM's ticket engine
+    pub kind: String,
+}
+
 pub async fn needs_license_update(
     content_id: &str,
     slug: Option<&str>,
@@ -181,18 +195,16 @@ pub async fn needs_license_update(
         return Ok(true);
     }
 
-    let bytes = tokio::fs::read(path).await;
-    if bytes.is_err() {
-        return Ok(true);
-    }
-
-    let license = decrypt_license(&bytes?[65..]);
-    if license.is_err() {
-        warn!("Failed to decrypt game license when checking for update");
-        return Ok(true);
-    }
-
-    let license = license?;
+    let license = match read_license(path).await {
+        Ok(license) => license,
+        Err(err) => {
+            warn!(
+                "Failed to read game license when checking for update: {}",
+                err
+            );
+            return Ok(true);
+        }
+    };
 
     // Not actually sure how long licenses last, two weeks is a guesstimate
     let date: DateTime<Utc> = license.start_time.parse()?;
@@ -204,6 +216,7 @@ pub async fn request_and_save_license(
     content_id: &str,
     mut game_path: PathBuf,
     slug: Option<&str>,
+    request_token: Option<&LicenseRequestToken>,
 ) -> Result<(), LicenseError> {
     if game_path.is_file() {
         game_path = game_path.safe_parent()?.to_path_buf();
@@ -217,13 +230,12 @@ pub async fn request_and_save_license(
     let version = detect_ooa_version(game_path).await.unwrap_or(1);
     debug!("OOA version is {version}");
 
-    let hw_info = HardwareInfo::new(version, slug);
+    let hw_info = HardwareInfo::cached(version, slug);
     let license = request_license(
         content_id,
         &hw_info.generate_hardware_hash(),
         auth,
-        None,
-        None,
+        request_token,
     )
     .await?;
     save_licenses(&license, state, slug).await?;
@@ -235,8 +247,7 @@ pub async fn request_license(
     content_id: &str,
     machine_hash: &str,
     auth: &LicenseAuth,
-    request_token: Option<&str>,
-    request_type: Option<&str>,
+    request_token: Option<&LicenseRequestToken>,
 ) -> Result<License, LicenseError> {
     let mut query = Vec::new();
     query.push(("contentId", content_id));
@@ -253,25 +264,19 @@ pub async fn request_license(
             query.push(("ea_eadmtoken", access_token));
         }
         LicenseAuth::Direct(persona, password) => {
-            if EMAIL_PATTERN.is_match(persona) {
-                query.push(("ea_email", persona));
-            } else {
-                query.push(("ea_persona", persona));
-            }
-
+            let identifier = AccountIdentifier::parse(persona);
+            query.push((identifier.query_key(), identifier.value()));
             query.push(("ea_password", password));
         }
     }
 
     if let Some(request_token) = request_token {
-        query.push(("requestToken", request_token));
-    }
-    if let Some(request_type) = request_type {
-        query.push(("requestType", request_type));
+        query.push(("requestToken", &request_token.token));
+        query.push(("requestType", &request_token.kind));
     }
 
-    let res = Client::new()
-        .get(API_PROXY_NOVAFUSION_LICENSES)
+    let res = http::client()
+        .get(API_PROXY_NOVAFUSION_LICENSES.as_str())
         .query(&query)
         .header("X-Requester-Id", "Origin Online Activation")
         .header("User-Agent", "EACTransaction")
@@ -332,15 +337,54 @@ pub async fn save_license(
     }
 
     let signature_len = signature.len();
-    let license_blob: Vec<u8> = vec![signature, vec![0; 65 - signature_len], encrypted_data]
-        .into_iter()
-        .flatten()
-        .collect();
+    let license_blob: Vec<u8> = vec![
+        signature,
+        vec![0; LICENSE_SIGNATURE_SIZE - signature_len],
+        encrypted_data,
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
 
     fs::write(path, license_blob).await?;
     Ok(())
 }
 
+/// Reads a license written by [`save_license`]. The signature is given back the way
+/// [`License::signature`] holds it, base64 encoded, whichever way it was saved.
+pub async fn read_license(path: PathBuf) -> Result<License, LicenseError> {
+    let data = fs::read(path).await?;
+    if data.len() < LICENSE_SIGNATURE_SIZE {
+        return Err(LicenseError::Truncated);
+    }
+
+    let (signature, encrypted_data) = data.split_at(LICENSE_SIGNATURE_SIZE);
+    let mut license = decrypt_license(encrypted_data)?;
+
+    let signature = match signature.iter().rposition(|x| *x != 0) {
+        Some(end) => &signature[..=end],
+        None => &[],
+    };
+    // Saved as base64 text for SignatureEncoded, raw bytes otherwise
+    let encoded = std::str::from_utf8(signature)
+        .ok()
+        .filter(|x| general_purpose::STANDARD.decode(x).is_ok());
+    license.signature = match encoded {
+        Some(signature) => signature.to_owned(),
+        None => general_purpose::STANDARD.encode(signature),
+    };
+
+    Ok(license)
+}
+
+/// The license saved for `content_id`, in `slug`'s wine prefix on unix
+pub async fn read_saved_license(
+    content_id: &str,
+    slug: Option<&str>,
+) -> Result<License, LicenseError> {
+    read_license(get_license_dir(slug)?.join(format!("{}.dlf", content_id))).await
+}
+
 pub async fn save_licenses(
     license: &License,
     state: OOAState,
@@ -366,23 +410,47 @@ pub async fn save_licenses(
     Ok(())
 }
 
-#[cfg(windows)]
-pub fn get_license_dir(_slug: Option<&str>) -> Result<PathBuf, NativeError> {
-    let path = format!("C:/{}", LICENSE_PATH.to_string());
-    create_dir_all(&path)?;
-    Ok(PathBuf::from(path))
-}
-
-#[cfg(unix)]
 pub fn get_license_dir(slug: Option<&str>) -> Result<PathBuf, NativeError> {
-    use crate::unix::wine::wine_prefix_dir;
-
-    let path = format!(
-        "{}/drive_c/{}",
-        wine_prefix_dir(slug).unwrap().safe_str()?,
-        LICENSE_PATH.to_string()
-    );
+    let path = program_data_dir(slug)?.join(LICENSE_PATH);
     create_dir_all(&path)?;
+    Ok(path)
+}
 
-    Ok(PathBuf::from(path))
+#[cfg(test)]
+mod tests {
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn saved_license_reads_back() {
+        let license = License {
+            xmlns: "http://ea.com/license".to_owned(),
+            signature: general_purpose::STANDARD.encode([7u8; 48]),
+            cipher_key: "key".to_owned(),
+            machine_hash: "hash".to_owned(),
+            content_id: "1234".to_owned(),
+            user_id: "5678".to_owned(),
+            game_token: None,
+            grant_time: "2024-01-01T00:00:00Z".to_owned(),
+            start_time: "2024-01-01T00:00:00Z".to_owned(),
+            nonce: "abcd".to_owned(),
+        };
+
+        let dir = TempDir::new("license");
+        let encoded = dir.join("encoded.dlf");
+        let decoded = dir.join("decoded.dlf");
+        save_license(&license, OOAState::SignatureEncoded, encoded.clone())
+            .await
+            .unwrap();
+        save_license(&license, OOAState::SignatureDecoded, decoded.clone())
+            .await
+            .unwrap();
+
+        let encoded = read_license(encoded).await;
+        let decoded = read_license(decoded).await;
+
+        assert_eq!(encoded.unwrap(), license);
+        assert_eq!(decoded.unwrap(), license);
+    }
 }
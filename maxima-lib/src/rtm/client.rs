@@ -1,4 +1,4 @@
-use std::{io, sync::Arc, time::Duration};
+use std::{fs, io, sync::Arc, time::Duration};
 
 use core::future::Future;
 use derive_builder::Builder;
@@ -19,6 +19,7 @@ use super::{
 use crate::{
     core::auth::storage::{AuthError, LockedAuthStorage, TokenError},
     rtm::proto::{LoginRequestV3, PlatformV1, PresenceSubscribeV1, PresenceV1, UserType},
+    util::native::maxima_dir,
 };
 
 macro_rules! send_and_forget_rtm_request {
@@ -68,9 +69,59 @@ struct CustomRichPresenceData {
     version: i32,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// Custom rich presence data as other clients send it. Ours only has the product ID,
+/// EA's also describe what the player is doing.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ReceivedRichPresenceData {
+    #[serde(rename = "gameProductId")]
+    game_product_id: String,
+    #[serde(rename = "gameActivity_productId")]
+    activity_product_id: String,
+    #[serde(rename = "gameActivity_gameTitle")]
+    title: String,
+    #[serde(rename = "gameActivity_multiplayerId")]
+    multiplayer_id: String,
+    /// JSON of its own, see [`GamePresenceData`]
+    #[serde(rename = "gameActivity_gamePresence")]
+    game_presence: String,
+    /// JSON of its own, see [`VersionedPresenceData`]
+    #[serde(rename = "gameActivity_richPresence")]
+    rich_presence: String,
+    #[serde(rename = "gameActivity_joinable")]
+    joinable: bool,
+    #[serde(rename = "gameActivity_joinableInviteOnly")]
+    joinable_invite_only: bool,
+}
+
+#[derive(Deserialize)]
+struct VersionedPresenceData {
+    data: String,
+}
+
+#[derive(Deserialize)]
+struct GamePresenceData {
+    data: GamePresenceSession,
+}
+
+#[derive(Deserialize)]
+struct GamePresenceSession {
+    #[serde(default)]
+    session: String,
+}
+
+fn non_empty(value: String) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum BasicPresence {
     Unknown,
+    /// Set as the local user's presence, they appear invisible
     Offline,
     /// Doesn't work
     Dnd,
@@ -78,11 +129,112 @@ pub enum BasicPresence {
     Online,
 }
 
+const PRESENCE_FILE: &str = "presence.json";
+
+/// The presence the local user picked, kept in `maxima_dir()/presence.json` so it
+/// outlasts game launches and restarts
+#[derive(Clone, Debug, Serialize, Deserialize, Getters)]
+pub struct LocalPresence {
+    basic: BasicPresence,
+    /// Message friends see, empty for none
+    status: String,
+}
+
+impl Default for LocalPresence {
+    fn default() -> Self {
+        Self {
+            basic: BasicPresence::Online,
+            status: String::new(),
+        }
+    }
+}
+
+impl LocalPresence {
+    /// Appearing offline, so nothing about the game being played is sent
+    pub fn is_invisible(&self) -> bool {
+        self.basic == BasicPresence::Offline
+    }
+
+    fn load() -> Self {
+        maxima_dir()
+            .ok()
+            .and_then(|dir| fs::read_to_string(dir.join(PRESENCE_FILE)).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) -> Result<(), RtmError> {
+        fs::write(
+            maxima_dir()?.join(PRESENCE_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// What a player is doing in the game they're playing
+#[derive(Clone, Debug, Default, PartialEq, Getters)]
+pub struct GameActivity {
+    /// Offer ID of the game
+    product_id: String,
+    /// The game's name, if the client sent it
+    title: Option<String>,
+    /// Where they are in the game, like "Conquest" or "In the menus"
+    rich_presence: Option<String>,
+    joinable: bool,
+    /// Only joinable by those they've invited
+    joinable_invite_only: bool,
+    multiplayer_id: Option<String>,
+    /// The game's own description of the session they're in, which it needs to join them
+    session: Option<String>,
+}
+
+impl GameActivity {
+    /// Whether anyone can join the session, without an invite
+    pub fn can_join(&self) -> bool {
+        self.joinable && !self.joinable_invite_only && self.session.is_some()
+    }
+
+    fn parse(rich: &RichPresenceV1) -> Option<Self> {
+        let data: ReceivedRichPresenceData =
+            serde_json::from_str(&rich.custom_rich_presence_data).unwrap_or_default();
+
+        let product_id =
+            non_empty(data.game_product_id).or_else(|| non_empty(data.activity_product_id))?;
+
+        // Usually wrapped in a versioned object, but take it as it is otherwise
+        let rich_presence = match serde_json::from_str::<VersionedPresenceData>(&data.rich_presence)
+        {
+            Ok(versioned) => non_empty(versioned.data),
+            Err(_) => non_empty(data.rich_presence),
+        };
+
+        let session = serde_json::from_str::<GamePresenceData>(&data.game_presence)
+            .ok()
+            .and_then(|x| non_empty(x.data.session))
+            .or_else(|| non_empty(rich.game_session_data.clone()));
+
+        Some(Self {
+            product_id,
+            title: non_empty(data.title),
+            rich_presence: rich_presence.or_else(|| non_empty(rich.game_mode.clone())),
+            joinable: data.joinable,
+            joinable_invite_only: data.joinable_invite_only,
+            multiplayer_id: non_empty(data.multiplayer_id),
+            session,
+        })
+    }
+}
+
 #[derive(Clone, Builder, Getters, Debug)]
 pub struct RichPresence {
     basic: BasicPresence,
+    /// The status as the client sent it, often the game's name followed by what they're doing
     status: String,
+    /// Offer ID of the game they're playing
     game: Option<String>,
+    #[builder(default)]
+    activity: Option<GameActivity>,
 }
 
 impl RichPresence {
@@ -96,17 +248,13 @@ impl RichPresence {
         };
 
         let rich = presence.rich_presence.clone().unwrap_or_default();
-        let custom_data: CustomRichPresenceData =
-            serde_json::from_str(&rich.custom_rich_presence_data).unwrap_or_default();
+        let activity = GameActivity::parse(&rich);
 
         Self {
             basic,
             status: rich.game,
-            game: if !custom_data.game_product_id.is_empty() {
-                Some(custom_data.game_product_id)
-            } else {
-                None
-            },
+            game: activity.as_ref().map(|x| x.product_id.clone()),
+            activity,
         }
     }
 }
@@ -124,15 +272,27 @@ pub struct RtmClient {
 
     conn_man: RtmConnectionManager,
     presence_store: LockedRtmPresenceStore,
+    /// Never connects, every request succeeds without being sent. For playing without
+    /// logging in, where there's no one to see or be seen by.
+    #[getter(skip)]
+    offline: bool,
+    /// What the local user picked, see [`RtmClient::set_local_presence`]
+    local_presence: LocalPresence,
+    /// Rich presence and offer ID of the game being played, empty when there's none
+    #[getter(skip)]
+    game_presence: (String, String),
 }
 
 impl RtmClient {
-    pub fn new(auth: LockedAuthStorage) -> RtmClient {
+    pub fn new(auth: LockedAuthStorage, offline: bool) -> RtmClient {
         let (sender_tx, mut receiver_tx) = mpsc::channel(32);
 
         let client = Self {
             conn_man: RtmConnectionManager::new(Duration::from_millis(50), sender_tx),
             auth,
+            offline,
+            local_presence: LocalPresence::load(),
+            game_presence: (String::new(), String::new()),
             presence_store: Arc::new(Mutex::new(
                 Cache::builder()
                     .max_capacity(256)
@@ -200,6 +360,11 @@ impl RtmClient {
     }
 
     pub async fn login(&mut self) -> Result<(), RtmError> {
+        if self.offline {
+            debug!("Offline, not connecting to RTM");
+            return Ok(());
+        }
+
         let token = self
             .auth
             .lock()
@@ -247,18 +412,68 @@ impl RtmClient {
         Ok(())
     }
 
+    /// Appear as `basic` from now on, with `status` as a message friends see. Kept across
+    /// game launches and restarts. While it's [`BasicPresence::Offline`], the local user
+    /// appears invisible and the game they're playing isn't shown.
+    pub async fn set_local_presence(
+        &mut self,
+        basic: BasicPresence,
+        status: &str,
+    ) -> Result<(), RtmError> {
+        self.local_presence = LocalPresence {
+            basic,
+            status: status.to_owned(),
+        };
+        self.local_presence.save()?;
+        self.update_presence().await
+    }
+
+    /// Show `status` for the game `offer_id` alongside the local user's presence, both
+    /// empty once they stop playing
+    pub async fn set_game_presence(
+        &mut self,
+        status: &str,
+        offer_id: &str,
+    ) -> Result<(), RtmError> {
+        self.game_presence = (status.to_owned(), offer_id.to_owned());
+        self.update_presence().await
+    }
+
+    /// Send the local user's presence, and the game they're playing unless they're invisible
+    pub async fn update_presence(&mut self) -> Result<(), RtmError> {
+        if self.local_presence.is_invisible() {
+            return self.send_presence(BasicPresence::Offline, "", None).await;
+        }
+
+        let LocalPresence { basic, status } = self.local_presence.clone();
+        let (game, offer_id) = self.game_presence.clone();
+        self.send_presence(basic, &status, Some((&game, &offer_id)))
+            .await
+    }
+
     pub async fn set_presence(
         &mut self,
         basic_presence: BasicPresence,
         status: &str,
         offer_id: &str,
     ) -> Result<(), RtmError> {
-        info!("Updating RTM presence to '{}'", status);
+        self.send_presence(basic_presence, "", Some((status, offer_id)))
+            .await
+    }
 
-        let rpc_data = CustomRichPresenceData {
-            game_product_id: offer_id.to_owned(),
-            version: 1,
-        };
+    /// `game` is the rich presence and offer ID of the game being played
+    async fn send_presence(
+        &mut self,
+        basic_presence: BasicPresence,
+        user_defined: &str,
+        game: Option<(&str, &str)>,
+    ) -> Result<(), RtmError> {
+        if self.offline {
+            return Ok(());
+        }
+
+        let (status, offer_id) = game.unwrap_or_default();
+        info!("Updating RTM presence to {:?} '{}'", basic_presence, status);
 
         let basic_presence_type = match basic_presence {
             BasicPresence::Unknown => BasicPresenceType::UnknownPresence,
@@ -268,27 +483,43 @@ impl RtmClient {
             BasicPresence::Online => BasicPresenceType::Online,
         };
 
+        let rich_presence = match game {
+            Some(_) => {
+                let rpc_data = CustomRichPresenceData {
+                    game_product_id: offer_id.to_owned(),
+                    version: 1,
+                };
+
+                Some(RichPresenceV1 {
+                    game: status.to_owned(),
+                    platform: PlatformV1::Pc as i32,
+                    game_mode_type: "".to_owned(),
+                    game_mode: "".to_owned(),
+                    game_session_data: "".to_owned(),
+                    rich_presence_type: RichPresenceType::UnknownRichPresence as i32,
+                    start_timestamp: "".to_owned(),
+                    end_timestamp: "".to_owned(),
+                    custom_rich_presence_data: serde_json::to_string(&rpc_data)?,
+                })
+            }
+            None => None,
+        };
+
         send_and_forget_rtm_request!(self.conn_man, PresenceUpdate, PresenceUpdateV1, {
             status: "".to_owned(),
             basic_presence_type: basic_presence_type as i32,
-            user_defined_presence: "".to_owned(),
-            rich_presence: Some(RichPresenceV1 {
-                game: status.to_owned(),
-                platform: PlatformV1::Pc as i32,
-                game_mode_type: "".to_owned(),
-                game_mode: "".to_owned(),
-                game_session_data: "".to_owned(),
-                rich_presence_type: RichPresenceType::UnknownRichPresence as i32,
-                start_timestamp: "".to_owned(),
-                end_timestamp: "".to_owned(),
-                custom_rich_presence_data: serde_json::to_string(&rpc_data)?,
-            })
+            user_defined_presence: user_defined.to_owned(),
+            rich_presence
         })
         .await
     }
 
     /// Subscribe to a list of user IDs' presences
     pub async fn subscribe(&mut self, players: &Vec<String>) -> Result<(), RtmError> {
+        if self.offline {
+            return Ok(());
+        }
+
         send_and_forget_rtm_request!(self.conn_man, PresenceSubscribe, PresenceSubscribeV1, {
             players: players.iter().map(|id| Player{ player_id: id.to_owned(), product_id: String::from("origin"), }).collect()
         })
@@ -296,6 +527,10 @@ impl RtmClient {
     }
 
     pub async fn session_cleanup(&mut self, session_key: &str) -> Result<(), RtmError> {
+        if self.offline {
+            return Ok(());
+        }
+
         send_and_forget_rtm_request!(self.conn_man, SessionCleanupV1, SessionCleanupV1, {
             session_key: session_key.to_owned()
         })
@@ -303,6 +538,54 @@ impl RtmClient {
     }
 
     pub async fn heartbeat(&mut self) -> Result<(), RtmError> {
+        if self.offline {
+            return Ok(());
+        }
+
         send_and_forget_rtm_request!(self.conn_man, Heartbeat, HeartbeatV1, {}).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_ea_game_activity() {
+        let rich = RichPresenceV1 {
+            game: "STAR WARS™ Battlefront™ II In the menus".to_owned(),
+            custom_rich_presence_data: include_str!("test.json").to_owned(),
+            ..Default::default()
+        };
+
+        let activity = GameActivity::parse(&rich).unwrap();
+        assert_eq!(activity.product_id(), "Origin.OFR.50.0002148");
+        assert_eq!(
+            activity.title().as_deref(),
+            Some("STAR WARS™ Battlefront™ II")
+        );
+        assert_eq!(activity.rich_presence().as_deref(), Some("In the menus"));
+        assert!(!activity.joinable());
+        assert_eq!(activity.multiplayer_id().as_deref(), Some("1035052"));
+        assert!(!activity.can_join());
+    }
+
+    #[test]
+    fn parse_maxima_game_activity() {
+        let rich = RichPresenceV1 {
+            game: "Battlefield 4".to_owned(),
+            game_mode: "Conquest".to_owned(),
+            custom_rich_presence_data: r#"{"gameProductId":"Origin.OFR.50.0000500","version":1}"#
+                .to_owned(),
+            ..Default::default()
+        };
+
+        let activity = GameActivity::parse(&rich).unwrap();
+        assert_eq!(activity.product_id(), "Origin.OFR.50.0000500");
+        assert_eq!(activity.title(), &None);
+        assert_eq!(activity.rich_presence().as_deref(), Some("Conquest"));
+
+        let idle = RichPresenceV1::default();
+        assert_eq!(GameActivity::parse(&idle), None);
+    }
+}
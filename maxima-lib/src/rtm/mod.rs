@@ -18,6 +18,8 @@ pub enum RtmError {
     #[error(transparent)]
     Json(#[from] serde_json::Error),
     #[error(transparent)]
+    Native(#[from] crate::util::native::NativeError),
+    #[error(transparent)]
     Send(#[from] tokio::sync::mpsc::error::SendError<connection::RtmRequest>),
     #[error(transparent)]
     Token(#[from] crate::core::auth::storage::TokenError),
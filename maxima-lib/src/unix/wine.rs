@@ -8,6 +8,7 @@ use std::{
     process::{ExitStatus, Stdio},
 };
 
+use derive_getters::Getters;
 use flate2::read::GzDecoder;
 use lazy_static::lazy_static;
 use log::{info, warn};
@@ -28,7 +29,11 @@ use crate::{
         github::{
             fetch_github_release, fetch_github_releases, github_download_asset, GithubRelease,
         },
-        native::{maxima_dir, DownloadError, NativeError, SafeParent, SafeStr, WineError},
+        http,
+        native::{
+            maxima_dir, DownloadError, NativeError, SafeParent, SafeStr, SpawnWithContext,
+            WineError,
+        },
         registry::RegistryError,
     },
 };
@@ -67,9 +72,10 @@ pub(crate) struct LutrisRuntime {
     url: String,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+/// Versions of the wine components Maxima installed, empty when one isn't installed
+#[derive(Clone, Debug, Serialize, Deserialize, Default, Getters)]
 #[serde(default)]
-struct Versions {
+pub struct Versions {
     proton: String,
     eac_runtime: String,
     umu: String,
@@ -107,7 +113,52 @@ pub fn umu_bin() -> Result<PathBuf, NativeError> {
     Ok(maxima_dir()?.join("wine/umu/umu-run"))
 }
 
-fn versions() -> Result<Versions, NativeError> {
+/// `MAXIMA_WINE_COMMAND`, or umu-run if it isn't set
+fn wine_command() -> Result<String, NativeError> {
+    Ok(match env::var("MAXIMA_WINE_COMMAND") {
+        Ok(command) => command,
+        Err(_) => umu_bin()?.to_string_lossy().to_string(),
+    })
+}
+
+/// Proton keeps the wine prefix in a `pfx` folder inside the one it's given,
+/// plain wine uses the given folder as is
+fn prefix_system_reg(prefix: &Path, command: &str) -> PathBuf {
+    if command.ends_with("umu-run") {
+        prefix.join("pfx").join("system.reg")
+    } else {
+        prefix.join("system.reg")
+    }
+}
+
+/// Checks wine and `slug`'s prefix are there before launching it, so a broken setup
+/// fails with what to fix rather than an OS error from spawning
+pub fn check_wine_launch(slug: &str) -> Result<(), NativeError> {
+    let runner = wine_command()?;
+    let command = PathBuf::from(&runner);
+    // A bare name like `wine` is looked up in PATH
+    let found = if command.components().count() > 1 {
+        command.is_file()
+    } else {
+        env::var_os("PATH")
+            .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join(&command).is_file()))
+    };
+    if !found {
+        return Err(WineError::NotFound(command).into());
+    }
+
+    let prefix = load_game_info_from_json(slug)
+        .ok()
+        .and_then(|info| info.wine_prefix())
+        .ok_or_else(|| WineError::NoPrefix(slug.to_owned()))?;
+    if !prefix_system_reg(&prefix, &runner).exists() {
+        return Err(WineError::PrefixNotInitialized(prefix).into());
+    }
+
+    Ok(())
+}
+
+pub fn versions() -> Result<Versions, NativeError> {
     let file = maxima_dir()?.join(VERSION_FILE);
     if !file.exists() {
         return Ok(Versions::default());
@@ -144,7 +195,7 @@ pub(crate) async fn check_wine_validity() -> Result<bool, NativeError> {
 }
 
 pub(crate) async fn get_lutris_runtimes() -> Result<Vec<LutrisRuntime>, WineError> {
-    let client = reqwest::Client::builder()
+    let client = http::client_builder()
         .user_agent("ArmchairDevelopers/Maxima")
         .build()?;
     let res = client.get("https://lutris.net/api/runtimes").send().await?;
@@ -257,15 +308,14 @@ pub async fn run_wine_command<I: IntoIterator<Item = T>, T: AsRef<OsStr>>(
     want_output: bool,
     command_type: CommandType,
     slug: Option<&str>,
+    env: Option<&HashMap<String, String>>,
 ) -> Result<String, NativeError> {
     let proton_path = proton_dir()?;
     let proton_prefix_path = wine_prefix_dir(slug).unwrap();
     let eac_path = eac_dir()?;
-    let umu_bin = umu_bin()?;
 
     info!("Wine Prefix: {:?}", proton_prefix_path);
-    let wine_path =
-        env::var("MAXIMA_WINE_COMMAND").unwrap_or_else(|_| umu_bin.to_string_lossy().to_string());
+    let wine_path = wine_command()?;
 
     // Create command with all necessary wine env variables
     let mut binding = Command::new(wine_path.clone());
@@ -297,19 +347,24 @@ pub async fn run_wine_command<I: IntoIterator<Item = T>, T: AsRef<OsStr>>(
         child.current_dir(cwd);
     }
 
+    // Set last, so they replace the defaults above
+    if let Some(env) = env {
+        child = child.envs(env);
+    }
+
     let status: ExitStatus;
     let mut output_str = String::new();
 
     if want_output {
         let output = child
             .stdout(Stdio::piped())
-            .spawn()?
+            .spawn_with_context()?
             .wait_with_output()
             .await?;
         output_str = String::from_utf8_lossy(&output.stdout).to_string();
         status = output.status;
     } else {
-        status = child.spawn()?.wait().await?;
+        status = child.spawn_with_context()?.wait().await?;
     };
 
     if !status.success() {
@@ -444,6 +499,7 @@ pub async fn setup_wine_registry(slug: Option<&str>) -> Result<(), NativeError>
         false,
         CommandType::Run,
         slug,
+        None,
     )
     .await?;
 
@@ -493,10 +549,7 @@ async fn parse_wine_registry(file_path: &str) -> WineRegistry {
 }
 
 pub async fn parse_mx_wine_registry(slug: Option<&str>) -> Result<WineRegistry, NativeError> {
-    let path = wine_prefix_dir(slug)
-        .unwrap()
-        .join("pfx")
-        .join("system.reg");
+    let path = prefix_system_reg(&wine_prefix_dir(slug).unwrap(), &wine_command()?);
     if !path.exists() {
         return Ok(HashMap::new());
     }
@@ -537,3 +590,31 @@ pub async fn get_mx_wine_registry_value(
 
     Ok(value.map(|x| x.replace("Z:", "").replace("\\", "/")))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::util::temp_dir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn prefix_layout_follows_runner() {
+        let dir = TempDir::new("wine-prefix");
+        let prefix = dir.path();
+        fs::create_dir_all(prefix.join("pfx")).unwrap();
+
+        // Initialized by plain wine
+        fs::write(prefix.join("system.reg"), "").unwrap();
+        assert!(prefix_system_reg(prefix, "wine").exists());
+        assert!(prefix_system_reg(prefix, "/usr/bin/wine64").exists());
+        assert!(!prefix_system_reg(prefix, "/opt/umu/umu-run").exists());
+
+        // Initialized by Proton
+        fs::remove_file(prefix.join("system.reg")).unwrap();
+        fs::write(prefix.join("pfx").join("system.reg"), "").unwrap();
+        assert!(prefix_system_reg(prefix, "/opt/umu/umu-run").exists());
+        assert!(!prefix_system_reg(prefix, "wine").exists());
+    }
+}
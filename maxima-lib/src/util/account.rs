@@ -0,0 +1,72 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref EMAIL_PATTERN: Regex = Regex::new(
+        r"(?i)^([a-z0-9_+]([a-z0-9_+.\-]*[a-z0-9_+])?)@([a-z0-9]+([\-\.]{1}[a-z0-9]+)*\.[a-z]{2,})$"
+    )
+    .unwrap();
+}
+
+/// What an account was named by when logging in with a password. EA's direct login
+/// endpoints take emails and persona names (display names) in different fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountIdentifier<'a> {
+    Email(&'a str),
+    Persona(&'a str),
+}
+
+impl<'a> AccountIdentifier<'a> {
+    pub fn parse(identifier: &'a str) -> Self {
+        let identifier = identifier.trim();
+        if EMAIL_PATTERN.is_match(identifier) {
+            AccountIdentifier::Email(identifier)
+        } else {
+            AccountIdentifier::Persona(identifier)
+        }
+    }
+
+    pub fn value(&self) -> &'a str {
+        match self {
+            AccountIdentifier::Email(value) | AccountIdentifier::Persona(value) => value,
+        }
+    }
+
+    /// The query parameter EA expects this in
+    pub fn query_key(&self) -> &'static str {
+        match self {
+            AccountIdentifier::Email(_) => "ea_email",
+            AccountIdentifier::Persona(_) => "ea_persona",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_emails_from_personas() {
+        assert_eq!(
+            AccountIdentifier::parse("player@example.com"),
+            AccountIdentifier::Email("player@example.com")
+        );
+        assert_eq!(
+            AccountIdentifier::parse(" Player.One@Example.Co.UK "),
+            AccountIdentifier::Email("Player.One@Example.Co.UK")
+        );
+        assert_eq!(
+            AccountIdentifier::parse("first-last@mail.technology").query_key(),
+            "ea_email"
+        );
+
+        assert_eq!(
+            AccountIdentifier::parse("xX_Player_Xx"),
+            AccountIdentifier::Persona("xX_Player_Xx")
+        );
+        assert_eq!(
+            AccountIdentifier::parse("player@example.com trailing").query_key(),
+            "ea_persona"
+        );
+    }
+}
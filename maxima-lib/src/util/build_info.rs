@@ -0,0 +1,87 @@
+use std::fmt::Display;
+
+use chrono::{DateTime, TimeZone, Utc};
+use derive_getters::Getters;
+use serde::Serialize;
+
+#[cfg(unix)]
+use crate::unix::wine::Versions;
+
+/// Matches the `flate2` pin and features in Cargo.toml
+const ZLIB: &str = "zlib via flate2 1.0.28 (zlib-default)";
+
+/// What this copy of Maxima was built from and for, to go at the top of bug reports
+#[derive(Clone, Debug, Getters, Serialize)]
+pub struct BuildInfo {
+    version: &'static str,
+    /// Short hash of the commit it was built from, `unknown` outside a git checkout
+    git_sha: &'static str,
+    built_at: Option<DateTime<Utc>>,
+    os: &'static str,
+    arch: &'static str,
+    /// The zlib downloads are inflated with
+    zlib: &'static str,
+    /// The wine components installed for running games, `None` if they couldn't be read
+    #[cfg(unix)]
+    wine: Option<Versions>,
+}
+
+pub fn build_info() -> BuildInfo {
+    let built_at = env!("MAXIMA_BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|secs| Utc.timestamp_opt(secs, 0).single());
+
+    BuildInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("MAXIMA_GIT_SHA"),
+        built_at,
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        zlib: ZLIB,
+        #[cfg(unix)]
+        wine: crate::unix::wine::versions().ok(),
+    }
+}
+
+#[cfg(unix)]
+fn or_none(version: &str) -> &str {
+    if version.is_empty() {
+        "none"
+    } else {
+        version
+    }
+}
+
+impl Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let built_at = self
+            .built_at
+            .map(|x| x.format("%Y-%m-%d %H:%M UTC").to_string())
+            .unwrap_or_else(|| "unknown".to_owned());
+
+        writeln!(
+            f,
+            "Maxima {} ({}, built {})",
+            self.version, self.git_sha, built_at
+        )?;
+        writeln!(f, "Platform: {} {}", self.os, self.arch)?;
+        write!(f, "Compression: {}", self.zlib)?;
+
+        #[cfg(unix)]
+        {
+            match &self.wine {
+                Some(wine) => write!(
+                    f,
+                    "\nProton: {}, EAC runtime: {}, umu: {}",
+                    or_none(wine.proton()),
+                    or_none(wine.eac_runtime()),
+                    or_none(wine.umu())
+                )?,
+                None => write!(f, "\nWine: unknown")?,
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! HTTP clients for everything Maxima sends to EA and its CDNs.
+//!
+//! Proxies are picked in this order:
+//! 1. `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` (or their lowercase forms), along with
+//!    `NO_PROXY`, as reqwest reads them
+//! 2. The proxy given to [`set_proxy`], used only when none of those variables are set
+//! 3. No proxy
+//!
+//! Clients are built once and kept, so [`set_proxy`] needs to be called before Maxima is
+//! created.
+
+use std::{env, sync::RwLock};
+
+use reqwest::{redirect, Client, ClientBuilder, NoProxy, Proxy};
+use thiserror::Error;
+
+/// Variables reqwest takes a proxy from, see [`reqwest::Proxy::system`]
+const PROXY_ENV_VARS: [&str; 6] = [
+    "HTTPS_PROXY",
+    "https_proxy",
+    "HTTP_PROXY",
+    "http_proxy",
+    "ALL_PROXY",
+    "all_proxy",
+];
+
+/// Redirects followed before giving up, the same as reqwest's default
+const MAX_REDIRECTS: usize = 10;
+
+static PROXY: RwLock<Option<String>> = RwLock::new(None);
+
+#[derive(Error, Debug)]
+pub enum ProxyError {
+    #[error("invalid proxy `{0}`: {1}")]
+    Invalid(String, reqwest::Error),
+}
+
+/// Send requests through `proxy`, such as `http://127.0.0.1:8080` or
+/// `socks5://127.0.0.1:1080`, unless the proxy environment variables are set. `None`
+/// or an empty string goes back to connecting directly.
+pub fn set_proxy(proxy: Option<&str>) -> Result<(), ProxyError> {
+    let proxy = proxy.map(str::trim).filter(|x| !x.is_empty());
+    if let Some(proxy) = proxy {
+        Proxy::all(proxy).map_err(|err| ProxyError::Invalid(proxy.to_owned(), err))?;
+    }
+
+    *PROXY.write().unwrap() = proxy.map(str::to_owned);
+    Ok(())
+}
+
+/// The proxy from [`set_proxy`], if it's the one in use
+pub fn configured_proxy() -> Option<String> {
+    if env_proxy_set() {
+        return None;
+    }
+
+    PROXY.read().unwrap().clone()
+}
+
+fn env_proxy_set() -> bool {
+    PROXY_ENV_VARS
+        .iter()
+        .any(|var| env::var(var).is_ok_and(|x| !x.is_empty()))
+}
+
+/// A [`ClientBuilder`] set up with the proxy in use
+pub fn client_builder() -> ClientBuilder {
+    let builder = Client::builder();
+
+    // Validated by set_proxy
+    match configured_proxy().and_then(|proxy| Proxy::all(proxy).ok()) {
+        Some(proxy) => builder.proxy(proxy.no_proxy(NoProxy::from_env())),
+        None => builder,
+    }
+}
+
+/// A [`Client`] with default settings and the proxy in use
+pub fn client() -> Client {
+    client_builder()
+        .build()
+        .expect("failed to build the HTTP client")
+}
+
+/// Follow redirects, but never from HTTPS to plain HTTP. CDNs redirect between edges,
+/// and a downgrade would send their signed URLs in the clear.
+pub fn secure_redirects() -> redirect::Policy {
+    redirect::Policy::custom(|attempt| {
+        let downgrade = attempt.url().scheme() != "https"
+            && attempt.previous().iter().any(|x| x.scheme() == "https");
+
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("too many redirects")
+        } else if downgrade {
+            attempt.error("refused a redirect from HTTPS to HTTP")
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_invalid_proxy() {
+        assert!(set_proxy(Some("http://proxy host:80")).is_err());
+        assert!(PROXY.read().unwrap().is_none());
+    }
+}
@@ -1,10 +1,42 @@
-use std::env;
+use std::{collections::VecDeque, env, sync::Mutex};
 
+use chrono::Utc;
+use lazy_static::lazy_static;
 use log::{Level, LevelFilter, Metadata, Record};
 
+/// How many lines [`recent_logs`] remembers
+const RECENT_LOG_LINES: usize = 2000;
+
+lazy_static! {
+    static ref RECENT_LOGS: Mutex<VecDeque<String>> =
+        Mutex::new(VecDeque::with_capacity(RECENT_LOG_LINES));
+}
+
 pub struct SimpleLogger;
 pub static LOGGER: SimpleLogger = SimpleLogger;
 
+/// The last lines logged by this process, oldest first and without colors, for
+/// diagnostics bundles
+pub fn recent_logs() -> Vec<String> {
+    RECENT_LOGS.lock().unwrap().iter().cloned().collect()
+}
+
+fn remember(record: &Record) {
+    let line = format!(
+        "{} {} [{}] {}",
+        Utc::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+        record.level(),
+        record.module_path().unwrap_or_default(),
+        record.args()
+    );
+
+    let mut logs = RECENT_LOGS.lock().unwrap();
+    if logs.len() == RECENT_LOG_LINES {
+        logs.pop_front();
+    }
+    logs.push_back(line);
+}
+
 impl log::Log for SimpleLogger {
     fn enabled(&self, metadata: &Metadata) -> bool {
         let enable_debug = if let Ok(x) = env::var("MAXIMA_LOG_LEVEL") {
@@ -24,6 +56,8 @@ impl log::Log for SimpleLogger {
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
+            remember(record);
+
             let level = record.level();
             let color: &str = match level {
                 Level::Error => "31", // red
@@ -1,5 +1,8 @@
+pub mod account;
+pub mod build_info;
 pub mod github;
 pub mod hash;
+pub mod http;
 pub mod log;
 pub mod native;
 pub mod registry;
@@ -10,6 +13,9 @@ pub mod wmi_utils;
 #[cfg(windows)]
 pub mod dll_injector;
 
+#[cfg(test)]
+pub(crate) mod temp_dir;
+
 #[derive(thiserror::Error, Debug)]
 pub enum BackgroundServiceControlError {
     #[error(transparent)]
@@ -4,6 +4,7 @@ use std::{
     num::ParseIntError,
     path::{Path, PathBuf},
 };
+use sysinfo::{DiskExt, System, SystemExt};
 use thiserror::Error;
 
 #[cfg(windows)]
@@ -58,7 +59,43 @@ pub enum WineError {
     UnimplementedRuntime(String),
     #[error("couldn't find suitable wine release")]
     Fetch,
+    #[error(
+        "wine not found at `{0}`, launch the game from Maxima to install it or set `MAXIMA_WINE_COMMAND` to a wine binary"
+    )]
+    NotFound(PathBuf),
+    #[error("`{0}` has no wine prefix, launch it from Maxima to create one")]
+    NoPrefix(String),
+    #[error("wine prefix `{0}` is not initialized, launch the game from Maxima to set it up")]
+    PrefixNotInitialized(PathBuf),
 }
+
+pub trait SpawnWithContext {
+    /// Like `spawn`, but the error says what was being started and where, rather than
+    /// just the OS error
+    fn spawn_with_context(&mut self) -> Result<tokio::process::Child, NativeError>;
+}
+
+impl SpawnWithContext for tokio::process::Command {
+    fn spawn_with_context(&mut self) -> Result<tokio::process::Child, NativeError> {
+        self.spawn().map_err(|source| {
+            let command = self.as_std();
+            NativeError::Spawn {
+                program: PathBuf::from(command.get_program()),
+                args: command
+                    .get_args()
+                    .map(|x| x.to_string_lossy().into_owned())
+                    .collect(),
+                cwd: command
+                    .get_current_dir()
+                    .map(Path::to_path_buf)
+                    .or_else(|| env::current_dir().ok())
+                    .unwrap_or_default(),
+                source,
+            }
+        })
+    }
+}
+
 pub trait SafeParent {
     fn safe_parent(&self) -> Result<&Path, NativeError>;
 }
@@ -122,6 +159,23 @@ pub enum NativeError {
     Pid(String),
     #[error("could not find PID pattern")]
     PidPattern,
+    #[error("`{0}` already exists and isn't an empty folder or a link")]
+    LinkExists(PathBuf),
+    #[error(
+        "could not link `{link}` to `{target}`, the filesystem may not support links: {reason}"
+    )]
+    Link {
+        link: PathBuf,
+        target: PathBuf,
+        reason: String,
+    },
+    #[error("could not start `{program}` with arguments {args:?} in `{cwd}`: {source}")]
+    Spawn {
+        program: PathBuf,
+        args: Vec<String>,
+        cwd: PathBuf,
+        source: std::io::Error,
+    },
 
     // Windows
     #[error("failed to elevate `{0}`")]
@@ -237,6 +291,11 @@ pub fn module_path() -> Result<PathBuf, NativeError> {
 pub fn maxima_dir() -> Result<PathBuf, NativeError> {
     use directories::ProjectDirs;
 
+    #[cfg(test)]
+    if let Some(path) = super::temp_dir::data_dir() {
+        return Ok(path);
+    }
+
     let dirs = ProjectDirs::from("com", "ArmchairDevelopers", "Maxima");
     let path = dirs.unwrap().data_dir().to_path_buf();
     create_dir_all(&path)?;
@@ -245,6 +304,11 @@ pub fn maxima_dir() -> Result<PathBuf, NativeError> {
 
 #[cfg(unix)]
 pub fn maxima_dir() -> Result<PathBuf, NativeError> {
+    #[cfg(test)]
+    if let Some(path) = super::temp_dir::data_dir() {
+        return Ok(path);
+    }
+
     let home = if let Ok(home) = env::var("XDG_DATA_HOME") {
         home
     } else if let Ok(home) = env::var("HOME") {
@@ -253,11 +317,124 @@ pub fn maxima_dir() -> Result<PathBuf, NativeError> {
         return Err(NativeError::MissingEnvironmentVariable("HOME".to_string()));
     };
 
-    let path = PathBuf::from(format!("{}/maxima", home));
+    let path = maxima_dir_in(Path::new(&home));
     create_dir_all(&path)?;
     Ok(path)
 }
 
+/// Maxima's folder in `data_home`, the XDG data folder
+#[cfg(unix)]
+fn maxima_dir_in(data_home: &Path) -> PathBuf {
+    data_home.join("maxima")
+}
+
+/// The ProgramData folder games see. On unix, that's the one inside `slug`'s wine prefix.
+#[cfg(windows)]
+pub fn program_data_dir(_slug: Option<&str>) -> Result<PathBuf, NativeError> {
+    env::var_os("ProgramData")
+        .map(PathBuf::from)
+        .ok_or_else(|| NativeError::MissingEnvironmentVariable("ProgramData".to_string()))
+}
+
+#[cfg(unix)]
+pub fn program_data_dir(slug: Option<&str>) -> Result<PathBuf, NativeError> {
+    Ok(program_data_in(&crate::unix::wine::wine_prefix_dir(slug)?))
+}
+
+/// The ProgramData folder inside the wine prefix at `prefix`
+#[cfg(unix)]
+fn program_data_in(prefix: &Path) -> PathBuf {
+    prefix.join("drive_c/ProgramData")
+}
+
+/// The mount point and free space, in bytes, of the volume that holds `path`.
+/// The path doesn't need to exist yet, its closest existing ancestor is used instead.
+pub fn volume_space(path: &Path) -> Option<(PathBuf, u64)> {
+    let existing = path.ancestors().find(|x| x.exists())?;
+
+    // Resolves symlinks to other drives. Skipped on Windows, where it produces
+    // verbatim `\\?\` paths that never match a mount point.
+    #[cfg(unix)]
+    let existing = existing.canonicalize().ok()?;
+
+    let mut system = System::new();
+    system.refresh_disks_list();
+    system
+        .disks()
+        .iter()
+        .filter(|disk| existing.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.mount_point().to_path_buf(), disk.available_space()))
+}
+
+/// Create a folder link at `link` that points to `target`, creating `target` if needed.
+/// Windows gets a junction, which unlike a symlink doesn't need elevation. Elsewhere a
+/// symlink is used, which wine also follows. An existing link to `target` is left alone.
+pub fn link_dir(target: &Path, link: &Path) -> Result<(), NativeError> {
+    create_dir_all(target)?;
+
+    if std::fs::read_link(link).is_ok() {
+        if link.canonicalize().ok() == target.canonicalize().ok() {
+            return Ok(());
+        }
+
+        return Err(NativeError::LinkExists(link.to_path_buf()));
+    }
+
+    if link.exists() {
+        if !link.is_dir() || std::fs::read_dir(link)?.next().is_some() {
+            return Err(NativeError::LinkExists(link.to_path_buf()));
+        }
+
+        std::fs::remove_dir(link)?;
+    }
+
+    create_dir_all(link.safe_parent()?)?;
+
+    let link_error = |reason: String| NativeError::Link {
+        link: link.to_path_buf(),
+        target: target.to_path_buf(),
+        reason,
+    };
+
+    #[cfg(windows)]
+    {
+        let output = std::process::Command::new("cmd")
+            .arg("/C")
+            .arg("mklink")
+            .arg("/J")
+            .arg(link)
+            .arg(target)
+            .output()?;
+
+        if !output.status.success() {
+            return Err(link_error(
+                String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, link).map_err(|err| link_error(err.to_string()))?;
+
+    Ok(())
+}
+
+/// Remove a link created by [`link_dir`], leaving the folder it points to alone
+pub fn remove_link(link: &Path) -> Result<(), NativeError> {
+    if !std::fs::symlink_metadata(link)?.file_type().is_symlink() {
+        return Err(NativeError::LinkExists(link.to_path_buf()));
+    }
+
+    // Junctions and directory symlinks are folders as far as Windows is concerned
+    #[cfg(windows)]
+    std::fs::remove_dir(link)?;
+    #[cfg(unix)]
+    std::fs::remove_file(link)?;
+
+    Ok(())
+}
+
 #[cfg(unix)]
 pub fn platform_path<P: AsRef<Path>>(path: P) -> PathBuf {
     PathBuf::from(format!("Z:{}", path.as_ref().to_str().unwrap()))
@@ -267,3 +444,32 @@ pub fn platform_path<P: AsRef<Path>>(path: P) -> PathBuf {
 pub fn platform_path<P: AsRef<Path>>(path: P) -> PathBuf {
     PathBuf::from(path.as_ref())
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn assert_unix_path(path: &Path) {
+        let path = path.to_string_lossy();
+        assert!(path.starts_with('/'), "`{path}` isn't absolute");
+        assert!(
+            !path.contains(":/") && !path.contains(":\\"),
+            "`{path}` is a Windows path"
+        );
+    }
+
+    #[test]
+    fn core_paths_are_unix_paths() {
+        let root = env::temp_dir().join(format!("maxima-native-{}", std::process::id()));
+        assert_unix_path(&maxima_dir_in(&root));
+
+        let program_data = program_data_in(&root.join("prefix"));
+        assert_unix_path(&program_data);
+        assert_unix_path(&program_data.join(crate::ooa::LICENSE_PATH));
+
+        assert_unix_path(&maxima_dir().unwrap());
+        assert_unix_path(&crate::unix::wine::wine_dir().unwrap());
+        assert_unix_path(&crate::unix::wine::proton_dir().unwrap());
+        assert_unix_path(&crate::content::downloader::zstate_root().unwrap());
+    }
+}
@@ -66,6 +66,8 @@ pub enum RegistryError {
     InvalidStoredClientPath,
     #[error("invalid qrc protocol")]
     InvalidQrcProtocol,
+    #[error("invalid origin2 protocol")]
+    InvalidOrigin2Protocol,
 
     // Linux
     #[error("xdg-mime command is not available. Please install xdg-utils")]
@@ -101,6 +103,10 @@ pub fn check_registry_validity() -> Result<(), RegistryError> {
         return Err(RegistryError::InvalidQrcProtocol);
     }
 
+    if hkcr.open_subkey("origin2").is_err() {
+        return Err(RegistryError::InvalidOrigin2Protocol);
+    }
+
     Ok(())
 }
 
@@ -317,15 +323,15 @@ pub fn set_up_registry() -> Result<(), RegistryError> {
     // Hijack Qt's protocol for our login redirection
     register_custom_protocol("qrc", "Maxima Protocol", bootstrap_path)?;
 
-    // These are disabled until properly implemented in bootstrap. Epic/Steam-owned games
+    // Battlelog hands game launches back to the launcher through this
+    register_custom_protocol("origin2", "Maxima Launcher", bootstrap_path)?;
+
+    // This is disabled until properly implemented in bootstrap. Epic/Steam-owned games
     // can be launched directly from Maxima until that's done
 
     // We link2maxima now
     //register_custom_protocol("link2ea", "Maxima Launcher", bootstrap_path)?;
 
-    // maxima2
-    //register_custom_protocol("origin2", "Maxima Launcher", bootstrap_path)?;
-
     Ok(())
 }
 
@@ -354,6 +360,9 @@ pub fn set_up_registry() -> Result<(), RegistryError> {
     // Hijack Qt's protocol for our login redirection
     register_custom_protocol("qrc", "Maxima Launcher", bootstrap_path)?;
 
+    // Battlelog hands game launches back to the launcher through this
+    register_custom_protocol("origin2", "Maxima Launcher", bootstrap_path)?;
+
     Ok(())
 }
 
@@ -0,0 +1,90 @@
+//! Folders for tests to write to, deleted once they're dropped
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, MutexGuard, RwLock,
+    },
+};
+
+/// Tells apart the folders of tests running at the same time
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Where [`super::native::maxima_dir`] points while a [`TempDataDir`] is alive
+static DATA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Held by the [`TempDataDir`] that's alive, since there's one data folder per process
+static DATA_DIR_LOCK: Mutex<()> = Mutex::new(());
+
+/// An empty folder in the system's temp folder, deleted with everything in it on drop
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// `name` goes in the folder's name, so one left behind by a crash can be told apart
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "maxima-{}-{}-{}",
+            name,
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A [`TempDir`] that [`super::native::maxima_dir`] returns until it's dropped, so what a
+/// test saves there, like game info, the download queue or the request cache, doesn't touch
+/// the real one. Tests using one run one at a time.
+pub struct TempDataDir {
+    dir: TempDir,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl TempDataDir {
+    pub fn new() -> Self {
+        // A test that failed while holding it leaves nothing behind that matters
+        let lock = DATA_DIR_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+        let dir = TempDir::new("data");
+        *DATA_DIR.write().unwrap() = Some(dir.path().to_owned());
+
+        Self { dir, _lock: lock }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.dir.join(path)
+    }
+}
+
+impl Drop for TempDataDir {
+    fn drop(&mut self) {
+        *DATA_DIR.write().unwrap() = None;
+    }
+}
+
+/// The folder of the [`TempDataDir`] that's alive, if there is one
+pub(crate) fn data_dir() -> Option<PathBuf> {
+    DATA_DIR.read().unwrap().clone()
+}
@@ -17,12 +17,15 @@ pub enum ServerError {
 
     #[error("attempted to inject into invalid process")]
     InvalidInjectionTarget,
+    #[error("only requests from this machine are answered")]
+    NotLocal,
 }
 
 impl error::ResponseError for ServerError {
     fn status_code(&self) -> StatusCode {
         match self {
             ServerError::InvalidInjectionTarget => StatusCode::BAD_REQUEST,
+            ServerError::NotLocal => StatusCode::FORBIDDEN,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
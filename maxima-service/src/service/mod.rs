@@ -1,14 +1,14 @@
 use std::fs::File;
 
-use actix_web::{get, post, web, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
 use log::info;
 use maxima::util::registry::set_up_registry;
 use maxima::util::service::SERVICE_NAME;
 use std::ffi::OsString;
-use std::path::Path;
 use std::sync::mpsc::{self, Receiver};
+use std::sync::OnceLock;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use structured_logger::json::new_writer;
 use windows_service::service::{
     ServiceControl, ServiceControlAccept, ServiceExitCode, ServiceState, ServiceStatus, ServiceType,
@@ -20,15 +20,20 @@ use windows_service::{
 
 use crate::service::error::ServerError;
 use crate::service::hash::get_sha256_hash_of_pid;
-use maxima::core::background_service::{ServiceLibraryInjectionRequest, BACKGROUND_SERVICE_PORT};
+use maxima::core::background_service::{
+    ServiceHealth, ServiceLibraryInjectionRequest, BACKGROUND_SERVICE_PORT,
+};
 use maxima::util::dll_injector::{DllInjector, InjectionError};
-use maxima::util::native::SafeParent;
+use maxima::util::native::{program_data_dir, SafeParent};
 
 pub(crate) mod error;
 mod hash;
 
 define_windows_service!(ffi_service_main, service_main);
 
+/// When the service started, for its uptime
+static STARTED: OnceLock<Instant> = OnceLock::new();
+
 fn service_main(arguments: Vec<OsString>) {
     if let Err(_e) = bootstrap_service(arguments) {
         // Handle error in some way.
@@ -88,6 +93,19 @@ async fn req_set_up_registry() -> impl Responder {
     format!("Done")
 }
 
+/// Version, uptime and registry state, so Maxima can tell whether the service running
+/// is the one it shipped with
+#[get("/health")]
+async fn req_health(req: HttpRequest) -> Result<HttpResponse, self::ServerError> {
+    // The server only listens on localhost, but don't rely on that alone
+    if !req.peer_addr().is_some_and(|x| x.ip().is_loopback()) {
+        return Err(self::ServerError::NotLocal);
+    }
+
+    let uptime = STARTED.get().map(|x| x.elapsed()).unwrap_or_default();
+    Ok(HttpResponse::Ok().json(ServiceHealth::current(uptime)))
+}
+
 pub fn inject_dll(pid: u32, dll_path: &str) -> Result<(), InjectionError> {
     let injector = DllInjector::new(pid);
     injector.inject(dll_path)
@@ -119,15 +137,16 @@ async fn req_inject_library(body: web::Bytes) -> Result<HttpResponse, self::Serv
 }
 
 fn run_service(shutdown_rx: Receiver<()>) -> Result<(), self::ServerError> {
-    let log_path = Path::new("C:/ProgramData/Maxima/Logs/MaximaBackgroundService.log");
+    let log_path = program_data_dir(None)?.join("Maxima/Logs/MaximaBackgroundService.log");
     std::fs::create_dir_all(log_path.safe_parent()?)?;
-    let log_file = File::create(log_path)?;
+    let log_file = File::create(&log_path)?;
 
     structured_logger::Builder::new()
         .with_default_writer(new_writer(log_file))
         .init();
 
     info!("Started Background Service");
+    STARTED.get_or_init(Instant::now);
 
     thread::spawn(|| {
         actix_web::rt::System::new()
@@ -138,6 +157,7 @@ fn run_service(shutdown_rx: Receiver<()>) -> Result<(), self::ServerError> {
                     App::new()
                         .service(req_set_up_registry)
                         .service(req_inject_library)
+                        .service(req_health)
                 })
                 .bind(("127.0.0.1", BACKGROUND_SERVICE_PORT))?
                 .run()
@@ -3,30 +3,24 @@ use clap::{Parser, Subcommand};
 use futures::StreamExt;
 use inquire::Select;
 use lazy_static::lazy_static;
-use log::{debug, error, info, warn};
+use log::{debug, error, info};
 use regex::Regex;
-use service::{BridgeThread, MaximaLibRequest, MaximaLibResponse};
+use service::{BridgeThread, MaximaLibRequest, MaximaLibResponse, SetupStep};
 
 use std::{
+    collections::HashMap,
     io::stdout,
     sync::Arc,
     time::{Duration, Instant},
 };
 
-#[cfg(windows)]
-use is_elevated::is_elevated;
-
-#[cfg(windows)]
-use maxima::{
-    core::background_service::request_registry_setup,
-    util::service::{is_service_running, is_service_valid, register_service_user, start_service},
-};
-
 use maxima::{
     content::downloader::ZipDownloader,
     core::{
         auth::{nucleus_token_exchange, TokenResponse},
         clients::JUNO_PC_CLIENT_ID,
+        cloudsync::CloudSyncFilter,
+        concurrency::CancellationToken,
         launch::LaunchMode,
         library::OwnedTitle,
         service_layer::{
@@ -49,9 +43,9 @@ use maxima::{
         },
         launch::{self, LaunchOptions},
         service_layer::ServiceUserGameProduct,
-        Maxima, MaximaEvent,
+        Maxima, MaximaEvent, SHUTDOWN_TIMEOUT,
     },
-    util::{log::init_logger, native::take_foreground_focus, registry::check_registry_validity},
+    util::{log::init_logger, native::take_foreground_focus},
 };
 
 lazy_static! {
@@ -120,6 +114,31 @@ impl App {
             self.handle_responses()?;
         }
 
+        self.shutdown(terminal)
+    }
+
+    /// Let the bridge sync saves and save downloads before the runtime goes away
+    fn shutdown(&mut self, terminal: &mut Terminal<impl Backend>) -> Result<()> {
+        if self
+            .bridge
+            .tx
+            .send(MaximaLibRequest::ShutdownRequest)
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        self.popup = Some("Shutting down...".to_owned());
+        self.draw(terminal)?;
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT + Duration::from_secs(1);
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match self.bridge.rx.recv_timeout(remaining) {
+                Ok(MaximaLibResponse::ShutdownFinished) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
+
         Ok(())
     }
 
@@ -155,6 +174,23 @@ impl App {
 
         let message = message.unwrap();
         match message {
+            MaximaLibResponse::SetupStepStarted(step) => {
+                self.popup = Some(
+                    match step {
+                        SetupStep::InstallService => "Installing service...",
+                        SetupStep::UpdateService => "Updating service...",
+                        SetupStep::StartService => "Starting service...",
+                        SetupStep::FixRegistry => "Fixing registry...",
+                    }
+                    .to_owned(),
+                );
+            }
+            MaximaLibResponse::SetupStepFinished(_) => {
+                self.popup = Some("Logging in...".to_owned());
+            }
+            MaximaLibResponse::SetupStepFailed(step, err) => {
+                self.popup = Some(format!("{:?} failed: {}", step, err));
+            }
             MaximaLibResponse::LoginResponse(response) => {
                 self.popup = None;
                 self.username = response.name;
@@ -344,48 +380,11 @@ async fn main() {
     }
 }
 
-#[cfg(windows)]
-async fn native_setup() -> Result<()> {
-    if !is_elevated() {
-        if !is_service_valid()? {
-            info!("Installing service...");
-            register_service_user()?;
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-        }
-
-        if !is_service_running()? {
-            info!("Starting service...");
-            start_service().await?;
-        }
-    }
-
-    if let Err(err) = check_registry_validity() {
-        warn!("{}, fixing...", err);
-        request_registry_setup().await?;
-    }
-
-    Ok(())
-}
-
-#[cfg(not(windows))]
-async fn native_setup() -> Result<()> {
-    use maxima::util::registry::set_up_registry;
-
-    if let Err(err) = check_registry_validity() {
-        warn!("{}, fixing...", err);
-        set_up_registry()?;
-    }
-
-    Ok(())
-}
-
 async fn startup() -> Result<()> {
     //init_logger();
 
     info!("Starting Maxima...");
 
-    native_setup().await?;
-
     // Take back the focus since the browser and bootstrap will take it
     take_foreground_focus()?;
 
@@ -445,6 +444,12 @@ async fn start_game(
         path_override: game_path_override,
         arguments: game_args,
         cloud_saves: true,
+        cloud_sync_filter: CloudSyncFilter::default(),
+        cancel: CancellationToken::new(),
+        join: None,
+        env: HashMap::new(),
+        license_request: None,
+        working_dir: None,
     };
 
     if login.is_none() {
@@ -478,6 +483,8 @@ async fn start_game(
 
         maxima.update().await;
         if maxima.playing().is_none() {
+            // Saves are uploaded in the background, exiting would cut that off
+            maxima.finish_saves_upload().await;
             break;
         }
 
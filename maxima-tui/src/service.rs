@@ -1,12 +1,27 @@
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::{
+    future::Future,
+    sync::mpsc::{self, Receiver, Sender},
+};
 
 use anyhow::{bail, Result};
-use log::info;
-use maxima::core::{
-    auth::{
-        context::AuthContext, login::begin_oauth_login_flow, nucleus_token_exchange, TokenResponse,
+use log::{info, warn};
+use maxima::{
+    core::{
+        auth::{
+            context::AuthContext, login::begin_oauth_login_flow, nucleus_token_exchange,
+            TokenResponse,
+        },
+        LockedMaxima, Maxima, MaximaOptionsBuilder,
     },
-    LockedMaxima, Maxima, MaximaOptionsBuilder,
+    util::registry::check_registry_validity,
+};
+
+#[cfg(windows)]
+use is_elevated::is_elevated;
+
+#[cfg(windows)]
+use maxima::core::background_service::{
+    request_registry_setup, set_up_service, ServiceSetupProgress, ServiceSetupStep,
 };
 
 pub struct InteractThreadLoginResponse {
@@ -25,7 +40,34 @@ pub enum MaximaLibRequest {
     ShutdownRequest,
 }
 
+/// A step of getting the background service and registry ready, before Maxima starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetupStep {
+    /// Windows only
+    InstallService,
+    /// Reinstalling a service from another version of Maxima. Windows only.
+    UpdateService,
+    /// Windows only
+    StartService,
+    FixRegistry,
+}
+
+#[cfg(windows)]
+impl From<ServiceSetupStep> for SetupStep {
+    fn from(step: ServiceSetupStep) -> Self {
+        match step {
+            ServiceSetupStep::Install => SetupStep::InstallService,
+            ServiceSetupStep::Update => SetupStep::UpdateService,
+            ServiceSetupStep::Start => SetupStep::StartService,
+        }
+    }
+}
+
 pub enum MaximaLibResponse {
+    SetupStepStarted(SetupStep),
+    SetupStepFinished(SetupStep),
+    /// The step failed and the bridge is stopping, with why
+    SetupStepFailed(SetupStep, String),
     LoginResponse(InteractThreadLoginResponse),
     LoginCacheEmpty,
     GameInfoResponse(),
@@ -34,6 +76,7 @@ pub enum MaximaLibResponse {
     GameDetailsResponse(),
     GameUIImagesResponse(),
     InteractionThreadDiedResponse,
+    ShutdownFinished,
 }
 
 pub struct BridgeThread {
@@ -63,7 +106,72 @@ impl BridgeThread {
         Self { rx: rx0, tx: tx0 }
     }
 
+    /// Run one step of the native setup, telling the app when it starts and how it went
+    async fn setup_step<E>(
+        tx1: &Sender<MaximaLibResponse>,
+        step: SetupStep,
+        run: impl Future<Output = Result<(), E>>,
+    ) -> Result<()>
+    where
+        anyhow::Error: From<E>,
+    {
+        tx1.send(MaximaLibResponse::SetupStepStarted(step))?;
+        match run.await.map_err(anyhow::Error::from) {
+            Ok(()) => {
+                tx1.send(MaximaLibResponse::SetupStepFinished(step))?;
+                Ok(())
+            }
+            Err(err) => {
+                tx1.send(MaximaLibResponse::SetupStepFailed(step, err.to_string()))?;
+                Err(err)
+            }
+        }
+    }
+
+    #[cfg(windows)]
+    async fn native_setup(tx1: &Sender<MaximaLibResponse>) -> Result<()> {
+        if !is_elevated() {
+            // Stops once the app is gone, since nobody's left to see it
+            set_up_service(|progress| {
+                let response = match progress {
+                    ServiceSetupProgress::Started(step) => {
+                        MaximaLibResponse::SetupStepStarted(step.into())
+                    }
+                    ServiceSetupProgress::Finished(step) => {
+                        MaximaLibResponse::SetupStepFinished(step.into())
+                    }
+                    ServiceSetupProgress::Failed(step, err) => {
+                        MaximaLibResponse::SetupStepFailed(step.into(), err.to_string())
+                    }
+                };
+                tx1.send(response).is_ok()
+            })
+            .await?;
+        }
+
+        if let Err(err) = check_registry_validity() {
+            warn!("{}, fixing...", err);
+            Self::setup_step(tx1, SetupStep::FixRegistry, request_registry_setup()).await?;
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    async fn native_setup(tx1: &Sender<MaximaLibResponse>) -> Result<()> {
+        use maxima::util::registry::set_up_registry;
+
+        if let Err(err) = check_registry_validity() {
+            warn!("{}, fixing...", err);
+            Self::setup_step(tx1, SetupStep::FixRegistry, async { set_up_registry() }).await?;
+        }
+
+        Ok(())
+    }
+
     async fn run(rx1: Receiver<MaximaLibRequest>, tx1: Sender<MaximaLibResponse>) -> Result<()> {
+        Self::native_setup(&tx1).await?;
+
         let maxima_arc: LockedMaxima = Maxima::new_with_options(
             MaximaOptionsBuilder::default()
                 .dummy_local_user(false)
@@ -159,14 +267,18 @@ impl BridgeThread {
                 MaximaLibRequest::StartGameRequest(offer_id, hardcode) => {
                     //start_game_request(maxima_arc.clone(), offer_id.clone(), hardcode).await;
                 }
-                MaximaLibRequest::ShutdownRequest => break 'outer Ok(()),
+                MaximaLibRequest::ShutdownRequest => {
+                    maxima_arc.lock().await.shutdown().await;
+                    tx1.send(MaximaLibResponse::ShutdownFinished)?;
+                    break 'outer Ok(());
+                }
             }
         }
     }
 }
 
 pub async fn login_flow() -> Result<TokenResponse> {
-    let mut auth_context = AuthContext::new()?;
+    let mut auth_context = AuthContext::for_login()?;
     begin_oauth_login_flow(&mut auth_context).await?;
 
     if auth_context.code().is_none() {
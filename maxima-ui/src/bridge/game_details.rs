@@ -1,36 +1,32 @@
 use egui::Context;
-use std::sync::mpsc::Sender;
+use log::warn;
+use std::{path::PathBuf, sync::mpsc::Sender};
 
 use crate::{
     bridge_thread::{BackendError, InteractThreadGameDetailsResponse, MaximaLibResponse},
     util::markdown::html_to_easymark,
     GameDetails,
 };
-use maxima::core::{
-    service_layer::{
-        ServiceGameSystemRequirements, ServiceGameSystemRequirementsRequestBuilder,
-        SERVICE_REQUEST_GAMESYSTEMREQUIREMENTS,
-    },
-    LockedMaxima,
-};
+use maxima::core::{system_requirements::RequirementSpec, LockedMaxima};
+
+fn summarize(spec: &RequirementSpec) -> Option<String> {
+    if spec.is_empty() {
+        None
+    } else {
+        Some(spec.summary())
+    }
+}
 
 pub async fn game_details_request(
     maxima_arc: LockedMaxima,
     slug: String,
+    install_folder: Option<PathBuf>,
     channel: Sender<MaximaLibResponse>,
     ctx: &Context,
 ) -> Result<(), BackendError> {
-    let maxima = maxima_arc.lock().await;
-
-    let rq = maxima.service_layer().request(
-        SERVICE_REQUEST_GAMESYSTEMREQUIREMENTS,
-        ServiceGameSystemRequirementsRequestBuilder::default()
-            .slug(slug.clone())
-            .locale(maxima.locale().short_str().to_owned())
-            .build()
-            .unwrap(),
-    );
-    let rq: ServiceGameSystemRequirements = rq.await?;
+    let mut maxima = maxima_arc.lock().await;
+
+    let rq = maxima.system_requirements(&slug).await?;
 
     //TODO: parse async
 
@@ -43,6 +39,41 @@ pub async fn game_details_request(
         (None, None)
     };
 
+    let (min_summary, rec_summary) = if let Some(requirements) = rq.system_requirements().first() {
+        let parsed = requirements.parsed();
+        (summarize(&parsed.minimum), summarize(&parsed.recommended))
+    } else {
+        (None, None)
+    };
+
+    let verdict = match &install_folder {
+        Some(folder) => maxima.meets_requirements_at(&slug, Some(folder)).await,
+        None => maxima.meets_requirements(&slug).await,
+    };
+    let requirements_verdict = match verdict {
+        Ok(verdict) => Some(verdict),
+        Err(err) => {
+            warn!(
+                "Failed to compare system requirements for {}: {}",
+                slug, err
+            );
+            None
+        }
+    };
+
+    let offer = maxima.mut_library().game_by_base_slug(&slug).await?.clone();
+    // Measuring walks the whole install, which shouldn't hold up the rest of the backend
+    drop(maxima);
+
+    let installed_size = match offer.installed_size().await {
+        Ok(size) => size,
+        Err(err) => {
+            warn!("Failed to measure the size of {}: {}", slug, err);
+            None
+        }
+    };
+    let last_played = offer.stats().last_played().map(|x| x.format("%Y-%m-%d").to_string());
+
     let res = MaximaLibResponse::GameDetailsResponse(InteractThreadGameDetailsResponse {
         slug: slug.clone(),
         response: GameDetails {
@@ -52,6 +83,11 @@ pub async fn game_details_request(
             path: String::new(),
             system_requirements_min: min,
             system_requirements_rec: rec,
+            system_requirements_min_summary: min_summary,
+            system_requirements_rec_summary: rec_summary,
+            requirements_verdict,
+            installed_size,
+            last_played,
         },
     });
     let _ = channel.send(res);
@@ -1,6 +1,9 @@
 use egui::Context;
 use log::debug;
-use maxima::{core::LockedMaxima, rtm::client::BasicPresence};
+use maxima::{
+    core::{service_layer::ServicePlayer, LockedMaxima},
+    rtm::client::BasicPresence,
+};
 use std::sync::mpsc::Sender;
 
 use crate::{
@@ -22,7 +25,17 @@ pub async fn get_friends_request(
         return Err(BackendError::LoggedOut);
     }
 
-    let friends = maxima.friends(0).await?;
+    let friends = maxima.all_friends().await?;
+    send_friends(friends.friends(), &channel, &remote_provider_channel, ctx)
+}
+
+/// Report `friends` to the frontend and queue their avatars for loading
+pub fn send_friends(
+    friends: &[ServicePlayer],
+    channel: &Sender<MaximaLibResponse>,
+    remote_provider_channel: &Sender<UIImageCacheLoaderCommand>,
+    ctx: &Context,
+) -> Result<(), BackendError> {
     for friend in friends {
         remote_provider_channel.send(UIImageCacheLoaderCommand::ProvideRemote(
             crate::ui_image::UIImageType::Avatar(friend.id().to_string()),
@@ -34,6 +47,8 @@ pub async fn get_friends_request(
             online: BasicPresence::Offline,
             game: None,
             game_presence: None,
+            game_offer: None,
+            joinable: false,
         };
 
         let res = MaximaLibResponse::FriendInfoResponse(InteractThreadFriendListResponse {
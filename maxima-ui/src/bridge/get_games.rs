@@ -4,16 +4,18 @@ use crate::{
     GameDetailsWrapper, GameInfo, GameSettings, GameVersionInfo,
 };
 use egui::Context;
-use log::{debug, info};
+use log::{debug, info, warn};
 use maxima::{
     core::{
+        concurrency::CancellationToken,
+        library::OwnedTitle,
         service_layer::{
             ServiceGame, ServiceGameHubCollection, ServiceGameImagesRequestBuilder,
-            ServiceHeroBackgroundImageRequestBuilder, ServiceLayerClient,
-            SERVICE_REQUEST_GAMEIMAGES, SERVICE_REQUEST_GETHEROBACKGROUNDIMAGE,
+            SharedServiceLayer, SERVICE_REQUEST_GAMEIMAGES,
         },
         LockedMaxima,
     },
+    gameinfo::channel::build_channel,
     util::native::maxima_dir,
 };
 use std::{fs, sync::mpsc::Sender};
@@ -104,7 +106,8 @@ async fn handle_images(
     has_logo: bool,
     has_background: bool,
     channel: Sender<UIImageCacheLoaderCommand>,
-    service_layer: ServiceLayerClient,
+    service_layer: SharedServiceLayer,
+    maxima_arc: LockedMaxima,
 ) -> Result<(), BackendError> {
     debug!("handling image downloads for {}", &slug);
     let images_0 = if has_hero && has_logo {
@@ -122,19 +125,6 @@ async fn handle_images(
             ),
         )
     };
-    let images_1 = if has_background {
-        None
-    } else {
-        Some(
-            service_layer.request(
-                SERVICE_REQUEST_GETHEROBACKGROUNDIMAGE,
-                ServiceHeroBackgroundImageRequestBuilder::default()
-                    .game_slug(slug.clone())
-                    .locale(locale.clone())
-                    .build()?,
-            ),
-        )
-    };
 
     let images_0 = if let Some(images) = images_0 {
         images.await?
@@ -166,14 +156,10 @@ async fn handle_images(
 
     // I'm doing it down here because this call has a tendency to fail at the time of writing.
     // If it's down here it only takes down the background image, and not the logo/hero.
-    let images_1 = if let Some(images) = images_1 {
-        images.await?
-    } else {
-        None
-    };
-
     if !has_background {
-        if let Some(background_image) = get_preferred_bg_hero(&images_1) {
+        // Maxima is locked while the game list is sent, so this waits for it to finish
+        let background = maxima_arc.lock().await.hero_background(&slug).await?;
+        if let Some(background_image) = get_preferred_bg_hero(&background) {
             channel.send(UIImageCacheLoaderCommand::ProvideRemote(
                 crate::ui_image::UIImageType::Background(slug),
                 background_image,
@@ -188,28 +174,47 @@ pub async fn get_games_request(
     maxima_arc: LockedMaxima,
     channel: Sender<MaximaLibResponse>,
     channel1: Sender<UIImageCacheLoaderCommand>,
+    cancel: &CancellationToken,
     ctx: &Context,
 ) -> Result<(), BackendError> {
     debug!("received request to load games");
     let mut maxima = maxima_arc.lock().await;
     let service_layer = maxima.service_layer().clone();
-    let locale = maxima.locale().short_str().to_owned();
+    let locale = maxima.locale().short_code().to_owned();
     let logged_in = maxima.auth_storage().lock().await.current().is_some();
     if !logged_in {
         return Err(BackendError::LoggedOut);
     }
 
+    maxima.mut_library().load(cancel).await?;
     let owned_games = maxima.mut_library().games().await?.clone();
+    send_games(
+        owned_games,
+        maxima_arc.clone(),
+        service_layer,
+        locale,
+        channel,
+        channel1,
+        ctx,
+    )
+    .await
+}
 
+/// Report `owned_games` to the frontend, fetching any of their images that aren't cached yet
+pub async fn send_games(
+    owned_games: Vec<OwnedTitle>,
+    maxima_arc: LockedMaxima,
+    service_layer: SharedServiceLayer,
+    locale: String,
+    channel: Sender<MaximaLibResponse>,
+    channel1: Sender<UIImageCacheLoaderCommand>,
+    ctx: &Context,
+) -> Result<(), BackendError> {
     for game in owned_games {
         let slug = game.base_offer().slug().clone();
         info!("processing {}", &slug);
-        let downloads = game.base_offer().offer().downloads();
-        let opt = if downloads.len() == 1 {
-            &downloads[0]
-        } else {
-            downloads.iter().find(|item| item.download_type() == "LIVE").unwrap()
-        };
+        let game_channel = build_channel(&slug);
+        let opt = game.base_offer().offer().channel_download(game_channel).unwrap();
 
         let version = if let Ok(version) = game.base_offer().installed_version().await {
             version
@@ -217,10 +222,13 @@ pub async fn get_games_request(
             "Unknown".to_owned()
         };
 
+        let stats = game.base_offer().stats();
+        let installed = game.base_offer().is_installed().await;
         let game_info = GameInfo {
             slug: slug.clone(),
             offer: game.base_offer().offer().offer_id().to_string(),
             name: game.name(),
+            publisher: game.publisher().map(str::to_owned),
             details: GameDetailsWrapper::Unloaded,
             version: GameVersionInfo {
                 installed: version,
@@ -228,21 +236,47 @@ pub async fn get_games_request(
                 mandatory: opt.treat_updates_as_mandatory().clone(),
             },
             dlc: game.extra_offers().clone(),
-            installed: game.base_offer().is_installed().await,
+            installed,
             has_cloud_saves: game.base_offer().offer().has_cloud_save(),
+            trial: game.trial(),
+            disabled: game.is_disabled(),
+            access_ended: game.access_ended(),
+            unavailable: game.unavailable(),
+            ownership: game.ownership_method().cloned(),
+            platforms: game.platforms().clone(),
+            platform_support: game.platform_support(),
+            installed_size: stats.installed_size(),
+            last_played: stats.last_played().map(|x| x.timestamp()),
+            build_channel: game_channel.into(),
         };
         let slug = game_info.slug.clone();
-        let settings = GameSettings {
-            exe_override: String::new(),
-            launch_args: String::new(),
-            cloud_saves: true,
-        };
+        let settings = GameSettings::new();
         let res = MaximaLibResponse::GameInfoResponse(InteractThreadGameListResponse {
             game: game_info,
             settings,
         });
         channel.send(res)?;
 
+        // Sorting by size only knows the sizes that were measured, so measure the rest
+        if installed && stats.installed_size().is_none() {
+            let offer = game.base_offer().clone();
+            let channel = channel.clone();
+            let context = ctx.clone();
+            tokio::task::spawn(async move {
+                match offer.installed_size().await {
+                    Ok(Some(size)) => {
+                        let _ = channel.send(MaximaLibResponse::InstalledSizeMeasured(
+                            offer.slug().clone(),
+                            size,
+                        ));
+                        context.request_repaint();
+                    }
+                    Ok(None) => {}
+                    Err(err) => warn!("Failed to measure the size of {}: {}", offer.slug(), err),
+                }
+            });
+        }
+
         let bg = maxima_dir()?.join("cache/ui/images/").join(&slug).join("background.jpg");
         let game_hero = maxima_dir()?.join("cache/ui/images/").join(&slug).join("hero.jpg");
         let game_logo = maxima_dir()?.join("cache/ui/images/").join(&slug).join("logo.png");
@@ -256,6 +290,7 @@ pub async fn get_games_request(
             let locale_send = locale.clone();
             let channel_send = channel1.clone();
             let service_layer_send = service_layer.clone();
+            let maxima_send = maxima_arc.clone();
             tokio::task::spawn(async move {
                 handle_images(
                     slug_send,
@@ -265,6 +300,7 @@ pub async fn get_games_request(
                     has_background,
                     channel_send,
                     service_layer_send,
+                    maxima_send,
                 )
                 .await
             });
@@ -273,5 +309,8 @@ pub async fn get_games_request(
 
         egui::Context::request_repaint(&ctx);
     }
+
+    channel.send(MaximaLibResponse::GameListFinished)?;
+    egui::Context::request_repaint(&ctx);
     Ok(())
 }
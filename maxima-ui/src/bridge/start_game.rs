@@ -1,26 +1,25 @@
 use crate::{bridge_thread::BackendError, GameInfo, GameSettings};
 use log::{debug, error, info};
 use maxima::core::{
+    cloudsync::CloudSyncFilter,
+    concurrency::CancellationToken,
     launch::{self, LaunchError, LaunchMode, LaunchOptions},
-    LockedMaxima,
+    LockedMaxima, Maxima,
 };
+use std::path::PathBuf;
 
-pub async fn start_game_request(
-    maxima_arc: LockedMaxima,
-    game_info: GameInfo,
-    game_settings: Option<GameSettings>,
-) -> Result<(), LaunchError> {
-    let maxima = maxima_arc.lock().await;
-    let logged_in = maxima.auth_storage().lock().await.current().is_some();
-    if !logged_in {
-        info!("Ignoring request to start game, not logged in.");
-        return Ok(()); // TODO(headassbtw): look into if it's worth properly reporting this
-    }
-
-    debug!("got request to start game {:?}", game_info.offer);
+fn launch_options(game_settings: Option<GameSettings>, cancel: CancellationToken) -> LaunchOptions {
+    let env = game_settings.as_ref().map(|x| x.env_overrides().0).unwrap_or_default();
+    let working_dir = game_settings
+        .as_ref()
+        .map(|x| x.working_dir.trim())
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from);
 
     // This is kind of gross, but it kind of makes sense to have?
-    let (exe_override, args, cloud_saves) = if let Some(settings) = game_settings {
+    let (exe_override, args, cloud_saves, cloud_sync_filter) = if let Some(settings) = game_settings
+    {
+        let cloud_sync_filter = settings.cloud_sync_filter();
         (
             if settings.exe_override.is_empty() {
                 None
@@ -29,20 +28,61 @@ pub async fn start_game_request(
             },
             launch::parse_arguments(&settings.launch_args),
             settings.cloud_saves,
+            cloud_sync_filter,
         )
     } else {
-        (None, Vec::new(), true)
+        (None, Vec::new(), true, CloudSyncFilter::default())
     };
 
+    LaunchOptions {
+        path_override: exe_override,
+        arguments: args,
+        cloud_saves,
+        cloud_sync_filter,
+        cancel,
+        join: None,
+        env,
+        license_request: None,
+        working_dir,
+    }
+}
+
+pub async fn start_game_request(
+    maxima_arc: LockedMaxima,
+    game_info: GameInfo,
+    game_settings: Option<GameSettings>,
+    cancel: CancellationToken,
+) -> Result<(), LaunchError> {
+    let maxima = maxima_arc.lock().await;
+    let logged_in = maxima.auth_storage().lock().await.current().is_some();
+    if !logged_in {
+        info!("Ignoring request to start game, not logged in.");
+        return Ok(()); // TODO(headassbtw): look into if it's worth properly reporting this
+    }
+
+    debug!("got request to start game {:?}", game_info.offer);
+
     drop(maxima);
     launch::start_game(
         maxima_arc.clone(),
         LaunchMode::Online(game_info.offer),
-        LaunchOptions {
-            path_override: exe_override,
-            arguments: args,
-            cloud_saves,
-        },
+        launch_options(game_settings, cancel),
+    )
+    .await
+}
+
+pub async fn join_friend_request(
+    maxima_arc: LockedMaxima,
+    friend_id: String,
+    game_settings: Option<GameSettings>,
+    cancel: CancellationToken,
+) -> Result<(), LaunchError> {
+    debug!("got request to join {}", friend_id);
+
+    Maxima::join_friend(
+        maxima_arc,
+        &friend_id,
+        launch_options(game_settings, cancel),
     )
     .await
 }
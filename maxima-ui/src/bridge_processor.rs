@@ -1,10 +1,14 @@
 use crate::{
     bridge_thread::{self, BackendError},
     views::downloads_view::QueuedDownload,
-    BackendStallState, GameDetails, GameDetailsWrapper, MaximaEguiApp,
+    BackendStallState, GameDetails, GameDetailsWrapper, GameMoveProgress, MaximaEguiApp,
 };
 use log::{error, info, warn};
-use std::sync::mpsc::TryRecvError;
+use std::{
+    collections::HashMap,
+    sync::mpsc::TryRecvError,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 pub fn frontend_processor(app: &mut MaximaEguiApp, ctx: &egui::Context) {
     puffin::profile_function!();
@@ -18,6 +22,10 @@ pub fn frontend_processor(app: &mut MaximaEguiApp, ctx: &egui::Context) {
             Ok(result) => {
                 use bridge_thread::MaximaLibResponse::*;
                 match result {
+                    SetupStepStarted(step) => app.setup_step = Some(step),
+                    SetupStepFinished(_) => app.setup_step = None,
+                    // The backend stops after this, the critical error has the details
+                    SetupStepFailed(step, err) => warn!("Setup step {:?} failed: {}", step, err),
                     LoginResponse(res) => {
                         if let Err(error) = &res {
                             warn!("Login failed. {}", error);
@@ -29,23 +37,48 @@ pub fn frontend_processor(app: &mut MaximaEguiApp, ctx: &egui::Context) {
                         app.user_name = res.you.display_name().clone();
                         app.user_id = res.you.id().clone();
                         app.backend_state = BackendStallState::BingChilling;
-                        app.backend
-                            .backend_commander
-                            .send(bridge_thread::MaximaLibRequest::GetGamesRequest)
-                            .unwrap();
-                        app.backend
-                            .backend_commander
-                            .send(bridge_thread::MaximaLibRequest::GetFriendsRequest)
-                            .unwrap();
+                        // Games and friends arrive on their own, they're fetched alongside the user
+                        app.send_download_limits();
+                        app.send_cloud_saves();
+                        app.send_update_poll();
                     }
                     LoginCacheEmpty => app.backend_state = BackendStallState::UserNeedsToLogIn,
+                    LoginCancelled => {
+                        app.login_cancel = None;
+                        app.backend_state = BackendStallState::UserNeedsToLogIn;
+                    }
                     ServiceNeedsStarting => {
                         app.backend_state = BackendStallState::UserNeedsToInstallService
                     }
                     ServiceStarted => app.backend_state = BackendStallState::Starting,
+                    ServiceUnavailable => app.service_unavailable = true,
+                    LocalPresence(presence) => app.local_presence = presence,
                     GameInfoResponse(res) => {
                         app.games.insert(res.game.slug.clone(), res.game);
                     }
+                    GameListFinished => {
+                        app.games_loaded = true;
+                        let folders = if app.settings.default_install_folder.is_empty() {
+                            Vec::new()
+                        } else {
+                            vec![app.settings.default_install_folder.clone().into()]
+                        };
+                        app.backend
+                            .backend_commander
+                            .send(bridge_thread::MaximaLibRequest::ScanInstalledGamesRequest(
+                                folders,
+                            ))
+                            .unwrap();
+                    }
+                    PartialInstallFound(offer_id, queued) => {
+                        info!("Found partial install of {}, queued: {}", offer_id, queued);
+                        app.partial_installs.insert(offer_id);
+                    }
+                    InstalledGamesFound(found) => {
+                        info!("Found {} games installed outside Maxima", found.len());
+                        app.found_installs =
+                            found.into_iter().map(|x| (x.slug().clone(), x)).collect();
+                    }
                     GameDetailsResponse(res) => {
                         let response = res.response;
 
@@ -61,72 +94,166 @@ pub fn frontend_processor(app: &mut MaximaEguiApp, ctx: &egui::Context) {
                                 path: response.path.clone(),
                                 system_requirements_min: response.system_requirements_min.clone(),
                                 system_requirements_rec: response.system_requirements_rec.clone(),
+                                system_requirements_min_summary: response
+                                    .system_requirements_min_summary
+                                    .clone(),
+                                system_requirements_rec_summary: response
+                                    .system_requirements_rec_summary
+                                    .clone(),
+                                requirements_verdict: response.requirements_verdict,
+                                installed_size: response.installed_size,
+                                last_played: response.last_played.clone(),
                             });
+                            game.installed_size = response.installed_size;
                         }
                     }
                     FriendInfoResponse(res) => app.friends.push(res.friend),
+                    DiagnosticsWritten(path) => app.diagnostics_path = Some(path),
+                    SyncLockChecked(released) => {
+                        info!(
+                            "Checked for a leftover cloud save lock, released: {}",
+                            released
+                        );
+                        app.sync_lock_released = Some(released);
+                    }
+                    InstalledSizeMeasured(slug, size) => {
+                        if let Some(game) = app.games.get_mut(&slug) {
+                            game.installed_size = Some(size);
+                        }
+                    }
+                    FriendRequestFinished(id, outcome) => {
+                        info!("Friend request with {} finished: {:?}", id, outcome);
+                        app.friends_view_bar.add_friend_outcome = Some(outcome);
+                    }
                     CriticalError(err) => app.critical_error = Some(*err),
                     NonFatalError(err) => app.nonfatal_errors.push(*err),
-                    ActiveGameChanged(slug) => app.playing_game = slug,
+                    ActiveGameChanged(slug) => {
+                        // The backend recorded the launch, reload the details to show it
+                        if let Some(game) = slug.as_ref().and_then(|x| app.games.get_mut(x)) {
+                            game.last_played = SystemTime::now()
+                                .duration_since(UNIX_EPOCH)
+                                .ok()
+                                .map(|x| x.as_secs() as i64);
+                            game.details = GameDetailsWrapper::Unloaded;
+                        }
+                        app.playing_game = slug;
+                    }
                     LocateGameResponse(res) => {
                         app.installer_state.locate_response = Some(res);
                         app.installer_state.locating = false;
                     }
+                    InstallSpaceResponse(offer, space, build) => {
+                        if app.installer_state.offer == offer {
+                            app.installer_state.required_space = Some(space);
+                            app.installer_state.build = Some(build);
+                        }
+                    }
+                    BuildChannelChanged(slug, latest, mandatory) => {
+                        if let Some(game) = app.games.get_mut(&slug) {
+                            game.version.latest = latest;
+                            game.version.mandatory = mandatory;
+                        }
+                    }
+                    UpdateAvailable(offer_id, latest) => {
+                        if let Some(game) = app.games.values_mut().find(|x| x.offer == offer_id) {
+                            game.version.latest = latest;
+                        }
+                    }
                     DownloadProgressChanged(offer_id, progress) => {
-                        if let Some(dl_ing) = app.installing_now.as_mut() {
-                            if dl_ing.offer == offer_id {
-                                dl_ing.downloaded_bytes = progress.bytes;
-                                dl_ing.total_bytes = progress.bytes_total;
+                        if let Some(dl_ing) =
+                            app.installing_now.iter_mut().find(|n| n.offer == offer_id)
+                        {
+                            dl_ing.downloaded_bytes = progress.bytes;
+                            dl_ing.total_bytes = progress.bytes_total;
+                            dl_ing.speed_bps = progress.speed_bps;
+                            dl_ing.eta = progress.eta;
+                            dl_ing.verification = progress.verification;
+                        }
+                    }
+                    InstallFailed(offer_id, log) => match log {
+                        Some(log) => {
+                            app.failed_install_logs.insert(offer_id, log);
+                        }
+                        None => {
+                            app.failed_install_logs.remove(&offer_id);
+                        }
+                    },
+                    DownloadFinished(offer_id) => {
+                        app.partial_installs.remove(&offer_id);
+                        app.failed_install_logs.remove(&offer_id);
+                        // Its size is measured again when the details are next loaded
+                        if let Some(game) = app.games.values_mut().find(|x| x.offer == offer_id) {
+                            game.installed_size = None;
+                            game.details = GameDetailsWrapper::Unloaded;
+                            // Installs and updates get the build of the game's channel, which `latest` tracks
+                            game.version.installed = game.version.latest.clone();
+
+                            if app.launch_after_update.as_ref() == Some(&game.slug) {
+                                app.launch_after_update = None;
+                                app.playing_game = Some(game.slug.clone());
+                                let settings = app.settings.game_settings.get(&game.slug).cloned();
+                                let _ = app.backend.backend_commander.send(
+                                    bridge_thread::MaximaLibRequest::StartGameRequest(
+                                        game.clone(),
+                                        settings,
+                                    ),
+                                );
                             }
                         }
                     }
-                    DownloadFinished(_) => {}
-                    DownloadQueueUpdate(current, queue) => {
-                        if let Some(current) = current {
-                            if !app.installing_now.as_ref().is_some_and(|n| n.offer == current) {
-                                app.installing_now = Some(QueuedDownload {
-                                    slug: {
-                                        // This sucks!
-                                        let mut rtn: String = String::new();
-                                        for (slug, game) in &app.games {
-                                            if game.offer.eq(&current) {
-                                                // "but it's less code in the nest"
-                                                // "WHO CARES"
-                                                // (it was the same amount overall)
-                                                rtn = slug.to_string();
-                                                break;
-                                            }
-                                        }
-                                        rtn
-                                    },
-                                    offer: current,
-                                    downloaded_bytes: 0,
-                                    total_bytes: 0,
-                                })
+                    MoveProgressChanged(slug, copied, total) => {
+                        app.game_move = Some(GameMoveProgress {
+                            slug,
+                            copied,
+                            total,
+                        });
+                    }
+                    MoveFinished(slug) => {
+                        app.game_move = None;
+                        if let Some(game) = app.games.get_mut(&slug) {
+                            game.details = GameDetailsWrapper::Unloaded;
+                        }
+                    }
+                    ShutdownFinished => {}
+                    DownloadQueueUpdate(active, queue) => {
+                        // The update was cancelled or couldn't be started
+                        if let Some(slug) = &app.launch_after_update {
+                            let offer = app.games.get(slug).map(|x| &x.offer);
+                            if !offer.is_some_and(|x| active.contains(x) || queue.contains(x)) {
+                                app.launch_after_update = None;
                             }
-                        } else {
-                            app.installing_now = None;
                         }
 
-                        app.install_queue.clear();
-                        for offer in queue {
-                            let i_fucking_hate_this = QueuedDownload {
-                                slug: {
-                                    let mut rtn: String = String::new();
-                                    for (slug, game) in &app.games {
-                                        if game.offer.eq(&offer) {
-                                            rtn = slug.to_string();
-                                            break;
-                                        }
-                                    }
-                                    rtn
+                        // Rebuilt from the backend's queue, keeping the progress of
+                        // downloads that are still going
+                        let mut known: Vec<QueuedDownload> = app.installing_now.drain(..).collect();
+                        known.extend(app.install_queue.drain().map(|(_, x)| x));
+                        let mut take =
+                            |offer: String| match known.iter().position(|x| x.offer == offer) {
+                                Some(index) => known.swap_remove(index),
+                                None => QueuedDownload {
+                                    slug: app
+                                        .games
+                                        .values()
+                                        .find(|x| x.offer == offer)
+                                        .map(|x| x.slug.clone())
+                                        .unwrap_or_default(),
+                                    build: app.install_builds.remove(&offer),
+                                    offer,
+                                    downloaded_bytes: 0,
+                                    total_bytes: 0,
+                                    speed_bps: None,
+                                    eta: None,
+                                    verification: None,
                                 },
-                                offer: offer.clone(),
-                                downloaded_bytes: 0,
-                                total_bytes: 0,
                             };
-                            app.install_queue.insert(offer, i_fucking_hate_this);
-                        }
+
+                        let installing_now: Vec<QueuedDownload> =
+                            active.into_iter().map(&mut take).collect();
+                        let install_queue: HashMap<String, QueuedDownload> =
+                            queue.into_iter().map(|x| (x.clone(), take(x))).collect();
+                        app.installing_now = installing_now;
+                        app.install_queue = install_queue;
                     }
                 }
                 ctx.request_repaint();
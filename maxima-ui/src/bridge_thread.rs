@@ -3,8 +3,11 @@ use log::{error, info, warn};
 
 use crate::{
     bridge::{
-        game_details::game_details_request, get_friends::get_friends_request,
-        get_games::get_games_request, login_oauth::login_oauth, start_game::start_game_request,
+        game_details::game_details_request,
+        get_friends::{get_friends_request, send_friends},
+        get_games::{get_games_request, send_games},
+        login_oauth::login_oauth,
+        start_game::{join_friend_request, start_game_request},
     },
     event_thread::{EventThread, MaximaEventRequest, MaximaEventResponse},
     ui_image::UIImageCacheLoaderCommand,
@@ -13,22 +16,33 @@ use crate::{
 };
 use maxima::{
     content::manager::{
-        ContentManager, ContentManagerError, QueuedGameBuilder, QueuedGameBuilderError,
+        ContentManagerError, DownloadQueue, DownloaderError, QueuedGameBuilder,
+        QueuedGameBuilderError, RequiredSpace,
     },
     core::{
         auth::storage::{AuthError, TokenError},
+        cloudsync::CloudSyncError,
+        concurrency::{cancellable, CancellationToken, Cancelled},
+        diagnostics::DiagnosticsError,
+        friends::{FriendRequestError, FriendRequestOutcome},
+        install_scan::FoundInstall,
         launch::LaunchError,
         library::LibraryError,
         manifest::{self, ManifestError, MANIFEST_RELATIVE_PATH},
         service_layer::{
-            ServiceGameImagesRequestBuilderError, ServiceHeroBackgroundImageRequestBuilderError,
-            ServiceLayerError, ServicePlayer,
+            ServiceAvailableBuild, ServiceGameImagesRequestBuilderError,
+            ServiceHeroBackgroundImageRequestBuilderError, ServiceLayerError, ServicePlayer,
+            ServiceUser,
         },
-        LockedMaxima, Maxima, MaximaCreationError, MaximaOptionsBuilder, MaximaOptionsBuilderError,
+        BootstrapEvent, LockedMaxima, Maxima, MaximaCreationError, MaximaOptionsBuilder,
+        MaximaOptionsBuilderError,
+    },
+    gameinfo::{
+        channel::{build_channel, set_build_channel, BuildChannel},
+        GameInstallInfo, GameVersionError,
     },
-    gameinfo::GameInstallInfo,
     lsx::service::LSXServerError,
-    rtm::RtmError,
+    rtm::{client::BasicPresence, RtmError},
     util::{
         native::{maxima_dir, NativeError},
         registry::{check_registry_validity, set_up_registry, RegistryError},
@@ -36,7 +50,8 @@ use maxima::{
 };
 use std::sync::mpsc::{SendError, TryRecvError};
 use std::{
-    path::PathBuf,
+    future::Future,
+    path::{Path, PathBuf},
     sync::mpsc::{Receiver, Sender},
     time::{Duration, SystemTime},
 };
@@ -73,36 +88,122 @@ pub enum InteractThreadLocateGameResponse {
 pub struct InteractThreadDownloadProgressResponse {
     pub bytes: usize,
     pub bytes_total: usize,
+    pub speed_bps: Option<u64>,
+    pub eta: Option<Duration>,
+    /// Files checked out of the total, once the download is being verified
+    pub verification: Option<(usize, usize)>,
 }
 
 pub enum MaximaLibRequest {
     StartService,
-    LoginRequestOauth,
+    /// Carry on without the background service, declining the prompt to install it
+    SkipService,
+    LoginRequestOauth(CancellationToken), // cancelled from the logging in screen
     GetGamesRequest,
     GetFriendsRequest,
-    GetGameDetailsRequest(String),
+    GetGameDetailsRequest(String, Option<PathBuf>, CancellationToken), // slug, install folder if not installed, cancelled when another game is selected
     StartGameRequest(GameInfo, Option<GameSettings>),
-    InstallGameRequest(String, String, PathBuf, Option<PathBuf>), // offer, slug, path, wine prefix (unix only)
+    JoinFriendRequest(String, Option<GameSettings>), // friend's user ID, settings of the game they're playing
+    InstallGameRequest(String, String, PathBuf, Option<PathBuf>, Option<PathBuf>), // offer, slug, path, wine prefix (unix only), real path if linked
     LocateGameRequest(String, String, Option<PathBuf>), // slug, path, wine prefix (unix only)
+    InstallSpaceRequest(String, String),                // offer, slug
+    UpdateGameRequest(String),                          // offer
+    ResumeInstallRequest(String),                       // offer
+    OpenInstallFolderRequest(String),                   // offer
+    OpenSaveFolderRequest(String),                      // offer
+    OpenInstallLogRequest(PathBuf),                     // log file
+    CancelInstallRequest(String),                       // offer
+    MoveGameRequest(String, String, PathBuf),           // offer, slug, new path
+    SetDownloadLimits(usize, Option<u64>), // concurrent downloads, bandwidth limit in bytes per second
+    SetCloudSaves(bool, usize),            // sync automatically, save backups to keep per game
+    SetUpdatePollInterval(Option<Duration>),
+    SetBuildChannelRequest(String, BuildChannel), // slug, channel to install and update from
+    ScanInstalledGamesRequest(Vec<PathBuf>),      // extra folders to look in
+    SendFriendRequest(String),                    // user ID
+    SetPresenceRequest(BasicPresence),            // online, away, or offline to appear invisible
+    WriteDiagnosticsRequest,
+    ReleaseSyncLockRequest,
     ShutdownRequest,
 }
 
+/// A step of getting the background service and registry ready, before Maxima starts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetupStep {
+    /// Registering the background service, which needs the user to allow it. Windows only.
+    InstallService,
+    /// Reinstalling a service from another version of Maxima, which needs the user to
+    /// allow it too. Windows only.
+    UpdateService,
+    /// Windows only
+    StartService,
+    /// Pointing the registry entries games look for at Maxima
+    FixRegistry,
+}
+
+#[cfg(windows)]
+impl From<maxima::core::background_service::ServiceSetupStep> for SetupStep {
+    fn from(step: maxima::core::background_service::ServiceSetupStep) -> Self {
+        use maxima::core::background_service::ServiceSetupStep;
+        match step {
+            ServiceSetupStep::Install => SetupStep::InstallService,
+            ServiceSetupStep::Update => SetupStep::UpdateService,
+            ServiceSetupStep::Start => SetupStep::StartService,
+        }
+    }
+}
+
 pub enum MaximaLibResponse {
+    SetupStepStarted(SetupStep),
+    SetupStepFinished(SetupStep),
+    /// The step failed and the backend is stopping, with why
+    SetupStepFailed(SetupStep, String),
     LoginResponse(Result<InteractThreadLoginResponse, anyhow::Error>),
     LoginCacheEmpty,
+    /// The browser login was cancelled or not finished in time, so it has to be started again
+    LoginCancelled,
     ServiceNeedsStarting,
     ServiceStarted,
+    /// Running without the background service, so the registry isn't fixed and DLLs
+    /// can't be injected
+    ServiceUnavailable,
+    /// The presence the local user picked last time
+    LocalPresence(BasicPresence),
     GameInfoResponse(InteractThreadGameListResponse),
+    /// Sent once every game in the library has been sent, even if there were none
+    GameListFinished,
     FriendInfoResponse(InteractThreadFriendListResponse),
     GameDetailsResponse(InteractThreadGameDetailsResponse),
     LocateGameResponse(InteractThreadLocateGameResponse),
+    InstallSpaceResponse(String, RequiredSpace, ServiceAvailableBuild), // offer, space needed by its channel's build, that build
+    /// Slug, and the version and whether updating is mandatory on the game's new channel
+    BuildChannelChanged(String, String, bool),
+    /// Offer ID of an install that didn't finish, and whether it's still queued
+    PartialInstallFound(String, bool),
+    /// Owned games installed outside Maxima, complete or not
+    InstalledGamesFound(Vec<FoundInstall>),
+    /// A friend request went through, or theirs was accepted
+    FriendRequestFinished(String, FriendRequestOutcome),
+    /// Path of the diagnostics bundle that was just written
+    DiagnosticsWritten(PathBuf),
+    /// Whether a cloud save lock left behind by a previous session was released
+    SyncLockChecked(bool),
+    /// Slug and bytes taken up by an install whose size wasn't known when it was listed
+    InstalledSizeMeasured(String, u64),
     // Alerts, rather than responses:
     CriticalError(Box<BackendError>),
     NonFatalError(Box<BackendError>),
     ActiveGameChanged(Option<String>),
     DownloadProgressChanged(String, InteractThreadDownloadProgressResponse),
     DownloadFinished(String),
-    DownloadQueueUpdate(Option<String>, Vec<String>),
+    /// Offer ID of an installed game, and the version of the newer build on its channel
+    UpdateAvailable(String, String),
+    /// Offer ID of an install that gave up, and where it was logged
+    InstallFailed(String, Option<PathBuf>),
+    DownloadQueueUpdate(Vec<String>, Vec<String>),
+    MoveProgressChanged(String, u64, u64), // slug, bytes copied, bytes total
+    MoveFinished(String),
+    /// The bridge has stopped, after flushing Maxima's state if it was running
+    ShutdownFinished,
 }
 pub struct BridgeThread {
     pub backend_listener: Receiver<MaximaLibResponse>,
@@ -110,6 +211,10 @@ pub struct BridgeThread {
 
     pub rtm_listener: Receiver<MaximaEventResponse>,
     pub rtm_commander: Sender<MaximaEventRequest>, // currently unused except for shutdown
+
+    /// Cancelled when the window closes, so whatever the backend is busy with stops
+    /// instead of holding up shutdown. Tokens for single requests should be children of it.
+    pub cancel: CancellationToken,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -121,8 +226,18 @@ pub enum BackendError {
     #[error(transparent)]
     BackgroundServiceClient(#[from] maxima::core::error::BackgroundServiceClientError),
     #[error(transparent)]
+    Cancelled(#[from] Cancelled),
+    #[error(transparent)]
+    CloudSync(#[from] CloudSyncError),
+    #[error(transparent)]
     ContentManager(#[from] ContentManagerError),
     #[error(transparent)]
+    Diagnostics(#[from] DiagnosticsError),
+    #[error(transparent)]
+    FriendRequest(#[from] FriendRequestError),
+    #[error(transparent)]
+    GameVersion(#[from] GameVersionError),
+    #[error(transparent)]
     Launch(#[from] LaunchError),
     #[error(transparent)]
     Library(#[from] LibraryError),
@@ -157,33 +272,94 @@ pub enum BackendError {
 
     #[error("backend-frontend communication channel disconnected")]
     ChannelDisconnected,
+    #[error("failed to upload the saves of {0}: {1}")]
+    SavesUpload(String, String),
     #[error("tried to perform an action that requires being logged in, but was logged out")]
     LoggedOut,
+    #[error("`{0}` has no save folder yet, games usually make it the first time they save")]
+    NoSaveFolder(String),
+    #[error("failed to open {0}: {1}")]
+    OpenFolder(PathBuf, std::io::Error),
 }
 
-impl BridgeThread {
-    fn update_queue(
-        content_manager: &ContentManager,
-        backend_responder: Sender<MaximaLibResponse>,
-    ) {
-        let current = if let Some(now) = content_manager.queue().current() {
-            Some(now.offer_id().to_owned())
-        } else {
-            None
-        };
+/// Show `path` in the system's file manager
+fn open_folder(path: &Path) -> Result<(), BackendError> {
+    open::that(path).map_err(|err| BackendError::OpenFolder(path.to_owned(), err))
+}
 
-        let mut queue: Vec<String> = Vec::new();
+impl BridgeThread {
+    /// Run one step of the native setup, telling the frontend when it starts and how it went
+    async fn setup_step<E>(
+        backend_responder: &Sender<MaximaLibResponse>,
+        ctx: &Context,
+        step: SetupStep,
+        run: impl Future<Output = Result<(), E>>,
+    ) -> Result<(), BackendError>
+    where
+        BackendError: From<E>,
+    {
+        backend_responder.send(MaximaLibResponse::SetupStepStarted(step))?;
+        ctx.request_repaint();
 
-        for game in content_manager.queue().queued() {
-            queue.push(game.offer_id().to_owned());
+        let result = run.await.map_err(BackendError::from);
+        match &result {
+            Ok(()) => backend_responder.send(MaximaLibResponse::SetupStepFinished(step))?,
+            Err(err) => {
+                error!("{:?} failed: {}", step, err);
+                backend_responder.send(MaximaLibResponse::SetupStepFailed(step, err.to_string()))?
+            }
         }
+        ctx.request_repaint();
+        result
+    }
+
+    fn update_queue(queue: &DownloadQueue, backend_responder: &Sender<MaximaLibResponse>) {
+        let active: Vec<String> =
+            queue.active().iter().map(|game| game.offer_id().to_owned()).collect();
+        let queued: Vec<String> =
+            queue.queued().iter().map(|game| game.offer_id().to_owned()).collect();
 
         backend_responder
-            .send(MaximaLibResponse::DownloadQueueUpdate(current, queue))
+            .send(MaximaLibResponse::DownloadQueueUpdate(active, queued))
             .unwrap();
     }
 
-    pub fn new(ctx: &Context, remote_provider_channel: Sender<UIImageCacheLoaderCommand>) -> Self {
+    fn send_local_user(
+        user: ServiceUser,
+        backend_responder: &Sender<MaximaLibResponse>,
+        remote_provider_channel: &Sender<UIImageCacheLoaderCommand>,
+    ) -> Result<(), BackendError> {
+        let player = user.player().as_ref().ok_or(ServiceLayerError::MissingField)?;
+        backend_responder.send(MaximaLibResponse::LoginResponse(Ok(
+            InteractThreadLoginResponse {
+                you: player.to_owned(),
+            },
+        )))?;
+
+        let res = remote_provider_channel.send(UIImageCacheLoaderCommand::ProvideRemote(
+            crate::ui_image::UIImageType::Avatar(user.id().to_string()),
+            player
+                .avatar()
+                .as_ref()
+                .ok_or(ServiceLayerError::MissingField)?
+                .medium()
+                .path()
+                .to_string(),
+        ));
+        if let Err(err) = res {
+            error!("failed to send user pfp to loader: {:?}", err);
+        }
+
+        Ok(())
+    }
+
+    /// With `skip_service`, the background service isn't installed or started on Windows,
+    /// and whatever needs it is left off
+    pub fn new(
+        ctx: &Context,
+        remote_provider_channel: Sender<UIImageCacheLoaderCommand>,
+        skip_service: bool,
+    ) -> Self {
         puffin::profile_function!();
         let (backend_commander, backend_cmd_listener) = std::sync::mpsc::channel();
         let (backend_responder, backend_listener) = std::sync::mpsc::channel();
@@ -191,6 +367,8 @@ impl BridgeThread {
         let (rtm_commander, rtm_cmd_listener) = std::sync::mpsc::channel();
         let (rtm_responder, rtm_listener) = std::sync::mpsc::channel();
         let context = ctx.clone();
+        let cancel = CancellationToken::new();
+        let run_cancel = cancel.clone();
 
         tokio::task::spawn(async move {
             let die_fallback_transmitter = backend_responder.clone();
@@ -201,6 +379,8 @@ impl BridgeThread {
                 rtm_cmd_listener,
                 rtm_responder,
                 remote_provider_channel,
+                run_cancel,
+                skip_service,
                 &context,
             )
             .await;
@@ -209,7 +389,8 @@ impl BridgeThread {
                     .send(MaximaLibResponse::CriticalError(Box::from(err)))
                     .unwrap();
             } else {
-                info!("Interact thread shut down")
+                info!("Interact thread shut down");
+                let _ = die_fallback_transmitter.send(MaximaLibResponse::ShutdownFinished);
             }
         });
 
@@ -218,6 +399,7 @@ impl BridgeThread {
             backend_commander,
             rtm_listener,
             rtm_commander,
+            cancel,
         }
     }
 
@@ -227,6 +409,8 @@ impl BridgeThread {
         rtm_cmd_listener: Receiver<MaximaEventRequest>,
         rtm_responder: Sender<MaximaEventResponse>,
         remote_provider_channel: Sender<UIImageCacheLoaderCommand>,
+        cancel: CancellationToken,
+        skip_service: bool,
         ctx: &Context,
     ) -> Result<(), BackendError> {
         // first things first check registry
@@ -234,53 +418,100 @@ impl BridgeThread {
         // so we're doing both here, instead of selectively cfg'd functions!
         #[cfg(not(windows))]
         {
+            // There's no service to skip outside windows
+            let _ = skip_service;
             if let Err(err) = check_registry_validity() {
                 warn!("{}, fixing...", err);
-                set_up_registry()?;
+                Self::setup_step(&backend_responder, ctx, SetupStep::FixRegistry, async {
+                    set_up_registry()
+                })
+                .await?;
             }
         }
         #[cfg(windows)]
         {
             use maxima::{
-                core::background_service::request_registry_setup,
-                util::{
-                    registry::check_registry_validity,
-                    service::{
-                        is_service_running, is_service_valid, register_service_user, start_service,
-                    },
+                core::background_service::{
+                    request_registry_setup, set_up_service, ServiceSetupProgress,
                 },
+                util::registry::check_registry_validity,
             };
-            if !is_elevated::is_elevated() {
-                if !is_service_valid()? {
-                    info!("Installing service...");
-                    backend_responder.send(MaximaLibResponse::ServiceNeedsStarting)?;
-                    'wait_for_user_to_authorize: loop {
-                        let request = backend_cmd_listener.try_recv();
-                        if request.is_err() {
-                            continue;
-                        }
+            // Elevated, Maxima does what it would ask the service to itself
+            let elevated = is_elevated::is_elevated();
+            let mut without_service = skip_service && !elevated;
+            if !elevated && !without_service {
+                let mut shutdown = false;
+                let set_up = set_up_service(|progress| {
+                    let response = match progress {
+                        ServiceSetupProgress::Started(step) => {
+                            let step = SetupStep::from(step);
 
-                        match request.unwrap() {
-                            MaximaLibRequest::StartService => {
-                                register_service_user()?;
-                                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-                                break 'wait_for_user_to_authorize;
+                            // Installing and updating need the user to allow it first
+                            if step != SetupStep::StartService {
+                                if backend_responder
+                                    .send(MaximaLibResponse::ServiceNeedsStarting)
+                                    .is_err()
+                                {
+                                    return false;
+                                }
+
+                                'wait_for_user_to_authorize: loop {
+                                    let request = backend_cmd_listener.try_recv();
+                                    if request.is_err() {
+                                        continue;
+                                    }
+
+                                    match request.unwrap() {
+                                        MaximaLibRequest::StartService => {
+                                            break 'wait_for_user_to_authorize
+                                        }
+                                        MaximaLibRequest::SkipService => return false,
+                                        MaximaLibRequest::ShutdownRequest => {
+                                            shutdown = true;
+                                            return false;
+                                        }
+                                        _ => {}
+                                    }
+                                }
                             }
-                            MaximaLibRequest::ShutdownRequest => return Ok(()),
-                            _ => {}
+                            MaximaLibResponse::SetupStepStarted(step)
                         }
-                    }
-                }
+                        ServiceSetupProgress::Finished(step) => {
+                            MaximaLibResponse::SetupStepFinished(step.into())
+                        }
+                        ServiceSetupProgress::Failed(step, err) => {
+                            error!("{:?} failed: {}", step, err);
+                            MaximaLibResponse::SetupStepFailed(step.into(), err.to_string())
+                        }
+                    };
+
+                    let sent = backend_responder.send(response).is_ok();
+                    ctx.request_repaint();
+                    sent
+                })
+                .await?;
 
-                if !is_service_running()? {
-                    info!("Starting service...");
-                    start_service().await?;
+                if shutdown {
+                    return Ok(());
                 }
+                without_service = !set_up;
             }
 
-            if let Err(err) = check_registry_validity() {
+            if without_service {
+                warn!("Running without the background service");
+                if let Err(err) = check_registry_validity() {
+                    warn!("{}, and it can't be fixed without the service", err);
+                }
+                backend_responder.send(MaximaLibResponse::ServiceUnavailable)?;
+            } else if let Err(err) = check_registry_validity() {
                 warn!("{}, fixing...", err);
-                request_registry_setup().await?;
+                Self::setup_step(
+                    &backend_responder,
+                    ctx,
+                    SetupStep::FixRegistry,
+                    request_registry_setup(),
+                )
+                .await?;
             }
         }
         let maxima_arc: LockedMaxima = Maxima::new_with_options(
@@ -309,14 +540,16 @@ impl BridgeThread {
                 }
 
                 match request? {
-                    MaximaLibRequest::LoginRequestOauth => {
-                        let channel = backend_responder.clone();
+                    MaximaLibRequest::LoginRequestOauth(cancel) => {
                         let maxima = maxima_arc.clone();
                         let context = ctx.clone();
-                        async move { login_oauth(maxima, channel, &context).await }
-                            .await
-                            .expect("// TODO(headassbtw): panic message");
-                        break 'outer;
+                        match login_oauth(maxima, &context, &cancel).await {
+                            Ok(()) => break 'outer,
+                            Err(BackendError::Auth(AuthError::LoginCancelled)) => {
+                                backend_responder.send(MaximaLibResponse::LoginCancelled)?;
+                            }
+                            Err(err) => return Err(err),
+                        }
                     }
                     MaximaLibRequest::ShutdownRequest => return Ok(()),
                     _ => {}
@@ -325,31 +558,89 @@ impl BridgeThread {
         }
 
         {
-            let maxima = maxima_arc.lock().await;
-            let user = maxima.local_user().await?;
+            let mut maxima = maxima_arc.lock().await;
+            let service_layer = maxima.service_layer().clone();
+            let locale = maxima.locale().short_code().to_owned();
+
+            let presence = maxima.local_presence().basic().clone();
+            backend_responder.send(MaximaLibResponse::LocalPresence(presence))?;
 
-            if logged_in {
-                let message = MaximaLibResponse::LoginResponse(Ok(InteractThreadLoginResponse {
-                    you: user.player().as_ref().unwrap().to_owned(),
-                }));
-                backend_responder.send(message)?;
+            let mut user_error = None;
+            let bootstrap = maxima.bootstrap(|event| {
+                let result = match event {
+                    BootstrapEvent::LocalUser(Err(err)) => {
+                        user_error = Some(err);
+                        Ok(())
+                    }
+                    BootstrapEvent::LocalUser(Ok(user)) => {
+                        Self::send_local_user(user, &backend_responder, &remote_provider_channel)
+                    }
+                    BootstrapEvent::Library(Ok(games)) => {
+                        // Checking install state is slow, don't hold up the rest
+                        let channel = backend_responder.clone();
+                        let channel1 = remote_provider_channel.clone();
+                        let maxima = maxima_arc.clone();
+                        let service_layer = service_layer.clone();
+                        let locale = locale.clone();
+                        let context = ctx.clone();
+                        tokio::spawn(async move {
+                            let result = send_games(
+                                games,
+                                maxima,
+                                service_layer,
+                                locale,
+                                channel.clone(),
+                                channel1,
+                                &context,
+                            )
+                            .await;
+                            if let Err(err) = result {
+                                let _ =
+                                    channel.send(MaximaLibResponse::NonFatalError(Box::from(err)));
+                            }
+                        });
+                        Ok(())
+                    }
+                    BootstrapEvent::Friends(Ok(friends)) => send_friends(
+                        friends.friends(),
+                        &backend_responder,
+                        &remote_provider_channel,
+                        ctx,
+                    ),
+                    BootstrapEvent::Presence(Ok(())) => Ok(()),
+                    BootstrapEvent::Library(Err(err)) => Err(err.into()),
+                    BootstrapEvent::Friends(Err(err)) => Err(err.into()),
+                    BootstrapEvent::Presence(Err(err)) => Err(err.into()),
+                };
+
+                if let Err(err) = result {
+                    let _ =
+                        backend_responder.send(MaximaLibResponse::NonFatalError(Box::from(err)));
+                }
+                ctx.request_repaint();
+            });
+
+            // Closed before everything loaded
+            let finished = tokio::select! {
+                _ = bootstrap => true,
+                _ = cancel.cancelled() => false,
+            };
+            if !finished {
+                maxima.shutdown().await;
+                return Ok(());
+            }
+
+            if let Some(err) = user_error {
+                return Err(err.into());
+            }
+
+            // Reported through events, once the update loop starts
+            if let Err(err) = maxima.reconcile_installs().await {
+                warn!("Failed to check for partial installs: {}", err);
             }
-            let res = remote_provider_channel.send(UIImageCacheLoaderCommand::ProvideRemote(
-                crate::ui_image::UIImageType::Avatar(user.id().to_string()),
-                user.player()
-                    .as_ref()
-                    .ok_or(ServiceLayerError::MissingField)?
-                    .avatar()
-                    .as_ref()
-                    .ok_or(ServiceLayerError::MissingField)?
-                    .medium()
-                    .path()
-                    .to_string(),
-            ));
-            if let Err(err) = res {
-                error!("failed to send user pfp to loader: {:?}", err);
+            if let Err(err) = maxima.release_orphaned_lock().await {
+                warn!("Failed to release a leftover cloud save lock: {}", err);
             }
-            ctx.request_repaint();
         }
 
         let _ = EventThread::new(
@@ -388,12 +679,15 @@ impl BridgeThread {
                     };
                 }
 
-                if let Some(dl) = maxima.content_manager().current() {
+                for dl in maxima.content_manager().active() {
                     backend_responder.send(MaximaLibResponse::DownloadProgressChanged(
                         dl.offer_id().to_string(),
                         InteractThreadDownloadProgressResponse {
                             bytes: dl.bytes_downloaded(),
                             bytes_total: dl.bytes_total(),
+                            speed_bps: dl.current_speed_bps(),
+                            eta: dl.eta(),
+                            verification: dl.verification_progress(),
                         },
                     ))?;
                 }
@@ -404,7 +698,51 @@ impl BridgeThread {
                         maxima::core::MaximaEvent::InstallFinished(offer_id) => {
                             backend_responder
                                 .send(MaximaLibResponse::DownloadFinished(offer_id))?;
-                            Self::update_queue(maxima.content_manager(), backend_responder.clone());
+                        }
+                        maxima::core::MaximaEvent::InstallEntryFailed {
+                            offer_id,
+                            file,
+                            reason,
+                        } => warn!("Failed to download {} of {}: {}", file, offer_id, reason),
+                        maxima::core::MaximaEvent::InstallFailed(offer_id, files) => {
+                            let log = maxima
+                                .content_manager()
+                                .install_log(&offer_id)
+                                .map(|x| x.to_owned());
+                            backend_responder
+                                .send(MaximaLibResponse::InstallFailed(offer_id, log))?;
+                            let err =
+                                ContentManagerError::from(DownloaderError::EntriesFailed(files));
+                            backend_responder
+                                .send(MaximaLibResponse::NonFatalError(Box::new(err.into())))?;
+                        }
+                        maxima::core::MaximaEvent::QueueChanged(queue) => {
+                            Self::update_queue(&queue, &backend_responder);
+                            ctx.request_repaint();
+                        }
+                        maxima::core::MaximaEvent::PartialInstallFound { offer_id, queued } => {
+                            backend_responder
+                                .send(MaximaLibResponse::PartialInstallFound(offer_id, queued))?;
+                        }
+                        maxima::core::MaximaEvent::SavesUploadStarted(offer_id) => {
+                            info!("Uploading saves of {}", offer_id)
+                        }
+                        maxima::core::MaximaEvent::SavesUploadFinished(_, None) => {}
+                        maxima::core::MaximaEvent::SavesUploadFinished(offer_id, Some(err)) => {
+                            let err = BackendError::SavesUpload(offer_id, err);
+                            backend_responder
+                                .send(MaximaLibResponse::NonFatalError(Box::new(err)))?;
+                        }
+                        maxima::core::MaximaEvent::OrphanedSyncLockReleased => {
+                            backend_responder.send(MaximaLibResponse::SyncLockChecked(true))?;
+                            ctx.request_repaint();
+                        }
+                        maxima::core::MaximaEvent::UpdateAvailable { offer_id, build } => {
+                            if let Some(version) = build.game_version().clone() {
+                                backend_responder
+                                    .send(MaximaLibResponse::UpdateAvailable(offer_id, version))?;
+                                ctx.request_repaint();
+                            }
                         }
                     }
                 }
@@ -415,7 +753,9 @@ impl BridgeThread {
             }
 
             let action = match request? {
-                MaximaLibRequest::LoginRequestOauth | MaximaLibRequest::StartService => {
+                MaximaLibRequest::LoginRequestOauth(_)
+                | MaximaLibRequest::StartService
+                | MaximaLibRequest::SkipService => {
                     error!("bro tried to log in twice");
                     Ok(())
                 }
@@ -424,7 +764,8 @@ impl BridgeThread {
                     let channel1 = remote_provider_channel.clone();
                     let maxima = maxima_arc.clone();
                     let context = ctx.clone();
-                    async move { get_games_request(maxima, channel, channel1, &context).await }
+                    let cancel = cancel.clone();
+                    async move { get_games_request(maxima, channel, channel1, &cancel, &context).await }
                         .await
                 }
                 MaximaLibRequest::GetFriendsRequest => {
@@ -435,11 +776,18 @@ impl BridgeThread {
                     async move { get_friends_request(maxima, channel, channel1, &context).await }
                         .await
                 }
-                MaximaLibRequest::GetGameDetailsRequest(slug) => {
+                MaximaLibRequest::GetGameDetailsRequest(slug, install_folder, details_cancel) => {
                     let channel = backend_responder.clone();
                     let maxima = maxima_arc.clone();
                     let context = ctx.clone();
-                    async move { game_details_request(maxima, slug.clone(), channel, &context).await }.await
+                    let details = game_details_request(
+                        maxima,
+                        slug.clone(),
+                        install_folder,
+                        channel,
+                        &context,
+                    );
+                    cancellable(&details_cancel, details).await
                 }
                 MaximaLibRequest::LocateGameRequest(slug, path, wine_prefix) => {
                     let game_install_info =
@@ -450,7 +798,26 @@ impl BridgeThread {
                         path.remove(path.len() - 1);
                     }
                     let path = PathBuf::from(path);
-                    let manifest = manifest::read(path.join(MANIFEST_RELATIVE_PATH)).await;
+                    let content_id = match maxima_arc
+                        .lock()
+                        .await
+                        .mut_library()
+                        .game_by_base_slug(&slug)
+                        .await
+                    {
+                        Err(LibraryError::NotOwned(_)) => None,
+                        offer => Some(offer?.offer().content_id().to_owned()),
+                    };
+
+                    // Make sure the folder holds the game being located and not some other one
+                    let manifest = manifest::read(path.join(MANIFEST_RELATIVE_PATH))
+                        .await
+                        .and_then(|manifest| {
+                            if let Some(content_id) = &content_id {
+                                manifest.check_for(content_id)?;
+                            }
+                            Ok(manifest)
+                        });
                     if let Ok(manifest) = manifest {
                         let guh = manifest.run_touchup(&path, &slug).await;
                         if let Err(err) = guh {
@@ -493,11 +860,21 @@ impl BridgeThread {
                     ctx.request_repaint();
                     Ok(())
                 }
-                MaximaLibRequest::InstallGameRequest(offer, slug, path, wine_prefix) => {
+                MaximaLibRequest::InstallGameRequest(offer, slug, path, wine_prefix, real_path) => {
                     let mut maxima = maxima_arc.lock().await;
+                    // Can't be downloaded without owning it anyway
+                    let owned = maxima.mut_library().game_by_base_offer(&offer).await?;
+                    if let Some(reason) = owned.unavailable() {
+                        let name = owned.offer().display_name().to_owned();
+                        let err = BackendError::from(LibraryError::Unavailable(name, reason));
+                        backend_responder.send(MaximaLibResponse::NonFatalError(Box::new(err)))?;
+                        continue;
+                    }
+                    let content_id = owned.offer().content_id().to_owned();
+
                     let builds =
                         maxima.content_manager().service().available_builds(&offer).await?;
-                    let build = if let Some(build) = builds.live_build() {
+                    let build = if let Some(build) = builds.channel_build(build_channel(&slug)) {
                         build
                     } else {
                         continue;
@@ -515,16 +892,214 @@ impl BridgeThread {
                         .path(path.to_owned())
                         .slug(slug.to_owned())
                         .wine_prefix(wine_prefix)
+                        .real_path(real_path)
+                        .content_id(Some(content_id))
                         .build()?;
                     Ok(maxima.content_manager().add_install(game).await?)
                 }
-                MaximaLibRequest::StartGameRequest(info, settings) => {
-                    Ok(start_game_request(maxima_arc.clone(), info, settings).await?)
+                MaximaLibRequest::UpdateGameRequest(offer) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    let owned = maxima.mut_library().game_by_base_offer(&offer).await?.clone();
+                    let result = maxima.content_manager().add_update(&owned).await;
+                    // The queue didn't change, but the frontend has to stop waiting to launch
+                    if result.is_err() {
+                        Self::update_queue(maxima.content_manager().queue(), &backend_responder);
+                        ctx.request_repaint();
+                    }
+                    Ok(result?)
+                }
+                MaximaLibRequest::ResumeInstallRequest(offer) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    let owned = maxima.mut_library().game_by_base_offer(&offer).await?.clone();
+                    Ok(maxima.content_manager().resume_install(&owned).await?)
+                }
+                MaximaLibRequest::OpenInstallFolderRequest(offer) => {
+                    let path = maxima_arc.lock().await.install_path(&offer).await?;
+                    open_folder(&path)
+                }
+                MaximaLibRequest::OpenInstallLogRequest(path) => open_folder(&path),
+                MaximaLibRequest::OpenSaveFolderRequest(offer) => {
+                    let folders = maxima_arc.lock().await.save_paths(&offer).await?;
+                    // Games tend to make their save folder the first time they save
+                    match folders.iter().find(|x| x.is_dir()) {
+                        Some(folder) => open_folder(folder),
+                        None => Err(BackendError::NoSaveFolder(offer)),
+                    }
+                }
+                MaximaLibRequest::CancelInstallRequest(offer) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    maxima.content_manager().cancel_install(&offer).await?;
+                    Ok(())
+                }
+                MaximaLibRequest::InstallSpaceRequest(offer, slug) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    let content_manager = maxima.content_manager();
+                    let builds = content_manager.service().available_builds(&offer).await?;
+                    let build = if let Some(build) = builds.channel_build(build_channel(&slug)) {
+                        build
+                    } else {
+                        continue;
+                    };
+
+                    let space = content_manager
+                        .required_space(&offer, build.build_id(), &slug, None)
+                        .await?;
+                    backend_responder.send(MaximaLibResponse::InstallSpaceResponse(
+                        offer,
+                        space,
+                        build.clone(),
+                    ))?;
+                    ctx.request_repaint();
+                    Ok(())
+                }
+                MaximaLibRequest::MoveGameRequest(offer, slug, path) => {
+                    let install_move =
+                        maxima_arc.lock().await.content_manager().prepare_move(&offer, &slug, path);
+                    match install_move {
+                        Ok(install_move) => {
+                            // Copying across drives can take a while, don't hold up other requests
+                            let channel = backend_responder.clone();
+                            let context = ctx.clone();
+                            tokio::spawn(async move {
+                                let progress_channel = channel.clone();
+                                let progress_slug = slug.clone();
+                                let progress_context = context.clone();
+                                let result = install_move
+                                    .run(Some(Box::new(move |copied, total| {
+                                        let _ = progress_channel.send(
+                                            MaximaLibResponse::MoveProgressChanged(
+                                                progress_slug.clone(),
+                                                copied,
+                                                total,
+                                            ),
+                                        );
+                                        progress_context.request_repaint();
+                                    })))
+                                    .await;
+
+                                if let Err(err) = result {
+                                    let _ = channel.send(MaximaLibResponse::NonFatalError(
+                                        Box::from(BackendError::from(err)),
+                                    ));
+                                }
+                                let _ = channel.send(MaximaLibResponse::MoveFinished(slug));
+                                context.request_repaint();
+                            });
+                            Ok(())
+                        }
+                        Err(err) => {
+                            // The frontend marks the move as started when it's requested
+                            let _ = backend_responder.send(MaximaLibResponse::MoveFinished(slug));
+                            ctx.request_repaint();
+                            Err(err.into())
+                        }
+                    }
+                }
+                MaximaLibRequest::SetDownloadLimits(concurrent, bandwidth_limit) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    let content_manager = maxima.content_manager();
+                    content_manager.set_max_concurrent(concurrent);
+                    content_manager.set_bandwidth_limit(bandwidth_limit);
+                    Ok(())
+                }
+                MaximaLibRequest::SetBuildChannelRequest(slug, channel) => {
+                    set_build_channel(&slug, channel)?;
+
+                    let mut maxima = maxima_arc.lock().await;
+                    let offer = maxima.mut_library().game_by_base_slug(&slug).await?;
+                    if let Some(download) = offer.offer().channel_download(channel) {
+                        backend_responder.send(MaximaLibResponse::BuildChannelChanged(
+                            slug,
+                            download.version().to_owned(),
+                            *download.treat_updates_as_mandatory(),
+                        ))?;
+                        ctx.request_repaint();
+                    }
+                    Ok(())
+                }
+                MaximaLibRequest::SetCloudSaves(enabled, backups) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    let cloud_sync = maxima.mut_cloud_sync();
+                    cloud_sync.set_automatic_sync(enabled);
+                    cloud_sync.set_backup_retention(backups);
+                    Ok(())
+                }
+                MaximaLibRequest::SetUpdatePollInterval(interval) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    maxima.set_update_poll_interval(interval);
+                    Ok(())
+                }
+                MaximaLibRequest::SetPresenceRequest(presence) => {
+                    let mut maxima = maxima_arc.lock().await;
+                    let status = maxima.local_presence().status().clone();
+                    maxima.set_local_presence(presence, &status).await?;
+                    Ok(())
+                }
+                MaximaLibRequest::ScanInstalledGamesRequest(folders) => {
+                    let found = maxima_arc.lock().await.scan_installed_games(&folders).await?;
+                    backend_responder.send(MaximaLibResponse::InstalledGamesFound(found))?;
+                    ctx.request_repaint();
+                    Ok(())
+                }
+                MaximaLibRequest::SendFriendRequest(id) => {
+                    let maxima = maxima_arc.lock().await;
+                    let outcome = maxima.send_friend_request(&id).await?;
+                    if outcome == FriendRequestOutcome::Accepted {
+                        let player = maxima.player_by_id(&id).await?;
+                        send_friends(
+                            &[player],
+                            &backend_responder,
+                            &remote_provider_channel,
+                            &ctx,
+                        )?;
+                    }
+                    backend_responder
+                        .send(MaximaLibResponse::FriendRequestFinished(id, outcome))?;
+                    ctx.request_repaint();
+                    Ok(())
+                }
+                MaximaLibRequest::WriteDiagnosticsRequest => {
+                    let path = maxima_arc.lock().await.write_diagnostics().await?;
+                    backend_responder.send(MaximaLibResponse::DiagnosticsWritten(path))?;
+                    ctx.request_repaint();
+                    Ok(())
+                }
+                MaximaLibRequest::ReleaseSyncLockRequest => {
+                    let released = maxima_arc.lock().await.release_orphaned_lock().await?;
+                    // A released lock is reported through its event
+                    if !released {
+                        backend_responder.send(MaximaLibResponse::SyncLockChecked(false))?;
+                        ctx.request_repaint();
+                    }
+                    Ok(())
+                }
+                MaximaLibRequest::StartGameRequest(info, settings) => Ok(start_game_request(
+                    maxima_arc.clone(),
+                    info,
+                    settings,
+                    cancel.child_token(),
+                )
+                .await?),
+                MaximaLibRequest::JoinFriendRequest(id, settings) => {
+                    Ok(
+                        join_friend_request(maxima_arc.clone(), id, settings, cancel.child_token())
+                            .await?,
+                    )
+                }
+                MaximaLibRequest::ShutdownRequest => {
+                    maxima_arc.lock().await.shutdown().await;
+                    break 'outer Ok(());
                 }
-                MaximaLibRequest::ShutdownRequest => break 'outer Ok(()), //TODO: kill the bridge thread
             };
-            if let Err(err) = action {
-                let _ = backend_responder.send(MaximaLibResponse::NonFatalError(Box::from(err)));
+            match action {
+                // Stopped on purpose, nothing went wrong
+                Ok(())
+                | Err(BackendError::Cancelled(_))
+                | Err(BackendError::Library(LibraryError::Cancelled(_))) => {}
+                Err(err) => {
+                    let _ =
+                        backend_responder.send(MaximaLibResponse::NonFatalError(Box::from(err)));
+                }
             }
 
             puffin::GlobalProfiler::lock().new_frame();
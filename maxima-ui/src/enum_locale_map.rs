@@ -2,9 +2,9 @@ use crate::{
     translation_manager::TranslationManager,
     views::{
         friends_view::{FriendsViewBarPage, FriendsViewBarStatusFilter},
-        game_view::{GameViewBarGenre, GameViewBarPlatform},
+        game_view::{GameViewBarGenre, GameViewBarPlatform, GameViewBarSort},
     },
-    FrontendLanguage,
+    FrontendLanguage, GameBuildChannel, UpdatePolicy,
 };
 
 pub trait EnumToString<T> {
@@ -67,6 +67,21 @@ impl EnumToString<GameViewBarPlatform> for TranslationManager {
     }
 }
 
+impl EnumToString<GameViewBarSort> for TranslationManager {
+    fn get_string_nonmut(&self, variant: &GameViewBarSort) -> &str {
+        let locale = &self.localization.games_view.toolbar.sort_options;
+        match variant {
+            GameViewBarSort::Name => &locale.name,
+            GameViewBarSort::LastPlayed => &locale.last_played,
+            GameViewBarSort::Installed => &locale.installed,
+            GameViewBarSort::InstalledSize => &locale.installed_size,
+        }
+    }
+    fn get_string(&self, variant: &mut GameViewBarSort) -> &str {
+        self.get_string_nonmut(variant)
+    }
+}
+
 impl EnumToString<FrontendLanguage> for TranslationManager {
     fn get_string_nonmut(&self, variant: &FrontendLanguage) -> &str {
         match variant {
@@ -78,3 +93,30 @@ impl EnumToString<FrontendLanguage> for TranslationManager {
         self.get_string_nonmut(variant)
     }
 }
+
+impl EnumToString<UpdatePolicy> for TranslationManager {
+    fn get_string_nonmut(&self, variant: &UpdatePolicy) -> &str {
+        let locale = &self.localization.modals.game_settings.update_policy_options;
+        match variant {
+            UpdatePolicy::AutoUpdate => &locale.auto_update,
+            UpdatePolicy::Notify => &locale.notify,
+            UpdatePolicy::Never => &locale.never,
+        }
+    }
+    fn get_string(&self, variant: &mut UpdatePolicy) -> &str {
+        self.get_string_nonmut(variant)
+    }
+}
+
+impl EnumToString<GameBuildChannel> for TranslationManager {
+    fn get_string_nonmut(&self, variant: &GameBuildChannel) -> &str {
+        let locale = &self.localization.modals.game_settings.build_channel_options;
+        match variant {
+            GameBuildChannel::Live => &locale.live,
+            GameBuildChannel::Staged => &locale.staged,
+        }
+    }
+    fn get_string(&self, variant: &mut GameBuildChannel) -> &str {
+        self.get_string_nonmut(variant)
+    }
+}
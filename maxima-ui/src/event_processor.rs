@@ -12,8 +12,24 @@ pub fn frontend_processor(app: &mut MaximaEguiApp, ctx: &egui::Context) {
                     }
 
                     friend.online = res.presence.basic().clone();
-                    if res.presence.game().is_some() {
-                        friend.game = Some(res.presence.status().clone());
+                    match res.presence.activity() {
+                        Some(activity) => {
+                            friend.game = Some(
+                                activity
+                                    .title()
+                                    .clone()
+                                    .unwrap_or_else(|| res.presence.status().clone()),
+                            );
+                            friend.game_presence = activity.rich_presence().clone();
+                            friend.game_offer = Some(activity.product_id().clone());
+                            friend.joinable = activity.can_join();
+                        }
+                        None => {
+                            friend.game = None;
+                            friend.game_presence = None;
+                            friend.game_offer = None;
+                            friend.joinable = false;
+                        }
                     }
                 }
                 ctx.request_repaint();
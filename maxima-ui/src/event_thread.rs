@@ -3,12 +3,7 @@ use std::sync::mpsc::{Receiver, Sender};
 
 use crate::bridge_thread::BackendError;
 use log::info;
-use maxima::core::{
-    service_layer::{
-        ServiceFriends, ServiceGetMyFriendsRequestBuilder, SERVICE_REQUEST_GETMYFRIENDS,
-    },
-    LockedMaxima,
-};
+use maxima::core::LockedMaxima;
 
 // TODO(headassbtw): integrate this into the enum too (out of scope for the PR i wrote this in)
 pub struct EventThreadFriendStatusResponse {
@@ -54,31 +49,7 @@ impl EventThread {
         ctx: &Context,
         maxima_arc: LockedMaxima,
     ) -> Result<(), BackendError> {
-        let mut maxima = maxima_arc.lock().await;
-
-        let friends: ServiceFriends = maxima
-            .service_layer()
-            .request(
-                SERVICE_REQUEST_GETMYFRIENDS,
-                ServiceGetMyFriendsRequestBuilder::default()
-                    .offset(0)
-                    .limit(100)
-                    .is_mutual_friends_enabled(false)
-                    .build()
-                    .unwrap(),
-            )
-            .await?;
-
-        let rtm = maxima.rtm();
-        rtm.login().await?;
-
-        let players: Vec<String> =
-            friends.friends().items().iter().map(|f| f.id().to_owned()).collect();
-        info!("Subscribed to {} players", players.len());
-
-        rtm.subscribe(&players).await?;
-        drop(maxima);
-
+        // RTM is logged into, and subscribed to friends, by `Maxima::bootstrap`
         'outer: loop {
             let mut maxima = maxima_arc.lock().await;
             maxima.rtm().heartbeat().await?;
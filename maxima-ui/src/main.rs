@@ -8,9 +8,40 @@ use egui::{
     vec2, Align2, Color32, FontData, FontDefinitions, FontFamily, FontId, Layout, Margin, Rect,
     Response, Rounding, Stroke, Style, TextureId, Ui, Vec2, ViewportBuilder, Visuals, Widget,
 };
-use log::error;
-use maxima::{core::library::OwnedOffer, util::log::init_logger};
-use std::{collections::HashMap, default::Default, ops::RangeInclusive, path::PathBuf};
+use log::{error, warn};
+use maxima::{
+    content::{
+        install_template::{
+            resolve_install_template, InstallTemplateValues, DEFAULT_INSTALL_TEMPLATE,
+        },
+        manager::RequiredSpace,
+    },
+    core::{
+        cloudsync::CloudSyncFilter,
+        concurrency::{
+            default_file_verification, CancellationToken, DEFAULT_IMAGE_DOWNLOADS,
+            FILE_VERIFICATION, IMAGE_DOWNLOADS,
+        },
+        install_scan::FoundInstall,
+        launch::check_env_override,
+        library::{AccessEnded, OwnedOffer, PlatformSupport, TrialStatus, Unavailable},
+        manifest::ManifestError,
+        save_backup::DEFAULT_SAVE_BACKUP_RETENTION,
+        service_layer::{ServiceAvailableBuild, ServiceOwnershipMethod, ServicePlatform},
+        system_requirements::RequirementsVerdict,
+        SHUTDOWN_TIMEOUT,
+    },
+    gameinfo::channel::BuildChannel,
+    rtm::client::BasicPresence,
+    util::{log::init_logger, native::volume_space},
+};
+use std::{
+    collections::{HashMap, HashSet},
+    default::Default,
+    ops::RangeInclusive,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 use strum_macros::EnumIter;
 use ui_image::{UIImageCache, UIImageType};
 use views::{
@@ -19,7 +50,7 @@ use views::{
     friends_view::{
         friends_view, FriendsViewBar, FriendsViewBarPage, FriendsViewBarStatusFilter, UIFriend,
     },
-    game_view::{games_view, GameViewBar, GameViewBarGenre, GameViewBarPlatform},
+    game_view::{games_view, GameViewBar, GameViewBarGenre, GameViewBarPlatform, GameViewBarSort},
     settings_view::settings_view,
     undefined_view::{coming_soon_view, undefined_view},
 };
@@ -29,11 +60,12 @@ use egui_extras::{Size, StripBuilder};
 use egui_glow::glow;
 
 use app_bg_renderer::AppBgRenderer;
-use bridge_thread::{BackendError, BridgeThread, InteractThreadLocateGameResponse};
+use bridge_thread::{BackendError, BridgeThread, InteractThreadLocateGameResponse, SetupStep};
 use game_view_bg_renderer::GameViewBgRenderer;
-use maxima::util::native::maxima_dir;
+use maxima::util::{http, native::maxima_dir};
 use renderers::{app_bg_renderer, game_view_bg_renderer};
 use translation_manager::{positional_replace, TranslationManager};
+use widgets::enum_dropdown::enum_dropdown;
 
 pub mod bridge;
 pub mod util;
@@ -63,8 +95,9 @@ struct Args {
     profile: bool,
     #[arg(short, long)]
     no_login: bool,
-    #[arg(short, long)]
-    allow_bf3: bool,
+    /// Don't install or start the background service on Windows, leaving off what needs it
+    #[arg(long)]
+    no_service: bool,
 }
 
 #[tokio::main]
@@ -178,6 +211,16 @@ pub struct GameDetails {
     system_requirements_min: Option<String>,
     /// Recommended specs to run the game, in EasyMark spec
     system_requirements_rec: Option<String>,
+    /// One-line summary of the parsed minimum specs, if anything could be parsed
+    system_requirements_min_summary: Option<String>,
+    /// One-line summary of the parsed recommended specs, if anything could be parsed
+    system_requirements_rec_summary: Option<String>,
+    /// How this PC compares to the system requirements
+    requirements_verdict: Option<RequirementsVerdict>,
+    /// Bytes the installed game takes up
+    installed_size: Option<u64>,
+    /// Date the game was last launched
+    last_played: Option<String>,
 }
 
 #[derive(Clone)]
@@ -192,6 +235,62 @@ pub struct GameSettings {
     cloud_saves: bool,
     launch_args: String,
     exe_override: String,
+    /// Extra cloud save globs, one per line
+    #[serde(default)]
+    cloud_save_include: String,
+    /// Cloud save globs to leave out, one per line
+    #[serde(default)]
+    cloud_save_exclude: String,
+    /// Environment variables for the game, one `KEY=VALUE` per line
+    #[serde(default)]
+    env_overrides: String,
+    /// Folder to run the game in, relative to the executable's. Empty for the executable's own.
+    #[serde(default)]
+    working_dir: String,
+    /// What to do when the game is played while an update is out
+    #[serde(default)]
+    update_policy: UpdatePolicy,
+}
+
+/// What to do when an out-of-date game is played
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, EnumIter, serde::Serialize, serde::Deserialize,
+)]
+pub enum UpdatePolicy {
+    /// Download the update, then start the game
+    AutoUpdate,
+    /// Warn that there's an update, and let the user choose
+    #[default]
+    Notify,
+    /// Start the game as it is
+    Never,
+}
+
+/// Which builds of a game are installed and updated to, see [`BuildChannel`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, EnumIter)]
+pub enum GameBuildChannel {
+    #[default]
+    Live,
+    /// Not released yet, may be unstable
+    Staged,
+}
+
+impl From<BuildChannel> for GameBuildChannel {
+    fn from(channel: BuildChannel) -> Self {
+        match channel {
+            BuildChannel::Live => GameBuildChannel::Live,
+            BuildChannel::Staged => GameBuildChannel::Staged,
+        }
+    }
+}
+
+impl From<GameBuildChannel> for BuildChannel {
+    fn from(channel: GameBuildChannel) -> Self {
+        match channel {
+            GameBuildChannel::Live => BuildChannel::Live,
+            GameBuildChannel::Staged => BuildChannel::Staged,
+        }
+    }
 }
 
 impl GameSettings {
@@ -200,6 +299,39 @@ impl GameSettings {
             cloud_saves: true,
             launch_args: String::new(),
             exe_override: String::new(),
+            cloud_save_include: String::new(),
+            cloud_save_exclude: String::new(),
+            env_overrides: String::new(),
+            working_dir: String::new(),
+            update_policy: UpdatePolicy::Notify,
+        }
+    }
+
+    /// The environment variable overrides, and the lines that can't be used as one
+    pub fn env_overrides(&self) -> (HashMap<String, String>, Vec<String>) {
+        let mut env = HashMap::new();
+        let mut invalid = Vec::new();
+
+        for line in self.env_overrides.lines().map(str::trim).filter(|x| !x.is_empty()) {
+            match line.split_once('=') {
+                Some((key, value)) if check_env_override(key.trim()).is_ok() => {
+                    env.insert(key.trim().to_owned(), value.to_owned());
+                }
+                _ => invalid.push(line.to_owned()),
+            }
+        }
+
+        (env, invalid)
+    }
+
+    pub fn cloud_sync_filter(&self) -> CloudSyncFilter {
+        let globs = |text: &str| {
+            text.lines().map(str::trim).filter(|x| !x.is_empty()).map(str::to_owned).collect()
+        };
+
+        CloudSyncFilter {
+            include: globs(&self.cloud_save_include),
+            exclude: globs(&self.cloud_save_exclude),
         }
     }
 }
@@ -219,12 +351,34 @@ pub struct GameInfo {
     offer: String,
     /// Display name of the game
     name: String,
+    /// Publisher of the game, if the catalog names one
+    publisher: Option<String>,
     /// Game info
     details: GameDetailsWrapper,
     version: GameVersionInfo,
     dlc: Vec<OwnedOffer>,
     installed: bool,
     has_cloud_saves: bool,
+    /// Set when the game is only owned as a trial
+    trial: Option<TrialStatus>,
+    /// The entitlement was disabled, e.g. refunded. Still listed, but can't be played.
+    disabled: bool,
+    /// Why the game can't be played anymore, covering expired trials as well
+    access_ended: Option<AccessEnded>,
+    /// Why the game can't be downloaded where the user is, like a region lock
+    unavailable: Option<Unavailable>,
+    /// How the game was obtained
+    ownership: Option<ServiceOwnershipMethod>,
+    /// Platforms the game is made for
+    platforms: Vec<ServicePlatform>,
+    /// Whether it can run where Maxima is running
+    platform_support: PlatformSupport,
+    /// Bytes the installed game takes up, if it's been measured
+    installed_size: Option<u64>,
+    /// When the game was last launched, in seconds since epoch
+    last_played: Option<i64>,
+    /// Channel the game is installed and updated from. `version.latest` is this channel's.
+    build_channel: GameBuildChannel,
 }
 
 #[derive(PartialEq, Eq)]
@@ -236,13 +390,31 @@ pub enum BackendStallState {
     BingChilling,
 }
 
+pub struct GameMoveProgress {
+    slug: String,
+    copied: u64,
+    total: u64,
+}
+
 pub struct InstallModalState {
     locate_path: String,
     install_folder: String,
     wine_prefix: String,
+    /// Store the files in `link_folder`, with a junction/symlink in `install_folder`
+    link: bool,
+    link_folder: String,
     locating: bool,
     locate_response: Option<InteractThreadLocateGameResponse>,
     should_close: bool,
+    /// Offer being installed, to match up the space response
+    offer: String,
+    /// Space the live build needs, once the backend has fetched its manifest
+    required_space: Option<RequiredSpace>,
+    /// The live build, for its version and release date
+    build: Option<ServiceAvailableBuild>,
+    /// Folder the files are stored in as of the last free space check, and what it found
+    space_checked_folder: Option<String>,
+    available_space: Option<u64>,
 }
 
 impl InstallModalState {
@@ -251,9 +423,16 @@ impl InstallModalState {
             locate_path: String::new(),
             install_folder: settings.default_install_folder.clone(),
             wine_prefix: settings.default_wine_prefix_path.clone(),
+            link: false,
+            link_folder: String::new(),
             locating: false,
             locate_response: None,
             should_close: false,
+            offer: String::new(),
+            required_space: None,
+            build: None,
+            space_checked_folder: None,
+            available_space: None,
         }
     }
 }
@@ -273,8 +452,12 @@ pub struct MaximaEguiApp {
     user_id: String,
     /// games
     games: HashMap<String, GameInfo>,
+    /// whether the backend has finished sending the games list, so an empty list means no games
+    games_loaded: bool,
     /// selected game
     game_sel: String,
+    /// cancels the selected game's details request, if it's still loading
+    details_cancel: Option<CancellationToken>,
     /// friends
     friends: Vec<UIFriend>,
     /// width of the friends sidebar
@@ -301,15 +484,42 @@ pub struct MaximaEguiApp {
     backend: BridgeThread,
     /// what the backend doin?
     backend_state: BackendStallState,
+    /// Native setup step the backend is on while starting, if any
+    setup_step: Option<SetupStep>,
+    /// cancels the browser login, while logging in
+    login_cancel: Option<CancellationToken>,
     /// what type of login we're using
     /// Slug of the game currently running, may not be fully accurate but it's good enough to let the user know the button was clicked
     playing_game: Option<String>,
-    /// Currently downloading game
-    installing_now: Option<QueuedDownload>,
+    /// Slug of the game to start once its update finishes
+    launch_after_update: Option<String>,
+    /// Currently downloading games
+    installing_now: Vec<QueuedDownload>,
     /// Queue of game installs, indexed by offer ID
     install_queue: HashMap<String, QueuedDownload>,
+    /// Builds picked in the installer modal, by offer ID, until their download shows up
+    install_builds: HashMap<String, ServiceAvailableBuild>,
     /// State for installer modal
     installer_state: InstallModalState,
+    /// Offer IDs of installs that were interrupted and haven't been resumed yet
+    partial_installs: HashSet<String>,
+    /// Logs of installs that gave up, by offer ID, linked from the game's page
+    failed_install_logs: HashMap<String, PathBuf>,
+    /// Running without the background service, so the registry isn't fixed and DLLs can't
+    /// be injected
+    service_unavailable: bool,
+    /// Online, away, or offline to appear invisible, as picked in the profile menu
+    local_presence: BasicPresence,
+    /// Owned games installed outside Maxima, by slug
+    found_installs: HashMap<String, FoundInstall>,
+    /// Folder entered in the settings modal to move the game to
+    move_folder: String,
+    /// Game being moved to a new folder, if any
+    game_move: Option<GameMoveProgress>,
+    /// Where the last diagnostics bundle was written
+    diagnostics_path: Option<PathBuf>,
+    /// Whether the last check for a leftover cloud save lock found one to release
+    sync_lock_released: Option<bool>,
     /// User Settings for the frontend
     settings: FrontendSettings,
 }
@@ -323,12 +533,24 @@ pub enum FrontendLanguage {
 #[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
 pub struct FrontendPerformanceSettings {
     disable_blur: bool,
+    /// How many images are downloaded at once
+    #[serde(default = "default_image_downloads")]
+    image_downloads: usize,
+    /// How many installed files are checked against their manifest at once
+    #[serde(default = "default_file_verification")]
+    file_verification: usize,
+}
+
+fn default_image_downloads() -> usize {
+    DEFAULT_IMAGE_DOWNLOADS
 }
 
 impl FrontendPerformanceSettings {
     pub fn new() -> Self {
         Self {
             disable_blur: false,
+            image_downloads: default_image_downloads(),
+            file_verification: default_file_verification(),
         }
     }
 }
@@ -336,22 +558,82 @@ impl FrontendPerformanceSettings {
 #[derive(serde::Serialize, serde::Deserialize)]
 pub struct FrontendSettings {
     default_install_folder: String,
+    /// Where in the install folder games go, see [`resolve_install_template`]
+    #[serde(default = "default_install_path_template")]
+    install_path_template: String,
     default_wine_prefix_path: String,
     language: FrontendLanguage,
     ignore_ood_games: bool,
     game_settings: HashMap<String, GameSettings>,
     performance_settings: FrontendPerformanceSettings,
+    #[serde(default = "default_concurrent_downloads")]
+    concurrent_downloads: usize,
+    /// Combined download speed limit in MB/s, 0 for unlimited
+    #[serde(default)]
+    bandwidth_limit_mb: u32,
+    /// Proxy for requests to EA, used when no proxy environment variables are set. Empty
+    /// to connect directly.
+    #[serde(default)]
+    proxy: String,
+    /// Order of the game list
+    #[serde(default)]
+    game_sort: GameViewBarSort,
+    /// Keep installed games above the rest, whatever the order
+    #[serde(default)]
+    group_installed: bool,
+    /// Sync saves when games launch and stop. Off, no game is synced whatever its own
+    /// setting says.
+    #[serde(default = "default_cloud_saves")]
+    cloud_saves: bool,
+    /// Backups of each game's local saves to keep, taken before a cloud save download
+    /// replaces them. 0 to not take any.
+    #[serde(default = "default_save_backups")]
+    save_backups: usize,
+    /// Minutes between checks for new builds of installed games, 0 to not check in the
+    /// background
+    #[serde(default)]
+    update_poll_minutes: u32,
+    /// Start without the background service on Windows, for when it can't be installed.
+    /// Applies after a restart.
+    #[serde(default)]
+    skip_service: bool,
+}
+
+fn default_cloud_saves() -> bool {
+    true
+}
+
+fn default_save_backups() -> usize {
+    DEFAULT_SAVE_BACKUP_RETENTION
+}
+
+fn default_concurrent_downloads() -> usize {
+    1
+}
+
+fn default_install_path_template() -> String {
+    DEFAULT_INSTALL_TEMPLATE.to_owned()
 }
 
 impl FrontendSettings {
     pub fn new() -> Self {
         Self {
             default_install_folder: String::new(),
+            install_path_template: default_install_path_template(),
             default_wine_prefix_path: String::new(),
             language: FrontendLanguage::SystemDefault,
             ignore_ood_games: false,
             game_settings: HashMap::new(),
             performance_settings: FrontendPerformanceSettings::new(),
+            concurrent_downloads: default_concurrent_downloads(),
+            bandwidth_limit_mb: 0,
+            proxy: String::new(),
+            game_sort: GameViewBarSort::Name,
+            group_installed: false,
+            cloud_saves: default_cloud_saves(),
+            save_backups: default_save_backups(),
+            update_poll_minutes: 0,
+            skip_service: false,
         }
     }
 }
@@ -449,6 +731,11 @@ impl MaximaEguiApp {
         } else {
             FrontendSettings::new()
         };
+        IMAGE_DOWNLOADS.set_limit(settings.performance_settings.image_downloads);
+        FILE_VERIFICATION.set_limit(settings.performance_settings.file_verification);
+        if let Err(err) = http::set_proxy(Some(&settings.proxy)) {
+            warn!("Not using the configured proxy: {}", err);
+        }
 
         let (img_cache, remote_provider_channel) = UIImageCache::new(cc.egui_ctx.clone());
 
@@ -466,11 +753,15 @@ impl MaximaEguiApp {
                 status_filter: FriendsViewBarStatusFilter::Name,
                 search_buffer: String::new(),
                 friend_sel: String::new(),
+                add_friend_buffer: String::new(),
+                add_friend_outcome: None,
             },
             user_name: "User".to_owned(),
             user_id: String::new(),
             games: HashMap::new(),
+            games_loaded: false,
             game_sel: String::new(),
+            details_cancel: None,
             friends: Vec::new(),
             friends_width: 300.0,
             force_friends: false,
@@ -483,12 +774,29 @@ impl MaximaEguiApp {
             locale: TranslationManager::new(&settings.language),
             critical_error: None,
             nonfatal_errors: Vec::new(),
-            backend: BridgeThread::new(&cc.egui_ctx, remote_provider_channel), //please don't fucking break
+            backend: BridgeThread::new(
+                &cc.egui_ctx,
+                remote_provider_channel,
+                args.no_service || settings.skip_service,
+            ), //please don't fucking break
             backend_state: BackendStallState::Starting,
+            setup_step: None,
+            login_cancel: None,
             playing_game: None,
-            installing_now: None,
+            launch_after_update: None,
+            installing_now: Vec::new(),
             install_queue: HashMap::new(),
+            install_builds: HashMap::new(),
             installer_state: InstallModalState::new(&settings),
+            partial_installs: HashSet::new(),
+            failed_install_logs: HashMap::new(),
+            service_unavailable: false,
+            local_presence: BasicPresence::Online,
+            found_installs: HashMap::new(),
+            move_folder: String::new(),
+            game_move: None,
+            diagnostics_path: None,
+            sync_lock_released: None,
             settings,
         }
     }
@@ -615,9 +923,33 @@ macro_rules! set_app_modal {
                             .game_settings
                             .insert(slug.clone(), crate::GameSettings::new());
                     }
+                    $arg1.move_folder = String::new();
                 }
-                PopupModal::GameInstall(_) => {
+                PopupModal::GameInstall(slug) => {
                     $arg1.installer_state = InstallModalState::new(&$arg1.settings);
+                    if let Some(found) = $arg1.found_installs.get(&slug) {
+                        if found.is_complete() {
+                            $arg1.installer_state.locate_path = found.path().display().to_string();
+                        } else if found.path().file_name() == Some(std::ffi::OsStr::new(&slug))
+                            && $arg1.settings.install_path_template == DEFAULT_INSTALL_TEMPLATE
+                        {
+                            // A fresh copy goes in <folder>/<slug>, so this installs over the broken one
+                            if let Some(folder) = found.path().parent() {
+                                $arg1.installer_state.install_folder = folder.display().to_string();
+                            }
+                        }
+                    }
+                    if let Some(game) = $arg1.games.get(&slug) {
+                        $arg1.installer_state.offer = game.offer.clone();
+                        $arg1
+                            .backend
+                            .backend_commander
+                            .send(crate::bridge_thread::MaximaLibRequest::InstallSpaceRequest(
+                                game.offer.clone(),
+                                slug.clone(),
+                            ))
+                            .unwrap();
+                    }
                 }
                 PopupModal::GameLaunchOOD(_) => {}
             }
@@ -631,6 +963,62 @@ macro_rules! set_app_modal {
 pub(crate) use set_app_modal;
 
 impl MaximaEguiApp {
+    /// Show `slug` in the game view, giving up on whatever was still loading for the last game
+    fn select_game(&mut self, slug: String) {
+        if self.game_sel == slug {
+            return;
+        }
+
+        if let Some(cancel) = self.details_cancel.take() {
+            cancel.cancel();
+        }
+        if let Some(game) = self.games.get_mut(&self.game_sel) {
+            // Fetched again if it's selected again
+            if let GameDetailsWrapper::Loading = game.details {
+                game.details = GameDetailsWrapper::Unloaded;
+            }
+        }
+        self.img_cache.cancel(UIImageType::Hero(self.game_sel.clone()));
+
+        self.game_sel = slug;
+    }
+
+    /// Pass the download settings on to the backend
+    fn send_download_limits(&self) {
+        let bandwidth_limit = match self.settings.bandwidth_limit_mb {
+            0 => None,
+            mb => Some(mb as u64 * 1000 * 1000),
+        };
+
+        let _ = self.backend.backend_commander.send(
+            bridge_thread::MaximaLibRequest::SetDownloadLimits(
+                self.settings.concurrent_downloads,
+                bandwidth_limit,
+            ),
+        );
+    }
+
+    /// Pass the global cloud saves switch and how many save backups to keep on to the backend
+    fn send_cloud_saves(&self) {
+        let _ =
+            self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::SetCloudSaves(
+                self.settings.cloud_saves,
+                self.settings.save_backups,
+            ));
+    }
+
+    /// Pass how often to look for game updates on to the backend
+    fn send_update_poll(&self) {
+        let interval = match self.settings.update_poll_minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(minutes as u64 * 60)),
+        };
+
+        let _ = self.backend.backend_commander.send(
+            bridge_thread::MaximaLibRequest::SetUpdatePollInterval(interval),
+        );
+    }
+
     fn tab_bar(&mut self, header: &mut Ui) {
         puffin::profile_function!();
         let navbar = egui::Frame::default()
@@ -651,6 +1039,12 @@ impl MaximaEguiApp {
                 if self.debug {
                     tab_button(ui, &mut self.page_view, PageType::Debug, "Debug");
                 }
+                if self.service_unavailable {
+                    let troubleshooting = &self.locale.localization.settings_view.troubleshooting;
+                    ui.add_space(8.0);
+                    ui.colored_label(Color32::YELLOW, "⚠")
+                        .on_hover_text(&troubleshooting.service_unavailable);
+                }
             });
         });
     }
@@ -668,13 +1062,15 @@ impl MaximaEguiApp {
             } else {
                 rtl.image((self.img_cache.placeholder_avatar.id(), vec2(36.0, 36.0)))
             };
-            let stroke = Stroke::new(2.0, {
-                if self.playing_game.is_some() {
-                    FRIEND_INGAME_COLOR
-                } else {
-                    Color32::GREEN
-                }
-            });
+            let stroke = Stroke::new(
+                2.0,
+                match self.local_presence {
+                    BasicPresence::Offline => Color32::GRAY,
+                    BasicPresence::Away => F9B233,
+                    _ if self.playing_game.is_some() => FRIEND_INGAME_COLOR,
+                    _ => Color32::GREEN,
+                },
+            );
             rtl.painter().rect(
                 img_response.rect.expand(1.0),
                 Rounding::same(4.0),
@@ -683,6 +1079,24 @@ impl MaximaEguiApp {
             );
             let point = img_response.rect.left_center() + vec2(-rtl.spacing().item_spacing.x, 2.0);
 
+            let loc = &self.locale.localization.profile_menu;
+            img_response.interact(egui::Sense::click()).context_menu(|ui| {
+                let options = [
+                    (BasicPresence::Online, &loc.appear_online),
+                    (BasicPresence::Away, &loc.appear_away),
+                    (BasicPresence::Offline, &loc.appear_invisible),
+                ];
+                for (presence, label) in options {
+                    if ui.radio(self.local_presence == presence, label).clicked() {
+                        let _ = self.backend.backend_commander.send(
+                            bridge_thread::MaximaLibRequest::SetPresenceRequest(presence.clone()),
+                        );
+                        self.local_presence = presence;
+                        ui.close_menu();
+                    }
+                }
+            });
+
             if let Some(game_slug) = &self.playing_game {
                 if let Some(game) = &self.games.get(game_slug) {
                     let offset = vec2(0.0, 0.5);
@@ -802,11 +1216,25 @@ impl MaximaEguiApp {
                                 ui.separator();
                                 if game.installed {
                                     if let Some(settings) = self.settings.game_settings.get_mut(&game.slug) {
-                                        ui.add_enabled(game.has_cloud_saves, egui::Checkbox::new(&mut settings.cloud_saves, &self.locale.localization.modals.game_settings.cloud_saves));
+                                        let cloud_saves = game.has_cloud_saves && self.settings.cloud_saves;
+                                        ui.add_enabled(cloud_saves, egui::Checkbox::new(&mut settings.cloud_saves, &self.locale.localization.modals.game_settings.cloud_saves));
+                                        ui.add_enabled_ui(cloud_saves && settings.cloud_saves, |ui| {
+                                            ui.label(&self.locale.localization.modals.game_settings.cloud_save_include);
+                                            ui.add_sized(vec2(ui.available_width(), 60.0), egui::TextEdit::multiline(&mut settings.cloud_save_include));
+                                            ui.label(&self.locale.localization.modals.game_settings.cloud_save_exclude);
+                                            ui.add_sized(vec2(ui.available_width(), 60.0), egui::TextEdit::multiline(&mut settings.cloud_save_exclude));
+                                        });
 
                                         ui.label(&self.locale.localization.modals.game_settings.launch_arguments);
                                         ui.add_sized(vec2(ui.available_width(), ui.style().spacing.interact_size.y), egui::TextEdit::singleline(&mut settings.launch_args).vertical_align(egui::Align::Center));
 
+                                        ui.label(&self.locale.localization.modals.game_settings.env_overrides);
+                                        ui.add_sized(vec2(ui.available_width(), 60.0), egui::TextEdit::multiline(&mut settings.env_overrides).font(egui::TextStyle::Monospace));
+                                        let (_, invalid) = settings.env_overrides();
+                                        if !invalid.is_empty() {
+                                            egui::Label::new(egui::RichText::new(positional_replace!(self.locale.localization.modals.game_settings.env_overrides_invalid, "lines", invalid.join(", "))).color(Color32::YELLOW)).ui(ui);
+                                        }
+
                                         ui.separator();
 
 
@@ -819,8 +1247,52 @@ impl MaximaEguiApp {
                                             ui.add_sized(button_size, egui::Button::new("BROWSE"));
                                         });
 
+                                        ui.label(&self.locale.localization.modals.game_settings.working_directory);
+                                        ui.add_sized(vec2(ui.available_width(), ui.style().spacing.interact_size.y), egui::TextEdit::singleline(&mut settings.working_dir).vertical_align(egui::Align::Center));
+
+                                        ui.label(&self.locale.localization.modals.game_settings.update_policy);
+                                        enum_dropdown(ui, "UpdatePolicyComboBox".to_owned(), &mut settings.update_policy, 260.0, "", &self.locale);
+
+                                        ui.label(&self.locale.localization.modals.game_settings.build_channel);
+                                        let channel = game.build_channel;
+                                        enum_dropdown(ui, "BuildChannelComboBox".to_owned(), &mut game.build_channel, 260.0, "", &self.locale);
+                                        if game.build_channel != channel {
+                                            let _ = self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::SetBuildChannelRequest(game.slug.clone(), game.build_channel.into()));
+                                        }
+                                        if game.build_channel == GameBuildChannel::Staged {
+                                            egui::Label::new(egui::RichText::new(&self.locale.localization.modals.game_settings.build_channel_staged_warning).color(Color32::YELLOW)).ui(ui);
+                                        }
+
                                         ui.separator();
                                     }
+
+                                    ui.horizontal(|ui| {
+                                        if ui.button(&self.locale.localization.modals.game_settings.open_install_folder).clicked() {
+                                            let _ = self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::OpenInstallFolderRequest(game.offer.clone()));
+                                        }
+                                        if ui.add_enabled(game.has_cloud_saves, egui::Button::new(&self.locale.localization.modals.game_settings.open_save_folder)).clicked() {
+                                            let _ = self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::OpenSaveFolderRequest(game.offer.clone()));
+                                        }
+                                    });
+
+                                    ui.label(&self.locale.localization.modals.game_settings.move_install);
+                                    if let Some(progress) = self.game_move.as_ref().filter(|x| x.slug == game.slug) {
+                                        let percent = progress.copied * 100 / progress.total.max(1);
+                                        ui.label(positional_replace!(self.locale.localization.modals.game_settings.move_in_progress, "percent", percent));
+                                    } else {
+                                        ui.horizontal(|ui| {
+                                            let button_size = vec2(100.0, 30.0);
+                                            let size = vec2(500.0 - (24.0 + ui.style().spacing.item_spacing.x), 30.0);
+                                            ui.add_sized(size, egui::TextEdit::singleline(&mut self.move_folder).vertical_align(egui::Align::Center));
+                                            let target = PathBuf::from(&self.move_folder);
+                                            let valid = self.game_move.is_none() && target.is_absolute() && target.exists();
+                                            if ui.add_enabled(valid, egui::Button::new(&self.locale.localization.modals.game_settings.move_action.to_ascii_uppercase()).min_size(button_size)).clicked() {
+                                                self.game_move = Some(GameMoveProgress { slug: game.slug.clone(), copied: 0, total: 0 });
+                                                self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::MoveGameRequest(game.offer.clone(), game.slug.clone(), target.join(&game.slug))).unwrap();
+                                            }
+                                        });
+                                    }
+
                                     ui.allocate_space(ui.available_size_before_wrap() - vec2(0.0, ui.spacing().interact_size.y));
 
                                     ui.horizontal(|ui| {
@@ -848,20 +1320,27 @@ impl MaximaEguiApp {
 
                                 ui.separator();
 
-                                if !self.args.allow_bf3 && (slug.eq("battlefield-3") || slug.eq("battlefield-4")) {
-                                    ui.heading("Battlefield 3 and 4 are currently unsupported due to how battlelog complicates game launching. This is on our radar, but isn't a huge priority at the moment.");
-                                    break 'outer;
-                                }
-
                                 let button_size = vec2(100.0, 30.0);
 
                                 ui.label(&self.locale.localization.modals.game_install.locate_installed);
+                                if let Some(found) = self.found_installs.get(slug) {
+                                    let path = found.path().display().to_string();
+                                    if found.is_complete() {
+                                        ui.label(positional_replace!(self.locale.localization.modals.game_install.found_install, "path", path));
+                                    } else {
+                                        egui::Label::new(egui::RichText::new(positional_replace!(self.locale.localization.modals.game_install.found_broken_install, "path", path)).color(Color32::YELLOW)).ui(ui);
+                                    }
+                                }
                                 if let Some(resp) = &self.installer_state.locate_response {
                                     match resp {
                                         InteractThreadLocateGameResponse::Success => {
                                             self.installer_state.should_close = true;
                                             game.installed = true;
                                         },
+                                        InteractThreadLocateGameResponse::Error(err) if matches!(err.reason, ManifestError::WrongGame { .. }) => {
+                                            egui::Label::new(egui::RichText::new(&self.locale.localization.modals.game_install.locate_wrong_game).color(Color32::RED)).ui(ui);
+                                            ui.label(format!("{}", err.reason));
+                                        },
                                         InteractThreadLocateGameResponse::Error(err) => {
                                             ui.spacing_mut().item_spacing.x = 0.0;
 
@@ -913,6 +1392,11 @@ impl MaximaEguiApp {
                                 }
                                 ui.label("");
                                 ui.label(&self.locale.localization.modals.game_install.fresh_download);
+                                if let Some(build) = &self.installer_state.build {
+                                    ui.label(positional_replace!(self.locale.localization.modals.game_install.fresh_build,
+                                        "version", build.game_version().as_deref().unwrap_or("?"),
+                                        "date", build.live_day().unwrap_or("?")));
+                                }
                                 ui.add_enabled_ui(!self.installer_state.locating, |ui| {
                                     let size = vec2(500.0 - (24.0 + ui.style().spacing.item_spacing.x*2.0), 30.0);
                                     ui.horizontal(|ui| {
@@ -922,7 +1406,31 @@ impl MaximaEguiApp {
                                         ui.add_sized(size, egui::TextEdit::singleline(&mut self.installer_state.install_folder).vertical_align(egui::Align::Center));
                                     });
                                     let path = PathBuf::from(self.installer_state.install_folder.clone());
-                                    let valid = path.exists();
+
+                                    ui.checkbox(&mut self.installer_state.link, &self.locale.localization.modals.game_install.fresh_link);
+                                    if self.installer_state.link {
+                                        ui.label(&self.locale.localization.modals.game_install.fresh_link_folder);
+                                        ui.horizontal(|ui| {
+                                            ui.add_sized(size, egui::TextEdit::singleline(&mut self.installer_state.link_folder).vertical_align(egui::Align::Center));
+                                        });
+                                    }
+                                    let storage_folder = if self.installer_state.link { &self.installer_state.link_folder } else { &self.installer_state.install_folder }.clone();
+                                    let storage_path = PathBuf::from(&storage_folder);
+
+                                    if self.installer_state.space_checked_folder.as_ref() != Some(&storage_folder) {
+                                        self.installer_state.available_space = volume_space(&storage_path).map(|(_, available)| available);
+                                        self.installer_state.space_checked_folder = Some(storage_folder);
+                                    }
+                                    // Unknown sizes don't block the install, the backend checks again before downloading
+                                    let enough_space = match (self.installer_state.required_space, self.installer_state.available_space) {
+                                        (Some(required), Some(available)) => available >= required.total(),
+                                        _ => true,
+                                    };
+                                    let paths_exist = path.exists() && storage_path.exists();
+                                    let resolve = |base: &PathBuf| resolve_install_template(&self.settings.install_path_template, &InstallTemplateValues { base: base.as_path(), slug, name: &game.name, publisher: game.publisher.as_deref() });
+                                    let install_path = resolve(&path);
+                                    let storage_install_path = resolve(&storage_path);
+                                    let valid = paths_exist && enough_space && install_path.is_ok();
 
                                     #[cfg(unix)]
                                     {
@@ -945,22 +1453,38 @@ impl MaximaEguiApp {
                                             #[cfg(not(unix))]
                                             let wine_prefix: Option<PathBuf> = None;
 
-                                            if self.installing_now.is_none() {
-                                                self.installing_now = Some(QueuedDownload { slug: game.slug.clone(), offer: game.offer.clone(), downloaded_bytes: 0, total_bytes: 0 });
-                                            } else {
-                                                self.install_queue.insert(game.offer.clone(),QueuedDownload { slug: game.slug.clone(), offer: game.offer.clone(), downloaded_bytes: 0, total_bytes: 0 });
+                                            let install_path = install_path.clone().unwrap_or_default();
+                                            let real_path = self.installer_state.link.then(|| storage_install_path.clone().unwrap_or_default());
+
+                                            // Shown once the backend reports the download
+                                            if let Some(build) = self.installer_state.build.clone() {
+                                                self.install_builds.insert(game.offer.clone(), build);
                                             }
-                                            self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::InstallGameRequest(game.offer.clone(), slug.clone(), path.join(slug), wine_prefix)).unwrap();
+                                            self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::InstallGameRequest(game.offer.clone(), slug.clone(), install_path, wine_prefix, real_path)).unwrap();
 
                                             clear = true;
                                         }
                                     });
                                     if !self.installer_state.install_folder.is_empty() {
-                                        ui.horizontal_wrapped(|folder_hint| {
-                                            egui::Label::new(&self.locale.localization.modals.game_install.fresh_path_confirmation).selectable(false).ui(folder_hint);
-                                            egui::Label::new(egui::RichText::new(format!("{}",
-                                                path.join(slug).display())).color(Color32::WHITE)).selectable(false).ui(folder_hint);
-                                        });
+                                        match &install_path {
+                                            Ok(install_path) => {
+                                                ui.horizontal_wrapped(|folder_hint| {
+                                                    egui::Label::new(&self.locale.localization.modals.game_install.fresh_path_confirmation).selectable(false).ui(folder_hint);
+                                                    egui::Label::new(egui::RichText::new(format!("{}",
+                                                        install_path.display())).color(Color32::WHITE)).selectable(false).ui(folder_hint);
+                                                });
+                                            }
+                                            Err(err) => {
+                                                egui::Label::new(egui::RichText::new(positional_replace!(self.locale.localization.modals.game_install.fresh_template_invalid, "error", err)).color(Color32::RED)).ui(ui);
+                                            }
+                                        }
+                                        if let (true, false, Ok(storage_install_path)) = (self.installer_state.link, self.installer_state.link_folder.is_empty(), &storage_install_path) {
+                                            ui.horizontal_wrapped(|folder_hint| {
+                                                egui::Label::new(&self.locale.localization.modals.game_install.fresh_link_folder).selectable(false).ui(folder_hint);
+                                                egui::Label::new(egui::RichText::new(format!("{}",
+                                                    storage_install_path.display())).color(Color32::WHITE)).selectable(false).ui(folder_hint);
+                                            });
+                                        }
                                         #[cfg(unix)]
                                         {
                                             let effective_prefix = if self.installer_state.wine_prefix.is_empty() {
@@ -977,8 +1501,14 @@ impl MaximaEguiApp {
                                                 egui::Label::new(egui::RichText::new(&effective_prefix).color(Color32::WHITE)).selectable(false).ui(prefix_hint);
                                             });
                                         }
-                                        if !valid {
+                                        if !paths_exist {
                                             egui::Label::new(egui::RichText::new(&self.locale.localization.modals.game_install.fresh_path_invalid).color(Color32::RED)).ui(ui);
+                                        } else if !enough_space {
+                                            let required = self.installer_state.required_space.map_or(0, |x| x.total());
+                                            let available = self.installer_state.available_space.unwrap_or_default();
+                                            egui::Label::new(egui::RichText::new(positional_replace!(self.locale.localization.modals.game_install.fresh_path_insufficient_space,
+                                                "required", humansize::SizeFormatter::new(required, humansize::DECIMAL),
+                                                "available", humansize::SizeFormatter::new(available, humansize::DECIMAL))).color(Color32::RED)).ui(ui);
                                         }
                                     }
                                 });
@@ -1008,6 +1538,13 @@ impl MaximaEguiApp {
                                 ui.label(positional_replace!(&self.locale.localization.modals.game_launch_out_of_date.comparison, "local", &game.version.installed, "online", &game.version.latest));
 
                                 ui.with_layout(Layout::bottom_up(egui::Align::Min), |ui| {
+                                    if ui.add_sized([ui.available_size_before_wrap().x, ui.spacing().interact_size.y], egui::Button::new(&self.locale.localization.modals.game_launch_out_of_date.update)).clicked() {
+                                        self.launch_after_update = Some(game.slug.clone());
+                                        let _ = self.backend.backend_commander.send(
+                                            crate::bridge_thread::MaximaLibRequest::UpdateGameRequest(game.offer.clone()),
+                                        );
+                                        clear = true
+                                    }
                                     if ui.add_sized([ui.available_size_before_wrap().x, ui.spacing().interact_size.y], egui::Button::new(&self.locale.localization.modals.game_launch_out_of_date.launch)).clicked() {
                                         self.playing_game = Some(game.slug.clone());
                                         let settings = self.settings.game_settings.get(&game.slug);
@@ -1059,10 +1596,14 @@ impl MaximaEguiApp {
             )
             .clicked()
         {
+            let cancel = self.backend.cancel.child_token();
             self.backend
                 .backend_commander
-                .send(bridge_thread::MaximaLibRequest::LoginRequestOauth)
+                .send(bridge_thread::MaximaLibRequest::LoginRequestOauth(
+                    cancel.clone(),
+                ))
                 .unwrap();
+            self.login_cancel = Some(cancel);
             self.backend_state = BackendStallState::LoggingIn;
         }
     }
@@ -1151,10 +1692,18 @@ impl eframe::App for MaximaEguiApp {
                 let app_rect = ui.available_rect_before_wrap().clone();
                 match self.backend_state {
                     BackendStallState::Starting => {
+                        let startup_flow = &self.locale.localization.startup_flow;
+                        let text = match self.setup_step {
+                            Some(SetupStep::InstallService) => &startup_flow.installing_service,
+                            Some(SetupStep::UpdateService) => &startup_flow.updating_service,
+                            Some(SetupStep::StartService) => &startup_flow.starting_service,
+                            Some(SetupStep::FixRegistry) => &startup_flow.fixing_registry,
+                            None => &startup_flow.starting,
+                        };
                         ui.painter().text(
                             app_rect.center(),
                             Align2::CENTER_CENTER,
-                            &self.locale.localization.startup_flow.starting,
+                            text,
                             FontId::proportional(30.0),
                             Color32::WHITE,
                         );
@@ -1199,6 +1748,22 @@ impl eframe::App for MaximaEguiApp {
                                 .unwrap();
                             self.backend_state = BackendStallState::Starting;
                         }
+
+                        let skip_rect = Rect::from_center_size(
+                            button_rect.center_bottom() + vec2(0.0, 19.0),
+                            vec2(240.0, 30.0),
+                        );
+                        let skip = egui::Button::new(
+                            &self.locale.localization.startup_flow.service_skip_button,
+                        )
+                        .frame(false);
+                        if ui.put(skip_rect, skip).clicked() {
+                            self.backend
+                                .backend_commander
+                                .send(bridge_thread::MaximaLibRequest::SkipService)
+                                .unwrap();
+                            self.backend_state = BackendStallState::Starting;
+                        }
                     }
                     BackendStallState::UserNeedsToLogIn => {
                         self.login(app_rect, ui);
@@ -1212,6 +1777,25 @@ impl eframe::App for MaximaEguiApp {
                             Color32::WHITE,
                         );
                         ui.put(app_rect, egui::Spinner::new().size(300.0));
+
+                        // The browser never calls back if it's closed before logging in
+                        let button_rect = Rect::from_center_size(
+                            app_rect.center_bottom() - vec2(0.0, 60.0),
+                            vec2(120.0, 30.0),
+                        );
+                        let cancel = egui::Button::new(
+                            &self
+                                .locale
+                                .localization
+                                .startup_flow
+                                .login_cancel
+                                .to_ascii_uppercase(),
+                        );
+                        if ui.put(button_rect, cancel).clicked() {
+                            if let Some(cancel) = self.login_cancel.take() {
+                                cancel.cancel();
+                            }
+                        }
                     }
                     BackendStallState::BingChilling => {
                         self.main(app_rect, ui);
@@ -1223,9 +1807,21 @@ impl eframe::App for MaximaEguiApp {
     }
 
     fn on_exit(&mut self, _gl: Option<&glow::Context>) {
-        self.backend
-            .backend_commander
-            .send(bridge_thread::MaximaLibRequest::ShutdownRequest)
-            .unwrap();
+        // Don't wait on game lists and the like, only saves and downloads matter now
+        self.backend.cancel.cancel();
+        let sent =
+            self.backend.backend_commander.send(bridge_thread::MaximaLibRequest::ShutdownRequest);
+        if sent.is_err() {
+            return;
+        }
+
+        // The backend dies with the window, so wait for it to sync saves and save downloads
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT + Duration::from_secs(1);
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match self.backend.backend_listener.recv_timeout(remaining) {
+                Ok(bridge_thread::MaximaLibResponse::ShutdownFinished) | Err(_) => break,
+                Ok(_) => {}
+            }
+        }
     }
 }
@@ -19,6 +19,8 @@ pub struct LocalizedStrings {
     pub games_view: LocalizedGamesView,
     /// Friends sidebar
     pub friends_view: LocalizedFriendsView,
+    /// Downloads page
+    pub downloads_view: LocalizedDownloadsView,
     /// Settings page
     pub settings_view: LocalizedSettingsView,
     /// Names of languages
@@ -43,18 +45,34 @@ pub struct LocalizedGameInstallModal {
     pub header: String,
     /// Label for the box to enter the path of an existing game install
     pub locate_installed: String,
+    /// Shown when an install was found outside Maxima, `{path}` is where
+    pub found_install: String,
+    /// Shown when a found install is missing files, `{path}` is where
+    pub found_broken_install: String,
     /// Button that initiates locating
     pub locate_action: String,
     /// Text informing the user that maxima is locating the game
     pub locate_in_progress: String,
     /// Text informing the user that the locate failed, english-only instructions always accompany it
     pub locate_failed: String,
+    /// Text informing the user that the folder they picked holds a different game than the one being located
+    pub locate_wrong_game: String,
     /// Label for the box to enter the path to install to
     pub fresh_download: String,
     /// Confirms the path the game is to be installed to
     pub fresh_path_confirmation: String,
+    /// Version and release date of the build that will be installed
+    pub fresh_build: String,
     /// Informs the user the path they're trying to locate a game at is invalid
     pub fresh_path_invalid: String,
+    /// Shown instead of the install path when the path template can't be resolved
+    pub fresh_template_invalid: String,
+    /// Informs the user the drive they're installing to doesn't have enough free space
+    pub fresh_path_insufficient_space: String,
+    /// Checkbox to store the files somewhere else, with a junction/symlink at the install path
+    pub fresh_link: String,
+    /// Label for the box to enter where the files are stored when linking
+    pub fresh_link_folder: String,
     /// Button that initiates the download
     pub fresh_action: String,
 }
@@ -66,16 +84,64 @@ pub struct LocalizedGameSettingsModal {
     pub not_installed: String,
     /// Checkbox to enable/disable cloud saves
     pub cloud_saves: String,
+    /// Label for a box of extra save file globs to sync, one per line
+    pub cloud_save_include: String,
+    /// Label for a box of save file globs to leave out of syncing, one per line
+    pub cloud_save_exclude: String,
     /// Label for a text box to enter command-line arguments
     pub launch_arguments: String,
+    /// Label for a box of `KEY=VALUE` environment variables for the game, one per line
+    pub env_overrides: String,
+    /// Warning listing the environment variable lines that will be ignored
+    pub env_overrides_invalid: String,
     /// Label for a text box to contain the full path to the EXE to run instead
     pub executable_override: String,
+    /// Label for a text box with the folder to run the game in
+    pub working_directory: String,
+    /// Label for the combo box choosing what happens when the game is out of date
+    pub update_policy: String,
+    /// Options for the update combo box
+    pub update_policy_options: LocalizedUpdatePolicyOptions,
+    /// Label for the combo box choosing which builds the game is installed and updated from
+    pub build_channel: String,
+    /// Options for the build channel combo box
+    pub build_channel_options: LocalizedBuildChannelOptions,
+    /// Shown while staged builds are chosen, warning that they may be unstable
+    pub build_channel_staged_warning: String,
     /// Button that initiates uninstallation
     pub uninstall: String,
+    /// Label for the box to enter the folder to move the game to
+    pub move_install: String,
+    /// Button that initiates moving the game
+    pub move_action: String,
+    /// Progress of copying the game's files to a new drive
+    pub move_in_progress: String,
+    /// Button that shows the game's files in the file manager
+    pub open_install_folder: String,
+    /// Button that shows the game's saves in the file manager
+    pub open_save_folder: String,
     /// Version label
     pub version: String,
 }
 
+#[derive(Deserialize)]
+pub struct LocalizedBuildChannelOptions {
+    /// The released build
+    pub live: String,
+    /// The build about to be released
+    pub staged: String,
+}
+
+#[derive(Deserialize)]
+pub struct LocalizedUpdatePolicyOptions {
+    /// Download the update before launching
+    pub auto_update: String,
+    /// Show the out-of-date warning
+    pub notify: String,
+    /// Launch without updating or warning
+    pub never: String,
+}
+
 #[derive(Deserialize)]
 pub struct LocalizedGameLaunchOODModal {
     pub header: String,
@@ -89,6 +155,8 @@ pub struct LocalizedGameLaunchOODModal {
     pub ok_i_get_it: String,
     /// "Launch Anyway" button
     pub launch: String,
+    /// Button to download the update, then launch
+    pub update: String,
 }
 
 #[derive(Deserialize)]
@@ -105,16 +173,28 @@ pub struct LocalizedStartupFlow {
     pub starting: String,
     /// Shown alongside a throbber, let the user maxima is logging in
     pub logging_in: String,
+    /// Shown alongside a throbber while the windows service is registered
+    pub installing_service: String,
+    /// Shown alongside a throbber while a windows service from another version is replaced
+    pub updating_service: String,
+    /// Shown alongside a throbber while the windows service starts up
+    pub starting_service: String,
+    /// Shown alongside a throbber while the registry entries games need are set up
+    pub fixing_registry: String,
     /// Warning the user they're not logged in
     pub login_header: String,
     /// Button that initiates login flow (through the browser)
     pub login_button: String,
+    /// Button that gives up on the login in progress, for when the browser was closed
+    pub login_cancel: String,
     /// Warning the user the windows service is not installed
     pub service_installer_header: String,
     /// Describes what the windows service does, and that it's needed for maxima to work
     pub service_installer_description: String,
     /// Button that initiates windows service installation
     pub service_installer_button: String,
+    /// Button that carries on without the windows service, with the features needing it off
+    pub service_skip_button: String,
 }
 
 #[derive(Deserialize)]
@@ -140,6 +220,12 @@ pub struct LocalizedMenubar {
 pub struct LocalizedProfileMenu {
     pub view_profile: String,
     pub view_wishlist: String,
+    /// Option to show friends you're online
+    pub appear_online: String,
+    /// Option to show friends you're away
+    pub appear_away: String,
+    /// Option to appear offline to friends, hiding the game you're playing too
+    pub appear_invisible: String,
 }
 
 #[derive(Deserialize)]
@@ -156,8 +242,14 @@ pub struct LocalizedSettingsView {
     pub interface: LocalizedInterfaceSettings,
     /// Label for a section of settings pertaining to the installation of games
     pub game_installation: LocalizedGameInstallationSettings,
+    /// Label for a section of settings pertaining to syncing game saves with EA
+    pub cloud_saves: LocalizedCloudSaveSettings,
     /// Label for a section of settings pertaining to performance of the launcher
     pub performance: LocalizedPerformanceSettings,
+    /// Label for a section of settings pertaining to how the launcher connects to EA
+    pub network: LocalizedNetworkSettings,
+    /// Label for a section of settings for gathering information about problems
+    pub troubleshooting: LocalizedTroubleshootingSettings,
 }
 
 #[derive(Deserialize)]
@@ -172,10 +264,24 @@ pub struct LocalizedGameInstallationSettings {
     pub header: String,
     /// Label for a text box for a default path to install games
     pub default_folder: String,
+    /// Label for the template of where games go inside the installation folder
+    pub install_path_template: String,
+    /// An example path the template resolves to, or why it doesn't
+    pub install_path_template_preview: String,
     /// Label for a text box for the default Wine prefix path
     pub default_wine_prefix: String,
     /// Checkbox for ignoring the out-of-date launch warning
     pub ignore_ood_warning: String,
+    /// Label for how many games can download at the same time
+    pub concurrent_downloads: String,
+    /// Label for the combined download speed limit, in MB/s
+    pub bandwidth_limit: String,
+    /// Shown next to the speed limit when it's 0
+    pub bandwidth_unlimited: String,
+    /// Label for the minutes between background checks for game updates
+    pub update_poll: String,
+    /// Shown next to the update check interval when it's 0
+    pub update_poll_off: String,
 }
 
 #[derive(Deserialize)]
@@ -183,6 +289,57 @@ pub struct LocalizedPerformanceSettings {
     pub header: String,
     /// Label for a checkbox to disable blur effects
     pub disable_blur: String,
+    /// Label for the number of images downloaded at once
+    pub image_downloads: String,
+    /// Label for the number of installed files checked against the manifest at once
+    pub file_verification: String,
+}
+
+#[derive(Deserialize)]
+pub struct LocalizedCloudSaveSettings {
+    pub header: String,
+    /// Checkbox for syncing saves when games launch and stop
+    pub enabled: String,
+    /// Explains that turning it off overrides each game's setting and stops all automatic
+    /// save traffic
+    pub hint: String,
+    /// Next to how many backups of each game's saves to keep
+    pub save_backups: String,
+    /// Shown next to it when it's 0
+    pub save_backups_off: String,
+    /// Button to release a save lock a previous session didn't release
+    pub release_lock: String,
+    /// Explains when releasing the lock helps, shown until the button is pressed
+    pub release_lock_hint: String,
+    /// Shown when a leftover lock was released, on its own or by the button
+    pub lock_released: String,
+    /// Shown when the button found no leftover lock
+    pub no_lock: String,
+}
+
+#[derive(Deserialize)]
+pub struct LocalizedNetworkSettings {
+    pub header: String,
+    /// Label for a text box for the proxy URL
+    pub proxy: String,
+    /// Explains the proxy format, that environment variables win, and that it applies
+    /// after a restart
+    pub proxy_hint: String,
+}
+
+#[derive(Deserialize)]
+pub struct LocalizedTroubleshootingSettings {
+    pub header: String,
+    /// Button to bundle logs and system state into a zip for bug reports
+    pub write_diagnostics: String,
+    /// Where the bundle was written. {path} is replaced with its path
+    pub diagnostics_written: String,
+    /// Checkbox to start without the windows service
+    pub skip_service: String,
+    /// Explains what stops working without the service, and that it applies after a restart
+    pub skip_service_hint: String,
+    /// Shown while running without the windows service
+    pub service_unavailable: String,
 }
 
 #[derive(Deserialize)]
@@ -195,6 +352,10 @@ pub struct LocalizedGamesViewToolbar {
     pub platform_filter: String,
     /// Options for a platform combo box
     pub platform_options: LocalizedGamesViewToolbarPlatformOptions,
+    /// Options for the order of the game list
+    pub sort_options: LocalizedGamesViewToolbarSortOptions,
+    /// Checkbox to list installed games before the others
+    pub group_installed: String,
     /// Displayed in the search bar when empty
     pub search_bar_hint: String,
     /// Appended after the title of a running game
@@ -217,6 +378,17 @@ pub struct LocalizedGamesViewToolbarPlatformOptions {
     pub mac: String,
 }
 
+#[derive(Deserialize)]
+pub struct LocalizedGamesViewToolbarSortOptions {
+    pub name: String,
+    /// Most recently played first
+    pub last_played: String,
+    /// Installed games first, then by name
+    pub installed: String,
+    /// Largest first
+    pub installed_size: String,
+}
+
 #[derive(Deserialize)]
 pub struct LocalizedGamesViewMain {
     /// Button to start the game if it's installed
@@ -231,14 +403,66 @@ pub struct LocalizedGamesViewMain {
     pub pause: String,
     /// Button to Resume the download
     pub resume: String,
+    /// Shown instead of Play while an installed game is updating, opens the downloads page
+    pub installing: String,
+    /// Shown when the game's last install gave up
+    pub install_failed: String,
+    /// Link next to `install_failed` that opens the install's log
+    pub open_install_log: String,
     /// Button that opens the settings modal
     pub settings: String,
     /// Label succeeded by the amount of hours/minutes the user has played the game
     pub playtime: String,
     /// Label succeeded by the amount of achievements the user has, "unlocked/total"
     pub achievements: String,
-    /// Informs the user that they either have no games, or the backend is loading them. There is currently no way to differentiate those
+    /// Label succeeded by how much disk space the installed game takes up
+    pub installed_size: String,
+    /// Label succeeded by the date the game was last launched
+    pub last_played: String,
+    /// Shown as the last played date of a game that hasn't been launched
+    pub never_played: String,
+    /// Informs the user that the backend is still loading their games
     pub no_loaded_games: String,
+    /// Informs the user that their account doesn't own any games
+    pub no_owned_games: String,
+    /// Link to the EA store, shown alongside `no_owned_games`
+    pub browse_store: String,
+    /// Badge for a trial without a time limit
+    pub trial: String,
+    /// Badge for a time-limited trial, with the minutes of playtime left
+    pub trial_time_left: String,
+    /// Badge for a trial with no playtime left
+    pub trial_expired: String,
+    /// Warning for games that aren't made for this platform, with the ones they're made for
+    pub unsupported_platform: String,
+    /// Shown in place of the play/install buttons for games the account no longer owns
+    pub ownership_disabled: String,
+    /// Shown in place of the play button when the subscription a game came with has ended
+    pub subscription_lapsed: String,
+    /// Button to renew the subscription, opening its page in the browser
+    pub renew_subscription: String,
+    /// Shown in place of the play button when a trial has no time left
+    pub trial_over: String,
+    /// Badge for games that aren't offered in the user's country
+    pub unavailable_region: String,
+    /// Badge for games that can't be downloaded yet, with the date they can be
+    pub unavailable_until: String,
+    /// Badge for games EA stopped offering, with the date it stopped
+    pub unavailable_retired: String,
+    /// Badge for games the catalog doesn't offer a download of
+    pub unavailable_no_download: String,
+    /// How the game was obtained, when it was bought or granted on EA
+    pub owned: String,
+    /// How the game was obtained, for EA Play subscriptions
+    pub owned_vault: String,
+    /// How the game was obtained, for Xbox Game Pass
+    pub owned_game_pass: String,
+    /// How the game was obtained, for games linked from Steam
+    pub owned_steam: String,
+    /// How the game was obtained, for games linked from the Epic Games Store
+    pub owned_epic: String,
+    /// Tooltip on Steam/Epic games, with the storefront's name
+    pub storefront_required: String,
 }
 
 #[derive(Deserialize)]
@@ -247,6 +471,12 @@ pub struct LocalizedGamesViewDetails {
     pub min_system_req: String,
     /// Recommended specs for a good experience
     pub rec_system_req: String,
+    /// Shown in green when the user's PC meets the recommended specs
+    pub meets_recommended: String,
+    /// Shown in yellow when the user's PC meets the minimum, but not the recommended specs
+    pub meets_minimum: String,
+    /// Shown in red when the user's PC doesn't meet the minimum specs
+    pub below_minimum: String,
 }
 
 #[derive(Deserialize)]
@@ -268,7 +498,14 @@ pub struct LocalizedFriendsViewToolbar {
     pub pending: String,
     /// Blocked users (TBC)
     pub blocked: String,
+    /// Button to send a friend request to the user ID typed next to it
     pub add_friend: String,
+    /// Placeholder for the user ID box next to the add friend button
+    pub add_friend_hint: String,
+    /// Shown once a friend request has been sent
+    pub friend_request_sent: String,
+    /// Shown when they'd already sent a friend request, so it was accepted instead
+    pub friend_request_accepted: String,
     pub filter_options: LocalizedFriendsViewToolbarSearchFilterOptions,
     pub search_hint: String,
 }
@@ -281,6 +518,8 @@ pub struct LocalizedFriendsViewFriendActions {
     pub chat: String,
     /// Remove the user from your friends list
     pub unfriend: String,
+    /// Launch the game the user is playing and join their session
+    pub join: String,
 }
 
 #[derive(Deserialize)]
@@ -304,6 +543,18 @@ pub struct LocalizedFriendsViewToolbarSearchFilterOptions {
     pub game: String,
 }
 
+#[derive(Deserialize)]
+pub struct LocalizedDownloadsView {
+    /// Version of the build being downloaded
+    pub version: String,
+    /// Added after the version when the build's release date is known
+    pub released: String,
+    /// Shown over the progress bar while downloaded files are checked
+    pub verifying: String,
+    /// Shown over the progress bar once the speed can be estimated
+    pub speed: String,
+}
+
 pub struct TranslationManager {
     pub localization: LocalizedStrings,
 }
@@ -9,6 +9,7 @@ use std::{
         mpsc::{Receiver, Sender},
         Arc, Mutex,
     },
+    time::{Duration, Instant},
 };
 use tokio::{fs::File, io};
 
@@ -16,7 +17,13 @@ use core::slice::SlicePattern;
 use log::{debug, error, info};
 
 use image::io::Reader as ImageReader;
-use maxima::util::native::{maxima_dir, NativeError, SafeStr};
+use maxima::{
+    core::concurrency::{cancellable, CancellationToken, Cancelled, Priority, IMAGE_DOWNLOADS},
+    util::{
+        http,
+        native::{maxima_dir, NativeError, SafeStr},
+    },
+};
 
 #[derive(Clone, PartialEq, Eq, Hash, std::fmt::Debug)]
 pub enum UIImageType {
@@ -26,6 +33,16 @@ pub enum UIImageType {
     Avatar(String),
 }
 
+impl UIImageType {
+    /// Game art is on screen as soon as the library is, avatars can fill in afterwards
+    fn priority(&self) -> Priority {
+        match self {
+            UIImageType::Avatar(_) => Priority::Low,
+            _ => Priority::High,
+        }
+    }
+}
+
 impl Display for UIImageType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -41,8 +58,29 @@ impl Display for UIImageType {
     }
 }
 
+/// How long a failed image waits before it's tried again, doubling with each failure
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+enum UIImageState {
+    /// Being loaded, after failing `attempts` times before
+    Loading {
+        attempts: u32,
+    },
+    Loaded(TextureHandle),
+    /// Never loads, like the logo of a game that doesn't have one
+    Stub,
+    /// The download or decoding failed, it's asked for again after `retry_at`
+    Failed {
+        attempts: u32,
+        retry_at: Instant,
+    },
+}
+
+type UIImageMap = Arc<Mutex<HashMap<UIImageType, UIImageState>>>; // lack of represents untouched
+
 pub struct UIImageCache {
-    cache: Arc<Mutex<HashMap<UIImageType, Option<TextureHandle>>>>, // none represents loading, lack of represents untouched
+    cache: UIImageMap,
     commander: Sender<UIImageCacheLoaderCommand>,
     pub placeholder_avatar: TextureHandle,
 }
@@ -52,6 +90,8 @@ pub enum UIImageCacheLoaderCommand {
     Load(UIImageType),
     /// Force an image to never load (like for games that don't have logos)
     Stub(UIImageType),
+    /// Stop downloading an image that isn't needed any more, it's loaded again when next asked for
+    Cancel(UIImageType),
 }
 
 pub fn load_image_bytes(image_bytes: &[u8]) -> ImageResult<ColorImage> {
@@ -64,6 +104,8 @@ pub fn load_image_bytes(image_bytes: &[u8]) -> ImageResult<ColorImage> {
 
 #[derive(thiserror::Error, Debug)]
 pub enum ImageLoadError {
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
     #[error(transparent)]
@@ -73,6 +115,8 @@ pub enum ImageLoadError {
     #[error(transparent)]
     Image(#[from] ImageError),
 
+    #[error("no remote or local file")]
+    NoSource,
     #[error("{path} had an unsupported amount of channels ({channels})")]
     UnsupportedChannelCount { path: PathBuf, channels: u8 },
 }
@@ -125,9 +169,10 @@ impl UIImageCache {
 
     async fn load(
         needle: UIImageType,
-        cache: Arc<Mutex<HashMap<UIImageType, Option<TextureHandle>>>>,
+        cache: UIImageMap,
         remotes: HashMap<UIImageType, String>,
         context: egui::Context,
+        cancel: CancellationToken,
     ) -> Result<(), ImageLoadError> {
         let path = UIImageCache::get_path_for_image(&needle)?;
 
@@ -141,29 +186,41 @@ impl UIImageCache {
                 }
             }
             if let Some(remote) = remotes.get(&needle) {
-                let result = reqwest::get(remote).await?;
+                // Only the download can be cancelled, a half-written file would be a broken image
+                let body = cancellable(&cancel, async {
+                    let _permit = IMAGE_DOWNLOADS.acquire(needle.priority()).await;
+                    let response = http::client().get(remote).send().await?.error_for_status()?;
+                    Ok::<_, ImageLoadError>(response.bytes().await?)
+                })
+                .await?;
 
+                // Decoded first, so a broken download isn't kept and loaded from disk forever
+                let ci = load_image_bytes(&body)?;
                 let mut file = File::create(&path).await?;
-
-                let body = result.bytes().await?;
                 io::copy(&mut body.as_slice(), &mut file).await?;
 
-                let ci = load_image_bytes(&body)?;
                 cache.lock().unwrap().insert(
                     needle,
-                    Some(context.load_texture(
+                    UIImageState::Loaded(context.load_texture(
                         path.safe_str()?.to_string(),
                         ci,
                         TextureOptions::LINEAR,
                     )),
                 );
             } else {
-                debug!("{:?} has no remote or local file", needle);
-                return Ok(());
+                // Tried again once the backend provides one
+                return Err(ImageLoadError::NoSource);
             }
         } else {
             let img = ImageReader::open(&path)?.with_guessed_format()?;
-            let img_decoded = img.decode()?;
+            let img_decoded = match img.decode() {
+                Ok(img) => img,
+                Err(err) => {
+                    // Downloaded again on the next try
+                    let _ = fs::remove_file(&path);
+                    return Err(err.into());
+                }
+            };
 
             let color_image = match img_decoded.color().channel_count() {
                 2 => {
@@ -188,7 +245,7 @@ impl UIImageCache {
 
             cache.lock().unwrap().insert(
                 needle,
-                Some(context.load_texture(
+                UIImageState::Loaded(context.load_texture(
                     path.safe_str()?.to_string(),
                     color_image,
                     TextureOptions::LINEAR,
@@ -199,12 +256,39 @@ impl UIImageCache {
         Ok(())
     }
 
+    /// Remember that `needle` failed to load, so it isn't tried again every frame
+    fn fail(cache: &UIImageMap, needle: UIImageType, context: &egui::Context) {
+        let mut cache = cache.lock().unwrap();
+        let attempts = match cache.get(&needle) {
+            Some(UIImageState::Loading { attempts }) => attempts + 1,
+            _ => 1,
+        };
+        let delay = RETRY_DELAY
+            .saturating_mul(1 << attempts.min(16).saturating_sub(1))
+            .min(MAX_RETRY_DELAY);
+        cache.insert(
+            needle,
+            UIImageState::Failed {
+                attempts,
+                retry_at: Instant::now() + delay,
+            },
+        );
+        // Draw the placeholder now, and ask for it again once the delay is up
+        context.request_repaint();
+        context.request_repaint_after(delay);
+    }
+
     async fn run(
         context: egui::Context,
         commander: Receiver<UIImageCacheLoaderCommand>,
-        cache: Arc<Mutex<HashMap<UIImageType, Option<TextureHandle>>>>,
+        cache: UIImageMap,
     ) {
         let mut remotes: HashMap<UIImageType, String> = HashMap::new();
+        // Loads remove themselves when they're over. The id makes sure one that ends after
+        // the image was asked for again doesn't remove the newer load.
+        let loading: Arc<Mutex<HashMap<UIImageType, (usize, CancellationToken)>>> =
+            Arc::new(Mutex::new(HashMap::new()));
+        let mut next_load = 0;
 
         'outer: loop {
             match commander.try_recv() {
@@ -217,41 +301,73 @@ impl UIImageCache {
                     UIImageCacheLoaderCommand::ProvideRemote(needle, target) => {
                         debug!("remote provided for {:?}", &needle);
                         // undoes a race condition, ui's fast so it can get to the images before the backend can
+                        // and a new remote is worth trying right away, even after a failure
                         let mut cache = cache.lock().unwrap();
-                        if let Some(test_none) = cache.get(&needle) {
-                            if test_none.is_none() {
-                                cache.remove(&needle);
-                            }
+                        if let Some(UIImageState::Loading { .. } | UIImageState::Failed { .. }) =
+                            cache.get(&needle)
+                        {
+                            cache.remove(&needle);
+                            context.request_repaint();
                         }
                         remotes.insert(needle, target);
                     }
                     UIImageCacheLoaderCommand::Load(needle) => {
                         // this might cause some slowdown, whoops!
                         let ctx_send = context.clone();
+                        let ctx_fail = context.clone();
                         let remotes_send = remotes.clone();
                         let cache_send = cache.clone();
+                        let loading_send = loading.clone();
+                        let cancel = CancellationToken::new();
+                        let id = next_load;
+                        next_load += 1;
+                        loading.lock().unwrap().insert(needle.clone(), (id, cancel.clone()));
 
                         tokio::task::spawn(async move {
-                            match UIImageCache::load(
+                            let result = UIImageCache::load(
                                 needle.clone(),
-                                cache_send,
+                                cache_send.clone(),
                                 remotes_send,
                                 ctx_send,
+                                cancel,
                             )
-                            .await
+                            .await;
+
                             {
+                                let mut loading = loading_send.lock().unwrap();
+                                if loading.get(&needle).is_some_and(|(current, _)| *current == id) {
+                                    loading.remove(&needle);
+                                }
+                            }
+
+                            match result {
                                 Ok(_) => {
                                     debug!("finished async load of {:?}", &needle);
                                 }
+                                Err(ImageLoadError::Cancelled(_)) => {
+                                    debug!("cancelled async load of {:?}", &needle);
+                                    // Forget it was loading, so it gets asked for again
+                                    cache_send.lock().unwrap().remove(&needle);
+                                }
+                                Err(ImageLoadError::NoSource) => {
+                                    debug!("{:?} has no remote or local file", &needle);
+                                    UIImageCache::fail(&cache_send, needle, &ctx_fail);
+                                }
                                 Err(err) => {
                                     error!("async load of {} failed: {:?}", &needle, err);
+                                    UIImageCache::fail(&cache_send, needle, &ctx_fail);
                                 }
                             }
                         });
                         tokio::task::yield_now().await;
                     }
                     UIImageCacheLoaderCommand::Stub(needle) => {
-                        cache.lock().unwrap().insert(needle, None);
+                        cache.lock().unwrap().insert(needle, UIImageState::Stub);
+                    }
+                    UIImageCacheLoaderCommand::Cancel(needle) => {
+                        if let Some((_, cancel)) = loading.lock().unwrap().remove(&needle) {
+                            cancel.cancel();
+                        }
                     }
                 },
             }
@@ -259,19 +375,37 @@ impl UIImageCache {
         info!("Shutting down image loader thread");
     }
 
+    /// Stop loading `needle` if it's still being downloaded
+    pub fn cancel(&self, needle: UIImageType) {
+        let _ = self.commander.send(UIImageCacheLoaderCommand::Cancel(needle));
+    }
+
     pub fn get(&self, needle: UIImageType) -> Option<TextureHandle> {
         // i'm hardly building this in a performant way but it's robust and solid unlike the previous mess
         let mut cache = self.cache.lock().unwrap();
-        if let Some(loading_or_loaded) = cache.get(&needle) {
-            if let Some(loaded) = loading_or_loaded {
-                Some(loaded.clone())
-            } else {
-                None
+        let attempts = match cache.get(&needle) {
+            Some(UIImageState::Loaded(loaded)) => return Some(loaded.clone()),
+            Some(UIImageState::Loading { .. } | UIImageState::Stub) => return None,
+            Some(UIImageState::Failed { attempts, retry_at }) => {
+                if Instant::now() < *retry_at {
+                    return None;
+                }
+                *attempts
             }
-        } else {
-            cache.insert(needle.clone(), None);
-            let _ = self.commander.send(UIImageCacheLoaderCommand::Load(needle));
-            None
-        }
+            None => 0,
+        };
+
+        cache.insert(needle.clone(), UIImageState::Loading { attempts });
+        let _ = self.commander.send(UIImageCacheLoaderCommand::Load(needle));
+        None
+    }
+
+    /// Whether `needle` won't show for now, because it failed to load or doesn't exist.
+    /// A placeholder is drawn in its place instead of leaving it blank.
+    pub fn unavailable(&self, needle: &UIImageType) -> bool {
+        matches!(
+            self.cache.lock().unwrap().get(needle),
+            Some(UIImageState::Failed { .. } | UIImageState::Stub)
+        )
     }
 }
@@ -5,6 +5,17 @@ use crate::MaximaEguiApp;
 
 pub fn debug_view(_app: &mut MaximaEguiApp, ui: &mut Ui) {
     use egui_extras::{Size, StripBuilder};
+
+    let build_info = maxima::build_info().to_string();
+    ui.horizontal(|ui| {
+        ui.heading("Build");
+        if ui.button("Copy").clicked() {
+            ui.output_mut(|output| output.copied_text = build_info.clone());
+        }
+    });
+    ui.label(egui::RichText::new(&build_info).monospace());
+    ui.separator();
+
     StripBuilder::new(ui)
         .size(Size::exact(30.0))
         .size(Size::exact(300.0))
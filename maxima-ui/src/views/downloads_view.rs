@@ -1,7 +1,12 @@
 use egui::{pos2, vec2, Align2, Color32, FontId, Mesh, Rect, Rounding, Shape, Stroke, Ui, Widget};
 use humansize::DECIMAL;
+use maxima::core::service_layer::ServiceAvailableBuild;
+use std::time::Duration;
 
-use crate::{MaximaEguiApp, APP_MARGIN};
+use crate::{
+    bridge_thread, translation_manager::positional_replace, views::game_view::art_placeholder,
+    MaximaEguiApp, APP_MARGIN,
+};
 
 #[derive(Clone)]
 pub struct QueuedDownload {
@@ -9,6 +14,13 @@ pub struct QueuedDownload {
     pub offer: String,
     pub downloaded_bytes: usize,
     pub total_bytes: usize,
+    /// Smoothed download speed and time left, once the backend can estimate them
+    pub speed_bps: Option<u64>,
+    pub eta: Option<Duration>,
+    /// Files checked out of the total, once the download is being verified
+    pub verification: Option<(usize, usize)>,
+    /// Build being downloaded, if it was known when the download was queued
+    pub build: Option<ServiceAvailableBuild>,
     // maybe add a thing here for updates? idk there's no real api to hook this up to yet
 }
 
@@ -42,9 +54,15 @@ fn render_queued(app: &mut MaximaEguiApp, ui: &mut Ui, game: &QueuedDownload, is
             sw: corner_radius,
             se: 0.0,
         };
+        let hero_unavailable =
+            app.img_cache.unavailable(&crate::ui_image::UIImageType::Hero(game.slug.clone()));
         let img_response = if let Some(img) = hero {
             let img_size = vec2((160.0 / img.size_vec2().y) * img.size_vec2().x, 160.0);
             ui.add(egui::Image::new((img.id(), img_size)).rounding(img_rounding).max_size(img_size))
+        } else if hero_unavailable {
+            let response = ui.allocate_response(vec2(284.0, 160.0), egui::Sense::click());
+            art_placeholder(ui, response.rect, img_rounding, None);
+            response
         } else {
             let response = ui.allocate_response(vec2(284.0, 16.0), egui::Sense::click());
             ui.painter().rect_filled(response.rect, img_rounding, Color32::GRAY);
@@ -100,6 +118,28 @@ fn render_queued(app: &mut MaximaEguiApp, ui: &mut Ui, game: &QueuedDownload, is
             Color32::WHITE,
         );
 
+        let localization = &app.locale.localization.downloads_view;
+        if let Some(build) = &game_dl.build {
+            let mut version = positional_replace!(
+                localization.version,
+                "version",
+                build.game_version().as_deref().unwrap_or("?")
+            );
+            if let Some(day) = build.live_day() {
+                version += &positional_replace!(localization.released, "date", day);
+            }
+            ui.painter().text(
+                pos2(
+                    img_response.rect.max.x + 10.0,
+                    img_response.rect.min.y + 40.0,
+                ),
+                Align2::LEFT_TOP,
+                version,
+                FontId::proportional(14.0),
+                Color32::GRAY,
+            );
+        }
+
         let button_size = 40.0;
         let right_button_rect = Rect {
             min: pos2(
@@ -158,8 +198,42 @@ fn render_queued(app: &mut MaximaEguiApp, ui: &mut Ui, game: &QueuedDownload, is
                 Color32::WHITE,
             );
 
+            if let Some((verified, total)) = game_dl.verification {
+                ui.painter().text(
+                    progress_bar_rect.center_top() - vec2(0.0, 8.0),
+                    Align2::CENTER_BOTTOM,
+                    positional_replace!(
+                        localization.verifying,
+                        "verified",
+                        verified,
+                        "total",
+                        total
+                    ),
+                    FontId::proportional(12.0),
+                    Color32::WHITE,
+                );
+            } else if let (Some(speed), Some(eta)) = (game_dl.speed_bps, game_dl.eta) {
+                ui.painter().text(
+                    progress_bar_rect.center_top() - vec2(0.0, 8.0),
+                    Align2::CENTER_BOTTOM,
+                    positional_replace!(
+                        localization.speed,
+                        "speed",
+                        humansize::SizeFormatter::new(speed, DECIMAL),
+                        "minutes",
+                        eta.as_secs() / 60,
+                        "seconds",
+                        format!("{:02}", eta.as_secs() % 60)
+                    ),
+                    FontId::proportional(12.0),
+                    Color32::WHITE,
+                );
+            }
+
             if ui.put(left_button_rect, egui::Button::new("🗙")).clicked() {
-                //TODO: Remove
+                let _ = app.backend.backend_commander.send(
+                    bridge_thread::MaximaLibRequest::CancelInstallRequest(game_dl.offer.clone()),
+                );
             }
             if ui.put(right_button_rect, egui::Button::new("⏸")).clicked() {
                 //TODO: Pause
@@ -174,7 +248,9 @@ fn render_queued(app: &mut MaximaEguiApp, ui: &mut Ui, game: &QueuedDownload, is
             );
 
             if ui.put(left_button_rect, egui::Button::new("🗙")).clicked() {
-                //TODO: Remove
+                let _ = app.backend.backend_commander.send(
+                    bridge_thread::MaximaLibRequest::CancelInstallRequest(game_dl.offer.clone()),
+                );
             }
             if ui.put(right_button_rect, egui::Button::new("⮉")).clicked() {
                 //TODO: Move to top
@@ -184,7 +260,7 @@ fn render_queued(app: &mut MaximaEguiApp, ui: &mut Ui, game: &QueuedDownload, is
 }
 
 pub fn downloads_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
-    if let Some(now) = app.installing_now.clone() {
+    for now in app.installing_now.clone() {
         render_queued(app, ui, &now, true);
         ui.separator();
     }
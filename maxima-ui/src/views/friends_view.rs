@@ -1,10 +1,10 @@
 use egui::{pos2, vec2, Align2, Color32, FontId, Id, Rect, Rounding, Stroke, Ui, Vec2};
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
-use maxima::rtm::client::BasicPresence;
+use maxima::{core::friends::FriendRequestOutcome, rtm::client::BasicPresence};
 
 use crate::{
-    translation_manager::positional_replace, widgets::enum_dropdown::enum_dropdown, MaximaEguiApp,
-    FRIEND_INGAME_COLOR,
+    bridge_thread::MaximaLibRequest, translation_manager::positional_replace,
+    widgets::enum_dropdown::enum_dropdown, MaximaEguiApp, FRIEND_INGAME_COLOR,
 };
 
 use strum_macros::EnumIter;
@@ -35,6 +35,10 @@ pub struct FriendsViewBar {
     pub search_buffer: String,
     /// ID of the friend with buttons below
     pub friend_sel: String,
+    /// The buffer for the user ID to send a friend request to
+    pub add_friend_buffer: String,
+    /// How the last friend request went, shown until another one is sent
+    pub add_friend_outcome: Option<FriendRequestOutcome>,
 }
 
 pub struct UIFriend {
@@ -43,6 +47,10 @@ pub struct UIFriend {
     pub online: BasicPresence,
     pub game: Option<String>,
     pub game_presence: Option<String>,
+    /// Offer ID of the game they're playing
+    pub game_offer: Option<String>,
+    /// Whether their session can be joined without an invite
+    pub joinable: bool,
 }
 
 const F9B233: Color32 = Color32::from_rgb(249, 178, 51);
@@ -154,6 +162,31 @@ pub fn friends_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
               app.force_friends = true;
             }
           });
+          ui.horizontal(|ui| {
+            let button_width = 80.0;
+            let field = ui.add_sized(
+              [ui.available_width() - button_width - ui.spacing().item_spacing.x, 20.0],
+              egui::text_edit::TextEdit::singleline(&mut app.friends_view_bar.add_friend_buffer)
+              .vertical_align(egui::Align::Center)
+              .hint_text(&localization.toolbar.add_friend_hint)
+            );
+            if field.has_focus() {
+              app.force_friends = true;
+            }
+            let id = app.friends_view_bar.add_friend_buffer.trim().to_owned();
+            let submitted = field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            if ui.add_enabled(!id.is_empty(), egui::Button::new(&localization.toolbar.add_friend).min_size(vec2(button_width, 20.0))).clicked()
+            || (submitted && !id.is_empty()) {
+              app.friends_view_bar.add_friend_outcome = None;
+              app.friends_view_bar.add_friend_buffer.clear();
+              let _ = app.backend.backend_commander.send(MaximaLibRequest::SendFriendRequest(id));
+            }
+          });
+          match app.friends_view_bar.add_friend_outcome {
+            Some(FriendRequestOutcome::Sent) => { ui.label(&localization.toolbar.friend_request_sent); },
+            Some(FriendRequestOutcome::Accepted) => { ui.label(&localization.toolbar.friend_request_accepted); },
+            None => {},
+          }
         });
       }
 
@@ -250,7 +283,7 @@ pub fn friends_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
 
             if how_buttons > 0.0 {
               let (_, buttons_rect) = ui.allocate_space(vec2(width, (button_height + button_gap) * how_buttons));
-              let size = vec2((width - (ui.style().spacing.item_spacing.x * 2.0)) / 3.0, PFP_ELEMENT_SIZE * 0.6);
+              let size = vec2((width - (ui.style().spacing.item_spacing.x * 3.0)) / 4.0, PFP_ELEMENT_SIZE * 0.6);
 
               // visibility check is mostly for lazy loading avatars, but if we don't need to let egui cull things, why make it?
               if !ui.is_rect_visible(main_res.rect.with_min_y(main_res.rect.min.y - (PFP_ELEMENT_SIZE*3.0)).with_max_y(buttons_rect.max.y)) { return; }
@@ -270,6 +303,11 @@ pub fn friends_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
                 max: pos2(rect_1.max.x + size.x + ui.spacing().item_spacing.x, rect_0.max.y),
               };
 
+              let rect_3 = Rect {
+                min: pos2(rect_2.max.x +          ui.spacing().item_spacing.x, rect_0.min.y),
+                max: pos2(rect_2.max.x + size.x + ui.spacing().item_spacing.x, rect_0.max.y),
+              };
+
               let button_clip_rect = if buttons_rect.min.y < ui.clip_rect().min.y { buttons_rect.with_min_y(ui.clip_rect().min.y) } else { buttons_rect };
               let button_clip_rect = if button_clip_rect.max.y > ui.clip_rect().max.y { button_clip_rect.with_max_y(ui.clip_rect().max.y) } else { button_clip_rect };
               ui.add_enabled_ui(false, |buttons| {
@@ -281,6 +319,19 @@ pub fn friends_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
                   app.friends_view_bar.friend_sel = String::new();
                 }
               });
+              ui.add_enabled_ui(friend.joinable && app.playing_game.is_none(), |buttons| {
+                buttons.set_clip_rect(button_clip_rect);
+                if buttons.is_rect_visible(button_clip_rect)
+                && buttons.put(rect_3, egui::Button::new(app.locale.localization.friends_view.friend_actions.join.to_ascii_uppercase())).clicked() {
+                  let settings = app.games.values()
+                    .find(|game| friend.game_offer.as_ref() == Some(&game.offer))
+                    .and_then(|game| app.settings.game_settings.get(&game.slug).cloned());
+                  let _ = app.backend.backend_commander.send(
+                    crate::bridge_thread::MaximaLibRequest::JoinFriendRequest(friend.id.clone(), settings),
+                  );
+                  app.friends_view_bar.friend_sel = String::new();
+                }
+              });
             }
 
             let pfp_rect = Rect {
@@ -1,14 +1,22 @@
 use crate::{
-    bridge_thread, set_app_modal, translation_manager::TranslationManager,
-    widgets::enum_dropdown::enum_dropdown, GameDetails, GameDetailsWrapper, GameInfo,
-    InstallModalState, MaximaEguiApp, PageType, PopupModal,
+    bridge_thread, set_app_modal,
+    translation_manager::{positional_replace, TranslationManager},
+    widgets::enum_dropdown::enum_dropdown,
+    GameDetails, GameDetailsWrapper, GameInfo, InstallModalState, MaximaEguiApp, PageType,
+    PopupModal, UpdatePolicy,
 };
 use egui::{
-    pos2, vec2, Color32, Margin, Mesh, Pos2, Rect, RichText, Rounding, ScrollArea, Shape, Stroke,
-    Ui, Widget,
+    pos2, vec2, Align2, Color32, FontId, Margin, Mesh, Pos2, Rect, RichText, Rounding, ScrollArea,
+    Shape, Stroke, Ui, Widget,
 };
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use maxima::core::{
+    library::{AccessEnded, PlatformSupport, Unavailable},
+    service_layer::{ServiceOwnershipMethod, ServicePlatform, ServiceStorefront},
+    system_requirements::RequirementsVerdict,
+};
 
+use std::path::PathBuf;
 use strum_macros::EnumIter;
 
 #[derive(Debug, PartialEq, Default, EnumIter)]
@@ -27,6 +35,15 @@ pub enum GameViewBarPlatform {
     Mac,
 }
 
+#[derive(Debug, PartialEq, Default, EnumIter, serde::Serialize, serde::Deserialize)]
+pub enum GameViewBarSort {
+    #[default]
+    Name,
+    LastPlayed,
+    Installed,
+    InstalledSize,
+}
+
 pub struct GameViewBar {
     pub genre_filter: GameViewBarGenre, // game type filter on the game sort bar
     pub platform_filter: GameViewBarPlatform, // platform filter on the game sort bar
@@ -34,8 +51,26 @@ pub struct GameViewBar {
     pub search_buffer: String,          // search text on the game sort bar
 }
 
+const EA_STORE_URL: &str = "https://www.ea.com/games";
+
 const SKELETON_TEXT_COLOR: Color32 = Color32::from_rgba_premultiplied(53, 53, 53, 128);
 const SKELETON_INFO_COLOR: Color32 = Color32::from_rgba_premultiplied(127, 90, 26, 128);
+const PLACEHOLDER_ART_COLOR: Color32 = Color32::from_gray(32);
+
+/// Drawn in place of game art that couldn't be loaded, with the game's name if there's
+/// no logo to show it either
+pub fn art_placeholder(ui: &Ui, rect: Rect, rounding: Rounding, name: Option<&str>) {
+    ui.painter().rect_filled(rect, rounding, PLACEHOLDER_ART_COLOR);
+    if let Some(name) = name {
+        ui.painter().text(
+            rect.center(),
+            Align2::CENTER_CENTER,
+            name,
+            FontId::proportional(32.0),
+            Color32::GRAY,
+        );
+    }
+}
 
 fn skeleton_text_block(ui: &mut egui::Ui, width: f32, height: f32) {
     let mut skeleton_rect = ui.available_rect_before_wrap();
@@ -75,17 +110,73 @@ fn game_view_action_buttons(app: &mut MaximaEguiApp, game: &GameInfo, ui: &mut U
             buttons.style_mut().visuals.widgets.inactive.weak_bg_fill = Color32::TRANSPARENT;
             buttons.style_mut().spacing.item_spacing.x = 8.0;
 
-            if app.playing_game.as_ref().is_some_and(|slug| slug.eq(&game.slug)) {
+            if let Some(reason) = &game.access_ended {
+                let text = match reason {
+                    AccessEnded::SubscriptionLapsed => &localization.subscription_lapsed,
+                    AccessEnded::Revoked => &localization.ownership_disabled,
+                    AccessEnded::TrialExpired => &localization.trial_over,
+                };
+                buttons.label(RichText::new(text).color(Color32::GRAY));
+                match reason.store_url() {
+                    Some(url) => {
+                        buttons.hyperlink_to(&localization.renew_subscription, url);
+                    }
+                    None if *reason == AccessEnded::TrialExpired => {
+                        buttons.hyperlink_to(&localization.browse_store, EA_STORE_URL);
+                    }
+                    None => {}
+                }
+            } else if game
+                .unavailable
+                .as_ref()
+                .is_some_and(|x| !game.installed || x.blocks_launch())
+            {
+                // Shown with the badges below, there's nothing to install or play
+            } else if app.playing_game.as_ref().is_some_and(|slug| slug.eq(&game.slug)) {
                 let play_str = format!("  {}  ", &localization.stop.to_uppercase());
                 if game_view_action_button(play_str, buttons) {
                     //TODO
                 }
             } else {
-                if game.installed {
+                let installing = app.install_queue.contains_key(&game.offer)
+                    || app.installing_now.iter().any(|q| q.offer.eq(&game.offer));
+                let partial = app.partial_installs.contains(&game.offer);
+
+                // An update writes over the game's files, so it can't be played until it's done
+                if game.installed && installing {
+                    let installing_str = format!("  {}  ", &localization.installing.to_uppercase());
+                    if game_view_action_button(installing_str, buttons) {
+                        app.page_view = PageType::Downloads;
+                    }
+                } else if partial && !installing {
+                    // Some of its files are missing, it would only fail to launch
+                    let resume_str = format!("  {}  ", &localization.resume.to_uppercase());
+                    if game_view_action_button(resume_str, buttons) {
+                        let _ = app.backend.backend_commander.send(
+                            crate::bridge_thread::MaximaLibRequest::ResumeInstallRequest(
+                                game.offer.clone(),
+                            ),
+                        );
+                    }
+                } else if game.installed {
                     let play_str = format!("  {}  ", &localization.play.to_uppercase());
                     if game_view_action_button(play_str, buttons) {
-                        if !app.settings.ignore_ood_games
-                            && &game.version.installed != &game.version.latest
+                        let policy = app
+                            .settings
+                            .game_settings
+                            .get(&game.slug)
+                            .map_or(UpdatePolicy::default(), |x| x.update_policy);
+                        let out_of_date = &game.version.installed != &game.version.latest;
+                        if out_of_date && policy == UpdatePolicy::AutoUpdate {
+                            app.launch_after_update = Some(game.slug.clone());
+                            let _ = app.backend.backend_commander.send(
+                                crate::bridge_thread::MaximaLibRequest::UpdateGameRequest(
+                                    game.offer.clone(),
+                                ),
+                            );
+                        } else if out_of_date
+                            && policy == UpdatePolicy::Notify
+                            && !app.settings.ignore_ood_games
                         {
                             set_app_modal!(app, Some(PopupModal::GameLaunchOOD(game.slug.clone())));
                         } else {
@@ -104,9 +195,7 @@ fn game_view_action_buttons(app: &mut MaximaEguiApp, game: &GameInfo, ui: &mut U
                             );
                         }
                     }
-                } else if app.install_queue.contains_key(&game.offer)
-                    || app.installing_now.as_ref().is_some_and(|q| q.offer.eq(&game.offer))
-                {
+                } else if installing {
                     let install_str = format!("  {}  ", &localization.resume.to_uppercase());
                     if game_view_action_button(install_str, buttons) {
                         app.page_view = PageType::Downloads;
@@ -119,20 +208,124 @@ fn game_view_action_buttons(app: &mut MaximaEguiApp, game: &GameInfo, ui: &mut U
                 }
             }
 
+            if let Some(log) = app.failed_install_logs.get(&game.offer) {
+                buttons.label(RichText::new(&localization.install_failed).color(Color32::RED));
+                if buttons.link(&localization.open_install_log).clicked() {
+                    let _ = app.backend.backend_commander.send(
+                        crate::bridge_thread::MaximaLibRequest::OpenInstallLogRequest(log.clone()),
+                    );
+                }
+            }
+
             let settings_str = format!("  {}  ", &localization.settings.to_uppercase());
             if game_view_action_button(settings_str, buttons) {
                 set_app_modal!(app, Some(PopupModal::GameSettings(game.slug.clone())));
             }
+
+            if let Some(trial) = &game.trial {
+                let localization = &app.locale.localization.games_view.main;
+                let (text, color) = match trial.seconds_remaining() {
+                    _ if trial.is_expired() => (localization.trial_expired.clone(), Color32::RED),
+                    Some(seconds) => (
+                        positional_replace!(localization.trial_time_left, "minutes", seconds / 60),
+                        Color32::YELLOW,
+                    ),
+                    None => (localization.trial.clone(), Color32::YELLOW),
+                };
+                buttons.label(RichText::new(text).size(16.0).color(color));
+            }
+
+            if let Some(method) = &game.ownership {
+                game_view_ownership_label(&app.locale, method, buttons);
+            }
+
+            if let Some(reason) = &game.unavailable {
+                let localization = &app.locale.localization.games_view.main;
+                let text = match reason {
+                    Unavailable::RegionLocked(_) => localization.unavailable_region.clone(),
+                    Unavailable::NotReleased(date) => positional_replace!(
+                        localization.unavailable_until,
+                        "date",
+                        date.format("%Y-%m-%d")
+                    ),
+                    Unavailable::Retired(date) => positional_replace!(
+                        localization.unavailable_retired,
+                        "date",
+                        date.format("%Y-%m-%d")
+                    ),
+                    Unavailable::NotDownloadable => localization.unavailable_no_download.clone(),
+                };
+                let badge = buttons.label(RichText::new(text).size(16.0).color(Color32::RED));
+                if let Unavailable::RegionLocked(Some(country)) = reason {
+                    badge.on_hover_text(country);
+                }
+            }
+
+            if game.platform_support == PlatformSupport::Unsupported {
+                let localization = &app.locale.localization.games_view.main;
+                let platforms: Vec<&str> = game.platforms.iter().map(|x| x.label()).collect();
+                buttons.label(
+                    RichText::new(positional_replace!(
+                        localization.unsupported_platform,
+                        "platforms",
+                        platforms.join(", ")
+                    ))
+                    .size(16.0)
+                    .color(Color32::YELLOW),
+                );
+            }
         });
     });
 }
 
+fn game_view_ownership_label(
+    locale: &TranslationManager,
+    method: &ServiceOwnershipMethod,
+    ui: &mut Ui,
+) {
+    let localization = &locale.localization.games_view.main;
+    let storefront = method.storefront();
+    let text = match (method, &storefront) {
+        (ServiceOwnershipMethod::Unknown, _) => return,
+        (_, Some(ServiceStorefront::Steam)) => &localization.owned_steam,
+        (_, Some(ServiceStorefront::Epic)) => &localization.owned_epic,
+        (ServiceOwnershipMethod::Vault, _) => &localization.owned_vault,
+        (ServiceOwnershipMethod::XgpVault, _) => &localization.owned_game_pass,
+        _ => &localization.owned,
+    };
+
+    let label = ui.label(RichText::new(text).size(16.0).weak());
+    let storefront = match storefront {
+        Some(ServiceStorefront::Steam) => "Steam",
+        Some(ServiceStorefront::Epic) => "Epic Games",
+        _ => return,
+    };
+    label.on_hover_text(positional_replace!(
+        localization.storefront_required,
+        "storefront",
+        storefront
+    ));
+}
+
 fn game_view_system_requirements(
     locale: &TranslationManager,
     game_details: Option<GameDetails>,
     ui: &mut Ui,
 ) {
     let req_width = ((ui.available_size_before_wrap().x) - 5.0) / 2.0;
+    if let Some(verdict) = game_details.as_ref().and_then(|x| x.requirements_verdict) {
+        let details = &locale.localization.games_view.details;
+        let verdict = match verdict {
+            RequirementsVerdict::Recommended => Some((&details.meets_recommended, Color32::GREEN)),
+            RequirementsVerdict::Minimum => Some((&details.meets_minimum, Color32::YELLOW)),
+            RequirementsVerdict::BelowMinimum => Some((&details.below_minimum, Color32::RED)),
+            RequirementsVerdict::Unknown => None,
+        };
+
+        if let Some((text, color)) = verdict {
+            ui.label(RichText::new(text).color(color));
+        }
+    }
     ui.horizontal(|sys_req| {
         puffin::profile_scope!("system requirements");
         if let Some(details) = game_details {
@@ -142,6 +335,9 @@ fn game_view_system_requirements(
                     min.set_min_width(req_width);
                     min.set_max_width(req_width);
                     min.heading(&locale.localization.games_view.details.min_system_req);
+                    if let Some(summary) = &details.system_requirements_min_summary {
+                        min.weak(summary);
+                    }
                     egui_demo_lib::easy_mark::easy_mark(min, min_requirements);
                 });
             }
@@ -152,6 +348,9 @@ fn game_view_system_requirements(
                     rec.set_min_width(req_width);
                     rec.set_max_width(req_width);
                     rec.heading(&locale.localization.games_view.details.rec_system_req);
+                    if let Some(summary) = &details.system_requirements_rec_summary {
+                        rec.weak(summary);
+                    }
                     egui_demo_lib::easy_mark::easy_mark(rec, rec_requirements);
                 });
             }
@@ -204,15 +403,27 @@ pub fn game_view_details_panel(app: &mut MaximaEguiApp, ui: &mut Ui) {
             app.img_cache.get(crate::ui_image::UIImageType::Logo(game.slug.clone())),
         )
     };
+    let hero_unavailable =
+        app.img_cache.unavailable(&crate::ui_image::UIImageType::Hero(game.slug.clone()));
+    let logo_unavailable =
+        app.img_cache.unavailable(&crate::ui_image::UIImageType::Logo(game.slug.clone()));
 
     let game_details: Option<GameDetails> = match &game.details {
         GameDetailsWrapper::Unloaded => {
+            let cancel = app.backend.cancel.child_token();
+            // Where it'd be installed, to check that drive has room for it
+            let install_folder = Some(&app.settings.default_install_folder)
+                .filter(|x| !game.installed && !x.is_empty())
+                .map(PathBuf::from);
             app.backend
                 .backend_commander
                 .send(bridge_thread::MaximaLibRequest::GetGameDetailsRequest(
                     game.slug.clone(),
+                    install_folder,
+                    cancel.clone(),
                 ))
                 .unwrap();
+            app.details_cancel = Some(cancel);
             app.games.get_mut(&app.game_sel).unwrap().details = GameDetailsWrapper::Loading;
             None
         }
@@ -277,6 +488,9 @@ pub fn game_view_details_panel(app: &mut MaximaEguiApp, ui: &mut Ui) {
                             app.settings.performance_settings,
                         );
                     }
+                } else if hero_unavailable {
+                    let name = logo_unavailable.then_some(game.name.as_str());
+                    art_placeholder(ui, hero_rect, Rounding::ZERO, name);
                 } else {
                     ui.painter().rect_filled(hero_rect, Rounding::same(0.0), Color32::TRANSPARENT);
                 }
@@ -363,6 +577,41 @@ pub fn game_view_details_panel(app: &mut MaximaEguiApp, ui: &mut Ui) {
                                         ))
                                         .color(Color32::BLACK),
                                     );
+                                    let labels = &app.locale.localization.games_view.main;
+                                    if let Some(size) = details.installed_size {
+                                        stats.separator();
+                                        stats.label(
+                                            RichText::new(&labels.installed_size)
+                                                .color(Color32::BLACK)
+                                                .strong(),
+                                        );
+                                        stats.label(
+                                            RichText::new(format!(
+                                                ": {}",
+                                                humansize::SizeFormatter::new(
+                                                    size,
+                                                    humansize::DECIMAL
+                                                )
+                                            ))
+                                            .color(Color32::BLACK),
+                                        );
+                                    }
+                                    stats.separator();
+                                    stats.label(
+                                        RichText::new(&labels.last_played)
+                                            .color(Color32::BLACK)
+                                            .strong(),
+                                    );
+                                    stats.label(
+                                        RichText::new(format!(
+                                            ": {}",
+                                            details
+                                                .last_played
+                                                .as_ref()
+                                                .unwrap_or(&labels.never_played)
+                                        ))
+                                        .color(Color32::BLACK),
+                                    );
                                 } else {
                                     let mut skeleton_rect = stats.available_rect_before_wrap();
                                     skeleton_rect.set_width(126.0);
@@ -471,6 +720,30 @@ fn game_list_button_context_menu(app: &MaximaEguiApp, game: &GameInfo, ui: &mut
         }
     });
     ui.separator();
+    let localization = &app.locale.localization.modals.game_settings;
+    ui.add_enabled_ui(game.installed, |ui| {
+        if ui.button(&localization.open_install_folder).clicked() {
+            let _ = app.backend.backend_commander.send(
+                crate::bridge_thread::MaximaLibRequest::OpenInstallFolderRequest(
+                    game.offer.clone(),
+                ),
+            );
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(
+                game.has_cloud_saves,
+                egui::Button::new(&localization.open_save_folder),
+            )
+            .clicked()
+        {
+            let _ = app.backend.backend_commander.send(
+                crate::bridge_thread::MaximaLibRequest::OpenSaveFolderRequest(game.offer.clone()),
+            );
+            ui.close_menu();
+        }
+    });
+    ui.separator();
     if ui.button("UNINSTALL").clicked() {
         ui.close_menu();
     }
@@ -550,6 +823,18 @@ fn show_game_list_buttons(app: &mut MaximaEguiApp, ui: &mut Ui) {
                     )
                     .response;
                 });
+                enum_dropdown(
+                    filter_chunk,
+                    "SortComboBox".to_owned(),
+                    &mut app.settings.game_sort,
+                    260.0,
+                    "",
+                    &app.locale,
+                );
+                filter_chunk.checkbox(
+                    &mut app.settings.group_installed,
+                    &app.locale.localization.games_view.toolbar.group_installed,
+                );
             }
         });
 
@@ -591,8 +876,38 @@ fn show_game_list_buttons(app: &mut MaximaEguiApp, ui: &mut Ui) {
                                 .fuzzy_match(&obj.1.name, &app.game_view_bar.search_buffer)
                                 .is_some()
                         })
+                        .filter(|obj| match app.game_view_bar.platform_filter {
+                            GameViewBarPlatform::AllPlatforms => true,
+                            GameViewBarPlatform::Windows => {
+                                obj.1.platforms.contains(&ServicePlatform::Pc)
+                            }
+                            GameViewBarPlatform::Mac => {
+                                obj.1.platforms.contains(&ServicePlatform::MacPc)
+                            }
+                        })
                         .collect();
-                    games.sort_by(|(_, a_game), (_, b_game)| a_game.name.cmp(&b_game.name));
+                    let group_installed = app.settings.group_installed
+                        || app.settings.game_sort == GameViewBarSort::Installed;
+                    games.sort_by(|(_, a_game), (_, b_game)| {
+                        let group = if group_installed {
+                            b_game.installed.cmp(&a_game.installed)
+                        } else {
+                            std::cmp::Ordering::Equal
+                        };
+                        // Largest or most recent first, with games that have neither last
+                        let first = match app.settings.game_sort {
+                            GameViewBarSort::Name | GameViewBarSort::Installed => {
+                                std::cmp::Ordering::Equal
+                            }
+                            GameViewBarSort::LastPlayed => {
+                                b_game.last_played.cmp(&a_game.last_played)
+                            }
+                            GameViewBarSort::InstalledSize => {
+                                b_game.installed_size.cmp(&a_game.installed_size)
+                            }
+                        };
+                        group.then(first).then_with(|| a_game.name.cmp(&b_game.name))
+                    });
 
                     for (slug, game) in games {
                         puffin::profile_scope!("game list game");
@@ -606,7 +921,9 @@ fn show_game_list_buttons(app: &mut MaximaEguiApp, ui: &mut Ui) {
                         } else {
                             style.visuals.widgets.inactive.weak_bg_fill = Color32::TRANSPARENT;
                             style.visuals.widgets.inactive.fg_stroke = {
-                                if game.installed {
+                                if game.disabled {
+                                    Stroke::new(1.0, Color32::DARK_GRAY)
+                                } else if game.installed {
                                     Stroke::new(1.0, Color32::WHITE)
                                 } else {
                                     Stroke::new(1.0, Color32::GRAY)
@@ -646,7 +963,7 @@ fn show_game_list_buttons(app: &mut MaximaEguiApp, ui: &mut Ui) {
                         list_response
                             .context_menu(|ui| game_list_button_context_menu(app, game, ui));
                         if list_response.clicked() {
-                            app.game_sel = slug.clone();
+                            app.select_game(slug.clone());
                         }
                     }
                     games_list.allocate_space(games_list.available_size_before_wrap());
@@ -657,7 +974,16 @@ fn show_game_list_buttons(app: &mut MaximaEguiApp, ui: &mut Ui) {
 
 pub fn games_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
     puffin::profile_function!();
-    if app.games.len() < 1 {
+    if app.games.len() < 1 && app.games_loaded {
+        ui.vertical_centered(|ui| {
+            ui.add_space(ui.available_height() / 2.0 - 30.0);
+            ui.heading(&app.locale.localization.games_view.main.no_owned_games);
+            ui.hyperlink_to(
+                &app.locale.localization.games_view.main.browse_store,
+                EA_STORE_URL,
+            );
+        });
+    } else if app.games.len() < 1 {
         ui.with_layout(
             egui::Layout::centered_and_justified(egui::Direction::RightToLeft),
             |ui| {
@@ -1,6 +1,15 @@
-use egui::{vec2, Ui};
+use std::path::Path;
 
-use crate::{widgets::enum_dropdown::enum_dropdown, MaximaEguiApp};
+use egui::{vec2, Color32, Ui};
+use maxima::{
+    content::install_template::{resolve_install_template, InstallTemplateValues},
+    core::concurrency::{FILE_VERIFICATION, IMAGE_DOWNLOADS},
+};
+
+use crate::{
+    bridge_thread::MaximaLibRequest, translation_manager::positional_replace,
+    widgets::enum_dropdown::enum_dropdown, MaximaEguiApp,
+};
 
 pub fn settings_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
     let localization = &app.locale.localization.settings_view;
@@ -35,6 +44,34 @@ pub fn settings_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
         if ui.add_sized(vec2(100.0, 30.0), egui::Button::new("BROWSE")).clicked() {}
     });
 
+    ui.label(&localization.game_installation.install_path_template);
+    ui.add_sized(
+        vec2(ui.available_width(), 30.0),
+        egui::TextEdit::singleline(&mut app.settings.install_path_template)
+            .vertical_align(egui::Align::Center),
+    );
+    let preview = resolve_install_template(
+        &app.settings.install_path_template,
+        &InstallTemplateValues {
+            base: Path::new(&app.settings.default_install_folder),
+            slug: "battlefield-4",
+            name: "Battlefield 4",
+            publisher: Some("Electronic Arts"),
+        },
+    );
+    match preview {
+        Ok(path) => {
+            ui.weak(positional_replace!(
+                localization.game_installation.install_path_template_preview,
+                "path",
+                path.display()
+            ));
+        }
+        Err(err) => {
+            ui.colored_label(Color32::RED, err.to_string());
+        }
+    }
+
     #[cfg(unix)]
     {
         ui.label(&localization.game_installation.default_wine_prefix);
@@ -56,6 +93,71 @@ pub fn settings_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
         &app.locale.localization.settings_view.game_installation.ignore_ood_warning,
     );
 
+    let mut limits_changed = false;
+    ui.horizontal(|ui| {
+        limits_changed |= ui
+            .add(egui::DragValue::new(&mut app.settings.concurrent_downloads).range(1..=8))
+            .changed();
+        ui.label(&localization.game_installation.concurrent_downloads);
+    });
+    ui.horizontal(|ui| {
+        limits_changed |= ui
+            .add(egui::DragValue::new(&mut app.settings.bandwidth_limit_mb).range(0..=1000))
+            .changed();
+        ui.label(&localization.game_installation.bandwidth_limit);
+        if app.settings.bandwidth_limit_mb == 0 {
+            ui.weak(&localization.game_installation.bandwidth_unlimited);
+        }
+    });
+    if limits_changed {
+        app.send_download_limits();
+    }
+    ui.horizontal(|ui| {
+        let minutes = &mut app.settings.update_poll_minutes;
+        let changed = ui.add(egui::DragValue::new(minutes).range(0..=24 * 60)).changed();
+        ui.label(&localization.game_installation.update_poll);
+        if *minutes == 0 {
+            ui.weak(&localization.game_installation.update_poll_off);
+        }
+        if changed {
+            app.send_update_poll();
+        }
+    });
+
+    ui.heading("");
+    ui.heading(&localization.cloud_saves.header);
+    ui.separator();
+    if ui
+        .checkbox(
+            &mut app.settings.cloud_saves,
+            &localization.cloud_saves.enabled,
+        )
+        .changed()
+    {
+        app.send_cloud_saves();
+    }
+    ui.weak(&localization.cloud_saves.hint);
+    ui.horizontal(|ui| {
+        let backups = &mut app.settings.save_backups;
+        let changed = ui.add(egui::DragValue::new(backups).range(0..=50)).changed();
+        ui.label(&localization.cloud_saves.save_backups);
+        if *backups == 0 {
+            ui.weak(&localization.cloud_saves.save_backups_off);
+        }
+        if changed {
+            app.send_cloud_saves();
+        }
+    });
+    if ui.button(&localization.cloud_saves.release_lock).clicked() {
+        app.sync_lock_released = None;
+        let _ = app.backend.backend_commander.send(MaximaLibRequest::ReleaseSyncLockRequest);
+    }
+    match app.sync_lock_released {
+        Some(true) => ui.label(&localization.cloud_saves.lock_released),
+        Some(false) => ui.label(&localization.cloud_saves.no_lock),
+        None => ui.weak(&localization.cloud_saves.release_lock_hint),
+    };
+
     ui.heading("");
     ui.heading(&localization.performance.header);
     ui.separator();
@@ -63,4 +165,58 @@ pub fn settings_view(app: &mut MaximaEguiApp, ui: &mut Ui) {
         &mut app.settings.performance_settings.disable_blur,
         &localization.performance.disable_blur,
     );
+    ui.horizontal(|ui| {
+        let image_downloads = &mut app.settings.performance_settings.image_downloads;
+        if ui.add(egui::DragValue::new(image_downloads).range(1..=16)).changed() {
+            IMAGE_DOWNLOADS.set_limit(*image_downloads);
+        }
+        ui.label(&localization.performance.image_downloads);
+    });
+    ui.horizontal(|ui| {
+        let file_verification = &mut app.settings.performance_settings.file_verification;
+        if ui.add(egui::DragValue::new(file_verification).range(1..=64)).changed() {
+            FILE_VERIFICATION.set_limit(*file_verification);
+        }
+        ui.label(&localization.performance.file_verification);
+    });
+
+    ui.heading("");
+    ui.heading(&localization.network.header);
+    ui.separator();
+    ui.label(&localization.network.proxy);
+    ui.add_sized(
+        vec2(ui.available_width(), 30.0),
+        egui::TextEdit::singleline(&mut app.settings.proxy).vertical_align(egui::Align::Center),
+    );
+    ui.weak(&localization.network.proxy_hint);
+
+    ui.heading("");
+    ui.heading(&localization.troubleshooting.header);
+    ui.separator();
+    if ui.button(&localization.troubleshooting.write_diagnostics).clicked() {
+        app.diagnostics_path = None;
+        let _ = app.backend.backend_commander.send(MaximaLibRequest::WriteDiagnosticsRequest);
+    }
+    if let Some(path) = &app.diagnostics_path {
+        ui.label(positional_replace!(
+            localization.troubleshooting.diagnostics_written,
+            "path",
+            path.display()
+        ));
+    }
+
+    #[cfg(windows)]
+    {
+        ui.checkbox(
+            &mut app.settings.skip_service,
+            &localization.troubleshooting.skip_service,
+        );
+        ui.weak(&localization.troubleshooting.skip_service_hint);
+    }
+    if app.service_unavailable {
+        ui.colored_label(
+            Color32::YELLOW,
+            &localization.troubleshooting.service_unavailable,
+        );
+    }
 }